@@ -8,7 +8,8 @@ use semver::{BuildMetadata, Prerelease, Version};
 use std::path::{Path, PathBuf};
 
 lazy_static! {
-    static ref KUBEWARDEN_VERSION: Version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+    pub(crate) static ref KUBEWARDEN_VERSION: Version =
+        Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
 }
 
 pub(crate) enum Backend {
@@ -0,0 +1,796 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::api::authorization::v1::SubjectAccessReviewStatus;
+use policy_evaluator::{
+    callback_handler::CallbackHandlerBuilder,
+    callback_requests::{
+        CallbackRequest, CallbackRequestType, CallbackResponse, KubernetesResourceChanges,
+    },
+    kube::{
+        api::ApiResource,
+        core::{DynamicObject, GroupVersion, ObjectList, TypeMeta},
+    },
+    policy_fetcher::{sigstore::trust::ManualTrustRoot, sources::Sources},
+    policy_metadata::ResourceProjection,
+};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error};
+
+use super::DnsSettings;
+
+/// Settings of the offline Kubernetes backend
+#[derive(Clone, Debug)]
+pub(crate) struct KubeContextSettings {
+    /// Directory holding the Kubernetes manifests
+    pub directory: PathBuf,
+    /// The answer given to all the `can_i` requests
+    pub can_i: bool,
+}
+
+/// An in-memory collection of Kubernetes resources, loaded from a directory
+/// of YAML or JSON manifests.
+///
+/// The resources are indexed by their `apiVersion` and `kind`.
+#[derive(Debug, Default)]
+pub(crate) struct KubeContext {
+    resources: BTreeMap<(String, String), Vec<DynamicObject>>,
+}
+
+impl KubeContext {
+    /// Load all the `.yaml`, `.yml` and `.json` files found inside of the given
+    /// directory and its subdirectories. Files can hold multiple YAML documents,
+    /// `List` documents are expanded into their items.
+    pub fn from_dir(directory: &Path) -> Result<Self> {
+        let mut context = KubeContext::default();
+        for path in manifest_files(directory)? {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| anyhow!("cannot read manifest {}: {e}", path.display()))?;
+            context
+                .load_manifests(&contents)
+                .map_err(|e| anyhow!("cannot load manifest {}: {e}", path.display()))?;
+        }
+        Ok(context)
+    }
+
+    /// Load the resources defined inside of a YAML (or JSON) string
+    fn load_manifests(&mut self, contents: &str) -> Result<()> {
+        for document in serde_yaml::Deserializer::from_str(contents) {
+            let value = serde_json::Value::deserialize(document)?;
+            if value.is_null() {
+                continue;
+            }
+            self.load_value(value)?;
+        }
+        Ok(())
+    }
+
+    fn load_value(&mut self, value: serde_json::Value) -> Result<()> {
+        let is_list = value
+            .get("kind")
+            .and_then(|kind| kind.as_str())
+            .is_some_and(|kind| kind.ends_with("List"))
+            && value.get("items").is_some_and(|items| items.is_array());
+        if is_list {
+            if let Some(serde_json::Value::Array(items)) = value.get("items") {
+                for item in items {
+                    self.load_value(item.to_owned())?;
+                }
+            }
+            return Ok(());
+        }
+
+        let obj: DynamicObject = serde_json::from_value(value)?;
+        let types = obj
+            .types
+            .clone()
+            .ok_or_else(|| anyhow!("resource without apiVersion and kind"))?;
+        if obj.metadata.name.is_none() {
+            return Err(anyhow!(
+                "{}/{} resource without a name",
+                types.api_version,
+                types.kind
+            ));
+        }
+        self.insert(types, obj);
+        Ok(())
+    }
+
+    fn insert(&mut self, types: TypeMeta, obj: DynamicObject) {
+        let objects = self
+            .resources
+            .entry((types.api_version, types.kind))
+            .or_default();
+        objects.retain(|o| {
+            o.metadata.name != obj.metadata.name || o.metadata.namespace != obj.metadata.namespace
+        });
+        objects.push(obj);
+        objects.sort_by(|a, b| {
+            (&a.metadata.namespace, &a.metadata.name)
+                .cmp(&(&b.metadata.namespace, &b.metadata.name))
+        });
+    }
+
+    fn objects<'a>(
+        &'a self,
+        api_version: &str,
+        kind: &str,
+    ) -> impl Iterator<Item = &'a DynamicObject> + 'a {
+        self.resources
+            .get(&(api_version.to_owned(), kind.to_owned()))
+            .into_iter()
+            .flatten()
+    }
+
+    pub fn list_resources_by_namespace(
+        &self,
+        api_version: &str,
+        kind: &str,
+        namespace: &str,
+        label_selector: Option<&str>,
+        field_selector: Option<&str>,
+    ) -> Result<ObjectList<DynamicObject>> {
+        self.list(
+            api_version,
+            kind,
+            Some(namespace),
+            label_selector,
+            field_selector,
+        )
+    }
+
+    pub fn list_resources_all(
+        &self,
+        api_version: &str,
+        kind: &str,
+        label_selector: Option<&str>,
+        field_selector: Option<&str>,
+    ) -> Result<ObjectList<DynamicObject>> {
+        self.list(api_version, kind, None, label_selector, field_selector)
+    }
+
+    fn list(
+        &self,
+        api_version: &str,
+        kind: &str,
+        namespace: Option<&str>,
+        label_selector: Option<&str>,
+        field_selector: Option<&str>,
+    ) -> Result<ObjectList<DynamicObject>> {
+        let label_selector = label_selector
+            .map(LabelSelector::parse)
+            .transpose()?
+            .unwrap_or_default();
+        let field_selector = field_selector
+            .map(FieldSelector::parse)
+            .transpose()?
+            .unwrap_or_default();
+
+        let mut items = vec![];
+        for obj in self.objects(api_version, kind) {
+            if namespace.is_some() && obj.metadata.namespace.as_deref() != namespace {
+                continue;
+            }
+            if label_selector.matches(obj) && field_selector.matches(obj)? {
+                items.push(obj.to_owned());
+            }
+        }
+
+        Ok(ObjectList {
+            types: TypeMeta {
+                api_version: api_version.to_owned(),
+                kind: format!("{kind}List"),
+            },
+            metadata: Default::default(),
+            items,
+        })
+    }
+
+    pub fn get_resource(
+        &self,
+        api_version: &str,
+        kind: &str,
+        name: &str,
+        namespace: Option<&str>,
+    ) -> Result<DynamicObject> {
+        self.objects(api_version, kind)
+            .find(|obj| {
+                obj.metadata.name.as_deref() == Some(name)
+                    && obj.metadata.namespace.as_deref() == namespace
+            })
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!("Cannot find {api_version}/{kind} named '{name}' inside of namespace '{namespace:?}'")
+            })
+    }
+
+    pub fn get_resource_plural_name(&self, api_version: &str, kind: &str) -> Result<String> {
+        let gvk = api_version
+            .parse::<GroupVersion>()
+            .map_err(|e| anyhow!("invalid apiVersion '{api_version}': {e}"))?
+            .with_kind(kind);
+        Ok(ApiResource::from_gvk(&gvk).plural)
+    }
+}
+
+/// Find all the manifest files inside of the given directory. The files are sorted
+/// by their path, this makes the loading order predictable
+fn manifest_files(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let entries = fs::read_dir(directory)
+        .map_err(|e| anyhow!("cannot read directory {}: {e}", directory.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.append(&mut manifest_files(&path)?);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| matches!(ext, "yaml" | "yml" | "json"))
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// A requirement of a label selector, as documented
+/// [here](https://kubernetes.io/docs/concepts/overview/working-with-objects/labels/#label-selectors)
+#[derive(Debug, PartialEq)]
+enum LabelRequirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    DoesNotExist(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct LabelSelector(Vec<LabelRequirement>);
+
+impl LabelSelector {
+    fn parse(selector: &str) -> Result<Self> {
+        let mut requirements = vec![];
+        for term in split_selector(selector) {
+            let term = term.trim();
+            if term.is_empty() {
+                continue;
+            }
+            let requirement = if let Some((key, values)) = split_set_term(term, " notin ") {
+                LabelRequirement::NotIn(key, values?)
+            } else if let Some((key, values)) = split_set_term(term, " in ") {
+                LabelRequirement::In(key, values?)
+            } else if let Some((key, value)) = term.split_once("!=") {
+                LabelRequirement::NotEquals(key.trim().to_owned(), value.trim().to_owned())
+            } else if let Some((key, value)) = term.split_once("==") {
+                LabelRequirement::Equals(key.trim().to_owned(), value.trim().to_owned())
+            } else if let Some((key, value)) = term.split_once('=') {
+                LabelRequirement::Equals(key.trim().to_owned(), value.trim().to_owned())
+            } else if let Some(key) = term.strip_prefix('!') {
+                LabelRequirement::DoesNotExist(key.trim().to_owned())
+            } else {
+                LabelRequirement::Exists(term.to_owned())
+            };
+            requirements.push(requirement);
+        }
+        Ok(LabelSelector(requirements))
+    }
+
+    fn matches(&self, obj: &DynamicObject) -> bool {
+        let empty = BTreeMap::new();
+        let labels = obj.metadata.labels.as_ref().unwrap_or(&empty);
+        self.0.iter().all(|requirement| match requirement {
+            LabelRequirement::Equals(key, value) => labels.get(key) == Some(value),
+            LabelRequirement::NotEquals(key, value) => labels.get(key) != Some(value),
+            LabelRequirement::In(key, values) => {
+                labels.get(key).is_some_and(|v| values.contains(v))
+            }
+            LabelRequirement::NotIn(key, values) => {
+                labels.get(key).is_none_or(|v| !values.contains(v))
+            }
+            LabelRequirement::Exists(key) => labels.contains_key(key),
+            LabelRequirement::DoesNotExist(key) => !labels.contains_key(key),
+        })
+    }
+}
+
+/// Split a selector on the commas that are not part of a set of values (e.g. `env in (a,b)`)
+fn split_selector(selector: &str) -> Vec<&str> {
+    let mut terms = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                terms.push(&selector[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    terms.push(&selector[start..]);
+    terms
+}
+
+fn split_set_term(term: &str, operator: &str) -> Option<(String, Result<Vec<String>>)> {
+    let (key, values) = term.split_once(operator)?;
+    let values = values
+        .trim()
+        .strip_prefix('(')
+        .and_then(|values| values.strip_suffix(')'))
+        .map(|values| {
+            values
+                .split(',')
+                .map(|value| value.trim().to_owned())
+                .collect()
+        })
+        .ok_or_else(|| anyhow!("invalid label selector term: '{term}'"));
+    Some((key.trim().to_owned(), values))
+}
+
+/// A field selector. Each requirement is made of the path of the field (e.g. `metadata.name`),
+/// the value to compare and a boolean that is `true` when the values must be equal
+#[derive(Debug, Default, PartialEq)]
+struct FieldSelector(Vec<(String, String, bool)>);
+
+impl FieldSelector {
+    fn parse(selector: &str) -> Result<Self> {
+        let mut requirements = vec![];
+        for term in selector.split(',') {
+            let term = term.trim();
+            if term.is_empty() {
+                continue;
+            }
+            let requirement = if let Some((field, value)) = term.split_once("!=") {
+                (field, value, false)
+            } else if let Some((field, value)) = term.split_once("==") {
+                (field, value, true)
+            } else if let Some((field, value)) = term.split_once('=') {
+                (field, value, true)
+            } else {
+                return Err(anyhow!("invalid field selector term: '{term}'"));
+            };
+            requirements.push((
+                requirement.0.trim().to_owned(),
+                requirement.1.trim().to_owned(),
+                requirement.2,
+            ));
+        }
+        Ok(FieldSelector(requirements))
+    }
+
+    fn matches(&self, obj: &DynamicObject) -> Result<bool> {
+        if self.0.is_empty() {
+            return Ok(true);
+        }
+
+        let value = serde_json::to_value(obj)?;
+        Ok(self.0.iter().all(|(field, expected, equal)| {
+            let actual = field
+                .split('.')
+                .try_fold(&value, |value, key| value.get(key))
+                .map(|value| match value {
+                    serde_json::Value::String(s) => s.to_owned(),
+                    other => other.to_string(),
+                })
+                .unwrap_or_default();
+            (&actual == expected) == *equal
+        }))
+    }
+}
+
+/// Apply the projection requested by the policy to all the objects of the list
+fn project_list(
+    mut list: ObjectList<DynamicObject>,
+    projection: Option<&ResourceProjection>,
+) -> ObjectList<DynamicObject> {
+    if let Some(projection) = projection {
+        list.items = list
+            .items
+            .iter()
+            .map(|obj| projection.project(obj))
+            .collect();
+    }
+    list
+}
+
+/// A callback handler that serves the Kubernetes requests using the resources
+/// of a `KubeContext`. All the other requests are forwarded to a regular
+/// `policy_evaluator` CallbackHandler
+pub(crate) struct KubeContextCallbackHandler {
+    context: KubeContext,
+    can_i: bool,
+    sources: Option<Sources>,
+    sigstore_trust_root: Option<Arc<ManualTrustRoot<'static>>>,
+    dns_settings: DnsSettings,
+
+    rx: mpsc::Receiver<CallbackRequest>,
+    tx: mpsc::Sender<CallbackRequest>,
+    shutdown_channel: oneshot::Receiver<()>,
+}
+
+impl KubeContextCallbackHandler {
+    pub fn new(
+        settings: &KubeContextSettings,
+        shutdown_channel: oneshot::Receiver<()>,
+        sources: Option<Sources>,
+        sigstore_trust_root: Option<Arc<ManualTrustRoot<'static>>>,
+        dns_settings: DnsSettings,
+    ) -> Result<KubeContextCallbackHandler> {
+        let context = KubeContext::from_dir(&settings.directory)?;
+        let (tx, rx) = mpsc::channel(200);
+
+        Ok(Self {
+            context,
+            can_i: settings.can_i,
+            sources,
+            sigstore_trust_root,
+            dns_settings,
+            rx,
+            tx,
+            shutdown_channel,
+        })
+    }
+
+    pub fn sender_channel(&self) -> mpsc::Sender<CallbackRequest> {
+        self.tx.clone()
+    }
+
+    /// Produce the response to a Kubernetes request. `None` is returned when the
+    /// request is not about Kubernetes
+    fn handle_kubernetes_request(
+        &self,
+        request: &CallbackRequestType,
+    ) -> Option<Result<CallbackResponse>> {
+        let payload = match request {
+            CallbackRequestType::KubernetesListResourceNamespace {
+                api_version,
+                kind,
+                namespace,
+                label_selector,
+                field_selector,
+                projection,
+            } => self
+                .context
+                .list_resources_by_namespace(
+                    api_version,
+                    kind,
+                    namespace,
+                    label_selector.as_deref(),
+                    field_selector.as_deref(),
+                )
+                .map(|list| project_list(list, projection.as_ref()))
+                .and_then(|list| serde_json::to_vec(&list).map_err(anyhow::Error::new)),
+            CallbackRequestType::KubernetesListResourceAll {
+                api_version,
+                kind,
+                label_selector,
+                field_selector,
+                projection,
+            } => self
+                .context
+                .list_resources_all(
+                    api_version,
+                    kind,
+                    label_selector.as_deref(),
+                    field_selector.as_deref(),
+                )
+                .map(|list| project_list(list, projection.as_ref()))
+                .and_then(|list| serde_json::to_vec(&list).map_err(anyhow::Error::new)),
+            CallbackRequestType::KubernetesGetResource {
+                api_version,
+                kind,
+                name,
+                namespace,
+                projection,
+                ..
+            } => self
+                .context
+                .get_resource(api_version, kind, name, namespace.as_deref())
+                .map(|obj| match projection {
+                    Some(projection) => projection.project(&obj),
+                    None => obj,
+                })
+                .and_then(|obj| serde_json::to_vec(&obj).map_err(anyhow::Error::new)),
+            CallbackRequestType::KubernetesGetResourcePluralName { api_version, kind } => self
+                .context
+                .get_resource_plural_name(api_version, kind)
+                .and_then(|name| serde_json::to_vec(&name).map_err(anyhow::Error::new)),
+            // the resources never change
+            CallbackRequestType::HasKubernetesListResourceAllResultChangedSinceInstant {
+                ..
+            } => serde_json::to_vec(&false).map_err(anyhow::Error::new),
+            CallbackRequestType::KubernetesListResourceAllChangesSince { .. } => {
+                serde_json::to_vec(&KubernetesResourceChanges::Changes {
+                    changes: vec![],
+                    next_sequence: 0,
+                })
+                .map_err(anyhow::Error::new)
+            }
+            CallbackRequestType::KubernetesCanI { .. } => {
+                serde_json::to_vec(&SubjectAccessReviewStatus {
+                    allowed: self.can_i,
+                    denied: Some(!self.can_i),
+                    reason: Some("answer configured via kwctl --kube-context-can-i".to_string()),
+                    ..Default::default()
+                })
+                .map_err(anyhow::Error::new)
+            }
+            _ => return None,
+        };
+
+        Some(payload.map(|payload| CallbackResponse { payload }))
+    }
+
+    pub async fn loop_eval(&mut self) {
+        // This is a channel used to stop the tokio task that is run
+        // inside of the CallbackHandler
+        let (callback_handler_shutdown_channel_tx, callback_handler_shutdown_channel_rx) =
+            oneshot::channel();
+
+        // The real CallbackHandler, used for all the non-Kubernetes requests.
+        // It's built without a Kubernetes client on purpose
+        let mut callback_handler = self
+            .dns_settings
+            .configure(
+                CallbackHandlerBuilder::new(callback_handler_shutdown_channel_rx)
+                    .registry_config(self.sources.clone())
+                    .trust_root(self.sigstore_trust_root.clone()),
+            )
+            .build()
+            .await
+            .expect("cannot build callback handler");
+        let callback_handler_sender = callback_handler.sender_channel();
+
+        tokio::spawn(async move {
+            callback_handler.loop_eval().await;
+        });
+
+        loop {
+            tokio::select! {
+                // place the shutdown check before the message evaluation,
+                // as recommended by tokio's documentation about select!
+                _ = &mut self.shutdown_channel => {
+                    if let Err(e) = callback_handler_shutdown_channel_tx.send(()) {
+                        error!(error = ?e, "Cannot shutdown the real callback_handler");
+                    }
+                    return;
+                },
+                maybe_req = self.rx.recv() => {
+                    if let Some(req) = maybe_req {
+                        match self.handle_kubernetes_request(&req.request) {
+                            Some(response) => {
+                                debug!(request = ?req.request, "serving Kubernetes request from the kube context directory");
+                                req.response_channel
+                                    .send(response)
+                                    .expect("Cannot send back response to policy");
+                            }
+                            None => callback_handler_sender
+                                .send(req)
+                                .await
+                                .expect("cannot forward request to real callback handler"),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MANIFESTS: &str = r#"
+apiVersion: v1
+kind: Namespace
+metadata:
+  name: team-a
+  labels:
+    owner: alice
+---
+apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: Pod
+  metadata:
+    name: nginx
+    namespace: team-a
+    labels:
+      app: nginx
+      tier: frontend
+  spec:
+    nodeName: node-1
+- apiVersion: v1
+  kind: Pod
+  metadata:
+    name: redis
+    namespace: team-a
+    labels:
+      app: redis
+      tier: backend
+  spec:
+    nodeName: node-2
+- apiVersion: v1
+  kind: Pod
+  metadata:
+    name: nginx
+    namespace: team-b
+    labels:
+      app: nginx
+  spec:
+    nodeName: node-2
+"#;
+
+    fn context() -> KubeContext {
+        let mut context = KubeContext::default();
+        context.load_manifests(MANIFESTS).unwrap();
+        context
+            .load_manifests(
+                r#"{"apiVersion": "apps/v1", "kind": "Deployment", "metadata": {"name": "api", "namespace": "team-a"}}"#,
+            )
+            .unwrap();
+        context
+    }
+
+    fn names(list: &ObjectList<DynamicObject>) -> Vec<String> {
+        list.items
+            .iter()
+            .map(|obj| {
+                format!(
+                    "{}/{}",
+                    obj.metadata.namespace.clone().unwrap_or_default(),
+                    obj.metadata.name.clone().unwrap()
+                )
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case::no_selectors(None, None, vec!["team-a/nginx", "team-a/redis", "team-b/nginx"])]
+    #[case::label_equality(Some("app=nginx"), None, vec!["team-a/nginx", "team-b/nginx"])]
+    #[case::label_inequality(Some("app!=nginx"), None, vec!["team-a/redis"])]
+    #[case::label_set(Some("tier in (frontend, backend),app notin (redis)"), None, vec!["team-a/nginx"])]
+    #[case::label_exists(Some("tier"), None, vec!["team-a/nginx", "team-a/redis"])]
+    #[case::label_does_not_exist(Some("!tier"), None, vec!["team-b/nginx"])]
+    #[case::field(None, Some("spec.nodeName=node-2"), vec!["team-a/redis", "team-b/nginx"])]
+    #[case::field_and_label(Some("app=nginx"), Some("metadata.namespace!=team-a"), vec!["team-b/nginx"])]
+    fn list_resources_all(
+        #[case] label_selector: Option<&str>,
+        #[case] field_selector: Option<&str>,
+        #[case] expected: Vec<&str>,
+    ) {
+        let list = context()
+            .list_resources_all("v1", "Pod", label_selector, field_selector)
+            .unwrap();
+        assert_eq!(list.types.kind, "PodList");
+        assert_eq!(names(&list), expected);
+    }
+
+    #[test]
+    fn list_resources_by_namespace() {
+        let list = context()
+            .list_resources_by_namespace("v1", "Pod", "team-b", None, None)
+            .unwrap();
+        assert_eq!(names(&list), vec!["team-b/nginx"]);
+
+        let list = context()
+            .list_resources_by_namespace("apps/v1", "Deployment", "team-a", None, None)
+            .unwrap();
+        assert_eq!(names(&list), vec!["team-a/api"]);
+    }
+
+    #[test]
+    fn get_resource() {
+        let context = context();
+        let ns = context
+            .get_resource("v1", "Namespace", "team-a", None)
+            .unwrap();
+        assert_eq!(ns.metadata.labels.unwrap()["owner"], "alice");
+
+        assert!(context
+            .get_resource("v1", "Pod", "nginx", Some("team-c"))
+            .is_err());
+    }
+
+    #[rstest]
+    #[case("v1", "Pod", "pods")]
+    #[case("apps/v1", "Deployment", "deployments")]
+    #[case("networking.k8s.io/v1", "Ingress", "ingresses")]
+    #[case("v1", "Endpoints", "endpoints")]
+    fn plural_name(#[case] api_version: &str, #[case] kind: &str, #[case] expected: &str) {
+        assert_eq!(
+            context()
+                .get_resource_plural_name(api_version, kind)
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(true)]
+    #[case(false)]
+    fn can_i_answer(#[case] can_i: bool) {
+        let (_, shutdown_channel) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(1);
+        let handler = KubeContextCallbackHandler {
+            context: context(),
+            can_i,
+            sources: None,
+            sigstore_trust_root: None,
+            dns_settings: DnsSettings::default(),
+            rx,
+            tx,
+            shutdown_channel,
+        };
+
+        let request = CallbackRequestType::KubernetesCanI {
+            request: Default::default(),
+            disable_cache: true,
+        };
+        let response = handler
+            .handle_kubernetes_request(&request)
+            .expect("should be handled")
+            .unwrap();
+        let status: SubjectAccessReviewStatus = serde_json::from_slice(&response.payload).unwrap();
+        assert_eq!(status.allowed, can_i);
+
+        // non Kubernetes requests are not handled
+        let request = CallbackRequestType::DNSLookupHost {
+            host: "kubewarden.io".to_string(),
+        };
+        assert!(handler.handle_kubernetes_request(&request).is_none());
+    }
+
+    #[test]
+    fn projection_is_applied() {
+        let (_, shutdown_channel) = oneshot::channel();
+        let (tx, rx) = mpsc::channel(1);
+        let handler = KubeContextCallbackHandler {
+            context: context(),
+            can_i: false,
+            sources: None,
+            sigstore_trust_root: None,
+            dns_settings: DnsSettings::default(),
+            rx,
+            tx,
+            shutdown_channel,
+        };
+
+        let request = CallbackRequestType::KubernetesListResourceNamespace {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            namespace: "team-b".to_string(),
+            label_selector: None,
+            field_selector: None,
+            projection: Some(ResourceProjection::MetadataOnly),
+        };
+        let response = handler
+            .handle_kubernetes_request(&request)
+            .expect("should be handled")
+            .unwrap();
+        let list: ObjectList<DynamicObject> = serde_json::from_slice(&response.payload).unwrap();
+        assert_eq!(names(&list), vec!["team-b/nginx"]);
+        assert_eq!(list.items[0].data, serde_json::json!({}));
+        assert!(list.items[0].metadata.labels.is_some());
+    }
+
+    #[test]
+    fn resource_without_name_is_rejected() {
+        let mut context = KubeContext::default();
+        assert!(context
+            .load_manifests("apiVersion: v1\nkind: Pod\nmetadata: {}\n")
+            .is_err());
+    }
+}
@@ -1,13 +1,21 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 use anyhow::Result;
-use policy_evaluator::{callback_requests::CallbackRequest, kube};
+use policy_evaluator::{
+    callback_handler::{CallbackHandlerBuilder, DEFAULT_DNS_TIMEOUT},
+    callback_requests::CallbackRequest,
+    kube,
+};
 use tokio::sync::{mpsc, oneshot};
 
+mod kube_context;
 mod proxy;
+pub(crate) mod session;
+
+pub(crate) use kube_context::KubeContextSettings;
 
 use crate::{
-    callback_handler::proxy::CallbackHandlerProxy,
+    callback_handler::{kube_context::KubeContextCallbackHandler, proxy::CallbackHandlerProxy},
     config::{pull_and_run::PullAndRunSettings, HostCapabilitiesMode},
 };
 
@@ -17,6 +25,34 @@ pub(crate) enum ProxyMode {
     Replay { source: PathBuf },
 }
 
+/// How the DNS host capabilities perform their queries
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DnsSettings {
+    /// The name servers to query, the ones of the system are used when `None`
+    pub nameservers: Option<Vec<SocketAddr>>,
+    pub timeout: Duration,
+}
+
+impl Default for DnsSettings {
+    fn default() -> Self {
+        Self {
+            nameservers: None,
+            timeout: DEFAULT_DNS_TIMEOUT,
+        }
+    }
+}
+
+impl DnsSettings {
+    /// Apply the settings to the builder of the real CallbackHandler
+    pub fn configure(&self, builder: CallbackHandlerBuilder) -> CallbackHandlerBuilder {
+        let builder = builder.dns_timeout(self.timeout);
+        match &self.nameservers {
+            Some(nameservers) => builder.dns_nameservers(nameservers.clone()),
+            None => builder,
+        }
+    }
+}
+
 /// This is an abstraction over the callback_handler provided by the
 /// policy_evaluator crate.
 /// The goal is to allow kwctl to have a proxy handler, that can
@@ -24,6 +60,7 @@ pub(crate) enum ProxyMode {
 pub(crate) enum CallbackHandler {
     Direct(policy_evaluator::callback_handler::CallbackHandler),
     Proxy(proxy::CallbackHandlerProxy),
+    KubeContext(KubeContextCallbackHandler),
 }
 
 impl CallbackHandler {
@@ -39,6 +76,15 @@ impl CallbackHandler {
             HostCapabilitiesMode::Direct => {
                 new_transparent(cfg, kube_client, shutdown_channel_rx).await
             }
+            HostCapabilitiesMode::KubeContext(settings) => Ok(CallbackHandler::KubeContext(
+                KubeContextCallbackHandler::new(
+                    settings,
+                    shutdown_channel_rx,
+                    cfg.sources.clone(),
+                    cfg.sigstore_trust_root.clone(),
+                    cfg.dns_settings.clone(),
+                )?,
+            )),
         }
     }
 
@@ -46,6 +92,7 @@ impl CallbackHandler {
         match self {
             CallbackHandler::Direct(handler) => handler.sender_channel(),
             CallbackHandler::Proxy(handler) => handler.sender_channel(),
+            CallbackHandler::KubeContext(handler) => handler.sender_channel(),
         }
     }
 
@@ -53,6 +100,7 @@ impl CallbackHandler {
         match self {
             CallbackHandler::Direct(mut handler) => handler.loop_eval().await,
             CallbackHandler::Proxy(mut handler) => handler.loop_eval().await,
+            CallbackHandler::KubeContext(mut handler) => handler.loop_eval().await,
         }
     }
 }
@@ -69,6 +117,7 @@ async fn new_proxy(
         cfg.sources.clone(),
         cfg.sigstore_trust_root.clone(),
         kube_client,
+        cfg.dns_settings.clone(),
     )
     .await?;
 
@@ -80,10 +129,11 @@ async fn new_transparent(
     kube_client: Option<kube::Client>,
     shutdown_channel_rx: oneshot::Receiver<()>,
 ) -> Result<CallbackHandler> {
-    let mut callback_handler_builder =
-        policy_evaluator::callback_handler::CallbackHandlerBuilder::new(shutdown_channel_rx)
+    let mut callback_handler_builder = cfg.dns_settings.configure(
+        CallbackHandlerBuilder::new(shutdown_channel_rx)
             .registry_config(cfg.sources.clone())
-            .trust_root(cfg.sigstore_trust_root.clone());
+            .trust_root(cfg.sigstore_trust_root.clone()),
+    );
     if let Some(kc) = kube_client {
         callback_handler_builder = callback_handler_builder.kube_client(kc);
     }
@@ -1,36 +1,25 @@
-use super::ProxyMode;
+use super::{
+    session::{Matching, Response, Session, SessionExchange, SessionReplayer},
+    DnsSettings, ProxyMode,
+};
 use anyhow::{anyhow, Result};
 use policy_evaluator::{
     callback_handler::CallbackHandlerBuilder,
-    callback_requests::{CallbackRequest, CallbackRequestType, CallbackResponse},
+    callback_requests::{CallbackRequest, CallbackResponse},
     kube,
     policy_fetcher::{sigstore::trust::ManualTrustRoot, sources::Sources},
 };
-use serde::{Deserialize, Serialize};
-use std::{collections::VecDeque, fs::File, path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 use tokio::sync::{mpsc, oneshot};
 use tracing::{error, info, warn};
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-enum Response {
-    Success { payload: String },
-    Error { message: String },
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(tag = "type")]
-struct Exchange {
-    pub request: String,
-    pub response: Response,
-}
-
 /// A proxy against a `policy_evaluator::CallbackHandler`
 /// Can record guest requests, save them to file and reply them back
 pub(crate) struct CallbackHandlerProxy {
     sources: Option<Sources>,
     sigstore_trust_root: Option<Arc<ManualTrustRoot<'static>>>,
     kube_client: Option<kube::Client>,
+    dns_settings: DnsSettings,
     mode: ProxyMode,
 
     /// List of exchanges that happen between the policy and the
@@ -41,7 +30,7 @@ pub(crate) struct CallbackHandlerProxy {
     /// hence we store `Result` objects inside of this vector.
     /// We deal with failures later on, when writing the session
     /// file.
-    recorded_exchanges: Vec<Result<Exchange>>,
+    recorded_exchanges: Vec<Result<SessionExchange>>,
 
     rx: mpsc::Receiver<CallbackRequest>,
     tx: mpsc::Sender<CallbackRequest>,
@@ -55,6 +44,7 @@ impl CallbackHandlerProxy {
         sources: Option<Sources>,
         sigstore_trust_root: Option<Arc<ManualTrustRoot<'static>>>,
         kube_client: Option<kube::Client>,
+        dns_settings: DnsSettings,
     ) -> Result<CallbackHandlerProxy> {
         // the channels used to interact with this callback handler.
         // consumers of these channels think they are interacting
@@ -70,6 +60,7 @@ impl CallbackHandlerProxy {
             sources,
             sigstore_trust_root,
             kube_client,
+            dns_settings,
             recorded_exchanges: vec![],
         })
     }
@@ -80,27 +71,27 @@ impl CallbackHandlerProxy {
 
     fn record_exchange(
         &mut self,
-        request: Result<String>,
+        request: Result<serde_yaml::Value>,
         response: std::result::Result<&CallbackResponse, &anyhow::Error>,
     ) {
-        let exchange: Result<Exchange> = request
-            .map(|req_str| {
+        let exchange: Result<SessionExchange> = request
+            .map(|req_value| {
                 // the request is `Ok`. We have to convert the
                 // response payload now
                 response.map_or_else(
                     |resp_err| {
                         // host replied with an error (like trying to obtain the
                         // sigstore signature of an unsigned image). This is fine
-                        Ok(Exchange {
-                            request: req_str.clone(),
+                        Ok(SessionExchange {
+                            request: req_value.clone(),
                             response: Response::Error {
                                 message: resp_err.to_string(),
                             },
                         })
                     },
                     |resp| {
-                        Ok(Exchange {
-                            request: req_str.clone(),
+                        Ok(SessionExchange {
+                            request: req_value.clone(),
                             response: Response::Success {
                                 payload: String::from_utf8(resp.payload.clone()).map_err(|e| {
                                     anyhow!("cannot convert response payload to utf8: {}", e)
@@ -139,17 +130,19 @@ impl CallbackHandlerProxy {
         if !errors.is_empty() {
             error!(errors = ?errors, "Cannot record communication between host and policy, something went wrong while capturing the exchange");
         } else {
-            let exchanges: Vec<&Exchange> = self
-                .recorded_exchanges
-                .iter()
-                .filter_map(|exchange| exchange.as_ref().ok())
-                .collect();
-            match File::create(destination) {
-                Err(e) => error!(e = ?e, ?destination, "Cannot save context aware session to file"),
-                Ok(file) => match serde_yaml::to_writer(file, &exchanges) {
-                    Ok(_) => info!(?destination, "Context aware session saved to file"),
-                    Err(e) => error!(error = ?e, "Cannot save context aware session to file"),
-                },
+            let session = Session {
+                matching: Matching::Content,
+                default_response: None,
+                exchanges: self
+                    .recorded_exchanges
+                    .iter()
+                    .filter_map(|exchange| exchange.as_ref().ok())
+                    .cloned()
+                    .collect(),
+            };
+            match session.to_file(destination) {
+                Ok(_) => info!(?destination, "Context aware session saved to file"),
+                Err(e) => error!(error = ?e, "Cannot save context aware session to file"),
             }
         }
     }
@@ -168,12 +161,10 @@ impl CallbackHandlerProxy {
         // goes wrong here when dealing with channel message passing,
         // there's no nice way to handle errors here.
 
-        let mut exchanges: VecDeque<Exchange> = if let ProxyMode::Replay { source } = &self.mode {
-            let file = File::open(source).unwrap_or_else(|_| {
-                panic!("Cannot open host capabilities interactions file {source:?}")
-            });
-            serde_yaml::from_reader(file)
-                .unwrap_or_else(|_| panic!("cannot deserialize contents of {source:?}"))
+        let mut replayer = if let ProxyMode::Replay { source } = &self.mode {
+            SessionReplayer::new(Session::from_file(source).unwrap_or_else(|e| {
+                panic!("Cannot load host capabilities interactions file {source:?}: {e}")
+            }))
         } else {
             // this should never happen
             unreachable!()
@@ -184,14 +175,15 @@ impl CallbackHandlerProxy {
                 // place the shutdown check before the message evaluation,
                 // as recommended by tokio's documentation about select!
                 _ = &mut self.shutdown_channel => {
-                    if !exchanges.is_empty() {
-                        warn!(leftovers = ?exchanges, "Some of the recorded exchanges have not been replayed");
+                    let leftovers = replayer.leftovers();
+                    if !leftovers.is_empty() {
+                        warn!(?leftovers, "Some of the recorded exchanges have not been replayed");
                     }
                     return;
                 },
                 maybe_req = self.rx.recv() => {
                     if let Some(req) = maybe_req {
-                        let response = Self::produce_recorded_response(&req, &mut exchanges);
+                        let response = replayer.respond(&req.request);
 
                         req.response_channel.send(response).expect("Cannot send back response to policy");
                     }
@@ -200,33 +192,6 @@ impl CallbackHandlerProxy {
         }
     }
 
-    fn produce_recorded_response(
-        req: &CallbackRequest,
-        exchanges: &mut VecDeque<Exchange>,
-    ) -> Result<CallbackResponse> {
-        match exchanges.pop_front() {
-            None => Err(anyhow!("the list of recorded responses is empty")),
-            Some(exchange) => {
-                let expected_request: CallbackRequestType = serde_yaml::from_str(&exchange.request)
-                    .expect("Cannot deserialize recorded request into `CallbackRequestType`");
-                if expected_request == req.request {
-                    match exchange.response {
-                        Response::Success { payload } => Ok(CallbackResponse {
-                            payload: payload.into_bytes(),
-                        }),
-                        Response::Error { message } => Err(anyhow!("{message}")),
-                    }
-                } else {
-                    Err(anyhow!(
-                        "Replay error: unexpected request. Was expecting {:?}, got {:?} instead",
-                        expected_request,
-                        req.request
-                    ))
-                }
-            }
-        }
-    }
-
     /// The code used by the handler when running in `record` mode
     async fn loop_eval_recoder(&mut self) {
         // This is a channel used to stop the tokio task that is run
@@ -235,10 +200,11 @@ impl CallbackHandlerProxy {
             oneshot::channel();
 
         // Build the real CallbackHandler
-        let mut callback_handler_builder =
+        let mut callback_handler_builder = self.dns_settings.configure(
             CallbackHandlerBuilder::new(callback_handler_shutdown_channel_rx)
                 .registry_config(self.sources.clone())
-                .trust_root(self.sigstore_trust_root.clone());
+                .trust_root(self.sigstore_trust_root.clone()),
+        );
         if let Some(kc) = &self.kube_client {
             callback_handler_builder = callback_handler_builder.kube_client(kc.to_owned());
         }
@@ -276,7 +242,7 @@ impl CallbackHandlerProxy {
                     // there's no nice way to handle errors here.
 
                     if let Some(req) = maybe_req {
-                        let request = serde_yaml::to_string(&req.request)
+                        let request = serde_yaml::to_value(&req.request)
                             .map_err(|e| {
                                 // the recording is compromised, but we will
                                 // not panic here. We record the error and keep
@@ -332,20 +298,25 @@ impl CallbackHandlerProxy {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use policy_evaluator::callback_requests::CallbackRequestType;
+
+    fn sequential_replayer(exchanges: Vec<SessionExchange>) -> SessionReplayer {
+        SessionReplayer::new(Session {
+            matching: Matching::Sequential,
+            default_response: None,
+            exchanges,
+        })
+    }
 
     #[test]
     fn record_response_no_more_records() {
-        let mut exchanges: VecDeque<Exchange> = VecDeque::new();
+        let mut replayer = sequential_replayer(vec![]);
 
-        let (response_tx, _) = oneshot::channel::<Result<CallbackResponse>>();
-        let request = CallbackRequest {
-            request: CallbackRequestType::DNSLookupHost {
-                host: "kubewarden.io".to_string(),
-            },
-            response_channel: response_tx,
+        let request = CallbackRequestType::DNSLookupHost {
+            host: "kubewarden.io".to_string(),
         };
 
-        let response = CallbackHandlerProxy::produce_recorded_response(&request, &mut exchanges);
+        let response = replayer.respond(&request);
         assert!(response.is_err());
         let err = response.unwrap_err();
 
@@ -359,26 +330,21 @@ mod tests {
         let expected_request = CallbackRequestType::OciManifestDigest {
             image: "busybox".to_string(),
         };
-        let expected_exchange = Exchange {
-            request: serde_yaml::to_string(&expected_request)
+        let expected_exchange = SessionExchange {
+            request: serde_yaml::to_value(&expected_request)
                 .expect("cannot serialize expected request"),
             response: Response::Success {
                 payload: "not relevant".to_string(),
             },
         };
 
-        let mut exchanges: VecDeque<Exchange> = VecDeque::new();
-        exchanges.push_front(expected_exchange);
+        let mut replayer = sequential_replayer(vec![expected_exchange]);
 
-        let (response_tx, _) = oneshot::channel::<Result<CallbackResponse>>();
-        let request = CallbackRequest {
-            request: CallbackRequestType::DNSLookupHost {
-                host: "kubewarden.io".to_string(),
-            },
-            response_channel: response_tx,
+        let request = CallbackRequestType::DNSLookupHost {
+            host: "kubewarden.io".to_string(),
         };
 
-        let response = CallbackHandlerProxy::produce_recorded_response(&request, &mut exchanges);
+        let response = replayer.respond(&request);
         assert!(response.is_err());
         let err = response.unwrap_err();
 
@@ -393,24 +359,16 @@ mod tests {
             image: "busybox".to_string(),
         };
         let expected_payload = "hello world".to_string();
-        let exchange = Exchange {
-            request: serde_yaml::to_string(&request).expect("cannot serialize request"),
+        let exchange = SessionExchange {
+            request: serde_yaml::to_value(&request).expect("cannot serialize request"),
             response: Response::Success {
                 payload: expected_payload.clone(),
             },
         };
 
-        let mut exchanges: VecDeque<Exchange> = VecDeque::new();
-        exchanges.push_front(exchange);
-
-        let (response_tx, _) = oneshot::channel::<Result<CallbackResponse>>();
-        let request = CallbackRequest {
-            request,
-            response_channel: response_tx,
-        };
+        let mut replayer = sequential_replayer(vec![exchange]);
 
-        let response = CallbackHandlerProxy::produce_recorded_response(&request, &mut exchanges)
-            .expect("should not be an error");
+        let response = replayer.respond(&request).expect("should not be an error");
         assert_eq!(response.payload, expected_payload.into_bytes());
     }
 
@@ -420,23 +378,16 @@ mod tests {
             image: "busybox".to_string(),
         };
         let expected_err_msg = "something went wrong".to_string();
-        let exchange = Exchange {
-            request: serde_yaml::to_string(&request).expect("cannot serialize request"),
+        let exchange = SessionExchange {
+            request: serde_yaml::to_value(&request).expect("cannot serialize request"),
             response: Response::Error {
                 message: expected_err_msg.clone(),
             },
         };
 
-        let mut exchanges: VecDeque<Exchange> = VecDeque::new();
-        exchanges.push_front(exchange);
-
-        let (response_tx, _) = oneshot::channel::<Result<CallbackResponse>>();
-        let request = CallbackRequest {
-            request,
-            response_channel: response_tx,
-        };
+        let mut replayer = sequential_replayer(vec![exchange]);
 
-        let response = CallbackHandlerProxy::produce_recorded_response(&request, &mut exchanges);
+        let response = replayer.respond(&request);
         assert!(response.is_err());
         let err = response.unwrap_err();
         assert_eq!(err.to_string(), expected_err_msg);
@@ -0,0 +1,426 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::callback_requests::{CallbackRequestType, CallbackResponse};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::{fs, path::Path};
+
+/// The response recorded for a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub(crate) enum Response {
+    Success { payload: String },
+    Error { message: String },
+}
+
+impl Response {
+    fn to_callback_response(&self) -> Result<CallbackResponse> {
+        match self {
+            Response::Success { payload } => Ok(CallbackResponse {
+                payload: payload.to_owned().into_bytes(),
+            }),
+            Response::Error { message } => Err(anyhow!("{message}")),
+        }
+    }
+}
+
+/// How the requests made by the policy are matched against the exchanges of a session
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Matching {
+    /// The response is looked up by the contents of the request, the order
+    /// and the number of the requests do not matter
+    #[default]
+    Content,
+    /// The requests must be made in the same order they have been recorded
+    Sequential,
+}
+
+/// A request made by the policy, together with the response given by the host.
+///
+/// The request is the YAML representation of a `CallbackRequestType`. When used as a
+/// pattern, the fields that are not specified and the ones set to `"*"` match any value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SessionExchange {
+    pub request: Value,
+    pub response: Response,
+}
+
+impl SessionExchange {
+    /// The name of the `CallbackRequestType` variant of the request
+    pub fn request_type(&self) -> Option<String> {
+        match &self.request {
+            Value::Tagged(tagged) => {
+                Some(tagged.tag.to_string().trim_start_matches('!').to_owned())
+            }
+            Value::String(name) => Some(name.to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// The exchanges between a policy and the host capabilities, used to replay
+/// the responses of the host
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Session {
+    #[serde(default)]
+    pub matching: Matching,
+    /// The response given when no exchange matches the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_response: Option<Response>,
+    pub exchanges: Vec<SessionExchange>,
+}
+
+/// The format used by the session files created by older versions of kwctl:
+/// a list of exchanges, with the request stored as a YAML string
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+struct LegacyExchange {
+    request: String,
+    response: Response,
+}
+
+impl Session {
+    /// Load a session file. Files using the legacy format are converted on the fly,
+    /// their exchanges are matched sequentially
+    pub fn from_file(path: &Path) -> Result<Session> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read session file {}: {e}", path.display()))?;
+        Self::from_yaml(&contents)
+            .map_err(|e| anyhow!("cannot parse session file {}: {e}", path.display()))
+    }
+
+    fn from_yaml(contents: &str) -> Result<Session> {
+        let session_err = match serde_yaml::from_str::<Session>(contents) {
+            Ok(session) => return Ok(session),
+            Err(e) => e,
+        };
+        let legacy_exchanges = match serde_yaml::from_str::<Vec<LegacyExchange>>(contents) {
+            Ok(exchanges) => exchanges,
+            // report the error about the current format
+            Err(_) => return Err(session_err.into()),
+        };
+
+        let exchanges = legacy_exchanges
+            .into_iter()
+            .map(|exchange| {
+                Ok(SessionExchange {
+                    request: serde_yaml::from_str(&exchange.request)?,
+                    response: exchange.response,
+                })
+            })
+            .collect::<Result<Vec<SessionExchange>>>()?;
+
+        Ok(Session {
+            matching: Matching::Sequential,
+            default_response: None,
+            exchanges,
+        })
+    }
+
+    pub fn to_file(&self, path: &Path) -> Result<()> {
+        let file = fs::File::create(path)
+            .map_err(|e| anyhow!("cannot create session file {}: {e}", path.display()))?;
+        serde_yaml::to_writer(file, self)
+            .map_err(|e| anyhow!("cannot write session file {}: {e}", path.display()))
+    }
+
+    /// Merge multiple sessions into a single one. The exchanges are concatenated,
+    /// duplicated ones are removed. The order of the exchanges coming from different
+    /// sessions is meaningless, hence the merged session always matches requests by
+    /// content. The default response is the last one defined
+    pub fn merge(sessions: Vec<Session>) -> Session {
+        let mut merged = Session {
+            matching: Matching::Content,
+            ..Default::default()
+        };
+        for session in sessions {
+            if session.default_response.is_some() {
+                merged.default_response = session.default_response;
+            }
+            merged.exchanges.extend(session.exchanges);
+        }
+        merged.prune(&[], false);
+        merged
+    }
+
+    /// Remove the duplicated exchanges, the ones whose request type is listed
+    /// inside of `request_types` and, optionally, the ones with an error response
+    pub fn prune(&mut self, request_types: &[String], remove_errors: bool) {
+        let mut exchanges: Vec<SessionExchange> = vec![];
+        for exchange in self.exchanges.drain(..) {
+            if exchanges.contains(&exchange)
+                || (remove_errors && matches!(exchange.response, Response::Error { .. }))
+                || exchange
+                    .request_type()
+                    .is_some_and(|request_type| request_types.contains(&request_type))
+            {
+                continue;
+            }
+            exchanges.push(exchange);
+        }
+        self.exchanges = exchanges;
+    }
+}
+
+/// Produces the responses of a session
+pub(crate) struct SessionReplayer {
+    session: Session,
+    /// Tracks which exchanges have already been replayed
+    used: Vec<bool>,
+    /// The next exchange to be replayed, used only by the sequential matching
+    next: usize,
+}
+
+impl SessionReplayer {
+    pub fn new(session: Session) -> Self {
+        let used = vec![false; session.exchanges.len()];
+        Self {
+            session,
+            used,
+            next: 0,
+        }
+    }
+
+    /// Find the response for the given request
+    pub fn respond(&mut self, request: &CallbackRequestType) -> Result<CallbackResponse> {
+        let actual = serde_yaml::to_value(request)
+            .map_err(|e| anyhow!("cannot convert request to yaml: {e}"))?;
+
+        let idx = match self.session.matching {
+            Matching::Sequential => {
+                let idx = self.next;
+                let exchange = match self.session.exchanges.get(idx) {
+                    Some(exchange) => exchange,
+                    None => {
+                        return self.default_response("the list of recorded responses is empty")
+                    }
+                };
+                if !request_matches(&exchange.request, &actual) {
+                    return Err(anyhow!(
+                        "Replay error: unexpected request. Was expecting {:?}, got {:?} instead",
+                        exchange.request,
+                        request
+                    ));
+                }
+                self.next += 1;
+                idx
+            }
+            Matching::Content => {
+                let candidates: Vec<usize> = self
+                    .session
+                    .exchanges
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, exchange)| request_matches(&exchange.request, &actual))
+                    .map(|(idx, _)| idx)
+                    .collect();
+                // replay the exchanges in order, the last one is reused once they
+                // have all been replayed
+                match candidates
+                    .iter()
+                    .find(|idx| !self.used[**idx])
+                    .or(candidates.last())
+                {
+                    Some(idx) => *idx,
+                    None => {
+                        return self.default_response(&format!(
+                            "Replay error: no recorded exchange matches the request {request:?}"
+                        ))
+                    }
+                }
+            }
+        };
+
+        self.used[idx] = true;
+        self.session.exchanges[idx].response.to_callback_response()
+    }
+
+    fn default_response(&self, error: &str) -> Result<CallbackResponse> {
+        match &self.session.default_response {
+            Some(response) => response.to_callback_response(),
+            None => Err(anyhow!("{error}")),
+        }
+    }
+
+    /// The exchanges that have never been replayed
+    pub fn leftovers(&self) -> Vec<&SessionExchange> {
+        self.session
+            .exchanges
+            .iter()
+            .zip(&self.used)
+            .filter(|(_, used)| !**used)
+            .map(|(exchange, _)| exchange)
+            .collect()
+    }
+}
+
+/// Check if a request matches the given pattern. The fields missing from the pattern,
+/// and the ones set to `"*"`, match any value
+fn request_matches(pattern: &Value, actual: &Value) -> bool {
+    match (pattern, actual) {
+        (Value::String(wildcard), _) if wildcard == "*" => true,
+        (Value::Tagged(pattern), Value::Tagged(actual)) => {
+            pattern.tag == actual.tag && request_matches(&pattern.value, &actual.value)
+        }
+        (Value::Mapping(pattern), Value::Mapping(actual)) => {
+            pattern.iter().all(|(key, pattern_value)| {
+                request_matches(pattern_value, actual.get(key).unwrap_or(&Value::Null))
+            })
+        }
+        (Value::Sequence(pattern), Value::Sequence(actual)) => {
+            pattern.len() == actual.len()
+                && pattern
+                    .iter()
+                    .zip(actual)
+                    .all(|(pattern, actual)| request_matches(pattern, actual))
+        }
+        (pattern, actual) => pattern == actual,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn exchange(request: &str, payload: &str) -> SessionExchange {
+        SessionExchange {
+            request: serde_yaml::from_str(request).unwrap(),
+            response: Response::Success {
+                payload: payload.to_string(),
+            },
+        }
+    }
+
+    fn dns_lookup(host: &str) -> CallbackRequestType {
+        CallbackRequestType::DNSLookupHost {
+            host: host.to_string(),
+        }
+    }
+
+    #[rstest]
+    #[case("!DNSLookupHost\nhost: kubewarden.io", true)]
+    #[case("!DNSLookupHost\nhost: example.com", false)]
+    #[case("!DNSLookupHost\nhost: '*'", true)]
+    #[case("!DNSLookupHost {}", true)]
+    #[case("!OciManifestDigest\nimage: '*'", false)]
+    fn request_matching(#[case] pattern: &str, #[case] expected: bool) {
+        let pattern: Value = serde_yaml::from_str(pattern).unwrap();
+        let actual = serde_yaml::to_value(dns_lookup("kubewarden.io")).unwrap();
+        assert_eq!(request_matches(&pattern, &actual), expected);
+    }
+
+    #[test]
+    fn content_matching_ignores_the_order() {
+        let mut replayer = SessionReplayer::new(Session {
+            matching: Matching::Content,
+            default_response: None,
+            exchanges: vec![
+                exchange("!DNSLookupHost\nhost: a.com", "first a"),
+                exchange("!DNSLookupHost\nhost: b.com", "b"),
+                exchange("!DNSLookupHost\nhost: a.com", "second a"),
+            ],
+        });
+
+        let payload = |replayer: &mut SessionReplayer, host| {
+            String::from_utf8(replayer.respond(&dns_lookup(host)).unwrap().payload).unwrap()
+        };
+        assert_eq!(payload(&mut replayer, "b.com"), "b");
+        assert_eq!(payload(&mut replayer, "a.com"), "first a");
+        assert_eq!(payload(&mut replayer, "a.com"), "second a");
+        // the last matching exchange is reused
+        assert_eq!(payload(&mut replayer, "a.com"), "second a");
+        assert!(replayer.leftovers().is_empty());
+
+        let err = replayer.respond(&dns_lookup("c.com")).unwrap_err();
+        assert!(err.to_string().contains("no recorded exchange matches"));
+    }
+
+    #[test]
+    fn default_response() {
+        let mut replayer = SessionReplayer::new(Session {
+            matching: Matching::Content,
+            default_response: Some(Response::Error {
+                message: "not found".to_string(),
+            }),
+            exchanges: vec![],
+        });
+
+        let err = replayer.respond(&dns_lookup("c.com")).unwrap_err();
+        assert_eq!(err.to_string(), "not found");
+    }
+
+    #[test]
+    fn load_legacy_session() {
+        let contents = r#"
+- type: Exchange
+  request: |
+    !DNSLookupHost
+    host: kubewarden.io
+  response:
+    type: Success
+    payload: '["127.0.0.1"]'
+"#;
+        let session = Session::from_yaml(contents).unwrap();
+        assert_eq!(session.matching, Matching::Sequential);
+        assert_eq!(
+            session.exchanges,
+            vec![exchange(
+                "!DNSLookupHost\nhost: kubewarden.io",
+                r#"["127.0.0.1"]"#
+            )]
+        );
+    }
+
+    #[test]
+    fn load_session() {
+        let contents = r#"
+matching: content
+defaultResponse:
+  type: Error
+  message: not found
+exchanges:
+- request: !DNSLookupHost
+    host: kubewarden.io
+  response:
+    type: Success
+    payload: '["127.0.0.1"]'
+"#;
+        let session = Session::from_yaml(contents).unwrap();
+        assert_eq!(session.matching, Matching::Content);
+        assert_eq!(
+            session.exchanges[0].request_type().unwrap(),
+            "DNSLookupHost"
+        );
+    }
+
+    #[test]
+    fn merge_and_prune() {
+        let first = Session {
+            matching: Matching::Sequential,
+            default_response: None,
+            exchanges: vec![
+                exchange("!DNSLookupHost\nhost: a.com", "a"),
+                exchange("!OciManifestDigest\nimage: busybox", "digest"),
+            ],
+        };
+        let mut second = first.clone();
+        second.exchanges.push(SessionExchange {
+            request: serde_yaml::from_str("!DNSLookupHost\nhost: b.com").unwrap(),
+            response: Response::Error {
+                message: "not found".to_string(),
+            },
+        });
+
+        let mut merged = Session::merge(vec![first, second]);
+        assert_eq!(merged.matching, Matching::Content);
+        assert_eq!(merged.exchanges.len(), 3);
+
+        merged.prune(&["OciManifestDigest".to_string()], true);
+        assert_eq!(
+            merged.exchanges,
+            vec![exchange("!DNSLookupHost\nhost: a.com", "a")]
+        );
+    }
+}
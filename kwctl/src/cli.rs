@@ -5,7 +5,9 @@ use clap::{
 use lazy_static::lazy_static;
 
 pub(crate) mod bench;
+pub(crate) mod lint;
 pub(crate) mod run;
+pub(crate) mod test;
 
 lazy_static! {
     static ref VERSION_AND_BUILTINS: String = {
@@ -199,6 +201,79 @@ fn subcommand_verify() -> Command {
         .args(args)
 }
 
+fn subcommand_sign() -> Command {
+    let mut args = vec![
+        Arg::new("docker-config-json-path")
+            .long("docker-config-json-path")
+            .value_name("PATH")
+            .help("Path to a directory containing the Docker 'config.json' file. Can be used to indicate registry authentication details"),
+        Arg::new("sources-path")
+            .long("sources-path")
+            .value_name("PATH")
+            .help("YAML file holding source information (https, registry insecure hosts, custom CA's...)"),
+        Arg::new("key")
+            .short('k')
+            .long("key")
+            .required(true)
+            .value_name("PATH")
+            .help("Path to the private key used to sign the policy"),
+        Arg::new("annotation")
+            .short('a')
+            .long("annotation")
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .value_name("KEY=VALUE")
+            .help("Annotation in key=value format added to the signature. Can be repeated multiple times"),
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("PATH")
+            .value_parser(PossibleValuesParser::new(["text", "json"]))
+            .default_value("text")
+            .help("Output format"),
+    ];
+    args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    args.push(
+        Arg::new("uri")
+            .required(true)
+            .index(1)
+            .help("Policy URI. Supported schemes: registry://"),
+    );
+
+    Command::new("sign")
+        .about("Signs a Kubewarden policy pushed to an OCI registry")
+        .after_long_help(
+            r#"The signature is created with a cosign compatible private key, like the ones created by the 'generate-key-pair' command, and is pushed next to the policy.
+The password of the private key is read from the COSIGN_PASSWORD environment variable.
+The signature can be verified with 'kwctl verify --verification-key' or 'cosign verify --key'."#,
+        )
+        .args(args)
+}
+
+fn subcommand_generate_key_pair() -> Command {
+    Command::new("generate-key-pair")
+        .about("Generates a key pair to sign policies")
+        .after_long_help(
+            r#"The private key is encrypted with the password read from the COSIGN_PASSWORD environment variable.
+The keys are compatible with cosign."#,
+        )
+        .arg(
+            Arg::new("key-type")
+                .long("key-type")
+                .value_name("VALUE")
+                .value_parser(PossibleValuesParser::new(["ecdsa-p256", "ecdsa-p384", "ed25519"]))
+                .default_value("ecdsa-p256")
+                .help("Type of the key pair"),
+        )
+        .arg(
+            Arg::new("output-key-prefix")
+                .long("output-key-prefix")
+                .value_name("PREFIX")
+                .default_value("cosign")
+                .help("Prefix of the files holding the private key ('<PREFIX>.key') and the public key ('<PREFIX>.pub')"),
+        )
+}
+
 fn subcommand_push() -> Command {
     let mut args = vec![
         Arg::new("docker-config-json-path")
@@ -338,6 +413,11 @@ fn run_args() -> Vec<Arg> {
             .long("allow-context-aware")
             .num_args(0)
             .help("Grant access to the Kubernetes resources defined inside of the policy's `contextAwareResources` section. Warning: review the list of resources carefully to avoid abuses. Disabled by default"),
+        Arg::new("allow-dry-run")
+            .long("allow-dry-run")
+            .value_delimiter(',')
+            .value_name("API_VERSION/KIND")
+            .help("Grant the `kubernetes/dry_run` host capability access to the given Kubernetes resources (e.g. `apps/v1/Deployment,v1/Pod`). The dry-run requests go through the admission webhooks of the cluster, Kubewarden ones included. Disabled by default"),
         Arg::new("record-host-capabilities-interactions")
             .long("record-host-capabilities-interactions")
             .value_name("FILE")
@@ -350,12 +430,60 @@ Useful to be combined later with '--replay-host-capabilities-interactions' flag"
             .long_help(r#"During policy and host capabilities exchanges
 the host replays back the answers found inside of the provided file.
 This is useful to test policies in a reproducible way, given no external
-interactions with OCI registries, DNS, Kubernetes are performed."#),
+interactions with OCI registries, DNS, Kubernetes are performed.
+
+By default the responses are looked up by the contents of the requests,
+hence the order and the number of the requests do not matter. The fields
+of the recorded requests can be removed or set to '*' to match any value.
+Session files created by older versions of kwctl are replayed sequentially."#),
+        Arg::new("kube-context-dir")
+            .long("kube-context-dir")
+            .value_name("DIR")
+            .long_help(r#"Serve the Kubernetes requests made by context aware policies
+using the resources defined inside of the YAML and JSON files found in
+the given directory. Files can contain multiple documents, including
+`List` ones. No connection to a Kubernetes cluster is made."#),
+        Arg::new("kube-context-can-i")
+            .long("kube-context-can-i")
+            .value_name("ANSWER")
+            .value_parser(PossibleValuesParser::new(["allow", "deny"]))
+            .default_value("allow")
+            .requires("kube-context-dir")
+            .help("The answer given to the `can_i` requests when using '--kube-context-dir'"),
+        Arg::new("dns-nameservers")
+            .long("dns-nameservers")
+            .value_delimiter(',')
+            .value_name("IP[:PORT]")
+            .help("Name servers queried by the DNS host capabilities, the port defaults to 53. Defaults to the name servers configured on the system"),
+        Arg::new("dns-timeout")
+            .long("dns-timeout")
+            .value_name("SECONDS")
+            .default_value("5")
+            .help("Timeout of the queries done by the DNS host capabilities"),
      ]
 }
 
 fn subcommand_run() -> Command {
     let mut args = run_args();
+    args.push(
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FORMAT")
+            .value_parser(PossibleValuesParser::new(["json", "mutated-object", "diff", "summary"]))
+            .default_value("json")
+            .long_help(r#"How the result of the evaluation is printed:
+- json: the AdmissionResponse, in JSON format
+- mutated-object: the object of the request, with the patch of the response applied, in YAML format
+- diff: a unified diff between the object of the request and the mutated one. Nothing is printed when the object is not mutated
+- summary: a human readable summary of the response, including its message, warnings and audit annotations"#),
+    );
+    args.push(
+        Arg::new("rego-trace")
+            .long("rego-trace")
+            .num_args(0)
+            .help("Show the messages produced by Rego policies via `print` and `trace`, plus the evaluated entrypoints. They are printed on the standard error"),
+    );
     args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
     args.push(
         Arg::new("uri_or_sha_prefix_or_yaml_file")
@@ -378,6 +506,7 @@ fn subcommand_run() -> Command {
             ArgGroup::new("host-capabilities-proxy").args([
                 "record-host-capabilities-interactions",
                 "replay-host-capabilities-interactions",
+                "kube-context-dir",
             ]),
         )
 }
@@ -449,6 +578,56 @@ fn subcommand_inspect() -> Command {
         .args(args)
 }
 
+fn subcommand_lint() -> Command {
+    let mut args = vec![
+        Arg::new("metadata-path")
+            .long("metadata-path")
+            .short('m')
+            .value_name("PATH")
+            .help("File containing the metadata, the one given to the 'annotate' command"),
+        Arg::new("questions-path")
+            .long("questions-path")
+            .value_name("PATH")
+            .help("File containing the questions-ui of the policy"),
+        Arg::new("settings-path")
+            .long("settings-path")
+            .short('s')
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .requires("questions-path")
+            .value_name("PATH")
+            .help("File containing example settings of the policy, checked against the questions-ui. Can be repeated multiple times"),
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FORMAT")
+            .value_parser(PossibleValuesParser::new(["text", "sarif"]))
+            .default_value("text")
+            .help("Output format"),
+    ];
+    args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    args.push(
+        Arg::new("uri_or_sha_prefix")
+            .index(1)
+            .help("Annotated policy URI or SHA prefix. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory."),
+    );
+
+    Command::new("lint")
+        .about("Checks the metadata of a Kubewarden policy for common mistakes")
+        .after_long_help(
+            r#"The metadata is read either from a metadata file or from an annotated policy.
+Besides the validation done by the 'annotate' command, the rules are checked for wildcards and overlaps, mutating policies must match CREATE or UPDATE operations, the recommended annotations must be set and the minimum Kubewarden version must be sensible.
+The context aware resources are looked up in the resource catalog cached by the 'scaffold admission-request' command.
+The command fails when errors are found. The SARIF output can be uploaded to code scanning UIs."#,
+        )
+        .args(args)
+        .group(
+            ArgGroup::new("metadata-source")
+                .args(["metadata-path", "uri_or_sha_prefix"])
+                .required(true),
+        )
+}
+
 fn subcommand_scaffold() -> Command {
     let mut artifacthub_args = vec![
         Arg::new("metadata-path")
@@ -480,7 +659,8 @@ fn subcommand_scaffold() -> Command {
             .long("settings-path")
             .short('s')
             .value_name("PATH")
-            .help("File containing the settings for this policy"),
+            .action(ArgAction::Append)
+            .help("File containing the settings for this policy. Policy groups take one file for each policy, in the same order as the policies"),
         Arg::new("settings-json")
             .long("settings-json")
             .value_name("VALUE")
@@ -491,12 +671,34 @@ fn subcommand_scaffold() -> Command {
             .short('t')
             .required(true)
             .value_name("VALUE")
-            .value_parser(PossibleValuesParser::new(["ClusterAdmissionPolicy", "AdmissionPolicy"]))
+            .value_parser(PossibleValuesParser::new([
+                "ClusterAdmissionPolicy",
+                "AdmissionPolicy",
+                "ClusterAdmissionPolicyGroup",
+                "AdmissionPolicyGroup",
+            ]))
             .help("Kubewarden Custom Resource type"),
+        Arg::new("member-name")
+            .long("member-name")
+            .value_name("NAME")
+            .action(ArgAction::Append)
+            .required_if_eq_any([("type", "ClusterAdmissionPolicyGroup"), ("type", "AdmissionPolicyGroup")])
+            .help("Name of a policy group member, used to reference it inside of the group expression. One for each policy, in the same order as the policies"),
+        Arg::new("expression")
+            .long("expression")
+            .value_name("VALUE")
+            .required_if_eq_any([("type", "ClusterAdmissionPolicyGroup"), ("type", "AdmissionPolicyGroup")])
+            .help("Expression of the policy group, combining its members. For example: `signed() && trusted_registry()`"),
+        Arg::new("message")
+            .long("message")
+            .value_name("VALUE")
+            .required_if_eq_any([("type", "ClusterAdmissionPolicyGroup"), ("type", "AdmissionPolicyGroup")])
+            .help("Message returned when the policy group expression rejects the request"),
         Arg::new("title")
             .long("title")
             .value_name("VALUE")
-            .help("Policy title"),
+            .required_if_eq_any([("type", "ClusterAdmissionPolicyGroup"), ("type", "AdmissionPolicyGroup")])
+            .help("Policy title. Required by policy groups, it's used as the name of the resource"),
         Arg::new("allow-context-aware")
             .long("allow-context-aware")
             .num_args(0)
@@ -509,7 +711,8 @@ fn subcommand_scaffold() -> Command {
         Arg::new("uri_or_sha_prefix")
             .required(true)
             .index(1)
-            .help("Policy URI or SHA prefix. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory."),
+            .num_args(1..)
+            .help("Policy URI or SHA prefix. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory. Policy groups take the URI of each of their policies"),
     );
 
     let mut vap_args = vec![
@@ -528,19 +731,51 @@ fn subcommand_scaffold() -> Command {
             .long("binding")
             .short('b')
             .required(true)
+            .action(ArgAction::Append)
+            .number_of_values(1)
             .value_name("VALIDATING-ADMISSION-POLICY-BINDING.yaml")
-            .help("The file containing the ValidatingAdmissionPolicyBinding definition"),
+            .help("The file containing the ValidatingAdmissionPolicyBinding definitions. A policy is generated for each binding. Can be repeated multiple times"),
+        Arg::new("param")
+            .long("param")
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .value_name("PARAM.yaml")
+            .help("The file containing the param objects referenced by the bindings. The param of each binding is inlined into the settings of its policy. Can be repeated multiple times"),
     ];
     vap_args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
 
+    let mut gatekeeper_args = vec![
+        Arg::new("module")
+            .long("module")
+            .short('m')
+            .required(true)
+            .value_name("URI")
+            .help("The policy module built from the Rego code of the ConstraintTemplate. The module must be annotated with the gatekeeper execution mode"),
+        Arg::new("template")
+            .long("template")
+            .short('t')
+            .required(true)
+            .value_name("CONSTRAINT-TEMPLATE.yaml")
+            .help("The file containing the ConstraintTemplate definition"),
+        Arg::new("constraint")
+            .long("constraint")
+            .short('c')
+            .required(true)
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .value_name("CONSTRAINT.yaml")
+            .help("The file containing the Constraint definitions. Can be repeated multiple times"),
+    ];
+    gatekeeper_args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+
     let mut admission_request_args = vec![
         Arg::new("operation")
             .long("operation")
             .short('o')
             .required(true)
             .value_name("TYPE")
-            .value_parser(PossibleValuesParser::new(["CREATE"])) //TODO: add UPDATE and DELETE
-            .help("Kubewarden Custom Resource type"),
+            .value_parser(PossibleValuesParser::new(["CREATE", "UPDATE", "DELETE"]))
+            .help("The operation of the AdmissionRequest"),
         Arg::new("object")
             .long("object")
             .value_name("PATH")
@@ -549,6 +784,29 @@ fn subcommand_scaffold() -> Command {
             .long("old-object")
             .value_name("PATH")
             .help("The file containing the existing object"),
+        Arg::new("username")
+            .long("username")
+            .value_name("NAME")
+            .default_value("test-user")
+            .help("The name of the user performing the request"),
+        Arg::new("group")
+            .long("group")
+            .value_name("NAME")
+            .action(ArgAction::Append)
+            .help("A group of the user performing the request. Can be repeated, defaults to `system:masters`"),
+        Arg::new("namespace")
+            .long("namespace")
+            .short('n')
+            .value_name("NAMESPACE")
+            .help("The namespace of the request. Overrides the namespace of the objects"),
+        Arg::new("subresource")
+            .long("subresource")
+            .value_name("NAME")
+            .help("The subresource being requested (e.g. `status`, `scale`, `exec`)"),
+        Arg::new("dry-run")
+            .long("dry-run")
+            .action(ArgAction::SetTrue)
+            .help("Scaffold a dry-run request"),
     ];
     admission_request_args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
 
@@ -564,6 +822,19 @@ fn subcommand_scaffold() -> Command {
         Command::new("vap")
             .about("Convert a Kubernetes `ValidatingAdmissionPolicy` into a Kubewarden `ClusterAdmissionPolicy`")
             .args(vap_args),
+        Command::new("gatekeeper")
+            .about("Convert a Gatekeeper `ConstraintTemplate` and its `Constraints` into Kubewarden `ClusterAdmissionPolicies`")
+            .long_about(r#"Convert a Gatekeeper `ConstraintTemplate` and its `Constraints` into Kubewarden `ClusterAdmissionPolicies`.
+
+A `ClusterAdmissionPolicy` is generated for each Constraint:
+- the parameters of the Constraint become the settings of the policy
+- the kinds matched by the Constraint become the rules of the policy, for CREATE and UPDATE operations. The resources are looked up inside of the resource catalog built by `scaffold admission-request`. When the catalog is not available, they are derived from the kinds: review the irregular ones
+- the namespaces, excluded namespaces and namespace selector of the Constraint become the namespace selector of the policy
+- the label selector of the Constraint becomes the object selector of the policy
+- the `dryrun` and `warn` enforcement actions become the monitor mode
+
+The Rego code of the ConstraintTemplate is not compiled: the policy references the given module, which must be built from it."#)
+            .args(gatekeeper_args),
         Command::new("admission-request")
             .about("Scaffold an AdmissionRequest object")
             .args(admission_request_args),
@@ -576,6 +847,62 @@ fn subcommand_scaffold() -> Command {
         .subcommands(subcommands)
 }
 
+fn subcommand_session() -> Command {
+    let mut merge_args = vec![Arg::new("output")
+        .long("output")
+        .short('o')
+        .value_name("FILE")
+        .help("Path where the merged session is stored. Printed to the standard output when not provided")];
+    merge_args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    merge_args.push(
+        Arg::new("sessions")
+            .required(true)
+            .num_args(1..)
+            .index(1)
+            .help("The session files to merge"),
+    );
+
+    let mut prune_args = vec![
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FILE")
+            .help("Path where the pruned session is stored. Printed to the standard output when not provided"),
+        Arg::new("request-type")
+            .long("request-type")
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .value_name("TYPE")
+            .help("Remove the exchanges of the given request type (e.g. `DNSLookupHost`). Can be repeated multiple times"),
+        Arg::new("errors")
+            .long("errors")
+            .num_args(0)
+            .help("Remove the exchanges whose response is an error"),
+    ];
+    prune_args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    prune_args.push(
+        Arg::new("session")
+            .required(true)
+            .index(1)
+            .help("The session file to prune"),
+    );
+
+    let mut subcommands = vec![
+        Command::new("merge")
+            .about("Merge multiple host capabilities sessions into a single one, removing the duplicated exchanges")
+            .args(merge_args),
+        Command::new("prune")
+            .about("Remove the duplicated exchanges of a host capabilities session, plus the ones matching the given filters")
+            .args(prune_args),
+    ];
+    subcommands.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+    Command::new("session")
+        .about("Manage the host capabilities session files used by '--replay-host-capabilities-interactions'")
+        .subcommand_required(true)
+        .subcommands(subcommands)
+}
+
 fn subcommand_digest() -> Command {
     let mut args = vec![
         Arg::new("sources-path")
@@ -612,7 +939,7 @@ fn subcommand_bench() -> Command {
             .long("num-samples")
             .number_of_values(1)
             .value_name("NUM")
-            .help("How many resamples should be done. Recommended at least 50, above 100 doesn't seem to yield a significantly different result"),
+            .help("How many resamples should be done. Recommended at least 50, above 100 doesn't seem to yield a significantly different result. When the results are reported as JSON or compared with a baseline, it's the number of samples collected for each operation"),
         Arg::new("warm_up_time")
             .long("warm-up-time")
             .number_of_values(1)
@@ -621,6 +948,44 @@ fn subcommand_bench() -> Command {
         Arg::new("dump_results_to_disk")
             .long("dump-results-to-disk")
             .help("Puts results in target/tiny-bench/label/.. if target can be found. used for comparing previous runs"),
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FORMAT")
+            .value_parser(PossibleValuesParser::new(["text", "json"]))
+            .default_value("text")
+            .help("Output format. The json report contains the latency statistics of each policy and can be used as baseline of later runs"),
+        Arg::new("baseline")
+            .long("baseline")
+            .number_of_values(1)
+            .value_name("PATH")
+            .help("JSON report of a previous run to compare the results with. kwctl exits with an error when the mean latency of an operation regresses by more than the regression threshold"),
+        Arg::new("concurrency")
+            .long("concurrency")
+            .number_of_values(1)
+            .value_name("NUM")
+            .conflicts_with("baseline")
+            .help("Load test the policy: requests are evaluated by the given number of workers at the same time, rehydrating the policy for each request like policy-server does. Throughput and latency percentiles are reported"),
+        Arg::new("duration")
+            .long("duration")
+            .number_of_values(1)
+            .value_name("SECONDS")
+            .requires("concurrency")
+            .help("How long the load test runs, warm up excluded. Defaults to 10 seconds"),
+        Arg::new("replay-request-path")
+            .long("replay-request-path")
+            .action(ArgAction::Append)
+            .number_of_values(1)
+            .value_name("PATH")
+            .requires("concurrency")
+            .help("Additional request replayed during the load test. The requests are evaluated round-robin, starting with the one given by --request-path. Can be repeated multiple times"),
+        Arg::new("regression_threshold")
+            .long("regression-threshold")
+            .number_of_values(1)
+            .value_name("PERCENT")
+            .default_value("10")
+            .requires("baseline")
+            .help("Maximum increase of the mean latency, in percent, tolerated when comparing with the baseline"),
     ];
     let mut run_args = run_args();
     args.append(&mut run_args);
@@ -646,10 +1011,133 @@ fn subcommand_bench() -> Command {
             ArgGroup::new("host-capabilities-proxy").args([
                 "record-host-capabilities-interactions",
                 "replay-host-capabilities-interactions",
+                "kube-context-dir",
             ]),
         )
 }
 
+fn subcommand_test_rego() -> Command {
+    let mut args = vec![
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FORMAT")
+            .value_parser(PossibleValuesParser::new(["text", "junit", "tap"]))
+            .default_value("text")
+            .help("Output format"),
+        Arg::new("data-path")
+            .long("data-path")
+            .value_name("PATH")
+            .help("YAML or JSON file holding the `data` document made available to the tests"),
+        Arg::new("run")
+            .long("run")
+            .value_name("REGEX")
+            .help("Run only the tests whose entrypoint matches the given regular expression"),
+    ];
+    args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    args.push(
+        Arg::new("uri_or_sha_prefix")
+            .required(true)
+            .index(1)
+            .help("Rego policy URI or SHA prefix. Supported schemes: registry://, https://, file://. If schema is omitted, file:// is assumed, rooted on the current directory."),
+    );
+
+    Command::new("test-rego")
+        .about("Runs the unit tests of a Rego policy compiled to WebAssembly")
+        .long_about(
+            r#"Runs the unit tests of a Rego policy compiled to WebAssembly.
+
+All the entrypoints whose rule name starts with `test_` are evaluated with an
+empty input. A test passes when its result is defined and is not `false`.
+
+The tests must be exported as entrypoints when building the policy, e.g.:
+
+  opa build -t wasm -e policy/test_allow -e policy/test_deny policy.rego policy_test.rego"#,
+        )
+        .args(args)
+}
+
+fn subcommand_test() -> Command {
+    let mut args = vec![
+        Arg::new("output")
+            .long("output")
+            .short('o')
+            .value_name("FORMAT")
+            .value_parser(PossibleValuesParser::new(["text", "junit", "tap"]))
+            .default_value("text")
+            .help("Output format"),
+        Arg::new("run")
+            .long("run")
+            .value_name("REGEX")
+            .help("Run only the tests whose name matches the given regular expression"),
+        Arg::new("docker-config-json-path")
+            .long("docker-config-json-path")
+            .value_name("PATH")
+            .help("Path to a directory containing the Docker 'config.json' file. Can be used to indicate registry authentication details"),
+        Arg::new("sources-path")
+            .long("sources-path")
+            .value_name("PATH")
+            .help("YAML file holding source information (https, registry insecure hosts, custom CA's...)"),
+        Arg::new("disable-wasmtime-cache")
+            .long("disable-wasmtime-cache")
+            .num_args(0)
+            .help("Turn off usage of wasmtime cache"),
+    ];
+    args.sort_by(|a, b| a.get_id().cmp(b.get_id()));
+    args.push(
+        Arg::new("test_file")
+            .required(true)
+            .index(1)
+            .help("YAML file defining the test cases"),
+    );
+
+    Command::new("test")
+        .about("Runs a suite of policy tests defined inside of a YAML file")
+        .long_about(
+            r#"Runs a suite of policy tests defined inside of a YAML file.
+
+Each test evaluates a request the same way `kwctl run` does, then compares
+the response of the policy with the expected one:
+
+  policy: registry://ghcr.io/kubewarden/policies/pod-privileged:v0.2.5
+  settings: {}
+  tests:
+    - name: reject privileged pods
+      request: privileged-pod.json
+      expect:
+        allowed: false
+        message: "privileged container is not allowed"
+    - name: accept unprivileged pods
+      object:
+        apiVersion: v1
+        kind: Pod
+        metadata:
+          name: nginx
+        spec:
+          containers:
+            - name: nginx
+              image: nginx
+      expect:
+        allowed: true
+
+The top level `policy` and `settings` are used by the tests that do not
+define their own. The policy can be a URI, a SHA prefix or a YAML file
+holding a Kubewarden policy resource.
+
+Each test evaluates either the AdmissionRequest or AdmissionReview found
+inside of the `request` file, or a CREATE AdmissionRequest of the inline
+`object`. A host capabilities `session` file, recorded with `kwctl run`,
+can be replayed during the evaluation. In that case the policy is granted
+access to the context aware resources listed inside of its metadata.
+
+The `expect` section checks whether the request is `allowed`, the `message`
+of the response using a regular expression, the JSON `patch` of the
+response and the `patchedObject` obtained by applying it. Relative paths
+are resolved starting from the directory of the test file."#,
+        )
+        .args(args)
+}
+
 fn subcommand_save() -> Command {
     Command::new("save")
         .about("save policies to a tar.gz file")
@@ -722,13 +1210,19 @@ pub fn build_cli() -> Command {
         subcommand_pull(),
         subcommand_verify(),
         subcommand_push(),
+        subcommand_sign(),
+        subcommand_generate_key_pair(),
         subcommand_run(),
         subcommand_annotate(),
         subcommand_inspect(),
+        subcommand_lint(),
         subcommand_scaffold(),
         subcommand_digest(),
         subcommand_bench(),
         subcommand_save(),
+        subcommand_test(),
+        subcommand_test_rego(),
+        subcommand_session(),
         subcommand_docs(),
     ];
     subcommands.sort_by(|a, b| a.get_name().cmp(b.get_name()));
@@ -1,23 +1,125 @@
-use std::time::Duration;
+use std::{fs, path::Path, time::Duration};
 
 use anyhow::{anyhow, Result};
 use clap::ArgMatches;
 
-use crate::config::pull_and_run::{parse_policy_definitions, parse_pull_and_run_settings};
+use crate::{
+    command::bench::{
+        load_test::LoadTestConfig,
+        report::{BenchReport, OutputType},
+        Baseline,
+    },
+    config::pull_and_run::{parse_policy_definitions, parse_pull_and_run_settings},
+};
 
 pub(crate) async fn exec(matches: &ArgMatches) -> Result<()> {
     let policy_definitions = parse_policy_definitions(matches)?;
     let pull_and_run_settings = parse_pull_and_run_settings(matches, &policy_definitions).await?;
     let benchmark_config = create_benchmark_config(matches)?;
+    let output = OutputType::try_from(matches.get_one::<String>("output").map(|s| s.as_str()))?;
+    let baseline = parse_baseline(matches)?;
+    let load_test_config = parse_load_test_config(matches, &benchmark_config)?;
+    // `--num-samples` is handed to tiny-bench as its number of resamples, the
+    // JSON report uses it as the number of collected samples instead
+    let report_samples =
+        parse_num_samples(matches)?.unwrap_or(tiny_bench::BenchmarkConfig::default().num_samples);
 
     crate::command::bench::exec(
         &policy_definitions,
         &pull_and_run_settings,
         &benchmark_config,
+        report_samples,
+        &output,
+        baseline.as_ref(),
+        load_test_config.as_ref(),
     )
     .await
 }
 
+fn parse_load_test_config(
+    matches: &ArgMatches,
+    benchmark_config: &tiny_bench::BenchmarkConfig,
+) -> Result<Option<LoadTestConfig>> {
+    let concurrency: usize = match matches.get_one::<String>("concurrency") {
+        Some(concurrency) => concurrency
+            .parse()
+            .map_err(|e| anyhow!("Cannot convert 'concurrency' to number: {:?}", e))?,
+        None => return Ok(None),
+    };
+    if concurrency == 0 {
+        return Err(anyhow!("'concurrency' must be greater than zero"));
+    }
+
+    let duration = match matches.get_one::<String>("duration") {
+        Some(duration) => {
+            let duration: u64 = duration
+                .parse()
+                .map_err(|e| anyhow!("Cannot convert 'duration' to seconds: {:?}", e))?;
+            Duration::from_secs(duration)
+        }
+        None => Duration::from_secs(10),
+    };
+    if duration.is_zero() {
+        return Err(anyhow!("'duration' must be greater than zero"));
+    }
+
+    let requests = matches
+        .get_many::<String>("replay-request-path")
+        .unwrap_or_default()
+        .map(|request_path| {
+            let request = fs::read_to_string(request_path)
+                .map_err(|e| anyhow!("Error opening request file {}; {}", request_path, e))?;
+            serde_json::from_str(&request)
+                .map_err(|e| anyhow!("Error parsing request file {}; {}", request_path, e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Some(LoadTestConfig {
+        concurrency,
+        duration,
+        warm_up_time: benchmark_config.warm_up_time,
+        requests,
+    }))
+}
+
+fn parse_baseline(matches: &ArgMatches) -> Result<Option<Baseline>> {
+    let baseline_path = match matches.get_one::<String>("baseline") {
+        Some(path) => Path::new(path),
+        None => return Ok(None),
+    };
+    let regression_threshold: f64 = matches
+        .get_one::<String>("regression_threshold")
+        .map(|threshold| {
+            threshold.parse().map_err(|e| {
+                anyhow!(
+                    "Cannot convert 'regression-threshold' to percentage: {:?}",
+                    e
+                )
+            })
+        })
+        .transpose()?
+        .unwrap_or(10.0);
+    if regression_threshold < 0.0 {
+        return Err(anyhow!("'regression-threshold' cannot be negative"));
+    }
+
+    Ok(Some(Baseline {
+        report: BenchReport::load(baseline_path)?,
+        regression_threshold,
+    }))
+}
+
+fn parse_num_samples(matches: &ArgMatches) -> Result<Option<usize>> {
+    matches
+        .get_one::<String>("num_samples")
+        .map(|num_samples| {
+            num_samples
+                .parse()
+                .map_err(|e| anyhow!("Cannot convert 'num-samples' to number: {:?}", e))
+        })
+        .transpose()
+}
+
 fn create_benchmark_config(matches: &ArgMatches) -> Result<tiny_bench::BenchmarkConfig> {
     let mut benchmark_cfg = tiny_bench::BenchmarkConfig::default();
 
@@ -33,10 +135,7 @@ fn create_benchmark_config(matches: &ArgMatches) -> Result<tiny_bench::Benchmark
             .map_err(|e| anyhow!("Cannot convert 'num-resamples' to number: {:?}", e))?;
         benchmark_cfg.num_resamples = num;
     }
-    if let Some(num_samples) = matches.get_one::<String>("num_samples") {
-        let num: usize = num_samples
-            .parse()
-            .map_err(|e| anyhow!("Cannot convert 'num-samples' to number: {:?}", e))?;
+    if let Some(num) = parse_num_samples(matches)? {
         benchmark_cfg.num_resamples = num;
     }
     if let Some(warm_up_time) = matches.get_one::<String>("warm_up_time") {
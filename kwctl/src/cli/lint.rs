@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::ArgMatches;
+
+use crate::{
+    command::lint::{report::OutputType, LintSettings, LintTarget},
+    scaffold::RESOURCE_CATALOG_FILE,
+};
+
+pub(crate) fn exec(matches: &ArgMatches) -> Result<()> {
+    let target = match matches.get_one::<String>("metadata-path") {
+        Some(metadata_path) => LintTarget::MetadataFile(PathBuf::from(metadata_path)),
+        None => LintTarget::Policy(
+            matches
+                .get_one::<String>("uri_or_sha_prefix")
+                .expect("either the metadata or the policy is required")
+                .to_owned(),
+        ),
+    };
+    let settings = LintSettings {
+        questions_path: matches
+            .get_one::<String>("questions-path")
+            .map(PathBuf::from),
+        settings_paths: matches
+            .get_many::<String>("settings-path")
+            .unwrap_or_default()
+            .map(PathBuf::from)
+            .collect(),
+        resource_catalog_path: RESOURCE_CATALOG_FILE.clone(),
+        output: OutputType::try_from(matches.get_one::<String>("output").map(|s| s.as_str()))?,
+    };
+
+    crate::command::lint::exec(&target, &settings)
+}
@@ -1,11 +1,28 @@
 use anyhow::Result;
 use clap::ArgMatches;
 
-use crate::config::pull_and_run::{parse_policy_definitions, parse_pull_and_run_settings};
+use crate::{
+    command::run::output::OutputType,
+    config::pull_and_run::{parse_policy_definitions, parse_pull_and_run_settings},
+};
 
-pub(crate) async fn exec(matches: &ArgMatches) -> Result<()> {
+pub(crate) async fn exec(matches: &ArgMatches, no_color: bool) -> Result<()> {
     let policy_definitions = parse_policy_definitions(matches)?;
     let pull_and_run_settings = parse_pull_and_run_settings(matches, &policy_definitions).await?;
 
-    crate::command::run::exec(&policy_definitions, &pull_and_run_settings).await
+    let rego_trace = matches
+        .get_one::<bool>("rego-trace")
+        .unwrap_or(&false)
+        .to_owned();
+
+    let output = OutputType::try_from(matches.get_one::<String>("output").map(|s| s.as_str()))?;
+
+    crate::command::run::exec(
+        &policy_definitions,
+        &pull_and_run_settings,
+        rego_trace,
+        &output,
+        no_color,
+    )
+    .await
 }
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::ArgMatches;
+
+use crate::{command::test::TestSettings, config::sources::remote_server_options, test_report};
+
+pub(crate) async fn exec(matches: &ArgMatches) -> Result<()> {
+    let test_file = PathBuf::from(matches.get_one::<String>("test_file").unwrap());
+    let sources = remote_server_options(matches)
+        .map_err(|e| anyhow!("Error getting remote server options: {}", e))?;
+    let enable_wasmtime_cache = !matches
+        .get_one::<bool>("disable-wasmtime-cache")
+        .unwrap_or(&false)
+        .to_owned();
+    let filter = matches
+        .get_one::<String>("run")
+        .map(|run| {
+            regex::Regex::new(run)
+                .map_err(|e| anyhow!("invalid regular expression '{}': {}", run, e))
+        })
+        .transpose()?;
+    let output =
+        test_report::OutputType::try_from(matches.get_one::<String>("output").map(|s| s.as_str()))?;
+
+    let settings = TestSettings {
+        sources,
+        enable_wasmtime_cache,
+        filter,
+        output,
+    };
+
+    crate::command::test::exec(&test_file, &settings).await
+}
@@ -1,2 +1,4 @@
 pub(crate) mod bench;
+pub(crate) mod lint;
 pub(crate) mod run;
+pub(crate) mod test;
@@ -1,39 +1,233 @@
+use std::time::Instant;
+
 use anyhow::{anyhow, Result};
 use tiny_bench::{bench_with_configuration_labeled, BenchmarkConfig};
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
+
+use tokio::{sync::oneshot, task::JoinHandle};
 
 use crate::{
-    command::run::{evaluator::Evaluator, local_data::LocalData},
+    command::{
+        bench::{
+            load_test::LoadTestConfig,
+            report::{sample, BenchReport, LoadTestReport, OutputType, PolicyReport},
+        },
+        run::{evaluator::Evaluator, local_data::LocalData},
+    },
     config::{policy_definition::PolicyDefinition, pull_and_run::PullAndRunSettings},
 };
 
+pub(crate) mod load_test;
+pub(crate) mod report;
+
+/// A previous benchmark report the current results are compared with
+pub(crate) struct Baseline {
+    pub report: BenchReport,
+    /// Maximum slowdown of the mean latency, in percent, before an operation
+    /// is considered regressed
+    pub regression_threshold: f64,
+}
+
 pub(crate) async fn exec(
     policy_definitions: &[PolicyDefinition],
     pull_and_run_settings: &PullAndRunSettings,
     benchmark_config: &BenchmarkConfig,
+    report_samples: usize,
+    output: &OutputType,
+    baseline: Option<&Baseline>,
+    load_test: Option<&LoadTestConfig>,
 ) -> Result<()> {
     let local_data = LocalData::new(policy_definitions, pull_and_run_settings).await?;
 
+    if let Some(load_test_config) = load_test {
+        let mut report = BenchReport::default();
+        for policy_definition in policy_definitions {
+            let load_test_report = pull_and_load_test(
+                policy_definition,
+                pull_and_run_settings,
+                &local_data,
+                load_test_config,
+            )
+            .await
+            .map_err(|e| anyhow!("[{}] - {}", policy_definition, e))?;
+            report.load_tests.push(load_test_report);
+        }
+        print!("{}", report.render(output)?);
+
+        return Ok(());
+    }
+
+    // The human readable output of tiny-bench is kept, unless the results
+    // have to be reported or compared with a baseline
+    let collect_report = matches!(output, OutputType::Json) || baseline.is_some();
+    let report_config = BenchmarkConfig {
+        measurement_time: benchmark_config.measurement_time,
+        warm_up_time: benchmark_config.warm_up_time,
+        num_samples: report_samples,
+        ..Default::default()
+    };
+
+    let mut report = BenchReport::default();
     for policy_definition in policy_definitions {
-        pull_and_bench(
+        let policy_report = pull_and_bench(
             policy_definition,
             pull_and_run_settings,
             &local_data,
-            benchmark_config,
+            if collect_report {
+                &report_config
+            } else {
+                benchmark_config
+            },
+            collect_report,
         )
         .await
         .map_err(|e| anyhow!("[{}] - {}", policy_definition, e))?;
+        report.policies.extend(policy_report);
+    }
+
+    if !collect_report {
+        return Ok(());
+    }
+
+    if let Some(baseline) = baseline {
+        report.compare(&baseline.report, baseline.regression_threshold);
+        for policy in &report.policies {
+            if !report
+                .comparisons
+                .iter()
+                .any(|comparison| comparison.policy == policy.policy)
+            {
+                warn!(
+                    policy = policy.policy,
+                    "policy not found inside of the baseline"
+                );
+            }
+        }
+    }
+    print!("{}", report.render(output)?);
+
+    let regressions = report.regressions();
+    if let (Some(baseline), false) = (baseline, regressions.is_empty()) {
+        return Err(anyhow!(
+            "{} benchmarked operations regressed by more than {}% compared to the baseline: {}",
+            regressions.len(),
+            baseline.regression_threshold,
+            regressions
+                .iter()
+                .map(|comparison| format!(
+                    "{} {} ({:+.2}%)",
+                    comparison.policy, comparison.operation, comparison.change_percent
+                ))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
     }
 
     Ok(())
 }
 
+/// Benchmark the given policy. When `collect_report` is set, the statistics
+/// of the policy are returned instead of being printed by tiny-bench.
 pub(crate) async fn pull_and_bench(
     policy_definition: &PolicyDefinition,
     pull_and_run_settings: &PullAndRunSettings,
     local_data: &LocalData,
     benchmark_config: &BenchmarkConfig,
-) -> Result<()> {
+    collect_report: bool,
+) -> Result<Option<PolicyReport>> {
+    let instantiation_start = Instant::now();
+    let (mut evaluator, handler, shutdown_channel_tx) =
+        start_evaluator(policy_definition, pull_and_run_settings, local_data).await?;
+    let instantiation = instantiation_start.elapsed();
+
+    let policy_report = if collect_report {
+        // We have to wrap the benchmarks in a `tokio::task::block_in_place` context
+        // because if the policy uses context aware functions, this would lead to blocking the
+        // tokio runtime. Remember, we're running inside of an async context.
+        tokio::task::block_in_place(|| {
+            Ok(Some(PolicyReport {
+                policy: policy_id(policy_definition),
+                instantiation_ns: u64::try_from(instantiation.as_nanos()).unwrap_or(u64::MAX),
+                validate_settings: sample(benchmark_config, || evaluator.validate_settings())?,
+                validate: sample(benchmark_config, || evaluator.evaluate())?,
+            }))
+        })
+    } else {
+        // We have to wrap the settings validation in a `tokio::task::block_in_place` context
+        // because if the policy uses context aware functions, this would lead to blocking the
+        // tokio runtime. Remember, we're running inside of an async context.
+        tokio::task::block_in_place(|| {
+            bench_with_configuration_labeled("validate_settings", benchmark_config, || {
+                let _settings_validation_response = evaluator.validate_settings();
+            });
+        });
+
+        // We have to wrap the evaluation code inside of a `tokio::task::block_in_place` context
+        // because if the policy uses context aware functions, this would lead to blocking the
+        // tokio runtime. Remember, we're running inside of an async context.
+        tokio::task::block_in_place(|| {
+            bench_with_configuration_labeled("validate", benchmark_config, || {
+                let _evaluation_result = evaluator.evaluate();
+            });
+        });
+        Ok(None)
+    };
+
+    stop_callback_handler(handler, shutdown_channel_tx).await;
+
+    policy_report
+}
+
+/// Load test the given policy, evaluating requests from multiple workers
+/// at the same time
+pub(crate) async fn pull_and_load_test(
+    policy_definition: &PolicyDefinition,
+    pull_and_run_settings: &PullAndRunSettings,
+    local_data: &LocalData,
+    load_test_config: &LoadTestConfig,
+) -> Result<LoadTestReport> {
+    let (evaluator, handler, shutdown_channel_tx) =
+        start_evaluator(policy_definition, pull_and_run_settings, local_data).await?;
+    let shared_evaluator = evaluator.shared();
+
+    let load_test_report = std::iter::once(&pull_and_run_settings.request)
+        .chain(load_test_config.requests.iter())
+        .map(|request| shared_evaluator.validate_request(request))
+        .collect::<Result<Vec<_>>>()
+        .and_then(|requests| {
+            // We have to wrap the load test in a `tokio::task::block_in_place` context
+            // because if the policy uses context aware functions, this would lead to blocking the
+            // tokio runtime. Remember, we're running inside of an async context.
+            tokio::task::block_in_place(|| {
+                load_test::run(
+                    &policy_id(policy_definition),
+                    &shared_evaluator,
+                    &requests,
+                    load_test_config,
+                )
+            })
+        });
+
+    stop_callback_handler(handler, shutdown_channel_tx).await;
+
+    load_test_report
+}
+
+fn policy_id(policy_definition: &PolicyDefinition) -> String {
+    match policy_definition {
+        PolicyDefinition::Policy { id, .. } | PolicyDefinition::PolicyGroup { id, .. } => {
+            id.clone()
+        }
+    }
+}
+
+/// Build the evaluator of the policy, start its callback handler and
+/// validate the settings given by the user
+async fn start_evaluator(
+    policy_definition: &PolicyDefinition,
+    pull_and_run_settings: &PullAndRunSettings,
+    local_data: &LocalData,
+) -> Result<(Evaluator, JoinHandle<()>, oneshot::Sender<()>)> {
     let (mut evaluator, callback_handler, shutdown_channel_tx) =
         Evaluator::new(policy_definition, pull_and_run_settings, local_data).await?;
 
@@ -55,24 +249,10 @@ pub(crate) async fn pull_and_bench(
         ));
     }
 
-    // We have to wrap the settings validation in a `tokio::task::block_in_place` context
-    // because if the policy uses context aware functions, this would lead to blocking the
-    // tokio runtime. Remember, we're running inside of an async context.
-    tokio::task::block_in_place(|| {
-        bench_with_configuration_labeled("validate_settings", benchmark_config, || {
-            let _settings_validation_response = evaluator.validate_settings();
-        });
-    });
-
-    // We have to wrap the evaluation code inside of a `tokio::task::block_in_place` context
-    // because if the policy uses context aware functions, this would lead to blocking the
-    // tokio runtime. Remember, we're running inside of an async context.
-    tokio::task::block_in_place(|| {
-        bench_with_configuration_labeled("validate", benchmark_config, || {
-            let _evaluation_result = evaluator.evaluate();
-        });
-    });
+    Ok((evaluator, handler, shutdown_channel_tx))
+}
 
+async fn stop_callback_handler(handler: JoinHandle<()>, shutdown_channel_tx: oneshot::Sender<()>) {
     if shutdown_channel_tx.send(()).is_err() {
         error!("Cannot shut down the CallbackHandler task");
     } else if let Err(e) = handler.await {
@@ -81,6 +261,4 @@ pub(crate) async fn pull_and_bench(
             "Error waiting for the CallbackHandler task"
         );
     }
-
-    Ok(())
 }
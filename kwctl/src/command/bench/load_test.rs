@@ -0,0 +1,176 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_evaluator::ValidateRequest;
+use tracing::warn;
+
+use crate::command::{
+    bench::report::{LoadTestReport, Statistics},
+    run::evaluator::SharedEvaluator,
+};
+
+/// Settings of the load test
+pub(crate) struct LoadTestConfig {
+    /// The number of workers evaluating requests at the same time
+    pub concurrency: usize,
+    /// How long the requests are measured
+    pub duration: Duration,
+    /// How long the workers run before the requests are measured
+    pub warm_up_time: Duration,
+    /// Requests replayed round-robin, in addition to the one given by the
+    /// pull and run settings
+    pub requests: Vec<serde_json::Value>,
+}
+
+/// Evaluate the requests from `concurrency` threads for the configured
+/// duration.
+///
+/// Like policy-server does, each evaluation rehydrates a new policy instance
+/// out of the shared `PolicyEvaluatorPre`. The host capabilities requests of
+/// all the workers are served by the same callback handler.
+pub(crate) fn run(
+    policy: &str,
+    evaluator: &SharedEvaluator,
+    requests: &[ValidateRequest],
+    config: &LoadTestConfig,
+) -> Result<LoadTestReport> {
+    if requests.is_empty() {
+        return Err(anyhow!("No request to evaluate"));
+    }
+    if config.concurrency == 0 {
+        return Err(anyhow!("The concurrency must be greater than zero"));
+    }
+
+    let next_request = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    let samples = Mutex::new(Vec::new());
+
+    let start = Instant::now();
+    let measurement_start = start + config.warm_up_time;
+    let end = measurement_start + config.duration;
+
+    std::thread::scope(|scope| {
+        for _ in 0..config.concurrency {
+            scope.spawn(|| {
+                let mut worker_samples = Vec::new();
+                loop {
+                    let request_start = Instant::now();
+                    if request_start >= end {
+                        break;
+                    }
+                    let request =
+                        &requests[next_request.fetch_add(1, Ordering::Relaxed) % requests.len()];
+                    let result = evaluator.evaluate(request);
+                    if request_start < measurement_start {
+                        continue;
+                    }
+
+                    match result {
+                        Ok(_) => worker_samples.push(request_start.elapsed()),
+                        Err(e) => {
+                            if errors.fetch_add(1, Ordering::Relaxed) == 0 {
+                                warn!(error = e.to_string(), "cannot evaluate request");
+                            }
+                        }
+                    }
+                }
+                samples
+                    .lock()
+                    .expect("cannot lock samples")
+                    .append(&mut worker_samples);
+            });
+        }
+    });
+
+    let duration = measurement_start.elapsed();
+    let samples = samples.into_inner().expect("cannot lock samples");
+    let latency = Statistics::from_samples(&samples)
+        .map_err(|_| anyhow!("No request could be evaluated during the load test"))?;
+
+    Ok(LoadTestReport {
+        policy: policy.to_string(),
+        concurrency: config.concurrency,
+        duration_ns: u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX),
+        requests: samples.len(),
+        errors: errors.load(Ordering::Relaxed),
+        throughput_rps: samples.len() as f64 / duration.as_secs_f64(),
+        latency,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{path::Path, sync::Arc};
+
+    use policy_evaluator::{
+        evaluation_context::EvaluationContext,
+        policy_evaluator::{PolicyExecutionMode, PolicySettings},
+        policy_evaluator_builder::PolicyEvaluatorBuilder,
+    };
+
+    fn shared_evaluator() -> SharedEvaluator {
+        let policy_evaluator_pre = PolicyEvaluatorBuilder::new()
+            .policy_file(Path::new(
+                "tests/data/rego-annotate/no-default-namespace-rego.wasm",
+            ))
+            .expect("cannot read policy")
+            .execution_mode(PolicyExecutionMode::Opa)
+            .build_pre()
+            .expect("cannot build policy evaluator");
+
+        SharedEvaluator::Policy {
+            policy_evaluator_pre: Arc::new(policy_evaluator_pre),
+            eval_ctx: EvaluationContext::default(),
+            settings: PolicySettings::default(),
+            raw: false,
+        }
+    }
+
+    fn requests(evaluator: &SharedEvaluator) -> Vec<ValidateRequest> {
+        let request: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("tests/data/unprivileged-pod.json").unwrap(),
+        )
+        .unwrap();
+        vec![evaluator
+            .validate_request(&request)
+            .expect("cannot build request")]
+    }
+
+    fn config(concurrency: usize) -> LoadTestConfig {
+        LoadTestConfig {
+            concurrency,
+            duration: Duration::from_millis(200),
+            warm_up_time: Duration::from_millis(50),
+            requests: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn load_test_policy() {
+        let evaluator = shared_evaluator();
+
+        let report = run("my-policy", &evaluator, &requests(&evaluator), &config(2))
+            .expect("load test failed");
+
+        assert_eq!(report.policy, "my-policy");
+        assert_eq!(report.concurrency, 2);
+        assert_eq!(report.errors, 0);
+        assert!(report.requests > 0);
+        assert_eq!(report.latency.samples, report.requests);
+        assert!(report.duration_ns >= Duration::from_millis(200).as_nanos() as u64);
+        assert!(report.throughput_rps > 0.0);
+    }
+
+    #[test]
+    fn load_test_without_requests() {
+        assert!(run("my-policy", &shared_evaluator(), &[], &config(2)).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    convert::TryFrom,
+    fmt::Write,
+    fs::File,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tiny_bench::{black_box, BenchmarkConfig};
+
+/// How the results of the benchmark are printed
+pub(crate) enum OutputType {
+    /// The human readable output of tiny-bench
+    Text,
+    /// A JSON report with the statistics of each policy
+    Json,
+}
+
+impl TryFrom<Option<&str>> for OutputType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("text") | None => Ok(Self::Text),
+            Some("json") => Ok(Self::Json),
+            Some(unknown) => Err(anyhow!("Invalid output format '{}'", unknown)),
+        }
+    }
+}
+
+/// Latency statistics of a benchmarked operation. All the times are
+/// expressed in nanoseconds
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Statistics {
+    pub samples: usize,
+    pub mean_ns: f64,
+    pub stddev_ns: f64,
+    pub min_ns: u64,
+    pub p50_ns: u64,
+    pub p95_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+}
+
+impl Statistics {
+    /// Compute the statistics of the given samples
+    pub fn from_samples(samples: &[Duration]) -> Result<Self> {
+        if samples.is_empty() {
+            return Err(anyhow!("Cannot compute statistics without samples"));
+        }
+
+        let mut samples: Vec<u64> = samples
+            .iter()
+            .map(|sample| u64::try_from(sample.as_nanos()).unwrap_or(u64::MAX))
+            .collect();
+        samples.sort_unstable();
+
+        let count = samples.len() as f64;
+        let mean = samples.iter().map(|sample| *sample as f64).sum::<f64>() / count;
+        let variance = samples
+            .iter()
+            .map(|sample| (*sample as f64 - mean).powi(2))
+            .sum::<f64>()
+            / count;
+        // nearest-rank percentile
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * count).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+
+        Ok(Self {
+            samples: samples.len(),
+            mean_ns: mean,
+            stddev_ns: variance.sqrt(),
+            min_ns: samples[0],
+            p50_ns: percentile(50.0),
+            p95_ns: percentile(95.0),
+            p99_ns: percentile(99.0),
+            max_ns: samples[samples.len() - 1],
+        })
+    }
+}
+
+/// Time each invocation of the closure, honoring the warm up time, the
+/// measurement time and the number of samples of the given configuration.
+///
+/// Like tiny-bench does, the number of samples is prioritized over the
+/// measurement time.
+pub(crate) fn sample<T, F: FnMut() -> T>(
+    benchmark_config: &BenchmarkConfig,
+    mut closure: F,
+) -> Result<Statistics> {
+    let warm_up_start = Instant::now();
+    while warm_up_start.elapsed() < benchmark_config.warm_up_time {
+        black_box(closure());
+    }
+
+    let mut samples = Vec::with_capacity(benchmark_config.num_samples);
+    let measurement_start = Instant::now();
+    while samples.len() < benchmark_config.num_samples.max(1)
+        || measurement_start.elapsed() < benchmark_config.measurement_time
+    {
+        let start = Instant::now();
+        black_box(closure());
+        samples.push(start.elapsed());
+    }
+
+    Statistics::from_samples(&samples)
+}
+
+/// The benchmark results of a policy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct PolicyReport {
+    /// The id of the policy
+    pub policy: String,
+    /// The time spent loading and instantiating the Wasm module, measured once
+    pub instantiation_ns: u64,
+    pub validate_settings: Statistics,
+    pub validate: Statistics,
+}
+
+/// The results of the load test of a policy
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct LoadTestReport {
+    /// The id of the policy
+    pub policy: String,
+    /// The number of workers evaluating requests at the same time
+    pub concurrency: usize,
+    /// How long the requests have been measured, warm up excluded
+    pub duration_ns: u64,
+    /// The number of evaluated requests
+    pub requests: usize,
+    /// The number of requests that could not be evaluated
+    pub errors: usize,
+    /// Evaluated requests per second
+    pub throughput_rps: f64,
+    /// The latency of each request, rehydration of the policy included
+    pub latency: Statistics,
+}
+
+/// The comparison of a benchmarked operation against the baseline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Comparison {
+    pub policy: String,
+    pub operation: String,
+    pub baseline_mean_ns: f64,
+    pub mean_ns: f64,
+    /// The change of the mean latency, in percent. A positive value means
+    /// the operation became slower
+    pub change_percent: f64,
+    /// Whether the change exceeds the regression threshold
+    pub regressed: bool,
+}
+
+/// The results of a `kwctl bench` run, in a format that can be used as
+/// baseline by later runs
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub(crate) struct BenchReport {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policies: Vec<PolicyReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub load_tests: Vec<LoadTestReport>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comparisons: Vec<Comparison>,
+}
+
+impl BenchReport {
+    /// Load a report previously produced by `kwctl bench --output json`
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Cannot open baseline file {}: {}", path.display(), e))?;
+        serde_json::from_reader(file)
+            .map_err(|e| anyhow!("Cannot parse baseline file {}: {}", path.display(), e))
+    }
+
+    /// Compare the mean latency of each operation with the one of the baseline.
+    ///
+    /// Operations slower than the baseline by more than `threshold_percent`
+    /// are flagged as regressed. Policies missing from the baseline are
+    /// not compared.
+    pub fn compare(&mut self, baseline: &BenchReport, threshold_percent: f64) {
+        self.comparisons = self
+            .policies
+            .iter()
+            .filter_map(|policy| {
+                baseline
+                    .policies
+                    .iter()
+                    .find(|baseline_policy| baseline_policy.policy == policy.policy)
+                    .map(|baseline_policy| (policy, baseline_policy))
+            })
+            .flat_map(|(policy, baseline_policy)| {
+                [
+                    (
+                        "validate_settings",
+                        &policy.validate_settings,
+                        &baseline_policy.validate_settings,
+                    ),
+                    ("validate", &policy.validate, &baseline_policy.validate),
+                ]
+                .into_iter()
+                .map(|(operation, statistics, baseline_statistics)| {
+                    let change_percent = if baseline_statistics.mean_ns > 0.0 {
+                        (statistics.mean_ns - baseline_statistics.mean_ns)
+                            / baseline_statistics.mean_ns
+                            * 100.0
+                    } else {
+                        0.0
+                    };
+                    Comparison {
+                        policy: policy.policy.clone(),
+                        operation: operation.to_string(),
+                        baseline_mean_ns: baseline_statistics.mean_ns,
+                        mean_ns: statistics.mean_ns,
+                        change_percent,
+                        regressed: change_percent > threshold_percent,
+                    }
+                })
+            })
+            .collect();
+    }
+
+    /// The comparisons exceeding the regression threshold
+    pub fn regressions(&self) -> Vec<&Comparison> {
+        self.comparisons
+            .iter()
+            .filter(|comparison| comparison.regressed)
+            .collect()
+    }
+
+    pub fn render(&self, output: &OutputType) -> Result<String> {
+        match output {
+            OutputType::Json => Ok(format!("{}\n", serde_json::to_string_pretty(self)?)),
+            OutputType::Text => self.to_text(),
+        }
+    }
+
+    fn to_text(&self) -> Result<String> {
+        let mut text = String::new();
+        for policy in &self.policies {
+            writeln!(text, "{}", policy.policy)?;
+            writeln!(
+                text,
+                "  instantiation      {}",
+                format_ns(policy.instantiation_ns as f64)
+            )?;
+            for (operation, statistics) in [
+                ("validate_settings", &policy.validate_settings),
+                ("validate", &policy.validate),
+            ] {
+                writeln!(
+                    text,
+                    "  {operation:<18} mean {} p50 {} p95 {} p99 {} ({} samples)",
+                    format_ns(statistics.mean_ns),
+                    format_ns(statistics.p50_ns as f64),
+                    format_ns(statistics.p95_ns as f64),
+                    format_ns(statistics.p99_ns as f64),
+                    statistics.samples
+                )?;
+            }
+        }
+        for load_test in &self.load_tests {
+            writeln!(text, "{}", load_test.policy)?;
+            writeln!(
+                text,
+                "  {} requests in {} with {} workers, {} errors",
+                load_test.requests,
+                format_ns(load_test.duration_ns as f64),
+                load_test.concurrency,
+                load_test.errors
+            )?;
+            writeln!(
+                text,
+                "  throughput         {:.2} requests/s",
+                load_test.throughput_rps
+            )?;
+            writeln!(
+                text,
+                "  latency            mean {} p50 {} p95 {} p99 {} max {}",
+                format_ns(load_test.latency.mean_ns),
+                format_ns(load_test.latency.p50_ns as f64),
+                format_ns(load_test.latency.p95_ns as f64),
+                format_ns(load_test.latency.p99_ns as f64),
+                format_ns(load_test.latency.max_ns as f64)
+            )?;
+        }
+        for comparison in &self.comparisons {
+            writeln!(
+                text,
+                "{} {} {}: {} -> {} ({:+.2}%)",
+                if comparison.regressed {
+                    "REGRESSED"
+                } else {
+                    "ok"
+                },
+                comparison.policy,
+                comparison.operation,
+                format_ns(comparison.baseline_mean_ns),
+                format_ns(comparison.mean_ns),
+                comparison.change_percent
+            )?;
+        }
+
+        Ok(text)
+    }
+}
+
+fn format_ns(ns: f64) -> String {
+    if ns >= 1_000_000_000.0 {
+        format!("{:.2}s", ns / 1_000_000_000.0)
+    } else if ns >= 1_000_000.0 {
+        format!("{:.2}ms", ns / 1_000_000.0)
+    } else if ns >= 1_000.0 {
+        format!("{:.2}µs", ns / 1_000.0)
+    } else {
+        format!("{ns:.0}ns")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rstest::rstest;
+
+    fn statistics(mean_ns: f64) -> Statistics {
+        Statistics {
+            samples: 100,
+            mean_ns,
+            stddev_ns: 0.0,
+            min_ns: 0,
+            p50_ns: 0,
+            p95_ns: 0,
+            p99_ns: 0,
+            max_ns: 0,
+        }
+    }
+
+    fn report(policy: &str, validate_mean_ns: f64) -> BenchReport {
+        BenchReport {
+            policies: vec![PolicyReport {
+                policy: policy.to_string(),
+                instantiation_ns: 1_000_000,
+                validate_settings: statistics(1_000.0),
+                validate: statistics(validate_mean_ns),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compute_statistics() {
+        let samples: Vec<Duration> = (1..=100).map(Duration::from_nanos).collect();
+
+        let statistics = Statistics::from_samples(&samples).expect("cannot compute statistics");
+
+        assert_eq!(statistics.samples, 100);
+        assert_eq!(statistics.mean_ns, 50.5);
+        assert_eq!(statistics.min_ns, 1);
+        assert_eq!(statistics.p50_ns, 50);
+        assert_eq!(statistics.p95_ns, 95);
+        assert_eq!(statistics.p99_ns, 99);
+        assert_eq!(statistics.max_ns, 100);
+        assert!((statistics.stddev_ns - 28.866).abs() < 0.001);
+    }
+
+    #[test]
+    fn compute_statistics_without_samples() {
+        assert!(Statistics::from_samples(&[]).is_err());
+    }
+
+    #[rstest]
+    #[case::faster(900.0, 10.0, false)]
+    #[case::within_threshold(1_050.0, 10.0, false)]
+    #[case::regressed(1_200.0, 10.0, true)]
+    #[case::zero_threshold(1_001.0, 0.0, true)]
+    fn compare_with_baseline(
+        #[case] validate_mean_ns: f64,
+        #[case] threshold: f64,
+        #[case] regressed: bool,
+    ) {
+        let baseline = report("my-policy", 1_000.0);
+        let mut report = report("my-policy", validate_mean_ns);
+
+        report.compare(&baseline, threshold);
+
+        assert_eq!(report.comparisons.len(), 2);
+        assert!(!report.comparisons[0].regressed);
+        assert_eq!(report.comparisons[1].operation, "validate");
+        assert_eq!(report.comparisons[1].regressed, regressed);
+        assert_eq!(report.regressions().len(), usize::from(regressed));
+    }
+
+    #[test]
+    fn compare_with_baseline_of_other_policies() {
+        let baseline = report("another-policy", 1_000.0);
+        let mut report = report("my-policy", 2_000.0);
+
+        report.compare(&baseline, 10.0);
+
+        assert!(report.comparisons.is_empty());
+        assert!(report.regressions().is_empty());
+    }
+
+    #[test]
+    fn baseline_round_trip() {
+        let mut report = report("my-policy", 2_000.0);
+        report.compare(&report.clone(), 10.0);
+
+        let json = report.render(&OutputType::Json).expect("cannot render");
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("baseline.json");
+        std::fs::write(&path, json).unwrap();
+
+        assert_eq!(BenchReport::load(&path).expect("cannot load"), report);
+    }
+}
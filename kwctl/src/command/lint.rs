@@ -0,0 +1,805 @@
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::{
+    constants::*,
+    kube,
+    policy_metadata::{Metadata, Operation, PolicyType, Rule},
+    validator::Validate,
+    ProtocolVersion,
+};
+use tracing::debug;
+
+use crate::{
+    backend::KUBEWARDEN_VERSION,
+    command::lint::report::{Finding, Level, LintReport, LintRule, Location, OutputType},
+    scaffold::ApiResourceCatalog,
+};
+
+pub(crate) mod report;
+
+/// Annotations that are not mandatory, but are expected by Artifact Hub and
+/// by the Kubewarden UI
+const RECOMMENDED_ANNOTATIONS: [&str; 5] = [
+    KUBEWARDEN_ANNOTATION_POLICY_TITLE,
+    KUBEWARDEN_ANNOTATION_POLICY_SEVERITY,
+    KUBEWARDEN_ANNOTATION_POLICY_CATEGORY,
+    KUBEWARDEN_ANNOTATION_POLICY_SOURCE,
+    KUBEWARDEN_ANNOTATION_POLICY_LICENSE,
+];
+
+/// The metadata to be linted
+pub(crate) enum LintTarget {
+    /// A `metadata.yml` file, the one given to `kwctl annotate`
+    MetadataFile(PathBuf),
+    /// The URI or the SHA prefix of an annotated policy
+    Policy(String),
+}
+
+impl LintTarget {
+    /// Load the metadata, together with the location the findings are
+    /// reported against
+    fn load(&self) -> Result<(Metadata, Location)> {
+        match self {
+            LintTarget::MetadataFile(path) => {
+                let file =
+                    File::open(path).map_err(|e| anyhow!("Error opening metadata file: {}", e))?;
+                let metadata: Metadata = serde_yaml::from_reader(file)
+                    .map_err(|e| anyhow!("Error unmarshalling metadata {}", e))?;
+                Ok((metadata, Location::File(path.display().to_string())))
+            }
+            LintTarget::Policy(uri_or_sha_prefix) => {
+                let uri = crate::utils::get_uri(uri_or_sha_prefix)?;
+                let wasm_path = crate::utils::wasm_path(&uri)?;
+                let metadata = Metadata::from_path(&wasm_path)?.ok_or_else(|| {
+                    anyhow!(
+                        "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
+                        uri
+                    )
+                })?;
+                Ok((metadata, Location::Policy(uri)))
+            }
+        }
+    }
+}
+
+pub(crate) struct LintSettings {
+    /// The questions-ui file used by the Kubewarden UI to configure the policy
+    pub questions_path: Option<PathBuf>,
+    /// Example settings of the policy, checked against the questions-ui file
+    pub settings_paths: Vec<PathBuf>,
+    /// The resource catalog the context aware resources are looked up in
+    pub resource_catalog_path: PathBuf,
+    pub output: OutputType,
+}
+
+/// Lint the metadata of a policy, then print the findings.
+///
+/// Returns an error when at least one of the findings is an error.
+pub(crate) fn exec(target: &LintTarget, settings: &LintSettings) -> Result<()> {
+    let (metadata, location) = target.load()?;
+    debug!(target = %location, "linting policy metadata");
+
+    let report = lint(&metadata, &location, settings)?;
+    print!("{}", report.render(&settings.output)?);
+
+    let errors = report.count(Level::Error);
+    if errors > 0 {
+        Err(anyhow!(
+            "{} error(s) found while linting {}",
+            errors,
+            location
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn lint(metadata: &Metadata, location: &Location, settings: &LintSettings) -> Result<LintReport> {
+    let mut findings = Vec::new();
+    check_metadata(metadata, location, &mut findings);
+    check_rules(metadata, location, &mut findings);
+    check_mutating(metadata, location, &mut findings);
+    check_context_aware_resources(
+        metadata,
+        location,
+        &settings.resource_catalog_path,
+        &mut findings,
+    );
+    check_annotations(metadata, location, &mut findings);
+    check_minimum_kubewarden_version(metadata, location, &mut findings);
+    if let Some(questions_path) = &settings.questions_path {
+        check_questions(questions_path, &settings.settings_paths, &mut findings)?;
+    }
+
+    Ok(LintReport { findings })
+}
+
+fn check_metadata(metadata: &Metadata, location: &Location, findings: &mut Vec<Finding>) {
+    let mut metadata = metadata.clone();
+    // metadata.yml files usually do not have a protocol version, it's set by
+    // `kwctl annotate`
+    if metadata.protocol_version.is_none() {
+        metadata.protocol_version = Some(ProtocolVersion::V1);
+    }
+
+    if let Err(e) = metadata.validate() {
+        findings.push(Finding::new(
+            LintRule::InvalidMetadata,
+            format!("the metadata is not valid: {e}"),
+            location,
+        ));
+    }
+}
+
+fn check_rules(metadata: &Metadata, location: &Location, findings: &mut Vec<Finding>) {
+    for (index, rule) in metadata.rules.iter().enumerate() {
+        let number = index + 1;
+        if let Some(resource) = rule
+            .resources
+            .iter()
+            .find(|resource| *resource == "*" || *resource == "*/*")
+        {
+            findings.push(Finding::new(
+                LintRule::WildcardResources,
+                format!("rule #{number} matches all the resources ('{resource}')"),
+                location,
+            ));
+        }
+        if rule.api_groups.iter().any(|group| group == "*") {
+            findings.push(Finding::new(
+                LintRule::WildcardApiGroups,
+                format!("rule #{number} matches all the API groups"),
+                location,
+            ));
+        }
+        if rule.api_versions.iter().any(|version| version == "*") {
+            findings.push(Finding::new(
+                LintRule::WildcardApiVersions,
+                format!("rule #{number} matches all the API versions"),
+                location,
+            ));
+        }
+        if rule.operations.contains(&Operation::All) {
+            findings.push(Finding::new(
+                LintRule::WildcardOperations,
+                format!("rule #{number} matches all the operations, including DELETE and CONNECT"),
+                location,
+            ));
+        }
+    }
+
+    for (index, rule) in metadata.rules.iter().enumerate() {
+        for (other_index, other) in metadata.rules.iter().enumerate().skip(index + 1) {
+            if rules_overlap(rule, other) {
+                findings.push(Finding::new(
+                    LintRule::OverlappingRules,
+                    format!(
+                        "rules #{} and #{} match some of the same requests, they can be merged or narrowed",
+                        index + 1,
+                        other_index + 1
+                    ),
+                    location,
+                ));
+            }
+        }
+    }
+}
+
+/// Whether there is a request matched by both the rules
+fn rules_overlap(rule: &Rule, other: &Rule) -> bool {
+    let values_intersect = |values: &[String], others: &[String]| {
+        values.iter().any(|value| {
+            others
+                .iter()
+                .any(|other| value == "*" || other == "*" || value == other)
+        })
+    };
+    let operations_intersect = rule.operations.iter().any(|operation| {
+        other.operations.iter().any(|other_operation| {
+            *operation == Operation::All
+                || *other_operation == Operation::All
+                || operation == other_operation
+        })
+    });
+    let resources_match = rule.resources.iter().any(|resource| {
+        other
+            .resources
+            .iter()
+            .any(|other_resource| resources_intersect(resource, other_resource))
+    });
+
+    values_intersect(&rule.api_groups, &other.api_groups)
+        && values_intersect(&rule.api_versions, &other.api_versions)
+        && resources_match
+        && operations_intersect
+}
+
+/// Whether two resources of the rules match a common resource, following the
+/// Kubernetes semantics: `*` matches all the resources but not their
+/// subresources, `pods/*` matches all the subresources of pods and `*/*`
+/// matches everything
+fn resources_intersect(resource: &str, other: &str) -> bool {
+    if resource == "*/*" || other == "*/*" {
+        return true;
+    }
+
+    let parts_match =
+        |part: &str, other_part: &str| part == "*" || other_part == "*" || part == other_part;
+    match (resource.split_once('/'), other.split_once('/')) {
+        (None, None) => parts_match(resource, other),
+        (Some((resource, subresource)), Some((other, other_subresource))) => {
+            parts_match(resource, other) && parts_match(subresource, other_subresource)
+        }
+        _ => false,
+    }
+}
+
+fn check_mutating(metadata: &Metadata, location: &Location, findings: &mut Vec<Finding>) {
+    if !metadata.mutating || metadata.policy_type != PolicyType::Kubernetes {
+        return;
+    }
+
+    let mutates = metadata.rules.iter().any(|rule| {
+        rule.operations.iter().any(|operation| {
+            matches!(
+                operation,
+                Operation::Create | Operation::Update | Operation::All
+            )
+        })
+    });
+    if !mutates {
+        findings.push(Finding::new(
+            LintRule::MutatingWithoutCreateOrUpdate,
+            "the policy is mutating, but none of its rules matches CREATE or UPDATE operations",
+            location,
+        ));
+    }
+}
+
+fn check_context_aware_resources(
+    metadata: &Metadata,
+    location: &Location,
+    resource_catalog_path: &Path,
+    findings: &mut Vec<Finding>,
+) {
+    if metadata.context_aware_resources.is_empty() {
+        return;
+    }
+
+    let catalog = match ApiResourceCatalog::from_cache(resource_catalog_path) {
+        Ok(Some(catalog)) if !catalog.is_empty() => catalog,
+        Ok(_) => {
+            findings.push(Finding::new(
+                LintRule::ResourceCatalogMissing,
+                format!(
+                    "the context aware resources cannot be checked, the resource catalog {} does not exist or is empty. It is created by `kwctl scaffold admission-request` when a Kubernetes cluster can be reached",
+                    resource_catalog_path.display()
+                ),
+                location,
+            ));
+            return;
+        }
+        Err(e) => {
+            findings.push(Finding::new(
+                LintRule::ResourceCatalogMissing,
+                format!("the context aware resources cannot be checked: {e}"),
+                location,
+            ));
+            return;
+        }
+    };
+
+    for resource in &metadata.context_aware_resources {
+        let (group, version) = resource
+            .api_version
+            .split_once('/')
+            .unwrap_or(("", resource.api_version.as_str()));
+        let gvk = kube::api::GroupVersionKind {
+            group: group.to_owned(),
+            version: version.to_owned(),
+            kind: resource.kind.clone(),
+        };
+        if catalog.lookup(&gvk).is_none() {
+            findings.push(Finding::new(
+                LintRule::UnknownContextAwareResource,
+                format!(
+                    "the context aware resource {} ({}) is not known by the cluster the resource catalog was built from",
+                    resource.kind, resource.api_version
+                ),
+                location,
+            ));
+        }
+    }
+}
+
+fn check_annotations(metadata: &Metadata, location: &Location, findings: &mut Vec<Finding>) {
+    for annotation in RECOMMENDED_ANNOTATIONS {
+        let value = metadata
+            .annotations
+            .as_ref()
+            .and_then(|annotations| annotations.get(annotation));
+        if value.is_none_or(|value| value.trim().is_empty()) {
+            findings.push(Finding::new(
+                LintRule::MissingAnnotation,
+                format!("the recommended annotation {annotation} is not set"),
+                location,
+            ));
+        }
+    }
+}
+
+fn check_minimum_kubewarden_version(
+    metadata: &Metadata,
+    location: &Location,
+    findings: &mut Vec<Finding>,
+) {
+    let Some(version) = &metadata.minimum_kubewarden_version else {
+        return;
+    };
+
+    if !version.pre.is_empty() {
+        findings.push(Finding::new(
+            LintRule::MinimumKubewardenVersion,
+            format!("minimumKubewardenVersion {version} is a pre-release, its pre-release identifier is ignored"),
+            location,
+        ));
+    }
+    if (version.major, version.minor) > (KUBEWARDEN_VERSION.major, KUBEWARDEN_VERSION.minor) {
+        findings.push(Finding::new(
+            LintRule::MinimumKubewardenVersion,
+            format!(
+                "minimumKubewardenVersion {version} is newer than this kwctl ({}), the policy cannot be run by it",
+                *KUBEWARDEN_VERSION
+            ),
+            location,
+        ));
+    }
+    if version.patch != 0 {
+        findings.push(
+            Finding::new(
+                LintRule::MinimumKubewardenVersion,
+                format!("minimumKubewardenVersion {version} has a patch number, it is ignored because only the major and minor numbers are compared"),
+                location,
+            )
+            .with_level(Level::Note),
+        );
+    }
+}
+
+/// Check the questions-ui file is consistent with the settings of the given
+/// examples: every top level setting must be configurable through a
+/// question, every question should configure a setting used by an example
+fn check_questions(
+    questions_path: &Path,
+    settings_paths: &[PathBuf],
+    findings: &mut Vec<Finding>,
+) -> Result<()> {
+    let questions_location = Location::File(questions_path.display().to_string());
+    let variables = match read_questions_variables(questions_path) {
+        Ok(variables) => variables,
+        Err(e) => {
+            findings.push(Finding::new(
+                LintRule::QuestionsUiInvalid,
+                e.to_string(),
+                &questions_location,
+            ));
+            return Ok(());
+        }
+    };
+
+    let mut used_settings = BTreeSet::new();
+    for settings_path in settings_paths {
+        let settings_location = Location::File(settings_path.display().to_string());
+        for setting in read_settings_keys(settings_path)? {
+            let prefix = format!("{setting}.");
+            if !variables
+                .iter()
+                .any(|variable| *variable == setting || variable.starts_with(&prefix))
+            {
+                findings.push(Finding::new(
+                    LintRule::QuestionsUiMissingSetting,
+                    format!("the setting '{setting}' has no question in {questions_location}"),
+                    &settings_location,
+                ));
+            }
+            used_settings.insert(setting);
+        }
+    }
+
+    if settings_paths.is_empty() {
+        return Ok(());
+    }
+    for variable in &variables {
+        let setting = variable.split('.').next().unwrap_or(variable);
+        if !used_settings.contains(setting) {
+            findings.push(Finding::new(
+                LintRule::QuestionsUiUnusedQuestion,
+                format!("the question '{variable}' is not used by any of the settings examples"),
+                &questions_location,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect the variables configured by the questions, including the ones of
+/// their subquestions
+fn read_questions_variables(questions_path: &Path) -> Result<BTreeSet<String>> {
+    let contents = fs::read_to_string(questions_path)
+        .map_err(|e| anyhow!("cannot read the questions-ui file: {}", e))?;
+    let document: serde_yaml::Value = serde_yaml::from_str(&contents)
+        .map_err(|e| anyhow!("cannot parse the questions-ui file: {}", e))?;
+    let questions = document
+        .get("questions")
+        .and_then(|questions| questions.as_sequence())
+        .ok_or_else(|| anyhow!("the questions-ui file does not have a list of questions"))?;
+
+    fn collect(questions: &[serde_yaml::Value], variables: &mut BTreeSet<String>) {
+        for question in questions {
+            if let Some(variable) = question.get("variable").and_then(|v| v.as_str()) {
+                variables.insert(variable.to_owned());
+            }
+            if let Some(subquestions) = question
+                .get("subquestions")
+                .and_then(|subquestions| subquestions.as_sequence())
+            {
+                collect(subquestions, variables);
+            }
+        }
+    }
+
+    let mut variables = BTreeSet::new();
+    collect(questions, &mut variables);
+    Ok(variables)
+}
+
+/// The top level keys of a settings file, either in YAML or JSON format
+fn read_settings_keys(settings_path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(settings_path)
+        .map_err(|e| anyhow!("cannot read {}: {}", settings_path.display(), e))?;
+    let settings: serde_yaml::Value = serde_yaml::from_str(&contents)
+        .map_err(|e| anyhow!("cannot parse {}: {}", settings_path.display(), e))?;
+
+    match settings {
+        serde_yaml::Value::Null => Ok(Vec::new()),
+        serde_yaml::Value::Mapping(mapping) => Ok(mapping
+            .keys()
+            .filter_map(|key| key.as_str().map(str::to_owned))
+            .collect()),
+        _ => Err(anyhow!(
+            "the settings of {} must be an object",
+            settings_path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    fn location() -> Location {
+        Location::File("metadata.yml".to_string())
+    }
+
+    fn metadata(yaml: &str) -> Metadata {
+        serde_yaml::from_str(yaml).expect("invalid metadata")
+    }
+
+    fn rule_ids(findings: &[Finding]) -> Vec<&'static str> {
+        findings.iter().map(|finding| finding.rule.id()).collect()
+    }
+
+    #[test]
+    fn lint_invalid_metadata() {
+        let metadata = metadata(
+            r#"
+rules:
+- apiGroups: [""]
+  apiVersions: ["v1"]
+  resources: ["*", "pods"]
+  operations: ["CREATE"]
+mutating: false
+"#,
+        );
+        let mut findings = Vec::new();
+
+        check_metadata(&metadata, &location(), &mut findings);
+
+        assert_eq!(rule_ids(&findings), vec!["invalid-metadata"]);
+        assert_eq!(findings[0].level, Level::Error);
+    }
+
+    #[test]
+    fn lint_metadata_without_protocol_version() {
+        let metadata = metadata(
+            r#"
+rules:
+- apiGroups: [""]
+  apiVersions: ["v1"]
+  resources: ["pods"]
+  operations: ["CREATE"]
+mutating: false
+"#,
+        );
+        let mut findings = Vec::new();
+
+        check_metadata(&metadata, &location(), &mut findings);
+
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn lint_wildcard_rules() {
+        let metadata = metadata(
+            r#"
+rules:
+- apiGroups: ["*"]
+  apiVersions: ["*"]
+  resources: ["*"]
+  operations: ["*"]
+mutating: false
+"#,
+        );
+        let mut findings = Vec::new();
+
+        check_rules(&metadata, &location(), &mut findings);
+
+        assert_eq!(
+            rule_ids(&findings),
+            vec![
+                "wildcard-resources",
+                "wildcard-api-groups",
+                "wildcard-api-versions",
+                "wildcard-operations"
+            ]
+        );
+        assert_eq!(findings[3].level, Level::Note);
+    }
+
+    #[rstest]
+    #[case::same_resource(&["pods"], &["pods"], true)]
+    #[case::different_resources(&["pods"], &["services"], false)]
+    #[case::asterisk(&["*"], &["pods"], true)]
+    #[case::asterisk_and_subresource(&["*"], &["pods/status"], false)]
+    #[case::subresources_asterisk(&["pods/*"], &["pods/status"], true)]
+    #[case::subresources_asterisk_and_resource(&["pods/*"], &["pods"], false)]
+    #[case::any_resource_subresource(&["*/status"], &["pods/status"], true)]
+    #[case::double_asterisk(&["*/*"], &["pods"], true)]
+    fn lint_overlapping_resources(
+        #[case] resources: &[&str],
+        #[case] other_resources: &[&str],
+        #[case] overlap: bool,
+    ) {
+        let rule = |resources: &[&str]| Rule {
+            api_groups: vec!["".to_owned()],
+            api_versions: vec!["v1".to_owned()],
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            operations: vec![Operation::Create],
+        };
+
+        assert_eq!(
+            rules_overlap(&rule(resources), &rule(other_resources)),
+            overlap
+        );
+    }
+
+    #[test]
+    fn lint_overlapping_rules() {
+        let metadata = metadata(
+            r#"
+rules:
+- apiGroups: ["apps"]
+  apiVersions: ["v1"]
+  resources: ["deployments"]
+  operations: ["CREATE"]
+- apiGroups: ["apps"]
+  apiVersions: ["v1"]
+  resources: ["deployments"]
+  operations: ["DELETE"]
+- apiGroups: ["apps"]
+  apiVersions: ["v1"]
+  resources: ["deployments", "replicasets"]
+  operations: ["CREATE", "UPDATE"]
+mutating: false
+"#,
+        );
+        let mut findings = Vec::new();
+
+        check_rules(&metadata, &location(), &mut findings);
+
+        assert_eq!(rule_ids(&findings), vec!["overlapping-rules"]);
+        assert!(findings[0].message.starts_with("rules #1 and #3"));
+    }
+
+    #[rstest]
+    #[case::create(r#"["CREATE"]"#, true, false)]
+    #[case::delete(r#"["DELETE"]"#, true, true)]
+    #[case::asterisk(r#"["*"]"#, true, false)]
+    #[case::not_mutating(r#"["DELETE"]"#, false, false)]
+    fn lint_mutating_policy(
+        #[case] operations: &str,
+        #[case] mutating: bool,
+        #[case] expect_finding: bool,
+    ) {
+        let metadata = metadata(&format!(
+            r#"
+rules:
+- apiGroups: [""]
+  apiVersions: ["v1"]
+  resources: ["pods"]
+  operations: {operations}
+mutating: {mutating}
+"#
+        ));
+        let mut findings = Vec::new();
+
+        check_mutating(&metadata, &location(), &mut findings);
+
+        assert_eq!(!findings.is_empty(), expect_finding);
+    }
+
+    #[test]
+    fn lint_context_aware_resources() {
+        let tempdir = tempdir().unwrap();
+        let catalog_path = tempdir.path().join("resource_catalog.json");
+        let metadata = metadata(
+            r#"
+rules: []
+mutating: false
+contextAwareResources:
+- apiVersion: v1
+  kind: Namespace
+- apiVersion: apps/v1
+  kind: Deployment
+- apiVersion: example.com/v1
+  kind: Unknown
+"#,
+        );
+
+        let mut findings = Vec::new();
+        check_context_aware_resources(&metadata, &location(), &catalog_path, &mut findings);
+        assert_eq!(rule_ids(&findings), vec!["resource-catalog-missing"]);
+        assert_eq!(findings[0].level, Level::Note);
+
+        fs::write(
+            &catalog_path,
+            serde_json::json!({
+                "resources": {
+                    "|v1|Namespace": {
+                        "name": "namespaces",
+                        "singularName": "namespace",
+                        "namespaced": false,
+                        "kind": "Namespace",
+                        "verbs": ["get", "list"],
+                    },
+                    "apps|v1|Deployment": {
+                        "name": "deployments",
+                        "singularName": "deployment",
+                        "namespaced": true,
+                        "kind": "Deployment",
+                        "verbs": ["get", "list"],
+                    },
+                },
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut findings = Vec::new();
+        check_context_aware_resources(&metadata, &location(), &catalog_path, &mut findings);
+        assert_eq!(rule_ids(&findings), vec!["unknown-context-aware-resource"]);
+        assert!(findings[0].message.contains("Unknown (example.com/v1)"));
+    }
+
+    #[test]
+    fn lint_annotations() {
+        let metadata = metadata(
+            r#"
+rules: []
+mutating: false
+annotations:
+  io.kubewarden.policy.title: my-policy
+  io.kubewarden.policy.severity: medium
+  io.kubewarden.policy.category: PSP
+  io.kubewarden.policy.source: ""
+"#,
+        );
+        let mut findings = Vec::new();
+
+        check_annotations(&metadata, &location(), &mut findings);
+
+        let messages: Vec<&str> = findings.iter().map(|f| f.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "the recommended annotation io.kubewarden.policy.source is not set",
+                "the recommended annotation io.kubewarden.policy.license is not set",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::released("1.10.0", vec![])]
+    #[case::patch("1.10.2", vec![Level::Note])]
+    #[case::pre_release("1.10.0-rc1", vec![Level::Warning])]
+    #[case::newer_than_kwctl("99.0.0", vec![Level::Warning])]
+    fn lint_minimum_kubewarden_version(#[case] version: &str, #[case] levels: Vec<Level>) {
+        let metadata = metadata(&format!(
+            r#"
+rules: []
+mutating: false
+minimumKubewardenVersion: {version}
+"#
+        ));
+        let mut findings = Vec::new();
+
+        check_minimum_kubewarden_version(&metadata, &location(), &mut findings);
+
+        let found: Vec<Level> = findings.iter().map(|f| f.level).collect();
+        assert_eq!(found, levels);
+    }
+
+    #[test]
+    fn lint_questions() {
+        let tempdir = tempdir().unwrap();
+        let questions_path = tempdir.path().join("questions-ui.yml");
+        fs::write(
+            &questions_path,
+            r#"
+questions:
+- variable: allowed_registries
+  type: array[
+- variable: ignore
+  type: boolean
+  subquestions:
+  - variable: ignore.namespaces
+    type: array[
+- variable: unused
+  type: string
+"#,
+        )
+        .unwrap();
+        let settings_path = tempdir.path().join("settings.json");
+        fs::write(
+            &settings_path,
+            r#"{"allowed_registries": ["ghcr.io"], "ignore": {"namespaces": ["kube-system"]}, "mode": "strict"}"#,
+        )
+        .unwrap();
+        let mut findings = Vec::new();
+
+        check_questions(&questions_path, &[settings_path], &mut findings)
+            .expect("cannot check the questions");
+
+        assert_eq!(
+            rule_ids(&findings),
+            vec![
+                "questions-ui-missing-setting",
+                "questions-ui-unused-question"
+            ]
+        );
+        assert!(findings[0].message.contains("'mode'"));
+        assert!(findings[1].message.contains("'unused'"));
+    }
+
+    #[test]
+    fn lint_invalid_questions() {
+        let tempdir = tempdir().unwrap();
+        let questions_path = tempdir.path().join("questions-ui.yml");
+        fs::write(&questions_path, "title: no questions here").unwrap();
+        let mut findings = Vec::new();
+
+        check_questions(&questions_path, &[], &mut findings).expect("cannot check the questions");
+
+        assert_eq!(rule_ids(&findings), vec!["questions-ui-invalid"]);
+        assert_eq!(findings[0].level, Level::Error);
+    }
+}
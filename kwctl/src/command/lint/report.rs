@@ -0,0 +1,372 @@
+use std::{convert::TryFrom, fmt, fmt::Write};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::json;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+
+/// How the findings of the linter are printed
+pub(crate) enum OutputType {
+    /// A human readable list of findings
+    Text,
+    /// A SARIF 2.1.0 log, understood by code scanning UIs
+    Sarif,
+}
+
+impl TryFrom<Option<&str>> for OutputType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("text") | None => Ok(Self::Text),
+            Some("sarif") => Ok(Self::Sarif),
+            Some(unknown) => Err(anyhow!("Invalid output format '{}'", unknown)),
+        }
+    }
+}
+
+/// How serious a finding is. The names match the SARIF levels
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Level {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Level {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Note => "note",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// The checks performed by the linter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LintRule {
+    InvalidMetadata,
+    WildcardResources,
+    WildcardApiGroups,
+    WildcardApiVersions,
+    WildcardOperations,
+    OverlappingRules,
+    MutatingWithoutCreateOrUpdate,
+    UnknownContextAwareResource,
+    ResourceCatalogMissing,
+    MissingAnnotation,
+    MinimumKubewardenVersion,
+    QuestionsUiInvalid,
+    QuestionsUiMissingSetting,
+    QuestionsUiUnusedQuestion,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 14] = [
+        LintRule::InvalidMetadata,
+        LintRule::WildcardResources,
+        LintRule::WildcardApiGroups,
+        LintRule::WildcardApiVersions,
+        LintRule::WildcardOperations,
+        LintRule::OverlappingRules,
+        LintRule::MutatingWithoutCreateOrUpdate,
+        LintRule::UnknownContextAwareResource,
+        LintRule::ResourceCatalogMissing,
+        LintRule::MissingAnnotation,
+        LintRule::MinimumKubewardenVersion,
+        LintRule::QuestionsUiInvalid,
+        LintRule::QuestionsUiMissingSetting,
+        LintRule::QuestionsUiUnusedQuestion,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            LintRule::InvalidMetadata => "invalid-metadata",
+            LintRule::WildcardResources => "wildcard-resources",
+            LintRule::WildcardApiGroups => "wildcard-api-groups",
+            LintRule::WildcardApiVersions => "wildcard-api-versions",
+            LintRule::WildcardOperations => "wildcard-operations",
+            LintRule::OverlappingRules => "overlapping-rules",
+            LintRule::MutatingWithoutCreateOrUpdate => "mutating-without-create-or-update",
+            LintRule::UnknownContextAwareResource => "unknown-context-aware-resource",
+            LintRule::ResourceCatalogMissing => "resource-catalog-missing",
+            LintRule::MissingAnnotation => "missing-annotation",
+            LintRule::MinimumKubewardenVersion => "minimum-kubewarden-version",
+            LintRule::QuestionsUiInvalid => "questions-ui-invalid",
+            LintRule::QuestionsUiMissingSetting => "questions-ui-missing-setting",
+            LintRule::QuestionsUiUnusedQuestion => "questions-ui-unused-question",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LintRule::InvalidMetadata => "The metadata does not pass the Kubewarden validation",
+            LintRule::WildcardResources => "A rule matches all the resources",
+            LintRule::WildcardApiGroups => "A rule matches all the API groups",
+            LintRule::WildcardApiVersions => "A rule matches all the API versions",
+            LintRule::WildcardOperations => "A rule matches all the operations",
+            LintRule::OverlappingRules => "Two rules match the same requests",
+            LintRule::MutatingWithoutCreateOrUpdate => {
+                "A mutating policy is not evaluated on CREATE or UPDATE operations"
+            }
+            LintRule::UnknownContextAwareResource => {
+                "A context aware resource is not known by the Kubernetes cluster"
+            }
+            LintRule::ResourceCatalogMissing => {
+                "The context aware resources cannot be checked without the resource catalog"
+            }
+            LintRule::MissingAnnotation => "A recommended annotation is missing",
+            LintRule::MinimumKubewardenVersion => "The minimum Kubewarden version is suspicious",
+            LintRule::QuestionsUiInvalid => "The questions-ui file cannot be used",
+            LintRule::QuestionsUiMissingSetting => {
+                "A setting used by the examples has no question in the questions-ui file"
+            }
+            LintRule::QuestionsUiUnusedQuestion => {
+                "A question of the questions-ui file is not used by any example"
+            }
+        }
+    }
+
+    /// The level the findings of this rule are reported with, unless stated
+    /// otherwise
+    pub fn default_level(&self) -> Level {
+        match self {
+            LintRule::InvalidMetadata
+            | LintRule::MutatingWithoutCreateOrUpdate
+            | LintRule::QuestionsUiInvalid => Level::Error,
+            LintRule::WildcardOperations
+            | LintRule::ResourceCatalogMissing
+            | LintRule::QuestionsUiUnusedQuestion => Level::Note,
+            _ => Level::Warning,
+        }
+    }
+}
+
+/// What a finding is about
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Location {
+    /// A local file, like the `metadata.yml` one
+    File(String),
+    /// An annotated policy, identified by its URI. The file its metadata
+    /// has been read from is not known
+    Policy(String),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::File(path) => write!(f, "{path}"),
+            Location::Policy(uri) => write!(f, "{uri}"),
+        }
+    }
+}
+
+impl Location {
+    /// The SARIF location. Policies are not files of the analyzed repository,
+    /// they are reported as logical locations
+    fn to_sarif(&self) -> serde_json::Value {
+        match self {
+            Location::File(path) => json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": path },
+                },
+            }),
+            Location::Policy(uri) => json!({
+                "logicalLocations": [{
+                    "fullyQualifiedName": uri,
+                    "kind": "module",
+                }],
+            }),
+        }
+    }
+}
+
+/// A problem found by the linter
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Finding {
+    pub rule: LintRule,
+    pub level: Level,
+    pub message: String,
+    /// What the finding is about
+    pub location: Location,
+}
+
+impl Finding {
+    pub fn new(rule: LintRule, message: impl Into<String>, location: &Location) -> Self {
+        Self {
+            rule,
+            level: rule.default_level(),
+            message: message.into(),
+            location: location.clone(),
+        }
+    }
+
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+}
+
+/// The findings of a lint run
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LintReport {
+    pub findings: Vec<Finding>,
+}
+
+impl LintReport {
+    pub fn count(&self, level: Level) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| finding.level == level)
+            .count()
+    }
+
+    /// Render the report using the given format
+    pub fn render(&self, output: &OutputType) -> Result<String> {
+        match output {
+            OutputType::Text => Ok(self.to_text()),
+            OutputType::Sarif => Ok(serde_json::to_string_pretty(&self.to_sarif())?),
+        }
+    }
+
+    /// Render the report in a human readable format
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for finding in &self.findings {
+            let _ = writeln!(
+                text,
+                "{}[{}]: {}\n  --> {}",
+                finding.level.as_str(),
+                finding.rule.id(),
+                finding.message,
+                finding.location
+            );
+        }
+
+        let _ = writeln!(
+            text,
+            "{} error(s), {} warning(s), {} note(s)",
+            self.count(Level::Error),
+            self.count(Level::Warning),
+            self.count(Level::Note)
+        );
+
+        text
+    }
+
+    /// Render the report as a SARIF 2.1.0 log
+    pub fn to_sarif(&self) -> serde_json::Value {
+        let rules: Vec<serde_json::Value> = LintRule::ALL
+            .iter()
+            .map(|rule| {
+                json!({
+                    "id": rule.id(),
+                    "shortDescription": { "text": rule.description() },
+                    "defaultConfiguration": { "level": rule.default_level() },
+                })
+            })
+            .collect();
+        let results: Vec<serde_json::Value> = self
+            .findings
+            .iter()
+            .map(|finding| {
+                json!({
+                    "ruleId": finding.rule.id(),
+                    "ruleIndex": LintRule::ALL.iter().position(|rule| *rule == finding.rule),
+                    "level": finding.level,
+                    "message": { "text": finding.message },
+                    "locations": [finding.location.to_sarif()],
+                })
+            })
+            .collect();
+
+        json!({
+            "$schema": SARIF_SCHEMA,
+            "version": SARIF_VERSION,
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "kwctl",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://github.com/kubewarden/kwctl",
+                        "rules": rules,
+                    },
+                },
+                "results": results,
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> LintReport {
+        LintReport {
+            findings: vec![
+                Finding::new(
+                    LintRule::WildcardResources,
+                    "rule #1 matches all the resources",
+                    &Location::File("metadata.yml".to_string()),
+                ),
+                Finding::new(
+                    LintRule::MutatingWithoutCreateOrUpdate,
+                    "the policy is mutating",
+                    &Location::File("metadata.yml".to_string()),
+                ),
+                Finding::new(
+                    LintRule::MissingAnnotation,
+                    "the recommended annotation is not set",
+                    &Location::Policy("registry://ghcr.io/kubewarden/policy:v1.0.0".to_string()),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn render_text() {
+        let text = report().to_text();
+
+        assert_eq!(
+            text,
+            "warning[wildcard-resources]: rule #1 matches all the resources\n  --> metadata.yml\n\
+             error[mutating-without-create-or-update]: the policy is mutating\n  --> metadata.yml\n\
+             warning[missing-annotation]: the recommended annotation is not set\n  --> registry://ghcr.io/kubewarden/policy:v1.0.0\n\
+             1 error(s), 2 warning(s), 0 note(s)\n"
+        );
+    }
+
+    #[test]
+    fn render_sarif() {
+        let sarif = report().to_sarif();
+
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "kwctl");
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+        assert_eq!(rules.len(), LintRule::ALL.len());
+
+        let result = &run["results"][1];
+        assert_eq!(result["ruleId"], "mutating-without-create-or-update");
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "the policy is mutating");
+        assert_eq!(
+            result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "metadata.yml"
+        );
+        let rule_index = result["ruleIndex"].as_u64().unwrap() as usize;
+        assert_eq!(rules[rule_index]["id"], result["ruleId"]);
+
+        let location = &run["results"][2]["locations"][0];
+        assert!(location.get("physicalLocation").is_none());
+        assert_eq!(
+            location["logicalLocations"][0]["fullyQualifiedName"],
+            "registry://ghcr.io/kubewarden/policy:v1.0.0"
+        );
+    }
+}
@@ -1,19 +1,30 @@
+use std::io;
+
 use anyhow::{anyhow, Result};
-use policy_evaluator::admission_response_handler::AdmissionResponseHandler;
+use is_terminal::IsTerminal;
+use policy_evaluator::{
+    admission_response::AdmissionResponse,
+    admission_response_handler::AdmissionResponseHandler,
+    burrego::trace::{self, TraceEvent, TraceEventKind},
+};
 use tracing::{error, warn};
 
 use crate::{
-    command::run::{evaluator::Evaluator, local_data::LocalData},
+    command::run::{evaluator::Evaluator, local_data::LocalData, output::OutputType},
     config::{policy_definition::PolicyDefinition, pull_and_run::PullAndRunSettings},
 };
 
 pub(crate) mod evaluator;
 pub(crate) mod local_data;
+pub(crate) mod output;
 pub(crate) mod policy_execution_mode;
 
 pub(crate) async fn exec(
     policy_definitions: &[PolicyDefinition],
     pull_and_run_settings: &PullAndRunSettings,
+    rego_trace: bool,
+    output: &OutputType,
+    no_color: bool,
 ) -> Result<()> {
     let local_data = LocalData::new(policy_definitions, pull_and_run_settings).await?;
 
@@ -21,50 +32,158 @@ pub(crate) async fn exec(
         warn!("Multiple policies defined inside of the CRD file. All of them will run sequentially using the same request.");
     }
 
+    let color = !no_color && io::stdout().is_terminal();
     for policy_definition in policy_definitions {
-        let (mut evaluator, callback_handler, shutdown_channel_tx) =
-            Evaluator::new(policy_definition, pull_and_run_settings, &local_data).await?;
-
-        // start the callback handler
-        let handler = tokio::spawn(async { callback_handler.loop_eval().await });
-
-        // We have to wrap the evaluation code inside of a `tokio::task::block_in_place` context
-        // because if the policy uses context aware functions, this would lead to blocking the
-        // tokio runtime. Remember, we're running inside of an async context.
-        let evaluation_result = tokio::task::block_in_place(move || {
-            // validate the settings given by the user
-            let settings_validation_response = evaluator.validate_settings();
-            if !settings_validation_response.valid {
-                return Err(anyhow!(
-                    "Provided settings are not valid: {:?}",
-                    settings_validation_response.message.unwrap_or_default()
-                ));
-            }
-            let vanilla_validation_response = evaluator.evaluate();
-
-            let policy_id = policy_definition.get_policy_id()?;
-            let policy_mode = policy_definition.get_policy_mode();
-            let admission_response_handler = AdmissionResponseHandler::new(
-                &policy_id,
-                &policy_mode,
-                policy_definition.get_policy_allowed_to_mutate(),
-                policy_definition.get_policy_custom_rejection_message(),
-            );
-            Ok(admission_response_handler.process_response(vanilla_validation_response))
-        });
-
-        if shutdown_channel_tx.send(()).is_err() {
-            error!("Cannot shut down the CallbackHandler task");
-        } else if let Err(e) = handler.await {
-            error!(
-                error = e.to_string().as_str(),
-                "Error waiting for the CallbackHandler task"
+        let evaluation = evaluate(
+            policy_definition,
+            pull_and_run_settings,
+            &local_data,
+            rego_trace,
+        )
+        .await?;
+        let policy_id = policy_definition.get_policy_id()?.to_string();
+        if rego_trace {
+            // Printed on STDERR, to not interfere with the processing of the response
+            eprint!(
+                "{}",
+                render_trace_events(&policy_id, &evaluation.trace_events)
             );
         }
 
         // Print the evaluation result back to the user, on STDOUT
-        println!("{}", serde_json::to_string(&evaluation_result?)?);
+        if policy_definitions.len() > 1 && matches!(output, OutputType::MutatedObject) {
+            // keep the output a valid multi-document YAML
+            println!("---");
+        }
+        print!(
+            "{}",
+            output::render(&policy_id, &evaluation, output, color)?
+        );
     }
 
     Ok(())
 }
+
+/// The result of the evaluation of a policy
+pub(crate) struct Evaluation {
+    pub admission_response: AdmissionResponse,
+    /// The Rego trace events, collected only when requested
+    pub trace_events: Vec<TraceEvent>,
+    /// The object evaluated by the policy. The patch of the response is
+    /// computed against it
+    pub request_object: Option<serde_json::Value>,
+}
+
+/// Evaluate the policy against the request of the given settings, after having
+/// validated the policy settings.
+///
+/// The Rego trace events are collected only when `rego_trace` is enabled.
+pub(crate) async fn evaluate(
+    policy_definition: &PolicyDefinition,
+    pull_and_run_settings: &PullAndRunSettings,
+    local_data: &LocalData,
+    rego_trace: bool,
+) -> Result<Evaluation> {
+    let (mut evaluator, callback_handler, shutdown_channel_tx) =
+        Evaluator::new(policy_definition, pull_and_run_settings, local_data).await?;
+    let request_object = evaluator.request_object();
+
+    // start the callback handler
+    let handler = tokio::spawn(async { callback_handler.loop_eval().await });
+
+    // We have to wrap the evaluation code inside of a `tokio::task::block_in_place` context
+    // because if the policy uses context aware functions, this would lead to blocking the
+    // tokio runtime. Remember, we're running inside of an async context.
+    let evaluation_result = tokio::task::block_in_place(move || {
+        // validate the settings given by the user
+        let settings_validation_response = evaluator.validate_settings();
+        if !settings_validation_response.valid {
+            return Err(anyhow!(
+                "Provided settings are not valid: {:?}",
+                settings_validation_response.message.unwrap_or_default()
+            ));
+        }
+        let (vanilla_validation_response, trace_events) = if rego_trace {
+            trace::collect(|| evaluator.evaluate())
+        } else {
+            (evaluator.evaluate(), Vec::new())
+        };
+
+        let policy_id = policy_definition.get_policy_id()?;
+        let policy_mode = policy_definition.get_policy_mode();
+        let admission_response_handler = AdmissionResponseHandler::new(
+            &policy_id,
+            &policy_mode,
+            policy_definition.get_policy_allowed_to_mutate(),
+            policy_definition.get_policy_custom_rejection_message(),
+        );
+        Ok(Evaluation {
+            admission_response: admission_response_handler
+                .process_response(vanilla_validation_response),
+            trace_events,
+            request_object,
+        })
+    });
+
+    if shutdown_channel_tx.send(()).is_err() {
+        error!("Cannot shut down the CallbackHandler task");
+    } else if let Err(e) = handler.await {
+        error!(
+            error = e.to_string().as_str(),
+            "Error waiting for the CallbackHandler task"
+        );
+    }
+
+    evaluation_result
+}
+
+fn render_trace_events(policy_id: &str, trace_events: &[TraceEvent]) -> String {
+    if trace_events.is_empty() {
+        return format!("Rego trace of {policy_id}: no events collected\n");
+    }
+
+    let mut rendered = format!("Rego trace of {policy_id}:\n");
+    for event in trace_events {
+        let kind = match event.kind {
+            TraceEventKind::Entrypoint => "entrypoint",
+            TraceEventKind::Print => "print",
+            TraceEventKind::Trace => "trace",
+            TraceEventKind::Println => "println",
+        };
+        rendered.push_str(&format!("  {kind:<10} | {}\n", event.message));
+    }
+
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_rego_trace() {
+        let trace_events = vec![
+            TraceEvent {
+                kind: TraceEventKind::Entrypoint,
+                message: "policy/main".to_string(),
+            },
+            TraceEvent {
+                kind: TraceEventKind::Print,
+                message: "input.message: hello".to_string(),
+            },
+            TraceEvent {
+                kind: TraceEventKind::Trace,
+                message: "checking message".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            render_trace_events("my-policy", &trace_events),
+            "Rego trace of my-policy:\n  entrypoint | policy/main\n  print      | input.message: hello\n  trace      | checking message\n"
+        );
+        assert_eq!(
+            render_trace_events("my-policy", &[]),
+            "Rego trace of my-policy: no events collected\n"
+        );
+    }
+}
@@ -7,10 +7,10 @@ use policy_evaluator::{
     evaluation_context::EvaluationContext,
     kube,
     kubewarden_policy_sdk::settings::SettingsValidationResponse,
-    policy_evaluator::{PolicyEvaluator, PolicySettings, ValidateRequest},
+    policy_evaluator::{PolicyEvaluator, PolicyEvaluatorPre, PolicySettings, ValidateRequest},
     policy_evaluator_builder::PolicyEvaluatorBuilder,
     policy_group_evaluator::evaluator::PolicyGroupEvaluator,
-    policy_metadata::{ContextAwareResource, Metadata, PolicyType},
+    policy_metadata::{ContextAwareResource, HostCapabilityNamespace, Metadata, PolicyType},
 };
 use tokio::sync::oneshot;
 use tracing::{info, warn};
@@ -45,7 +45,8 @@ async fn build_callback_handler(
         None
     } else {
         match &cfg.host_capabilities_mode {
-            HostCapabilitiesMode::Proxy(ProxyMode::Replay { source: _ }) => None,
+            HostCapabilitiesMode::Proxy(ProxyMode::Replay { source: _ })
+            | HostCapabilitiesMode::KubeContext(_) => None,
             _ => Some(build_kube_client().await?),
         }
     };
@@ -53,8 +54,12 @@ async fn build_callback_handler(
 }
 
 pub(crate) enum Evaluator {
+    // The evaluator is boxed to avoid a large enum size causing memory layout
+    // problems. https://rust-lang.github.io/rust-clippy/master/index.html#large_enum_variant
     Policy {
-        policy_evaluator: PolicyEvaluator,
+        policy_evaluator: Box<PolicyEvaluator>,
+        policy_evaluator_pre: Arc<PolicyEvaluatorPre>,
+        eval_ctx: EvaluationContext,
         settings: PolicySettings,
         request: ValidateRequest,
     },
@@ -64,6 +69,49 @@ pub(crate) enum Evaluator {
     },
 }
 
+/// Evaluates requests by rehydrating a new policy instance for each one of
+/// them, the same way policy-server does. It can be shared between threads.
+#[derive(Clone)]
+pub(crate) enum SharedEvaluator {
+    Policy {
+        policy_evaluator_pre: Arc<PolicyEvaluatorPre>,
+        eval_ctx: EvaluationContext,
+        settings: PolicySettings,
+        raw: bool,
+    },
+    GroupPolicy {
+        policy_group_evaluator: Arc<PolicyGroupEvaluator>,
+    },
+}
+
+impl SharedEvaluator {
+    /// Build the request evaluated by the policy
+    pub(crate) fn validate_request(&self, request: &serde_json::Value) -> Result<ValidateRequest> {
+        let raw = matches!(self, Self::Policy { raw: true, .. });
+        build_validate_request(request, raw)
+    }
+
+    /// Rehydrate the policy and evaluate the request.
+    /// Note well: this does **not** validate the settings, it assumes that the settings
+    /// are already validated.
+    pub(crate) fn evaluate(&self, request: &ValidateRequest) -> Result<AdmissionResponse> {
+        match self {
+            Self::Policy {
+                policy_evaluator_pre,
+                eval_ctx,
+                settings,
+                ..
+            } => {
+                let mut policy_evaluator = policy_evaluator_pre.rehydrate(eval_ctx)?;
+                Ok(policy_evaluator.validate(request.clone(), settings))
+            }
+            Self::GroupPolicy {
+                policy_group_evaluator,
+            } => Ok(policy_group_evaluator.clone().validate(request)),
+        }
+    }
+}
+
 impl Evaluator {
     pub(crate) async fn new(
         policy: &PolicyDefinition,
@@ -79,6 +127,7 @@ impl Evaluator {
                 raw,
                 settings,
                 ctx_aware_cfg,
+                dry_run_resources,
                 ..
             } => {
                 let metadata = local_data.metadata(uri);
@@ -103,7 +152,7 @@ impl Evaluator {
                     build_validate_request(&cfg.request, *raw || has_raw_policy_type(metadata))?;
 
                 let callback_handler = build_callback_handler(
-                    !context_aware_allowed_resources.is_empty(),
+                    !context_aware_allowed_resources.is_empty() || !dry_run_resources.is_empty(),
                     cfg,
                     shutdown_channel_rx,
                 )
@@ -119,13 +168,20 @@ impl Evaluator {
                     policy_id: uri.to_owned(),
                     callback_channel: Some(callback_handler.sender_channel()),
                     ctx_aware_resources_allow_list: context_aware_allowed_resources.clone(),
+                    dry_run_resources_allow_list: dry_run_resources.clone(),
+                    host_capabilities_allow_list: build_host_capabilities_allow_list(
+                        metadata, None,
+                    ),
+                    rego_data: None,
                 };
-                let policy_evaluator =
-                    policy_evaluator_builder.build_pre()?.rehydrate(&eval_ctx)?;
+                let policy_evaluator_pre = Arc::new(policy_evaluator_builder.build_pre()?);
+                let policy_evaluator = policy_evaluator_pre.rehydrate(&eval_ctx)?;
 
                 Ok((
                     Self::Policy {
-                        policy_evaluator,
+                        policy_evaluator: Box::new(policy_evaluator),
+                        policy_evaluator_pre,
+                        eval_ctx,
                         request,
                         settings: settings.clone(),
                     },
@@ -166,10 +222,17 @@ impl Evaluator {
 
                     let policy_evaluator_pre = Arc::new(policy_evaluator_builder.build_pre()?);
 
+                    let mut member_settings = member.settings.clone();
+                    member_settings.host_capabilities_allow_list =
+                        build_host_capabilities_allow_list(
+                            local_data.metadata(&member.uri),
+                            member_settings.host_capabilities_allow_list,
+                        );
+
                     policy_group_evaluator.add_policy_member(
                         member_id,
                         policy_evaluator_pre,
-                        member.settings.clone(),
+                        member_settings,
                     );
                 }
 
@@ -194,6 +257,7 @@ impl Evaluator {
                 policy_evaluator,
                 settings,
                 request,
+                ..
             } => policy_evaluator.validate(request.clone(), settings),
             Self::GroupPolicy {
                 policy_group_evaluator,
@@ -202,6 +266,46 @@ impl Evaluator {
         }
     }
 
+    /// The object evaluated by the policy: the object of the AdmissionRequest
+    /// or the whole request, when the request is a raw one.
+    pub(crate) fn request_object(&self) -> Option<serde_json::Value> {
+        let request = match self {
+            Self::Policy { request, .. } | Self::GroupPolicy { request, .. } => request,
+        };
+        match request {
+            ValidateRequest::Raw(request) => Some(request.to_owned()),
+            ValidateRequest::AdmissionRequest(admission_request) => admission_request
+                .object
+                .as_ref()
+                .map(|object| object.0.to_owned()),
+        }
+    }
+
+    /// Build an evaluator that can be shared between threads. It evaluates
+    /// requests using the same policy and settings of this one.
+    pub(crate) fn shared(&self) -> SharedEvaluator {
+        match self {
+            Self::Policy {
+                policy_evaluator_pre,
+                eval_ctx,
+                settings,
+                request,
+                ..
+            } => SharedEvaluator::Policy {
+                policy_evaluator_pre: policy_evaluator_pre.clone(),
+                eval_ctx: eval_ctx.clone(),
+                settings: settings.clone(),
+                raw: matches!(request, ValidateRequest::Raw(_)),
+            },
+            Self::GroupPolicy {
+                policy_group_evaluator,
+                ..
+            } => SharedEvaluator::GroupPolicy {
+                policy_group_evaluator: policy_group_evaluator.clone(),
+            },
+        }
+    }
+
     /// Validates the settings given by the user.
     pub(crate) fn validate_settings(&mut self) -> SettingsValidationResponse {
         match self {
@@ -275,6 +379,16 @@ fn build_context_aware_allowed_resources(
     }
 }
 
+/// The namespaces of host capabilities the policy is allowed to use. The ones granted
+/// by the user take precedence over the ones declared inside of the policy metadata.
+/// `None` grants access to all of them, like it's done for policies that are not annotated
+fn build_host_capabilities_allow_list(
+    metadata: Option<&Metadata>,
+    granted: Option<BTreeSet<HostCapabilityNamespace>>,
+) -> Option<BTreeSet<HostCapabilityNamespace>> {
+    granted.or_else(|| metadata.and_then(|metadata| metadata.host_capabilities.clone()))
+}
+
 /// kwctl is built using rustls enabled. Unfortunately rustls does not support validating IP addresses
 /// yet (see https://github.com/kube-rs/kube/issues/1003).
 ///
@@ -313,10 +427,12 @@ mod tests {
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Pod".to_string(),
+                projection: None,
             },
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Service".to_string(),
+                projection: None,
             },
         ])
     }
@@ -333,6 +449,7 @@ mod tests {
         BTreeSet::from([ContextAwareResource {
             api_version: "apps/v1".to_string(),
             kind: "Deployment".to_string(),
+            projection: None,
         }])
     }
 
@@ -379,4 +496,34 @@ mod tests {
         let actual = build_context_aware_allowed_resources(metadata.as_ref(), &ctx_cfg);
         assert_eq!(actual, expected_allowed);
     }
+
+    #[rstest]
+    #[case::not_annotated(None, None, None)]
+    #[case::annotated_without_host_capabilities(Some(Metadata::default()), None, None)]
+    #[case::metadata(
+        Some(Metadata {
+            host_capabilities: Some(BTreeSet::from([HostCapabilityNamespace::Oci])),
+            ..Default::default()
+        }),
+        None,
+        Some(BTreeSet::from([HostCapabilityNamespace::Oci]))
+    )]
+    #[case::granted_overrides_metadata(
+        Some(Metadata {
+            host_capabilities: Some(BTreeSet::from([HostCapabilityNamespace::Oci])),
+            ..Default::default()
+        }),
+        Some(BTreeSet::new()),
+        Some(BTreeSet::new())
+    )]
+    fn determine_host_capabilities_allow_list(
+        #[case] metadata: Option<Metadata>,
+        #[case] granted: Option<BTreeSet<HostCapabilityNamespace>>,
+        #[case] expected: Option<BTreeSet<HostCapabilityNamespace>>,
+    ) {
+        assert_eq!(
+            build_host_capabilities_allow_list(metadata.as_ref(), granted),
+            expected
+        );
+    }
 }
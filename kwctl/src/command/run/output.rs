@@ -0,0 +1,335 @@
+use std::{convert::TryFrom, fmt::Write};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use color_print::cformat;
+use policy_evaluator::admission_response::AdmissionResponse;
+use serde_json::Value;
+use similar::TextDiff;
+
+use crate::command::run::Evaluation;
+
+/// How the result of an evaluation is printed
+pub(crate) enum OutputType {
+    /// The AdmissionResponse, in JSON format
+    Json,
+    /// The object of the request after having applied the patch of the
+    /// response, in YAML format
+    MutatedObject,
+    /// Unified diff between the object of the request and the mutated one
+    Diff,
+    /// Human readable summary of the response
+    Summary,
+}
+
+impl TryFrom<Option<&str>> for OutputType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("json") | None => Ok(Self::Json),
+            Some("mutated-object") => Ok(Self::MutatedObject),
+            Some("diff") => Ok(Self::Diff),
+            Some("summary") => Ok(Self::Summary),
+            Some(unknown) => Err(anyhow!("Invalid output format '{}'", unknown)),
+        }
+    }
+}
+
+/// Decode the JSON patch of the response. `None` is returned when the
+/// response does not mutate the object
+pub(crate) fn decode_patch(response: &AdmissionResponse) -> Result<Option<json_patch::Patch>> {
+    let patch = match &response.patch {
+        Some(patch) => patch,
+        None => return Ok(None),
+    };
+    let patch = general_purpose::STANDARD
+        .decode(patch)
+        .map_err(|e| anyhow!("cannot decode the patch of the response: {e}"))?;
+
+    serde_json::from_slice(&patch)
+        .map(Some)
+        .map_err(|e| anyhow!("cannot parse the patch of the response: {e}"))
+}
+
+/// Apply the patch of the response to the object of the request. `None` is
+/// returned when the response does not mutate the object
+pub(crate) fn mutated_object(
+    request_object: Option<&Value>,
+    response: &AdmissionResponse,
+) -> Result<Option<Value>> {
+    let patch = match decode_patch(response)? {
+        Some(patch) => patch,
+        None => return Ok(None),
+    };
+    let mut object = request_object
+        .cloned()
+        .ok_or_else(|| anyhow!("the request does not contain an object to patch"))?;
+    json_patch::patch(&mut object, &patch)
+        .map_err(|e| anyhow!("cannot apply the patch of the response: {e}"))?;
+
+    Ok(Some(object))
+}
+
+/// Render the result of the evaluation of the given policy
+pub(crate) fn render(
+    policy_id: &str,
+    evaluation: &Evaluation,
+    output: &OutputType,
+    color: bool,
+) -> Result<String> {
+    let response = &evaluation.admission_response;
+    let request_object = evaluation.request_object.as_ref();
+
+    match output {
+        OutputType::Json => Ok(format!("{}\n", serde_json::to_string(response)?)),
+        OutputType::MutatedObject => {
+            let object = match mutated_object(request_object, response)? {
+                Some(object) => object,
+                None => request_object
+                    .cloned()
+                    .ok_or_else(|| anyhow!("the request does not contain an object"))?,
+            };
+            Ok(serde_yaml::to_string(&object)?)
+        }
+        OutputType::Diff => {
+            let mutated_object = match mutated_object(request_object, response)? {
+                Some(object) => object,
+                // Nothing to show, like `diff` does with identical files
+                None => return Ok(String::new()),
+            };
+            let original = serde_yaml::to_string(&request_object)?;
+            let mutated = serde_yaml::to_string(&mutated_object)?;
+            Ok(render_diff(&original, &mutated, color))
+        }
+        OutputType::Summary => render_summary(policy_id, response, color),
+    }
+}
+
+fn render_diff(original: &str, mutated: &str, color: bool) -> String {
+    let diff = TextDiff::from_lines(original, mutated)
+        .unified_diff()
+        .header("original", "mutated")
+        .to_string();
+    if !color {
+        return diff;
+    }
+
+    diff.lines()
+        .map(|line| {
+            if line.starts_with("---") || line.starts_with("+++") {
+                cformat!("<bold>{}</bold>\n", line)
+            } else if line.starts_with("@@") {
+                cformat!("<cyan>{}</cyan>\n", line)
+            } else if line.starts_with('+') {
+                cformat!("<green>{}</green>\n", line)
+            } else if line.starts_with('-') {
+                cformat!("<red>{}</red>\n", line)
+            } else {
+                format!("{line}\n")
+            }
+        })
+        .collect()
+}
+
+fn render_summary(policy_id: &str, response: &AdmissionResponse, color: bool) -> Result<String> {
+    let bold = |text: &str| {
+        if color {
+            cformat!("<bold>{}</bold>", text)
+        } else {
+            text.to_string()
+        }
+    };
+    let label = |text: &str| bold(&format!("{text:<18}"));
+
+    let mut summary = String::new();
+    writeln!(summary, "{} {policy_id}", label("Policy:"))?;
+    let result = match (response.allowed, color) {
+        (true, true) => cformat!("<green>allowed</green>"),
+        (false, true) => cformat!("<red>denied</red>"),
+        (true, false) => "allowed".to_string(),
+        (false, false) => "denied".to_string(),
+    };
+    writeln!(summary, "{} {result}", label("Result:"))?;
+
+    if let Some(status) = &response.status {
+        if let Some(message) = &status.message {
+            writeln!(summary, "{} {message}", label("Message:"))?;
+        }
+        if let Some(code) = &status.code {
+            writeln!(summary, "{} {code}", label("Code:"))?;
+        }
+    }
+    if let Some(patch) = decode_patch(response)? {
+        writeln!(summary, "{} {}", label("Patch operations:"), patch.0.len())?;
+    }
+    if let Some(warnings) = response.warnings.as_ref().filter(|w| !w.is_empty()) {
+        writeln!(summary, "{}", bold("Warnings:"))?;
+        for warning in warnings {
+            writeln!(summary, "  - {warning}")?;
+        }
+    }
+    if let Some(annotations) = response
+        .audit_annotations
+        .as_ref()
+        .filter(|a| !a.is_empty())
+    {
+        writeln!(summary, "{}", bold("Audit annotations:"))?;
+        let mut annotations: Vec<_> = annotations.iter().collect();
+        annotations.sort();
+        for (key, value) in annotations {
+            writeln!(summary, "  {key}: {value}")?;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use policy_evaluator::admission_response::AdmissionResponseStatus;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn pod() -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx",
+                "labels": {"app": "nginx"}
+            }
+        })
+    }
+
+    fn evaluation(patch: Option<Value>) -> Evaluation {
+        Evaluation {
+            admission_response: AdmissionResponse {
+                uid: "uid".to_string(),
+                allowed: true,
+                patch: patch.map(|patch| general_purpose::STANDARD.encode(patch.to_string())),
+                ..Default::default()
+            },
+            trace_events: Vec::new(),
+            request_object: Some(pod()),
+        }
+    }
+
+    fn add_owner_label() -> Value {
+        json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team"}])
+    }
+
+    #[test]
+    fn render_mutated_object() {
+        let output = render(
+            "my-policy",
+            &evaluation(Some(add_owner_label())),
+            &OutputType::MutatedObject,
+            false,
+        )
+        .expect("cannot render output");
+
+        let object: Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(object["metadata"]["labels"]["owner"], "team");
+        assert_eq!(object["metadata"]["labels"]["app"], "nginx");
+    }
+
+    #[test]
+    fn render_not_mutated_object() {
+        let output = render(
+            "my-policy",
+            &evaluation(None),
+            &OutputType::MutatedObject,
+            false,
+        )
+        .expect("cannot render output");
+
+        assert_eq!(serde_yaml::from_str::<Value>(&output).unwrap(), pod());
+    }
+
+    #[rstest]
+    #[case::no_color(
+        false,
+        "--- original\n+++ mutated\n@@ -3,4 +3,5 @@\n metadata:\n   labels:\n     app: nginx\n+    owner: team\n   name: nginx\n"
+    )]
+    #[case::color(
+        true,
+        "\x1b[1m--- original\x1b[22m\n\x1b[1m+++ mutated\x1b[22m\n\x1b[36m@@ -3,4 +3,5 @@\x1b[39m\n metadata:\n   labels:\n     app: nginx\n\x1b[32m+    owner: team\x1b[39m\n   name: nginx\n"
+    )]
+    fn render_mutation_diff(#[case] color: bool, #[case] expected: &str) {
+        let output = render(
+            "my-policy",
+            &evaluation(Some(add_owner_label())),
+            &OutputType::Diff,
+            color,
+        )
+        .expect("cannot render output");
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn render_empty_diff() {
+        let output = render("my-policy", &evaluation(None), &OutputType::Diff, false)
+            .expect("cannot render output");
+
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn render_rejection_summary() {
+        let response = AdmissionResponse {
+            uid: "uid".to_string(),
+            allowed: false,
+            status: Some(AdmissionResponseStatus {
+                message: Some("privileged containers are not allowed".to_string()),
+                code: Some(400),
+                ..Default::default()
+            }),
+            warnings: Some(vec!["image uses the latest tag".to_string()]),
+            audit_annotations: Some(HashMap::from([(
+                "policy".to_string(),
+                "pod-privileged".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        let summary = render_summary("my-policy", &response, false).expect("cannot render");
+
+        assert_eq!(
+            summary,
+            "Policy:            my-policy\nResult:            denied\nMessage:           privileged containers are not allowed\nCode:              400\nWarnings:\n  - image uses the latest tag\nAudit annotations:\n  policy: pod-privileged\n"
+        );
+    }
+
+    #[test]
+    fn render_colored_summary() {
+        let summary = render("my-policy", &evaluation(None), &OutputType::Summary, true)
+            .expect("cannot render output");
+
+        assert_eq!(
+            summary,
+            "\x1b[1mPolicy:           \x1b[22m my-policy\n\x1b[1mResult:           \x1b[22m \x1b[32mallowed\x1b[39m\n"
+        );
+    }
+
+    #[test]
+    fn render_mutation_summary() {
+        let summary = render(
+            "my-policy",
+            &evaluation(Some(add_owner_label())),
+            &OutputType::Summary,
+            false,
+        )
+        .expect("cannot render output");
+
+        assert_eq!(
+            summary,
+            "Policy:            my-policy\nResult:            allowed\nPatch operations:  1\n"
+        );
+    }
+}
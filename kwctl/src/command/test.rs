@@ -0,0 +1,138 @@
+use std::{path::Path, time::Instant};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::sources::Sources;
+use regex::Regex;
+use tracing::debug;
+
+use crate::{
+    callback_handler::ProxyMode,
+    command::{
+        run::{evaluate, local_data::LocalData},
+        test::test_file::{TestCase, TestFile},
+    },
+    config::{
+        policy_definition::PolicyDefinition, pull_and_run::PullAndRunSettings, HostCapabilitiesMode,
+    },
+    test_report::{OutputType, TestCaseReport, TestOutcome, TestSuiteReport},
+};
+
+pub(crate) mod expectation;
+pub(crate) mod test_file;
+
+/// Settings shared by all the test cases of a test file
+pub(crate) struct TestSettings {
+    pub sources: Option<Sources>,
+    pub enable_wasmtime_cache: bool,
+    /// Run only the test cases whose name matches this regular expression
+    pub filter: Option<Regex>,
+    pub output: OutputType,
+}
+
+/// Run the test cases defined inside of the given test file.
+///
+/// Each test case is evaluated in the same way `kwctl run` does, then the
+/// response of the policy is compared with the expected one.
+pub(crate) async fn exec(test_file_path: &Path, settings: &TestSettings) -> Result<()> {
+    let test_file = TestFile::load(test_file_path)?;
+
+    let test_cases: Vec<&TestCase> = test_file
+        .tests
+        .iter()
+        .filter(|test_case| {
+            settings
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.is_match(&test_case.name))
+        })
+        .collect();
+    if test_cases.is_empty() {
+        return Err(anyhow!(
+            "No test cases of {} match the given filter",
+            test_file_path.display()
+        ));
+    }
+
+    // Pull all the policies once, before running the tests
+    let policy_definitions = test_cases
+        .iter()
+        .map(|test_case| test_file.policy_definition(test_case))
+        .collect::<Result<Vec<_>>>()?;
+    let local_data = LocalData::new(
+        &policy_definitions,
+        &PullAndRunSettings {
+            sources: settings.sources.clone(),
+            ..Default::default()
+        },
+    )
+    .await?;
+
+    let mut report = TestSuiteReport {
+        name: test_file.name.clone().unwrap_or_default(),
+        test_cases: Vec::with_capacity(test_cases.len()),
+    };
+    for (test_case, policy_definition) in test_cases.into_iter().zip(policy_definitions) {
+        debug!(test = test_case.name, "running policy test");
+        let start = Instant::now();
+        let outcome = run_test_case(
+            &test_file,
+            test_case,
+            &policy_definition,
+            &local_data,
+            settings,
+        )
+        .await
+        .unwrap_or_else(|e| TestOutcome::Error(e.to_string()));
+
+        report.test_cases.push(TestCaseReport {
+            name: test_case.name.clone(),
+            outcome,
+            duration: start.elapsed(),
+        });
+    }
+
+    print!("{}", report.render(&settings.output));
+
+    if report.is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} out of {} policy tests did not pass",
+            report.test_cases.len() - report.passed(),
+            report.test_cases.len()
+        ))
+    }
+}
+
+async fn run_test_case(
+    test_file: &TestFile,
+    test_case: &TestCase,
+    policy_definition: &PolicyDefinition,
+    local_data: &LocalData,
+    settings: &TestSettings,
+) -> Result<TestOutcome> {
+    let request = test_file.request(test_case)?;
+    let host_capabilities_mode = match test_file.session(test_case) {
+        Some(source) => HostCapabilitiesMode::Proxy(ProxyMode::Replay { source }),
+        None => HostCapabilitiesMode::Direct,
+    };
+    let pull_and_run_settings = PullAndRunSettings {
+        sources: settings.sources.clone(),
+        request,
+        enable_wasmtime_cache: settings.enable_wasmtime_cache,
+        host_capabilities_mode,
+        ..Default::default()
+    };
+
+    let evaluation = evaluate(policy_definition, &pull_and_run_settings, local_data, false).await?;
+
+    let failures = test_case.expect.check(
+        evaluation.request_object.as_ref(),
+        &evaluation.admission_response,
+    )?;
+    if failures.is_empty() {
+        Ok(TestOutcome::Passed)
+    } else {
+        Ok(TestOutcome::Failed(failures.join("\n")))
+    }
+}
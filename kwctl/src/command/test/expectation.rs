@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Result};
+use policy_evaluator::admission_response::AdmissionResponse;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::command::run::output::{decode_patch, mutated_object};
+
+/// The outcome expected from the evaluation of a test case
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct Expectation {
+    /// Whether the request must be accepted or rejected
+    pub allowed: bool,
+    /// Regular expression the message of the response must match
+    #[serde(default)]
+    pub message: Option<String>,
+    /// The object obtained by applying the patch of the response to the object
+    /// of the request. Use `patchedObject` or `patch` to test mutating policies
+    #[serde(default)]
+    pub patched_object: Option<Value>,
+    /// The JSON patch the response must contain
+    #[serde(default)]
+    pub patch: Option<Value>,
+}
+
+impl Expectation {
+    /// Check the response given by the policy to the request holding the given object.
+    ///
+    /// Returns the description of all the unmet expectations, the response
+    /// met the expectations when the list is empty.
+    pub fn check(
+        &self,
+        request_object: Option<&Value>,
+        response: &AdmissionResponse,
+    ) -> Result<Vec<String>> {
+        let mut failures = Vec::new();
+
+        let message = response
+            .status
+            .as_ref()
+            .and_then(|status| status.message.as_deref());
+        if response.allowed != self.allowed {
+            let mut failure = format!(
+                "expected allowed to be {}, got {}",
+                self.allowed, response.allowed
+            );
+            if let Some(message) = message {
+                failure.push_str(&format!(" with message: {message}"));
+            }
+            failures.push(failure);
+        }
+
+        if let Some(expected_message) = &self.message {
+            let regex = Regex::new(expected_message).map_err(|e| {
+                anyhow!("invalid message regular expression '{expected_message}': {e}")
+            })?;
+            match message {
+                Some(message) if regex.is_match(message) => {}
+                Some(message) => failures.push(format!(
+                    "expected message to match '{expected_message}', got: {message}"
+                )),
+                None => failures.push(format!(
+                    "expected message to match '{expected_message}', got no message"
+                )),
+            }
+        }
+
+        if let Some(expected_patch) = &self.patch {
+            let patch = match decode_patch(response)? {
+                Some(patch) => serde_json::to_value(patch)?,
+                None => Value::Array(Vec::new()),
+            };
+            if &patch != expected_patch {
+                failures.push(format!(
+                    "patch differs from the expected one\nexpected:\n{}\ngot:\n{}",
+                    serde_json::to_string_pretty(expected_patch)?,
+                    serde_json::to_string_pretty(&patch)?
+                ));
+            }
+        }
+
+        if let Some(expected_object) = &self.patched_object {
+            let patched_object = match mutated_object(request_object, response)? {
+                Some(object) => object,
+                None => request_object
+                    .cloned()
+                    .ok_or_else(|| anyhow!("the request does not contain an object"))?,
+            };
+            if &patched_object != expected_object {
+                failures.push(format!(
+                    "patched object differs from the expected one\n{}",
+                    render_diff(&patched_object, expected_object)
+                ));
+            }
+        }
+
+        Ok(failures)
+    }
+}
+
+/// Render the differences between two JSON documents, one line for each
+/// changed path
+fn render_diff(actual: &Value, expected: &Value) -> String {
+    json_patch::diff(actual, expected)
+        .iter()
+        .map(|operation| {
+            let path = operation.path().as_str();
+            let render = |value: Option<&Value>| {
+                value.map_or("nothing".to_string(), |value| value.to_string())
+            };
+            format!(
+                "  {path}: expected {}, got {}",
+                render(expected.pointer(path)),
+                render(actual.pointer(path))
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use base64::{engine::general_purpose, Engine as _};
+    use policy_evaluator::admission_response::AdmissionResponseStatus;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn request_object() -> Value {
+        json!({
+            "metadata": {
+                "name": "nginx",
+                "labels": {"app": "nginx"}
+            }
+        })
+    }
+
+    fn response(allowed: bool, message: Option<&str>, patch: Option<Value>) -> AdmissionResponse {
+        AdmissionResponse {
+            allowed,
+            status: message.map(|message| AdmissionResponseStatus {
+                message: Some(message.to_string()),
+                ..Default::default()
+            }),
+            patch: patch.map(|patch| general_purpose::STANDARD.encode(patch.to_string())),
+            ..Default::default()
+        }
+    }
+
+    fn expectation(allowed: bool) -> Expectation {
+        Expectation {
+            allowed,
+            message: None,
+            patched_object: None,
+            patch: None,
+        }
+    }
+
+    #[rstest]
+    #[case::allowed(expectation(true), response(true, None, None), 0)]
+    #[case::unexpected_rejection(
+        expectation(true),
+        response(false, Some("privileged containers are not allowed"), None),
+        1
+    )]
+    #[case::message_matches(
+        Expectation {
+            message: Some("privileged.*not allowed".to_string()),
+            ..expectation(false)
+        },
+        response(false, Some("privileged containers are not allowed"), None),
+        0
+    )]
+    #[case::message_does_not_match(
+        Expectation {
+            message: Some("^host network".to_string()),
+            ..expectation(false)
+        },
+        response(false, Some("privileged containers are not allowed"), None),
+        1
+    )]
+    #[case::missing_message(
+        Expectation {
+            message: Some("not allowed".to_string()),
+            ..expectation(true)
+        },
+        response(true, None, None),
+        1
+    )]
+    #[case::expected_patch(
+        Expectation {
+            patch: Some(json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team"}])),
+            ..expectation(true)
+        },
+        response(true, None, Some(json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team"}]))),
+        0
+    )]
+    #[case::unexpected_patch(
+        Expectation {
+            patch: Some(json!([])),
+            ..expectation(true)
+        },
+        response(true, None, Some(json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team"}]))),
+        1
+    )]
+    #[case::expected_patched_object(
+        Expectation {
+            patched_object: Some(json!({"metadata": {"name": "nginx", "labels": {"app": "nginx", "owner": "team"}}})),
+            ..expectation(true)
+        },
+        response(true, None, Some(json!([{"op": "add", "path": "/metadata/labels/owner", "value": "team"}]))),
+        0
+    )]
+    #[case::all_expectations_unmet(
+        Expectation {
+            message: Some("not allowed".to_string()),
+            patched_object: Some(json!({"metadata": {"name": "nginx", "labels": {"app": "nginx", "owner": "team"}}})),
+            ..expectation(false)
+        },
+        response(true, None, None),
+        3
+    )]
+    fn check_response(
+        #[case] expectation: Expectation,
+        #[case] response: AdmissionResponse,
+        #[case] expected_failures: usize,
+    ) {
+        let failures = expectation
+            .check(Some(&request_object()), &response)
+            .expect("check failed");
+
+        assert_eq!(failures.len(), expected_failures, "{failures:?}");
+    }
+
+    #[test]
+    fn render_patched_object_diff() {
+        let expectation = Expectation {
+            patched_object: Some(json!({
+                "metadata": {"name": "nginx", "labels": {"app": "web", "owner": "team"}}
+            })),
+            ..expectation(true)
+        };
+
+        let failures = expectation
+            .check(Some(&request_object()), &response(true, None, None))
+            .expect("check failed");
+
+        assert_eq!(
+            failures,
+            vec![
+                "patched object differs from the expected one\n  /metadata/labels/app: expected \"web\", got \"nginx\"\n  /metadata/labels/owner: expected \"team\", got nothing"
+                    .to_string()
+            ]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::{
+    admission_response_handler::policy_mode::PolicyMode, kube::api::DynamicObject,
+    policy_evaluator::PolicySettings,
+};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    command::test::expectation::Expectation,
+    config::policy_definition::{
+        ContextAwareConfiguration, PolicyDefinition, PolicyExecutionConfiguration,
+    },
+    scaffold::{build_admission_request, AdmissionRequestOperation, AdmissionRequestSettings},
+};
+
+/// A suite of policy tests, defined inside of a YAML file
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct TestFile {
+    /// The name of the suite. Defaults to the path of the test file
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The policy used by the test cases that do not define their own
+    #[serde(default)]
+    pub policy: Option<String>,
+    /// The settings used by the test cases that do not define their own
+    #[serde(default)]
+    pub settings: Option<Value>,
+    pub tests: Vec<TestCase>,
+    /// The directory holding the test file. Relative paths are resolved
+    /// starting from it
+    #[serde(skip)]
+    base_dir: PathBuf,
+}
+
+/// A single policy test
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct TestCase {
+    pub name: String,
+    /// The policy URI, SHA prefix or the YAML file holding a Kubewarden
+    /// policy resource
+    #[serde(default)]
+    pub policy: Option<String>,
+    /// The settings of the policy. Cannot be used when the policy is
+    /// defined by a YAML file
+    #[serde(default)]
+    pub settings: Option<Value>,
+    /// The file holding the AdmissionRequest or the AdmissionReview to
+    /// evaluate
+    #[serde(default)]
+    pub request: Option<PathBuf>,
+    /// The object of a CREATE AdmissionRequest to evaluate
+    #[serde(default)]
+    pub object: Option<Value>,
+    /// The host capabilities session replayed during the evaluation. The
+    /// policy is granted access to the context aware resources listed inside
+    /// of its metadata
+    #[serde(default)]
+    pub session: Option<PathBuf>,
+    pub expect: Expectation,
+}
+
+impl TestFile {
+    /// Load the test file, ensuring all its test cases are well defined
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Cannot open test file {}: {}", path.display(), e))?;
+        let mut test_file: TestFile = serde_yaml::from_reader(file)
+            .map_err(|e| anyhow!("Cannot parse test file {}: {}", path.display(), e))?;
+        test_file.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        if test_file.name.is_none() {
+            test_file.name = Some(path.display().to_string());
+        }
+
+        test_file.validate()?;
+
+        Ok(test_file)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.tests.is_empty() {
+            return Err(anyhow!("The test file does not define any test"));
+        }
+
+        for test_case in &self.tests {
+            if test_case.policy.is_none() && self.policy.is_none() {
+                return Err(anyhow!(
+                    "Test '{}' does not define a policy",
+                    test_case.name
+                ));
+            }
+            match (&test_case.request, &test_case.object) {
+                (Some(_), Some(_)) => {
+                    return Err(anyhow!(
+                        "Test '{}' cannot define both a request and an object",
+                        test_case.name
+                    ))
+                }
+                (None, None) => {
+                    return Err(anyhow!(
+                        "Test '{}' must define either a request or an object",
+                        test_case.name
+                    ))
+                }
+                _ => {}
+            }
+            if let Some(message) = &test_case.expect.message {
+                Regex::new(message).map_err(|e| {
+                    anyhow!(
+                        "Test '{}' has an invalid message regular expression: {}",
+                        test_case.name,
+                        e
+                    )
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the definition of the policy evaluated by the given test case
+    pub fn policy_definition(&self, test_case: &TestCase) -> Result<PolicyDefinition> {
+        let policy = test_case
+            .policy
+            .as_ref()
+            .or(self.policy.as_ref())
+            .ok_or_else(|| anyhow!("Test '{}' does not define a policy", test_case.name))?;
+        let settings = test_case.settings.as_ref().or(self.settings.as_ref());
+        let policy = self.resolve_policy(policy);
+
+        if policy.ends_with(".yaml") || policy.ends_with(".yml") {
+            if test_case.settings.is_some() {
+                return Err(anyhow!(
+                    "Test '{}' cannot define settings for a policy defined by a YAML file",
+                    test_case.name
+                ));
+            }
+            let mut policy_definitions = PolicyDefinition::from_yaml_file(&policy)?;
+            if policy_definitions.len() != 1 {
+                return Err(anyhow!(
+                    "The YAML file {} must define exactly one policy, found {}",
+                    policy,
+                    policy_definitions.len()
+                ));
+            }
+            return Ok(policy_definitions.remove(0));
+        }
+
+        let uri = crate::utils::map_path_to_uri(&policy)?;
+        let settings = match settings {
+            Some(settings) => PolicySettings::try_from(settings).map_err(anyhow::Error::msg)?,
+            None => PolicySettings::default(),
+        };
+        let ctx_aware_cfg = if test_case.session.is_some() {
+            ContextAwareConfiguration::TrustPolicyMetadata
+        } else {
+            ContextAwareConfiguration::NoAccess
+        };
+
+        Ok(PolicyDefinition::Policy {
+            id: "policy-from-test".to_string(),
+            uri,
+            user_execution_cfg: PolicyExecutionConfiguration::PolicyDefined,
+            raw: false,
+            policy_mode: PolicyMode::Protect,
+            allowed_to_mutate: true,
+            custom_rejection_message: None,
+            settings,
+            ctx_aware_cfg,
+            dry_run_resources: BTreeSet::new(),
+        })
+    }
+
+    /// Build the request evaluated by the given test case
+    pub fn request(&self, test_case: &TestCase) -> Result<Value> {
+        if let Some(request_path) = &test_case.request {
+            let request_path = self.base_dir.join(request_path);
+            let file = File::open(&request_path).map_err(|e| {
+                anyhow!("Cannot open request file {}: {}", request_path.display(), e)
+            })?;
+            return serde_yaml::from_reader(file).map_err(|e| {
+                anyhow!(
+                    "Cannot parse request file {}: {}",
+                    request_path.display(),
+                    e
+                )
+            });
+        }
+
+        let object = test_case
+            .object
+            .clone()
+            .ok_or_else(|| anyhow!("Test '{}' does not define a request", test_case.name))?;
+        let object: DynamicObject = serde_json::from_value(object)
+            .map_err(|e| anyhow!("Test '{}' has an invalid object: {}", test_case.name, e))?;
+        let request = build_admission_request(
+            AdmissionRequestOperation::Create,
+            Some(object),
+            None,
+            None,
+            &AdmissionRequestSettings::default(),
+        )?;
+
+        Ok(serde_json::to_value(request)?)
+    }
+
+    /// The host capabilities session replayed by the given test case
+    pub fn session(&self, test_case: &TestCase) -> Option<PathBuf> {
+        test_case
+            .session
+            .as_ref()
+            .map(|session| self.base_dir.join(session))
+    }
+
+    /// Local policies are looked up starting from the directory of the test file.
+    /// URIs and SHA prefixes are left untouched
+    fn resolve_policy(&self, policy: &str) -> String {
+        if Regex::new(r"^\w+://").unwrap().is_match(policy) {
+            return policy.to_string();
+        }
+
+        let path = self.base_dir.join(policy);
+        if path.exists() {
+            path.display().to_string()
+        } else {
+            policy.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use rstest::rstest;
+
+    fn write_test_file(dir: &Path, contents: &str) -> PathBuf {
+        let path = dir.join("tests.yaml");
+        let mut file = File::create(&path).expect("cannot create test file");
+        file.write_all(contents.as_bytes())
+            .expect("cannot write test file");
+        path
+    }
+
+    #[test]
+    fn load_test_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("policy.wasm"), b"").unwrap();
+        std::fs::write(
+            tempdir.path().join("request.json"),
+            br#"{"uid": "1299d386-525b-4032-98ae-1949f69f9cfc"}"#,
+        )
+        .unwrap();
+        let path = write_test_file(
+            tempdir.path(),
+            r#"
+policy: policy.wasm
+settings:
+  allowedLabels: ["app"]
+tests:
+  - name: reject privileged pod
+    request: request.json
+    expect:
+      allowed: false
+      message: "privileged"
+  - name: accept pod
+    settings: {}
+    object:
+      apiVersion: v1
+      kind: Pod
+      metadata:
+        name: nginx
+        namespace: default
+    session: session.yml
+    expect:
+      allowed: true
+"#,
+        );
+
+        let test_file = TestFile::load(&path).expect("cannot load test file");
+        assert_eq!(test_file.name, Some(path.display().to_string()));
+        assert_eq!(test_file.tests.len(), 2);
+
+        let request = test_file
+            .request(&test_file.tests[0])
+            .expect("cannot build request");
+        assert_eq!(request["uid"], "1299d386-525b-4032-98ae-1949f69f9cfc");
+
+        let request = test_file
+            .request(&test_file.tests[1])
+            .expect("cannot build request");
+        assert_eq!(request["operation"], "CREATE");
+        assert_eq!(request["kind"]["kind"], "Pod");
+        assert_eq!(request["namespace"], "default");
+        assert_eq!(request["object"]["metadata"]["name"], "nginx");
+
+        assert_eq!(test_file.session(&test_file.tests[0]), None);
+        assert_eq!(
+            test_file.session(&test_file.tests[1]),
+            Some(tempdir.path().join("session.yml"))
+        );
+
+        match test_file
+            .policy_definition(&test_file.tests[0])
+            .expect("cannot build policy definition")
+        {
+            PolicyDefinition::Policy {
+                uri,
+                settings,
+                ctx_aware_cfg,
+                ..
+            } => {
+                assert!(uri.starts_with("file://"));
+                assert!(uri.ends_with("/policy.wasm"));
+                assert_eq!(
+                    serde_json::to_value(settings).unwrap(),
+                    serde_json::json!({"allowedLabels": ["app"]})
+                );
+                assert_eq!(ctx_aware_cfg, ContextAwareConfiguration::NoAccess);
+            }
+            _ => panic!("unexpected policy definition"),
+        }
+        match test_file
+            .policy_definition(&test_file.tests[1])
+            .expect("cannot build policy definition")
+        {
+            PolicyDefinition::Policy {
+                settings,
+                ctx_aware_cfg,
+                ..
+            } => {
+                assert_eq!(
+                    serde_json::to_value(settings).unwrap(),
+                    serde_json::json!({})
+                );
+                assert_eq!(
+                    ctx_aware_cfg,
+                    ContextAwareConfiguration::TrustPolicyMetadata
+                );
+            }
+            _ => panic!("unexpected policy definition"),
+        }
+    }
+
+    #[rstest]
+    #[case::no_tests("policy: policy.wasm\ntests: []\n")]
+    #[case::no_policy(
+        "tests:\n  - name: test\n    request: request.json\n    expect:\n      allowed: true\n"
+    )]
+    #[case::no_request(
+        "policy: policy.wasm\ntests:\n  - name: test\n    expect:\n      allowed: true\n"
+    )]
+    #[case::request_and_object(
+        "policy: policy.wasm\ntests:\n  - name: test\n    request: request.json\n    object: {}\n    expect:\n      allowed: true\n"
+    )]
+    #[case::invalid_message_regex(
+        "policy: policy.wasm\ntests:\n  - name: test\n    request: request.json\n    expect:\n      allowed: true\n      message: \"(\"\n"
+    )]
+    #[case::unknown_field(
+        "policy: policy.wasm\ntests:\n  - name: test\n    request: request.json\n    expect:\n      allowed: true\n      allow: true\n"
+    )]
+    fn reject_invalid_test_file(#[case] contents: &str) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = write_test_file(tempdir.path(), contents);
+
+        assert!(TestFile::load(&path).is_err());
+    }
+}
@@ -8,4 +8,5 @@ pub(crate) enum HostCapabilitiesMode {
     #[default]
     Direct,
     Proxy(crate::callback_handler::ProxyMode),
+    KubeContext(crate::callback_handler::KubeContextSettings),
 }
@@ -45,6 +45,8 @@ pub(crate) enum PolicyDefinition {
         // determined after the policy is downloaded locally and its
         // metadata is inspected.
         ctx_aware_cfg: ContextAwareConfiguration,
+        // The Kubernetes resources the policy is allowed to dry-run
+        dry_run_resources: BTreeSet<ContextAwareResource>,
     },
     /// This is a group of policies. This can be defined only by providing a Kubewarden CRD
     /// file.
@@ -164,6 +166,7 @@ impl TryFrom<AdmissionPolicy> for PolicyDefinition {
             custom_rejection_message,
             settings,
             ctx_aware_cfg: ContextAwareConfiguration::NoAccess,
+            dry_run_resources: BTreeSet::new(),
         })
     }
 }
@@ -204,6 +207,7 @@ impl TryFrom<ClusterAdmissionPolicy> for PolicyDefinition {
             custom_rejection_message,
             settings,
             ctx_aware_cfg: ContextAwareConfiguration::AllowList(ctx_aware_allow_list),
+            dry_run_resources: BTreeSet::new(),
         })
     }
 }
@@ -387,6 +391,15 @@ impl PolicyDefinition {
         } else {
             ContextAwareConfiguration::NoAccess
         };
+        let dry_run_resources = matches
+            .get_many::<String>("allow-dry-run")
+            .map(|resources| {
+                resources
+                    .map(|resource| parse_dry_run_resource(resource))
+                    .collect::<Result<BTreeSet<ContextAwareResource>>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
 
         let raw = matches.get_one::<bool>("raw").unwrap_or(&false).to_owned();
 
@@ -404,6 +417,7 @@ impl PolicyDefinition {
             raw,
             settings,
             ctx_aware_cfg,
+            dry_run_resources,
         })
     }
 
@@ -417,6 +431,23 @@ impl PolicyDefinition {
     }
 }
 
+/// Parses a `<apiVersion>/<kind>` string, like `apps/v1/Deployment` or `v1/Pod`
+fn parse_dry_run_resource(resource: &str) -> Result<ContextAwareResource> {
+    match resource.rsplit_once('/') {
+        Some((api_version, kind)) if !api_version.is_empty() && !kind.is_empty() => {
+            Ok(ContextAwareResource {
+                api_version: api_version.to_owned(),
+                kind: kind.to_owned(),
+                projection: None,
+            })
+        }
+        _ => Err(anyhow!(
+            "invalid value for --allow-dry-run: {:?}, expected <API_VERSION>/<KIND>",
+            resource
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -424,6 +455,7 @@ mod tests {
     use k8s_openapi::apimachinery::pkg::runtime::RawExtension;
     use policy_evaluator::kubewarden_policy_sdk::crd::policies::common::ContextAwareResource as ContextAwareResourceSdk;
     use policy_evaluator::kubewarden_policy_sdk::crd::policies::common::PolicyMode as PolicyModeSdk;
+    use rstest::rstest;
     use serde_json::json;
 
     #[test]
@@ -468,6 +500,7 @@ mod tests {
                 settings,
                 raw,
                 ctx_aware_cfg,
+                dry_run_resources,
                 policy_mode,
                 allowed_to_mutate,
                 custom_rejection_message,
@@ -484,6 +517,7 @@ mod tests {
                 assert_eq!(custom_rejection_message, Some("foo".to_string()));
                 assert_eq!(settings, expected_settings);
                 assert!(matches!(ctx_aware_cfg, ContextAwareConfiguration::NoAccess));
+                assert!(dry_run_resources.is_empty());
             }
             _ => panic!("Expected Individual PolicyDefinition"),
         }
@@ -519,10 +553,12 @@ mod tests {
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Pod".to_string(),
+                projection: None,
             },
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Service".to_string(),
+                projection: None,
             },
         ]);
 
@@ -554,6 +590,7 @@ mod tests {
                 settings,
                 raw,
                 ctx_aware_cfg,
+                dry_run_resources,
                 policy_mode,
                 allowed_to_mutate,
                 custom_rejection_message,
@@ -573,6 +610,7 @@ mod tests {
                     ctx_aware_cfg,
                     ContextAwareConfiguration::AllowList(expected_context_aware_resources)
                 );
+                assert!(dry_run_resources.is_empty());
             }
             _ => panic!("Expected Individual PolicyDefinition"),
         }
@@ -603,10 +641,12 @@ mod tests {
                 ContextAwareResource {
                     api_version: "v1".to_string(),
                     kind: "Pod".to_string(),
+                    projection: None,
                 },
                 ContextAwareResource {
                     api_version: "v1".to_string(),
                     kind: "Service".to_string(),
+                    projection: None,
                 },
             ]);
         let pgm_1 = PolicyGroupMemberWithContext {
@@ -666,6 +706,9 @@ mod tests {
                             settings: PolicySettings::try_from(&pgm_1.settings.0)
                                 .expect("Failed to convert settings for member 1"),
                             ctx_aware_resources_allow_list: pgm_1_expected_context_aware_resources,
+                            dry_run_resources_allow_list: BTreeSet::new(),
+                            host_capabilities_allow_list: None,
+                            rego_data: None,
                         },
                     },
                 ),
@@ -677,6 +720,9 @@ mod tests {
                             settings: PolicySettings::try_from(&pgm_2.settings.0)
                                 .expect("Failed to convert settings for member 2"),
                             ctx_aware_resources_allow_list: BTreeSet::new(),
+                            dry_run_resources_allow_list: BTreeSet::new(),
+                            host_capabilities_allow_list: None,
+                            rego_data: None,
                         },
                     },
                 ),
@@ -782,4 +828,53 @@ mod tests {
             _ => panic!("Expected Group PolicyDefinition"),
         }
     }
+
+    fn run_matches(flags: &[&str]) -> ArgMatches {
+        let args = ["kwctl", "run", "-r", "request.json"]
+            .iter()
+            .chain(flags)
+            .chain(["registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5"].iter());
+        let matches = crate::cli::build_cli().try_get_matches_from(args).unwrap();
+        matches.subcommand_matches("run").unwrap().to_owned()
+    }
+
+    #[rstest]
+    #[case::not_granted(&[], &[])]
+    #[case::granted(
+        &["--allow-dry-run", "apps/v1/Deployment,v1/Pod"],
+        &[("apps/v1", "Deployment"), ("v1", "Pod")]
+    )]
+    fn policy_definition_from_cli_dry_run_resources(
+        #[case] flags: &[&str],
+        #[case] expected: &[(&str, &str)],
+    ) {
+        let expected: BTreeSet<ContextAwareResource> = expected
+            .iter()
+            .map(|(api_version, kind)| ContextAwareResource {
+                api_version: api_version.to_string(),
+                kind: kind.to_string(),
+                projection: None,
+            })
+            .collect();
+
+        match PolicyDefinition::from_cli(&run_matches(flags)).unwrap() {
+            PolicyDefinition::Policy {
+                dry_run_resources, ..
+            } => assert_eq!(dry_run_resources, expected),
+            _ => panic!("Expected Individual PolicyDefinition"),
+        }
+    }
+
+    #[rstest]
+    #[case::no_separator("Deployment")]
+    #[case::empty_api_version("/Deployment")]
+    #[case::empty_kind("apps/v1/")]
+    fn policy_definition_from_cli_invalid_dry_run_resource(#[case] resource: &str) {
+        let error = PolicyDefinition::from_cli(&run_matches(&["--allow-dry-run", resource]))
+            .expect_err("the resource should have been rejected");
+
+        assert!(error
+            .to_string()
+            .starts_with("invalid value for --allow-dry-run"));
+    }
 }
@@ -2,15 +2,21 @@ use std::{
     collections::{HashMap, HashSet},
     fs,
     io::{self, Read},
+    net::SocketAddr,
     path::PathBuf,
     str::FromStr,
     sync::Arc,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
 use clap::ArgMatches;
-use policy_evaluator::policy_fetcher::{
-    sigstore::trust::ManualTrustRoot, sources::Sources, verify::config::LatestVerificationConfig,
+use policy_evaluator::{
+    callback_handler::{parse_nameserver, DEFAULT_DNS_TIMEOUT},
+    policy_fetcher::{
+        sigstore::trust::ManualTrustRoot, sources::Sources,
+        verify::config::LatestVerificationConfig,
+    },
 };
 use tracing::info;
 
@@ -36,6 +42,7 @@ pub(crate) struct PullAndRunSettings {
     pub sigstore_trust_root: Option<Arc<ManualTrustRoot<'static>>>,
     pub enable_wasmtime_cache: bool,
     pub host_capabilities_mode: HostCapabilitiesMode,
+    pub dns_settings: callback_handler::DnsSettings,
 }
 
 pub(crate) fn parse_policy_definitions(matches: &ArgMatches) -> Result<Vec<PolicyDefinition>> {
@@ -134,6 +141,25 @@ pub(crate) async fn parse_pull_and_run_settings(
             HostCapabilitiesMode::Proxy(callback_handler::ProxyMode::Replay { source });
     }
 
+    if let Some(directory) = matches.get_one::<String>("kube-context-dir") {
+        let can_i = matches
+            .get_one::<String>("kube-context-can-i")
+            .map(|answer| answer == "allow")
+            .unwrap_or(true);
+
+        info!(
+            directory,
+            can_i, "Kubernetes requests served from local manifests"
+        );
+        host_capabilities_mode =
+            HostCapabilitiesMode::KubeContext(callback_handler::KubeContextSettings {
+                directory: PathBuf::from(directory),
+                can_i,
+            });
+    }
+
+    let dns_settings = parse_dns_settings(matches)?;
+
     Ok(PullAndRunSettings {
         sources,
         request,
@@ -141,6 +167,30 @@ pub(crate) async fn parse_pull_and_run_settings(
         sigstore_trust_root,
         enable_wasmtime_cache,
         host_capabilities_mode,
+        dns_settings,
+    })
+}
+
+fn parse_dns_settings(matches: &ArgMatches) -> Result<callback_handler::DnsSettings> {
+    let nameservers = matches
+        .get_many::<String>("dns-nameservers")
+        .map(|nameservers| {
+            nameservers
+                .map(|nameserver| parse_nameserver(nameserver))
+                .collect::<Result<Vec<SocketAddr>>>()
+        })
+        .transpose()?;
+    let timeout = match matches.get_one::<String>("dns-timeout") {
+        Some(timeout) => timeout
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| anyhow!("invalid value for --dns-timeout: {}", e))?,
+        None => DEFAULT_DNS_TIMEOUT,
+    };
+
+    Ok(callback_handler::DnsSettings {
+        nameservers,
+        timeout,
     })
 }
 
@@ -173,3 +223,51 @@ async fn build_verified_manifest_digests(
 
     Ok(verified_manifest_digests)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn run_matches(flags: &[&str]) -> ArgMatches {
+        let args = ["kwctl", "run", "-r", "request.json"]
+            .iter()
+            .chain(flags)
+            .chain(["policy.wasm"].iter());
+        let matches = crate::cli::build_cli().try_get_matches_from(args).unwrap();
+        matches.subcommand_matches("run").unwrap().to_owned()
+    }
+
+    #[rstest]
+    #[case::defaults(&[], None, DEFAULT_DNS_TIMEOUT)]
+    #[case::custom(
+        &["--dns-nameservers", "10.0.0.10,[fd00::1]:5353", "--dns-timeout", "1"],
+        Some(vec!["10.0.0.10:53", "[fd00::1]:5353"]),
+        Duration::from_secs(1)
+    )]
+    fn dns_settings(
+        #[case] flags: &[&str],
+        #[case] nameservers: Option<Vec<&str>>,
+        #[case] timeout: Duration,
+    ) {
+        let dns_settings = parse_dns_settings(&run_matches(flags)).unwrap();
+
+        assert_eq!(
+            dns_settings,
+            callback_handler::DnsSettings {
+                nameservers: nameservers.map(|nameservers| nameservers
+                    .into_iter()
+                    .map(|nameserver| nameserver.parse().unwrap())
+                    .collect()),
+                timeout,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_dns_nameserver() {
+        assert!(
+            parse_dns_settings(&run_matches(&["--dns-nameservers", "dns.example.com"])).is_err()
+        );
+    }
+}
@@ -23,6 +23,7 @@ use policy_evaluator::{
         sources::Sources,
     },
     policy_metadata::Metadata,
+    rego_builtins::RegoBuiltinsReport,
 };
 use prettytable::{format::FormatBuilder, row, Table};
 use termimad::{terminal_size, FmtText, MadSkin};
@@ -41,23 +42,53 @@ pub(crate) async fn inspect(
     let metadata = Metadata::from_path(&wasm_path)
         .map_err(|e| anyhow!("Error parsing policy metadata: {}", e))?;
 
-    match metadata {
-        Some(metadata) => metadata_printer.print(&metadata, no_color)?,
+    let metadata = match metadata {
+        Some(metadata) => metadata,
         None => return Err(anyhow!(
             "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
             uri
         )),
     };
+    metadata_printer.print(&metadata, no_color)?;
+
+    // Incompatible policies are reported after printing all the details, so
+    // that `kwctl inspect` can be used to gate CI pipelines
+    let mut not_implemented_builtins = Vec::new();
+    if matches!(
+        metadata.execution_mode,
+        PolicyExecutionMode::Opa | PolicyExecutionMode::OpaGatekeeper
+    ) {
+        let rego_builtins_report = RegoBuiltinsReport::from_path(&wasm_path)
+            .map_err(|e| anyhow!("Error inspecting Rego builtins: {}", e))?;
+        let rego_builtins_printer = RegoBuiltinsPrinter::from(&output);
+        rego_builtins_printer.print(&rego_builtins_report)?;
+        not_implemented_builtins = rego_builtins_report
+            .not_implemented()
+            .into_iter()
+            .map(String::from)
+            .collect();
+    }
+
+    if !no_signatures {
+        print_signatures(&uri, sources, &output).await;
+    }
 
-    if no_signatures {
-        return Ok(());
+    if !not_implemented_builtins.is_empty() {
+        return Err(anyhow!(
+            "The policy cannot be evaluated by Kubewarden, these builtins are not implemented: {}",
+            not_implemented_builtins.join(", ")
+        ));
     }
 
-    let signatures = fetch_signatures_manifest(&uri, sources).await;
+    Ok(())
+}
+
+async fn print_signatures(uri: &str, sources: Option<Sources>, output: &OutputType) {
+    let signatures = fetch_signatures_manifest(uri, sources).await;
     match signatures {
         Ok(signatures) => {
             if let Some(signatures) = signatures {
-                let sigstore_printer = SignaturesPrinter::from(&output);
+                let sigstore_printer = SignaturesPrinter::from(output);
                 sigstore_printer.print(&signatures);
             }
         }
@@ -74,8 +105,6 @@ pub(crate) async fn inspect(
             }
         }
     }
-
-    Ok(())
 }
 
 pub(crate) enum OutputType {
@@ -269,6 +298,51 @@ impl MetadataPrinter {
     }
 }
 
+enum RegoBuiltinsPrinter {
+    Yaml,
+    Pretty,
+}
+
+impl From<&OutputType> for RegoBuiltinsPrinter {
+    fn from(output_type: &OutputType) -> Self {
+        match output_type {
+            OutputType::Yaml => Self::Yaml,
+            OutputType::Pretty => Self::Pretty,
+        }
+    }
+}
+
+impl RegoBuiltinsPrinter {
+    fn print(&self, report: &RegoBuiltinsReport) -> Result<()> {
+        match self {
+            RegoBuiltinsPrinter::Yaml => {
+                let doc_entry = HashMap::from([("regoBuiltins", report)]);
+                print!("{}", serde_yaml::to_string(&doc_entry)?);
+            }
+            RegoBuiltinsPrinter::Pretty => {
+                println!();
+                let mut table = Table::new();
+                table.set_format(FormatBuilder::new().padding(0, 1).build());
+                table.add_row(row![Fmbl -> "Rego builtins"]);
+                for builtin in &report.builtins {
+                    if builtin.implemented {
+                        table.add_row(row![builtin.name, Fg -> "implemented"]);
+                    } else {
+                        table.add_row(row![builtin.name, Fr -> "not implemented"]);
+                    }
+                }
+                table.printstd();
+
+                if report.builtins.is_empty() {
+                    println!("The policy does not use any builtin provided by the host.");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
 enum SignaturesPrinter {
     Yaml,
     Pretty,
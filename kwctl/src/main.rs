@@ -7,7 +7,7 @@ use clap::ArgMatches;
 use itertools::Itertools;
 use lazy_static::lazy_static;
 use policy_evaluator::policy_fetcher::{registry::Registry, store::DEFAULT_ROOT, PullDestination};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use tracing_subscriber::{
     filter::{EnvFilter, LevelFilter},
     fmt,
@@ -40,6 +40,10 @@ mod push;
 mod rm;
 mod save;
 mod scaffold;
+mod session;
+mod sign;
+mod test_rego;
+mod test_report;
 mod utils;
 mod verify;
 
@@ -184,6 +188,66 @@ async fn main() -> Result<()> {
             };
             Ok(())
         }
+        Some("sign") => {
+            if let Some(matches) = matches.subcommand_matches("sign") {
+                let sources = remote_server_options(matches)?;
+                let uri = matches.get_one::<String>("uri").unwrap();
+                let key_path = PathBuf::from(matches.get_one::<String>("key").unwrap());
+                let password = std::env::var(sign::KEY_PASSWORD_ENV_VAR).unwrap_or_default();
+                let annotations = matches
+                    .get_many::<String>("annotation")
+                    .unwrap_or_default()
+                    .map(|annotation| {
+                        annotation
+                            .split_once('=')
+                            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                            .ok_or_else(|| {
+                                anyhow!("Invalid annotation '{annotation}', expected key=value")
+                            })
+                    })
+                    .collect::<Result<verify::VerificationAnnotations>>()?;
+
+                let signature_ref = sign::sign(
+                    uri,
+                    sources.as_ref(),
+                    &key_path,
+                    password.as_bytes(),
+                    &annotations,
+                )
+                .await?;
+
+                match matches.get_one::<String>("output").map(|s| s.as_str()) {
+                    Some("json") => {
+                        let mut response: HashMap<&str, String> = HashMap::new();
+                        response.insert("signature_ref", signature_ref);
+                        serde_json::to_writer(std::io::stdout(), &response)?
+                    }
+                    _ => {
+                        println!("Policy successfully signed: {signature_ref}");
+                    }
+                }
+            };
+            Ok(())
+        }
+        Some("generate-key-pair") => {
+            if let Some(matches) = matches.subcommand_matches("generate-key-pair") {
+                let key_type = matches.get_one::<String>("key-type").unwrap().parse()?;
+                let output_prefix = matches.get_one::<String>("output-key-prefix").unwrap();
+                let password = std::env::var(sign::KEY_PASSWORD_ENV_VAR).unwrap_or_default();
+                if password.is_empty() {
+                    warn!(
+                        "{} is not set, the private key is not protected by a password",
+                        sign::KEY_PASSWORD_ENV_VAR
+                    );
+                }
+
+                let (private_key_path, public_key_path) =
+                    sign::generate_key_pair(key_type, output_prefix, password.as_bytes())?;
+                println!("Private key written to {}", private_key_path.display());
+                println!("Public key written to {}", public_key_path.display());
+            };
+            Ok(())
+        }
         Some("rm") => {
             if let Some(matches) = matches.subcommand_matches("rm") {
                 let uri_or_sha_prefix = matches.get_one::<String>("uri_or_sha_prefix").unwrap();
@@ -195,7 +259,7 @@ async fn main() -> Result<()> {
             let run_arg = matches
                 .subcommand_matches("run")
                 .expect("run subcommand not found");
-            cli::run::exec(run_arg).await
+            cli::run::exec(run_arg, no_color).await
         }
         Some("bench") => {
             let bench_arg = matches
@@ -203,6 +267,12 @@ async fn main() -> Result<()> {
                 .expect("bench subcommand not found");
             cli::bench::exec(bench_arg).await
         }
+        Some("lint") => {
+            let lint_arg = matches
+                .subcommand_matches("lint")
+                .expect("lint subcommand not found");
+            cli::lint::exec(lint_arg)
+        }
         Some("annotate") => {
             if let Some(matches) = matches.subcommand_matches("annotate") {
                 let wasm_path = matches
@@ -290,16 +360,44 @@ async fn main() -> Result<()> {
                 if let Some(matches) = matches.subcommand_matches("vap") {
                     let cel_policy_uri = matches.get_one::<String>("cel-policy").unwrap();
                     let vap_file: PathBuf = matches.get_one::<String>("policy").unwrap().into();
-                    let vap_binding_file: PathBuf =
-                        matches.get_one::<String>("binding").unwrap().into();
+                    let vap_binding_files: Vec<PathBuf> = matches
+                        .get_many::<String>("binding")
+                        .unwrap()
+                        .map(PathBuf::from)
+                        .collect();
+                    let param_files: Vec<PathBuf> = matches
+                        .get_many::<String>("param")
+                        .unwrap_or_default()
+                        .map(PathBuf::from)
+                        .collect();
 
                     scaffold::vap(
                         cel_policy_uri.as_str(),
                         vap_file.as_path(),
-                        vap_binding_file.as_path(),
+                        &vap_binding_files,
+                        &param_files,
                     )?;
                 };
             }
+            if let Some(matches) = matches.subcommand_matches("scaffold") {
+                if let Some(matches) = matches.subcommand_matches("gatekeeper") {
+                    let module = matches.get_one::<String>("module").unwrap();
+                    let template_file: PathBuf =
+                        matches.get_one::<String>("template").unwrap().into();
+                    let constraint_files: Vec<PathBuf> = matches
+                        .get_many::<String>("constraint")
+                        .unwrap()
+                        .map(PathBuf::from)
+                        .collect();
+
+                    scaffold::gatekeeper(
+                        module.as_str(),
+                        template_file.as_path(),
+                        &constraint_files,
+                    )
+                    .await?;
+                };
+            }
             if let Some(matches) = matches.subcommand_matches("scaffold") {
                 if let Some(matches) = matches.subcommand_matches("admission-request") {
                     let operation: scaffold::AdmissionRequestOperation = matches
@@ -318,7 +416,19 @@ async fn main() -> Result<()> {
                         None
                     };
 
-                    scaffold::admission_request(operation, object_path, old_object_path).await?;
+                    let mut settings = scaffold::AdmissionRequestSettings {
+                        username: matches.get_one::<String>("username").unwrap().to_owned(),
+                        namespace: matches.get_one::<String>("namespace").cloned(),
+                        sub_resource: matches.get_one::<String>("subresource").cloned(),
+                        dry_run: matches.get_flag("dry-run"),
+                        ..Default::default()
+                    };
+                    if let Some(groups) = matches.get_many::<String>("group") {
+                        settings.groups = groups.cloned().collect();
+                    }
+
+                    scaffold::admission_request(operation, object_path, old_object_path, settings)
+                        .await?;
                 };
             }
 
@@ -356,6 +466,64 @@ async fn main() -> Result<()> {
             }
             Ok(())
         }
+        Some("test") => {
+            let test_arg = matches
+                .subcommand_matches("test")
+                .expect("test subcommand not found");
+            cli::test::exec(test_arg).await
+        }
+        Some("test-rego") => {
+            if let Some(matches) = matches.subcommand_matches("test-rego") {
+                let uri_or_sha_prefix = matches.get_one::<String>("uri_or_sha_prefix").unwrap();
+                let output = test_report::OutputType::try_from(
+                    matches.get_one::<String>("output").map(|s| s.as_str()),
+                )?;
+                let data_path = matches
+                    .get_one::<String>("data-path")
+                    .map(|path| PathBuf::from_str(path).unwrap());
+                let filter = matches
+                    .get_one::<String>("run")
+                    .map(|run| {
+                        regex::Regex::new(run)
+                            .map_err(|e| anyhow!("invalid regular expression '{}': {}", run, e))
+                    })
+                    .transpose()?;
+                test_rego::test_rego(
+                    uri_or_sha_prefix,
+                    data_path.as_deref(),
+                    filter.as_ref(),
+                    output,
+                )?;
+            }
+            Ok(())
+        }
+        Some("session") => {
+            if let Some(matches) = matches.subcommand_matches("session") {
+                if let Some(matches) = matches.subcommand_matches("merge") {
+                    let inputs: Vec<PathBuf> = matches
+                        .get_many::<String>("sessions")
+                        .unwrap()
+                        .map(PathBuf::from)
+                        .collect();
+                    let output = matches.get_one::<String>("output").map(PathBuf::from);
+                    session::merge(&inputs, output.as_deref())?;
+                }
+                if let Some(matches) = matches.subcommand_matches("prune") {
+                    let input = PathBuf::from(matches.get_one::<String>("session").unwrap());
+                    let request_types: Vec<String> = matches
+                        .get_many::<String>("request-type")
+                        .map(|types| types.cloned().collect())
+                        .unwrap_or_default();
+                    let remove_errors = matches
+                        .get_one::<bool>("errors")
+                        .unwrap_or(&false)
+                        .to_owned();
+                    let output = matches.get_one::<String>("output").map(PathBuf::from);
+                    session::prune(&input, &request_types, remove_errors, output.as_deref())?;
+                }
+            }
+            Ok(())
+        }
         Some("docs") => {
             if let Some(matches) = matches.subcommand_matches("docs") {
                 let output = matches.get_one::<String>("output").unwrap();
@@ -437,29 +605,26 @@ async fn pull_command(
  * This function will pull the policy if it is not already present in the local store.
  */
 async fn scaffold_manifest_command(matches: &ArgMatches) -> Result<()> {
-    let uri_or_sha_prefix = matches.get_one::<String>("uri_or_sha_prefix").unwrap();
+    let uris: Vec<&String> = matches
+        .get_many::<String>("uri_or_sha_prefix")
+        .unwrap()
+        .collect();
 
-    pull_if_needed(uri_or_sha_prefix, matches).await?;
+    for uri_or_sha_prefix in &uris {
+        pull_if_needed(uri_or_sha_prefix, matches).await?;
+    }
 
-    let resource_type = matches.get_one::<String>("type").unwrap();
+    let resource_type: scaffold::ManifestType =
+        matches.get_one::<String>("type").unwrap().parse()?;
     if matches.contains_id("settings-path") && matches.contains_id("settings-json") {
         return Err(anyhow!(
             "'settings-path' and 'settings-json' cannot be used at the same time"
         ));
     }
-    let settings = if matches.contains_id("settings-path") {
-        matches
-            .get_one::<String>("settings-path")
-            .map(|settings| -> Result<String> {
-                fs::read_to_string(settings)
-                    .map_err(|e| anyhow!("Error reading settings from {}: {}", settings, e))
-            })
-            .transpose()?
-    } else if matches.contains_id("settings-json") {
-        Some(matches.get_one::<String>("settings-json").unwrap().clone())
-    } else {
-        None
-    };
+    let settings_paths: Vec<&String> = matches
+        .get_many::<String>("settings-path")
+        .unwrap_or_default()
+        .collect();
     let policy_title = matches.get_one::<String>("title").cloned();
 
     let allow_context_aware_resources = matches
@@ -467,11 +632,108 @@ async fn scaffold_manifest_command(matches: &ArgMatches) -> Result<()> {
         .unwrap_or(&false)
         .to_owned();
 
+    if resource_type.is_group() {
+        return scaffold_manifest_group_command(
+            matches,
+            &uris,
+            &settings_paths,
+            resource_type,
+            policy_title.as_deref(),
+            allow_context_aware_resources,
+        );
+    }
+
+    for group_flag in ["member-name", "expression", "message"] {
+        if matches.contains_id(group_flag) {
+            return Err(anyhow!(
+                "'{}' can only be used with policy groups",
+                group_flag
+            ));
+        }
+    }
+    if uris.len() > 1 || settings_paths.len() > 1 {
+        return Err(anyhow!(
+            "only policy groups can be scaffolded from more than one policy"
+        ));
+    }
+
+    let settings = if let Some(settings) = settings_paths.first() {
+        Some(
+            fs::read_to_string(settings)
+                .map_err(|e| anyhow!("Error reading settings from {}: {}", settings, e))?,
+        )
+    } else if matches.contains_id("settings-json") {
+        Some(matches.get_one::<String>("settings-json").unwrap().clone())
+    } else {
+        None
+    };
+
     scaffold::manifest(
-        uri_or_sha_prefix,
-        resource_type.parse()?,
+        uris[0],
+        resource_type,
         settings.as_deref(),
         policy_title.as_deref(),
         allow_context_aware_resources,
     )
 }
+
+fn scaffold_manifest_group_command(
+    matches: &ArgMatches,
+    uris: &[&String],
+    settings_paths: &[&String],
+    resource_type: scaffold::ManifestType,
+    policy_title: Option<&str>,
+    allow_context_aware_resources: bool,
+) -> Result<()> {
+    if matches.contains_id("settings-json") {
+        return Err(anyhow!(
+            "'settings-json' cannot be used with policy groups, use 'settings-path' for each policy"
+        ));
+    }
+    let member_names: Vec<&String> = matches
+        .get_many::<String>("member-name")
+        .unwrap_or_default()
+        .collect();
+    if member_names.len() != uris.len() {
+        return Err(anyhow!(
+            "each policy of the group must have a 'member-name': got {} policies and {} names",
+            uris.len(),
+            member_names.len()
+        ));
+    }
+    if !settings_paths.is_empty() && settings_paths.len() != uris.len() {
+        return Err(anyhow!(
+            "each policy of the group must have a 'settings-path': got {} policies and {} settings files",
+            uris.len(),
+            settings_paths.len()
+        ));
+    }
+
+    let members = uris
+        .iter()
+        .enumerate()
+        .map(|(i, uri)| {
+            let settings = settings_paths
+                .get(i)
+                .map(|settings| {
+                    fs::read_to_string(settings)
+                        .map_err(|e| anyhow!("Error reading settings from {}: {}", settings, e))
+                })
+                .transpose()?;
+            Ok(scaffold::GroupMember {
+                name: member_names[i].to_string(),
+                uri_or_sha_prefix: uri.to_string(),
+                settings,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    scaffold::manifest_group(
+        &members,
+        resource_type,
+        matches.get_one::<String>("expression").unwrap(),
+        matches.get_one::<String>("message").unwrap(),
+        policy_title,
+        allow_context_aware_resources,
+    )
+}
@@ -1,11 +1,14 @@
 mod kubewarden_crds;
 
 mod manifest;
-pub(crate) use manifest::manifest;
+pub(crate) use manifest::{manifest, manifest_group, GroupMember, ManifestType};
 
 mod vap;
 pub(crate) use vap::vap;
 
+mod gatekeeper;
+pub(crate) use gatekeeper::gatekeeper;
+
 mod verification_config;
 pub(crate) use verification_config::verification_config;
 
@@ -14,4 +17,7 @@ pub(crate) use artifacthub::artifacthub;
 
 mod admission_request;
 pub(crate) use admission_request::Operation as AdmissionRequestOperation;
-pub(crate) use admission_request::{admission_request, DEFAULT_KWCTL_CACHE};
+pub(crate) use admission_request::{
+    admission_request, build_admission_request, AdmissionRequestSettings, ApiResourceCatalog,
+    DEFAULT_KWCTL_CACHE, RESOURCE_CATALOG_FILE,
+};
@@ -3,7 +3,7 @@ use std::{
     fmt::{self, Display, Formatter},
     fs::File,
     future::Future,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -76,7 +76,7 @@ enum ApiResourceCatalogRestoredFrom {
 /// inferred from the object itself. For example: knowning if a resource is namespaced or not, or
 /// the plural name of the resource.
 #[derive(Serialize, Deserialize, Debug)]
-struct ApiResourceCatalog {
+pub(crate) struct ApiResourceCatalog {
     resources: HashMap<String, APIResource>,
     #[serde(skip)]
     restored_from: ApiResourceCatalogRestoredFrom,
@@ -107,6 +107,19 @@ impl ApiResourceCatalog {
         }
     }
 
+    /// Load the catalog from the given cache file, without querying the Kubernetes API server.
+    /// Returns `None` when the cache file does not exist.
+    pub fn from_cache(catalog_path: &Path) -> Result<Option<Self>> {
+        if !catalog_path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(catalog_path)
+            .map_err(|err| anyhow!("failed to open resource catalog: {err:?}"))?;
+        let catalog: Self = serde_json::from_reader(file)
+            .map_err(|err| anyhow!("failed to read resource catalog: {err:?}"))?;
+        Ok(Some(catalog))
+    }
+
     async fn init<F, Fut>(catalog_path: PathBuf, build_kubeclient_fn: F) -> Result<Self>
     where
         F: FnOnce() -> Fut + Clone,
@@ -207,6 +220,24 @@ impl ApiResourceCatalog {
         self.resources.get(&Self::gvk_to_string(gvk))
     }
 
+    /// Find the name of the resource of the given kind, inside of any version of the given
+    /// API group. The `*` API group matches all of them
+    pub fn lookup_resource_name(&self, api_group: &str, kind: &str) -> Option<&str> {
+        self.resources
+            .iter()
+            .find(|(key, _)| {
+                let mut gvk = key.split('|');
+                let group = gvk.next().unwrap_or_default();
+                let resource_kind = gvk.next_back().unwrap_or_default();
+                (api_group == "*" || group == api_group) && resource_kind == kind
+            })
+            .map(|(_, resource)| resource.name.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
     /// Refresh the catalog by querying the Kubernetes API server.
     /// This applies only if the catalog was built from the cache.
     pub async fn refresh<F, Fut>(&mut self, build_kubeclient_fn: F) -> Result<()>
@@ -245,32 +276,56 @@ impl ApiResourceCatalog {
 // The scaffold command must be snappy, we don't want it to get stuck
 // waiting for the connection to Kubernetes to be established.
 // Because of that we set a connection timeout of 1 second.
-async fn build_kube_client() -> Result<kube::Client> {
+pub(super) async fn build_kube_client() -> Result<kube::Client> {
     let mut config = kube::Config::infer().await?;
     config.connect_timeout = Some(std::time::Duration::from_secs(1));
     let client = kube::Client::try_from(config)?;
     Ok(client)
 }
 
+/// Settings of the scaffolded AdmissionRequest that cannot be inferred from the objects
+pub(crate) struct AdmissionRequestSettings {
+    /// Name of the user performing the request
+    pub username: String,
+    /// Groups of the user performing the request
+    pub groups: Vec<String>,
+    /// Namespace of the request, overrides the one of the objects
+    pub namespace: Option<String>,
+    /// Subresource being requested (e.g. `status`, `scale`, `exec`)
+    pub sub_resource: Option<String>,
+    /// Whether the request is a dry-run one
+    pub dry_run: bool,
+}
+
+impl Default for AdmissionRequestSettings {
+    fn default() -> Self {
+        Self {
+            username: "test-user".to_string(),
+            groups: vec!["system:masters".to_string()],
+            namespace: None,
+            sub_resource: None,
+            dry_run: false,
+        }
+    }
+}
+
 pub(crate) async fn admission_request(
     operation: Operation,
     object: Option<PathBuf>,
     old_object: Option<PathBuf>,
+    settings: AdmissionRequestSettings,
 ) -> Result<()> {
     validate_params(&operation, object.as_ref(), old_object.as_ref())?;
 
-    let output = match operation {
-        Operation::Create => {
-            scaffold_create(
-                RESOURCE_CATALOG_FILE.to_path_buf(),
-                build_kube_client,
-                object.unwrap(),
-            )
-            .await?
-        }
-        Operation::Update => todo!(),
-        Operation::Delete => todo!(),
-    };
+    let output = scaffold(
+        RESOURCE_CATALOG_FILE.to_path_buf(),
+        build_kube_client,
+        operation,
+        object,
+        old_object,
+        &settings,
+    )
+    .await?;
 
     println!("{}", output);
     Ok(())
@@ -311,19 +366,8 @@ fn validate_params(
     Ok(())
 }
 
-async fn scaffold_create<F, Fut>(
-    resource_catalog_file: PathBuf,
-    kube_client: F,
-    object_path: PathBuf,
-) -> Result<String>
-where
-    F: FnOnce() -> Fut + Clone,
-    Fut: Future<Output = Result<kube::Client>>,
-{
-    let mut resource_catalog =
-        ApiResourceCatalog::new(resource_catalog_file, kube_client.clone()).await;
-
-    let file = File::open(object_path.clone()).map_err(|err| {
+fn read_object(object_path: &Path) -> Result<DynamicObject> {
+    let file = File::open(object_path).map_err(|err| {
         anyhow!(
             "failed to open object file {}: {}",
             object_path.to_string_lossy(),
@@ -337,19 +381,71 @@ where
             err
         )
     })?;
+    if object.types.is_none() {
+        return Err(anyhow!(
+            "object defined inside of {} is missing types",
+            object_path.to_string_lossy()
+        ));
+    }
+
+    Ok(object)
+}
+
+/// Ensure the object and the old object of an UPDATE operation describe the same resource
+fn validate_update_objects(object: &DynamicObject, old_object: &DynamicObject) -> Result<()> {
+    if object.types != old_object.types {
+        anyhow::bail!(
+            "object and old_object must have the same apiVersion and kind, got {:?} and {:?}",
+            object.types,
+            old_object.types
+        );
+    }
+    if object.metadata.name != old_object.metadata.name {
+        anyhow::bail!(
+            "object and old_object must have the same name, got {:?} and {:?}",
+            object.metadata.name,
+            old_object.metadata.name
+        );
+    }
+    if object.metadata.namespace != old_object.metadata.namespace {
+        anyhow::bail!(
+            "object and old_object must have the same namespace, got {:?} and {:?}",
+            object.metadata.namespace,
+            old_object.metadata.namespace
+        );
+    }
+
+    Ok(())
+}
+
+async fn scaffold<F, Fut>(
+    resource_catalog_file: PathBuf,
+    kube_client: F,
+    operation: Operation,
+    object_path: Option<PathBuf>,
+    old_object_path: Option<PathBuf>,
+    settings: &AdmissionRequestSettings,
+) -> Result<String>
+where
+    F: FnOnce() -> Fut + Clone,
+    Fut: Future<Output = Result<kube::Client>>,
+{
+    let object = object_path.as_deref().map(read_object).transpose()?;
+    let old_object = old_object_path.as_deref().map(read_object).transpose()?;
+    if let (Some(object), Some(old_object)) = (&object, &old_object) {
+        validate_update_objects(object, old_object)?;
+    }
 
-    let object_type_meta = object.clone().types.ok_or(anyhow!(
-        "object defined inside of {} is missing types",
-        object_path.to_string_lossy()
-    ))?;
+    let kube_gvk = object_gvk(object.as_ref(), old_object.as_ref())?;
 
-    let kube_gvk: kube::api::GroupVersionKind = object_type_meta.try_into()?;
+    let mut resource_catalog =
+        ApiResourceCatalog::new(resource_catalog_file.clone(), kube_client.clone()).await;
     let api_resource = match resource_catalog.lookup(&kube_gvk) {
         Some(ar) => Some(ar),
         None => {
             // Try to refresh the catalog and lookup again
             if resource_catalog.refresh(kube_client).await.is_ok() {
-                if let Err(err) = resource_catalog.save(object_path) {
+                if let Err(err) = resource_catalog.save(resource_catalog_file) {
                     warn!(?err, "Failed to save resource catalog");
                 }
                 resource_catalog.lookup(&kube_gvk)
@@ -365,13 +461,64 @@ where
         );
     }
 
+    let request = build_admission_request(operation, object, old_object, api_resource, settings)?;
+    let output = serde_json::to_string_pretty(&request)?;
+
+    Ok(output)
+}
+
+/// Find the GroupVersionKind of the objects of the request.
+/// The old object is used only by DELETE operations, which do not have an object.
+fn object_gvk(
+    object: Option<&DynamicObject>,
+    old_object: Option<&DynamicObject>,
+) -> Result<kube::api::GroupVersionKind> {
+    let object_type_meta = object
+        .or(old_object)
+        .ok_or(anyhow!("no object has been provided"))?
+        .types
+        .clone()
+        .ok_or(anyhow!("object is missing types"))?;
+
+    Ok(object_type_meta.try_into()?)
+}
+
+/// Build an AdmissionRequest for the given objects.
+///
+/// The `api_resource` describing the kind of the objects is used to find the plural name
+/// of the resource and to know whether it is namespaced. When not provided, a placeholder
+/// is used as plural name.
+pub(crate) fn build_admission_request(
+    operation: Operation,
+    mut object: Option<DynamicObject>,
+    mut old_object: Option<DynamicObject>,
+    api_resource: Option<&APIResource>,
+    settings: &AdmissionRequestSettings,
+) -> Result<AdmissionRequest> {
+    let kube_gvk = object_gvk(object.as_ref(), old_object.as_ref())?;
+    let reference_object = object.as_ref().or(old_object.as_ref()).unwrap();
+    let name = reference_object.metadata.name.clone();
+    let object_namespace = reference_object.metadata.namespace.clone();
+
     let resource = match api_resource {
         Some(ar) => ar.name.clone(),
         None => FALLBACK_API_RESOURCE_PLURAL_NAME.to_string(),
     };
 
-    let namespace = if object.metadata.namespace.is_some() {
-        object.metadata.namespace.clone()
+    let namespace = if let Some(namespace) = &settings.namespace {
+        if api_resource.is_some_and(|ar| !ar.namespaced) {
+            anyhow::bail!(
+                "cannot set the namespace of {:?}, it is a cluster-wide resource",
+                kube_gvk
+            );
+        }
+        // Keep the objects consistent with the namespace of the request
+        for obj in [object.as_mut(), old_object.as_mut()].into_iter().flatten() {
+            obj.metadata.namespace = Some(namespace.clone());
+        }
+        Some(namespace.clone())
+    } else if object_namespace.is_some() {
+        object_namespace
     } else if let Some(ar) = api_resource {
         if ar.namespaced {
             Some("default".to_string())
@@ -393,34 +540,53 @@ where
         resource,
     };
 
-    let object_json = serde_json::to_value(object.clone())?;
+    let object_json = object.map(serde_json::to_value).transpose()?;
+    let old_object_json = old_object.map(serde_json::to_value).transpose()?;
 
-    let request = AdmissionRequest {
+    Ok(AdmissionRequest {
         // hard-coded UID
         uid: "705ab4f5-6393-11e8-b7cc-42010a800002".to_string(),
         kind: object_kind.clone(),
         request_kind: Some(object_kind),
         resource: object_gvr.clone(),
         request_resource: Some(object_gvr),
-        sub_resource: None,
-        request_sub_resource: None,
-        name: object.metadata.name,
+        sub_resource: settings.sub_resource.clone(),
+        request_sub_resource: settings.sub_resource.clone(),
+        name,
         namespace,
-        operation: Operation::Create.to_string(),
+        operation: operation.to_string(),
         user_info: UserInfo {
-            username: Some("test-user".to_string()),
-            groups: Some(vec!["system:masters".to_string()]),
+            username: Some(settings.username.clone()),
+            groups: Some(settings.groups.clone()),
             ..Default::default()
         },
-        object: Some(RawExtension(object_json)),
-        old_object: None,
-        dry_run: None,
-        options: None,
-    };
+        object: object_json.map(RawExtension),
+        old_object: old_object_json.map(RawExtension),
+        dry_run: Some(settings.dry_run),
+        options: Some(RawExtension(operation_options(
+            &operation,
+            settings.dry_run,
+        ))),
+    })
+}
 
-    let output = serde_json::to_string_pretty(&request)?;
+/// Build the options object the API server attaches to the AdmissionRequest
+/// of the given operation
+fn operation_options(operation: &Operation, dry_run: bool) -> serde_json::Value {
+    let kind = match operation {
+        Operation::Create => "CreateOptions",
+        Operation::Update => "UpdateOptions",
+        Operation::Delete => "DeleteOptions",
+    };
+    let mut options = serde_json::json!({
+        "apiVersion": "meta.k8s.io/v1",
+        "kind": kind,
+    });
+    if dry_run {
+        options["dryRun"] = serde_json::json!(["All"]);
+    }
 
-    Ok(output)
+    options
 }
 
 #[cfg(test)]
@@ -640,6 +806,46 @@ mod tests {
         assert!(catalog_file.exists());
     }
 
+    #[test]
+    fn load_catalog_from_cache_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let catalog_file = tempdir.path().join("resource_catalog.json");
+
+        assert!(ApiResourceCatalog::from_cache(&catalog_file)
+            .expect("cannot load catalog")
+            .is_none());
+
+        let catalog = ApiResourceCatalog {
+            resources: vec![(
+                "apps|v1|Deployment".to_string(),
+                APIResource {
+                    name: "deployments".to_owned(),
+                    singular_name: "deployment".to_owned(),
+                    namespaced: true,
+                    kind: "Deployment".to_owned(),
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+            restored_from: ApiResourceCatalogRestoredFrom::ApiServer,
+        };
+        catalog
+            .save(catalog_file.clone())
+            .expect("failed to save catalog");
+
+        let catalog = ApiResourceCatalog::from_cache(&catalog_file)
+            .expect("cannot load catalog")
+            .expect("catalog not found");
+        assert!(catalog
+            .lookup(&kube::api::GroupVersionKind {
+                group: "apps".to_string(),
+                version: "v1".to_string(),
+                kind: "Deployment".to_string()
+            })
+            .is_some());
+    }
+
     const NAMESPACE_YAML: &str = r#"
         apiVersion: v1
         kind: Namespace
@@ -774,10 +980,13 @@ mod tests {
         scenario(handle).await;
 
         let build_mock_kube_client = || async { Ok(kube::Client::new(mocksvc, "default")) };
-        let output = scaffold_create(
+        let output = scaffold(
             catalog_filepath.clone(),
             build_mock_kube_client,
-            object_filepath.clone(),
+            Operation::Create,
+            Some(object_filepath.clone()),
+            None,
+            &AdmissionRequestSettings::default(),
         )
         .await
         .expect("scaffold failed");
@@ -802,4 +1011,205 @@ mod tests {
             assert!(catalog.lookup(&gvk).is_some());
         }
     }
+
+    const SERVICE_UPDATED_YAML: &str = r#"
+        apiVersion: v1
+        kind: Service
+        metadata:
+          name: my-service
+          namespace: my-namespace
+        spec:
+          selector:
+            app: my-other-app
+          ports:
+            - protocol: TCP
+              port: 80
+              targetPort: 9376"#;
+
+    fn write_object(dir: &Path, file_name: &str, raw_object: &str) -> PathBuf {
+        let path = dir.join(file_name);
+        let mut file = File::create(&path).expect("failed to create object file");
+        file.write_all(raw_object.as_bytes())
+            .expect("failed to write object file");
+        path
+    }
+
+    fn yaml_to_value(raw_object: &str) -> serde_json::Value {
+        serde_yaml::from_str(raw_object).expect("failed to convert raw object into serde value")
+    }
+
+    #[rstest]
+    #[case::update(
+        Operation::Update,
+        Some(SERVICE_UPDATED_YAML),
+        Some(SERVICE_YAML),
+        AdmissionRequestSettings::default(),
+        "UpdateOptions"
+    )]
+    #[case::delete(
+        Operation::Delete,
+        None,
+        Some(SERVICE_YAML),
+        AdmissionRequestSettings::default(),
+        "DeleteOptions"
+    )]
+    #[case::update_status_subresource_as_dry_run(
+        Operation::Update,
+        Some(SERVICE_UPDATED_YAML),
+        Some(SERVICE_YAML),
+        AdmissionRequestSettings {
+            username: "alice".to_string(),
+            groups: vec!["developers".to_string(), "system:authenticated".to_string()],
+            sub_resource: Some("status".to_string()),
+            dry_run: true,
+            ..Default::default()
+        },
+        "UpdateOptions",
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scaffold_update_and_delete_operations(
+        #[case] operation: Operation,
+        #[case] raw_object: Option<&str>,
+        #[case] raw_old_object: Option<&str>,
+        #[case] settings: AdmissionRequestSettings,
+        #[case] expected_options_kind: &str,
+    ) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let catalog_filepath = tempdir.path().join("resource_catalog.json");
+        let object_filepath = raw_object.map(|raw| write_object(tempdir.path(), "new.yaml", raw));
+        let old_object_filepath =
+            raw_old_object.map(|raw| write_object(tempdir.path(), "old.yaml", raw));
+
+        let (mocksvc, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        handle_discovery(handle).await;
+        let build_mock_kube_client = || async { Ok(kube::Client::new(mocksvc, "default")) };
+
+        let expected_operation = operation.to_string();
+        let output = scaffold(
+            catalog_filepath,
+            build_mock_kube_client,
+            operation,
+            object_filepath,
+            old_object_filepath,
+            &settings,
+        )
+        .await
+        .expect("scaffold failed");
+
+        let admission_request: AdmissionRequest =
+            serde_json::from_str(&output).expect("failed to parse output");
+        assert_eq!(admission_request.operation, expected_operation);
+        assert_eq!(
+            admission_request.resource,
+            GroupVersionResource {
+                group: "".to_string(),
+                version: "v1".to_string(),
+                resource: "services".to_string(),
+            }
+        );
+        assert_eq!(admission_request.name, Some("my-service".to_string()));
+        assert_eq!(
+            admission_request.namespace,
+            Some("my-namespace".to_string())
+        );
+        assert_eq!(
+            admission_request.object,
+            raw_object.map(|raw| RawExtension(yaml_to_value(raw)))
+        );
+        assert_eq!(
+            admission_request.old_object,
+            raw_old_object.map(|raw| RawExtension(yaml_to_value(raw)))
+        );
+        assert_eq!(admission_request.sub_resource, settings.sub_resource);
+        assert_eq!(
+            admission_request.request_sub_resource,
+            settings.sub_resource
+        );
+        assert_eq!(
+            admission_request.user_info.username,
+            Some(settings.username)
+        );
+        assert_eq!(admission_request.user_info.groups, Some(settings.groups));
+        assert_eq!(admission_request.dry_run, Some(settings.dry_run));
+
+        let options = admission_request.options.expect("options not set").0;
+        assert_eq!(options["kind"], expected_options_kind);
+        assert_eq!(options["dryRun"].is_array(), settings.dry_run);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scaffold_with_namespace_override() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let catalog_filepath = tempdir.path().join("resource_catalog.json");
+        let object_filepath = write_object(tempdir.path(), "new.yaml", SERVICE_UPDATED_YAML);
+        let old_object_filepath = write_object(tempdir.path(), "old.yaml", SERVICE_YAML);
+
+        let (mocksvc, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        handle_discovery(handle).await;
+        let build_mock_kube_client = || async { Ok(kube::Client::new(mocksvc, "default")) };
+
+        let output = scaffold(
+            catalog_filepath,
+            build_mock_kube_client,
+            Operation::Update,
+            Some(object_filepath),
+            Some(old_object_filepath),
+            &AdmissionRequestSettings {
+                namespace: Some("other-namespace".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .expect("scaffold failed");
+
+        let admission_request: AdmissionRequest =
+            serde_json::from_str(&output).expect("failed to parse output");
+        assert_eq!(
+            admission_request.namespace,
+            Some("other-namespace".to_string())
+        );
+        for object in [admission_request.object, admission_request.old_object] {
+            assert_eq!(
+                object.expect("object not set").0["metadata"]["namespace"],
+                "other-namespace"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case::different_kind(NAMESPACE_YAML, SERVICE_YAML)]
+    #[case::different_name(
+        SERVICE_YAML,
+        r#"
+        apiVersion: v1
+        kind: Service
+        metadata:
+          name: another-service
+          namespace: my-namespace"#
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scaffold_update_with_mismatching_objects(
+        #[case] raw_object: &str,
+        #[case] raw_old_object: &str,
+    ) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let catalog_filepath = tempdir.path().join("resource_catalog.json");
+        let object_filepath = write_object(tempdir.path(), "new.yaml", raw_object);
+        let old_object_filepath = write_object(tempdir.path(), "old.yaml", raw_old_object);
+
+        let (mocksvc, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        expect_no_request(handle).await;
+        let build_mock_kube_client = || async { Ok(kube::Client::new(mocksvc, "default")) };
+
+        let result = scaffold(
+            catalog_filepath,
+            build_mock_kube_client,
+            Operation::Update,
+            Some(object_filepath),
+            Some(old_object_filepath),
+            &AdmissionRequestSettings::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
 }
@@ -0,0 +1,678 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{
+    LabelSelector, LabelSelectorRequirement, ObjectMeta,
+};
+use policy_evaluator::policy_metadata::{Operation, Rule};
+use serde::Deserialize;
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+use crate::{
+    scaffold::{
+        admission_request::build_kube_client,
+        kubewarden_crds::{ClusterAdmissionPolicy, ClusterAdmissionPolicySpec},
+        ApiResourceCatalog, RESOURCE_CATALOG_FILE,
+    },
+    utils::read_yaml_documents,
+};
+
+const GATEKEEPER_ADMISSION_TARGET: &str = "admission.k8s.gatekeeper.sh";
+const NAMESPACE_NAME_LABEL: &str = "kubernetes.io/metadata.name";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplate {
+    metadata: ObjectMeta,
+    spec: ConstraintTemplateSpec,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplateSpec {
+    crd: ConstraintTemplateCrd,
+    #[serde(default)]
+    targets: Vec<ConstraintTemplateTarget>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplateCrd {
+    spec: ConstraintTemplateCrdSpec,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplateCrdSpec {
+    names: ConstraintTemplateNames,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplateNames {
+    kind: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintTemplateTarget {
+    target: String,
+    #[serde(default)]
+    rego: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Constraint {
+    kind: String,
+    metadata: ObjectMeta,
+    #[serde(default)]
+    spec: ConstraintSpec,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintSpec {
+    #[serde(default)]
+    enforcement_action: Option<String>,
+    #[serde(default, rename = "match")]
+    match_: ConstraintMatch,
+    #[serde(default)]
+    parameters: Option<serde_yaml::Mapping>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintMatch {
+    #[serde(default)]
+    kinds: Vec<ConstraintMatchKinds>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    namespaces: Vec<String>,
+    #[serde(default)]
+    excluded_namespaces: Vec<String>,
+    #[serde(default)]
+    label_selector: Option<LabelSelector>,
+    #[serde(default)]
+    namespace_selector: Option<LabelSelector>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ConstraintMatchKinds {
+    #[serde(default)]
+    api_groups: Vec<String>,
+    #[serde(default)]
+    kinds: Vec<String>,
+}
+
+pub(crate) async fn gatekeeper(
+    module: &str,
+    template_path: &Path,
+    constraint_paths: &[PathBuf],
+) -> Result<()> {
+    let template_file = File::open(template_path)
+        .map_err(|e| anyhow!("cannot open {}: #{e}", template_path.display()))?;
+    let template: ConstraintTemplate = serde_yaml::from_reader(template_file)
+        .map_err(|e| anyhow!("cannot convert given data into a ConstraintTemplate: #{e}"))?;
+
+    let mut constraints = Vec::new();
+    for constraint_path in constraint_paths {
+        // a file can contain multiple constraints
+        for document in read_yaml_documents(constraint_path)? {
+            let constraint: Constraint = serde_yaml::from_value(document)
+                .map_err(|e| anyhow!("cannot convert given data into a Constraint: #{e}"))?;
+            constraints.push(constraint);
+        }
+    }
+    if constraints.is_empty() {
+        return Err(anyhow!("no Constraint found"));
+    }
+
+    // The resources matched by the policies are looked up inside of the catalog, like
+    // `scaffold admission-request` does. The catalog is refreshed when it doesn't know
+    // some of the kinds
+    let mut catalog =
+        ApiResourceCatalog::new(RESOURCE_CATALOG_FILE.to_path_buf(), build_kube_client).await;
+    if catalog.is_empty() {
+        warn!("The resource catalog is not available, the resources matched by the policies are derived from the kinds of the constraints. Review them carefully, irregular resource names are not guessed correctly");
+    } else if constraints
+        .iter()
+        .flat_map(|constraint| &constraint.spec.match_.kinds)
+        .any(|match_kinds| {
+            match_kinds.kinds.iter().any(|kind| {
+                kind != "*" && resources_of(&catalog, &match_kinds.api_groups, kind).is_empty()
+            })
+        })
+        && catalog.refresh(build_kube_client).await.is_ok()
+    {
+        if let Err(err) = catalog.save(RESOURCE_CATALOG_FILE.to_path_buf()) {
+            warn!(?err, "Failed to save resource catalog");
+        }
+    }
+
+    let cluster_admission_policies = constraints
+        .into_iter()
+        .map(|constraint| {
+            convert_constraint_to_cluster_admission_policy(module, &template, constraint, &catalog)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let documents = cluster_admission_policies
+        .iter()
+        .map(serde_yaml::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    print!("{}", documents.join("---\n"));
+
+    Ok(())
+}
+
+fn convert_constraint_to_cluster_admission_policy(
+    module: &str,
+    template: &ConstraintTemplate,
+    constraint: Constraint,
+    catalog: &ApiResourceCatalog,
+) -> Result<ClusterAdmissionPolicy> {
+    let constraint_name = constraint.metadata.name.clone().unwrap_or_default();
+    let template_kind = &template.spec.crd.spec.names.kind;
+    if &constraint.kind != template_kind {
+        return Err(anyhow!(
+            "Constraint {constraint_name} is of kind {}, while the ConstraintTemplate {} defines the {template_kind} kind",
+            constraint.kind,
+            template.metadata.name.as_deref().unwrap_or_default(),
+        ));
+    }
+    // the Rego code must be built into the given module, kwctl does not compile it
+    if !template
+        .spec
+        .targets
+        .iter()
+        .any(|target| target.target == GATEKEEPER_ADMISSION_TARGET && target.rego.is_some())
+    {
+        return Err(anyhow!(
+            "the ConstraintTemplate does not define Rego code for the {GATEKEEPER_ADMISSION_TARGET} target"
+        ));
+    }
+
+    let mode = match constraint.spec.enforcement_action.as_deref() {
+        None | Some("deny") => None,
+        Some("dryrun") => Some("monitor".to_string()),
+        Some("warn") => {
+            warn!(
+                constraint = constraint_name,
+                "The 'warn' enforcement action is converted into the monitor mode, violations are not reported to the user"
+            );
+            Some("monitor".to_string())
+        }
+        Some(action) => {
+            return Err(anyhow!(
+                "Constraint {constraint_name} uses the '{action}' enforcement action, which cannot be converted"
+            ))
+        }
+    };
+
+    let constraint_match = constraint.spec.match_;
+    if constraint_match
+        .scope
+        .as_deref()
+        .is_some_and(|scope| scope != "*")
+    {
+        warn!(
+            constraint = constraint_name,
+            "match.scope is not supported by Kubewarden policies. It will be ignored."
+        );
+    }
+    if constraint_match.name.is_some() {
+        warn!(
+            constraint = constraint_name,
+            "match.name is not supported by Kubewarden policies. It will be ignored."
+        );
+    }
+
+    let rules = convert_match_kinds(&constraint_match.kinds, catalog)?;
+    let namespace_selector = convert_namespace_selector(
+        constraint_match.namespace_selector,
+        &constraint_match.namespaces,
+        &constraint_match.excluded_namespaces,
+    )?;
+
+    Ok(ClusterAdmissionPolicy {
+        api_version: "policies.kubewarden.io/v1".to_string(),
+        kind: "ClusterAdmissionPolicy".to_string(),
+        metadata: ObjectMeta {
+            name: constraint.metadata.name,
+            labels: constraint.metadata.labels,
+            ..Default::default()
+        },
+        spec: ClusterAdmissionPolicySpec {
+            module: module.to_string(),
+            settings: constraint.spec.parameters.unwrap_or_default(),
+            rules,
+            mutating: false,
+            background_audit: true,
+            context_aware_resources: BTreeSet::new(),
+            failure_policy: None,
+            mode,
+            match_policy: None,
+            match_conditions: None,
+            namespace_selector,
+            object_selector: constraint_match.label_selector,
+        },
+    })
+}
+
+/// Gatekeeper evaluates CREATE and UPDATE requests of the given kinds. Kubewarden
+/// rules reference resources, which are looked up inside of the resource catalog.
+fn convert_match_kinds(
+    match_kinds: &[ConstraintMatchKinds],
+    catalog: &ApiResourceCatalog,
+) -> Result<Vec<Rule>> {
+    let operations = vec![Operation::Create, Operation::Update];
+    if match_kinds.is_empty() {
+        return Ok(vec![Rule {
+            api_groups: vec!["*".to_string()],
+            api_versions: vec!["*".to_string()],
+            resources: vec!["*".to_string()],
+            operations,
+        }]);
+    }
+
+    match_kinds
+        .iter()
+        .map(|match_kinds| {
+            let api_groups = if match_kinds.api_groups.is_empty() {
+                vec!["*".to_string()]
+            } else {
+                match_kinds.api_groups.clone()
+            };
+            let resources = if match_kinds.kinds.is_empty()
+                || match_kinds.kinds.iter().any(|kind| kind == "*")
+            {
+                vec!["*".to_string()]
+            } else {
+                let mut resources = Vec::new();
+                for kind in &match_kinds.kinds {
+                    for resource in resource_names(catalog, &api_groups, kind)? {
+                        if !resources.contains(&resource) {
+                            resources.push(resource);
+                        }
+                    }
+                }
+                resources
+            };
+            Ok(Rule {
+                api_groups,
+                api_versions: vec!["*".to_string()],
+                resources,
+                operations: operations.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The names of the resources of the given kind, inside of the given API groups.
+/// The pluralization heuristic is used only when there's no resource catalog
+fn resource_names(
+    catalog: &ApiResourceCatalog,
+    api_groups: &[String],
+    kind: &str,
+) -> Result<Vec<String>> {
+    if catalog.is_empty() {
+        return Ok(vec![resource_of(kind)]);
+    }
+
+    let resources = resources_of(catalog, api_groups, kind);
+    if resources.is_empty() {
+        return Err(anyhow!(
+            "the {kind} kind of the API groups {api_groups:?} is not known by the cluster the resource catalog {} was built from",
+            RESOURCE_CATALOG_FILE.display()
+        ));
+    }
+    Ok(resources.into_iter().map(str::to_string).collect())
+}
+
+/// Look up the names of the resources of the given kind inside of the catalog.
+/// Gatekeeper treats an empty list of API groups as all of them
+fn resources_of<'a>(
+    catalog: &'a ApiResourceCatalog,
+    api_groups: &[String],
+    kind: &str,
+) -> BTreeSet<&'a str> {
+    if api_groups.is_empty() {
+        return catalog
+            .lookup_resource_name("*", kind)
+            .into_iter()
+            .collect();
+    }
+    api_groups
+        .iter()
+        .filter_map(|api_group| catalog.lookup_resource_name(api_group, kind))
+        .collect()
+}
+
+/// The resource name of the given kind, following the usual pluralization
+/// rules of the Kubernetes resources. Irregular resources, like `endpoints`,
+/// are not guessed correctly
+fn resource_of(kind: &str) -> String {
+    let kind = kind.to_lowercase();
+    if kind.ends_with('s') || kind.ends_with('x') || kind.ends_with("ch") || kind.ends_with("sh") {
+        format!("{kind}es")
+    } else if let Some(stem) = kind
+        .strip_suffix('y')
+        .filter(|stem| !stem.ends_with(['a', 'e', 'i', 'o', 'u']))
+    {
+        format!("{stem}ies")
+    } else {
+        format!("{kind}s")
+    }
+}
+
+/// Gatekeeper namespace matchers are expressed by the Kubewarden policy
+/// namespace selector, using the label set by Kubernetes on each namespace
+fn convert_namespace_selector(
+    namespace_selector: Option<LabelSelector>,
+    namespaces: &[String],
+    excluded_namespaces: &[String],
+) -> Result<Option<LabelSelector>> {
+    if let Some(pattern) = namespaces
+        .iter()
+        .chain(excluded_namespaces)
+        .find(|namespace| namespace.contains('*'))
+    {
+        return Err(anyhow!(
+            "the namespace pattern '{pattern}' cannot be converted into a namespaceSelector"
+        ));
+    }
+
+    let mut match_expressions = Vec::new();
+    if !namespaces.is_empty() {
+        match_expressions.push(LabelSelectorRequirement {
+            key: NAMESPACE_NAME_LABEL.to_string(),
+            operator: "In".to_string(),
+            values: Some(namespaces.to_vec()),
+        });
+    }
+    if !excluded_namespaces.is_empty() {
+        match_expressions.push(LabelSelectorRequirement {
+            key: NAMESPACE_NAME_LABEL.to_string(),
+            operator: "NotIn".to_string(),
+            values: Some(excluded_namespaces.to_vec()),
+        });
+    }
+    if match_expressions.is_empty() {
+        return Ok(namespace_selector);
+    }
+
+    let mut namespace_selector = namespace_selector.unwrap_or_default();
+    namespace_selector
+        .match_expressions
+        .get_or_insert_with(Vec::new)
+        .extend(match_expressions);
+
+    Ok(Some(namespace_selector))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::collections::BTreeMap;
+
+    const MODULE: &str = "registry://ghcr.io/my-org/policies/required-labels:v1.0.0";
+
+    fn test_data(path: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("data")
+            .join(path)
+    }
+
+    fn template() -> ConstraintTemplate {
+        let file = File::open(test_data("gatekeeper/constraint-template.yml")).unwrap();
+        serde_yaml::from_reader(file).unwrap()
+    }
+
+    fn constraint(yaml: &str) -> Constraint {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn load_catalog(resources: serde_json::Value) -> ApiResourceCatalog {
+        let tempdir = tempfile::tempdir().unwrap();
+        let catalog_path = tempdir.path().join("resource_catalog.json");
+        std::fs::write(
+            &catalog_path,
+            serde_json::json!({ "resources": resources }).to_string(),
+        )
+        .unwrap();
+        ApiResourceCatalog::from_cache(&catalog_path)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn api_resource(name: &str, kind: &str) -> serde_json::Value {
+        serde_json::json!({
+            "name": name,
+            "singularName": name.trim_end_matches('s'),
+            "namespaced": true,
+            "kind": kind,
+            "verbs": ["get", "list"],
+        })
+    }
+
+    fn catalog() -> ApiResourceCatalog {
+        load_catalog(serde_json::json!({
+            "|v1|Namespace": api_resource("namespaces", "Namespace"),
+            "|v1|Pod": api_resource("pods", "Pod"),
+            "|v1|Endpoints": api_resource("endpoints", "Endpoints"),
+            "networking.k8s.io|v1|Ingress": api_resource("ingresses", "Ingress"),
+            "networking.k8s.io|v1|NetworkPolicy": api_resource("networkpolicies", "NetworkPolicy"),
+        }))
+    }
+
+    #[test]
+    fn from_constraint_to_cluster_admission_policy() {
+        let constraint = constraint(
+            r#"
+apiVersion: constraints.gatekeeper.sh/v1beta1
+kind: K8sRequiredLabels
+metadata:
+  name: all-must-have-owner
+  resourceVersion: "1234"
+  labels:
+    team: platform
+spec:
+  match:
+    kinds:
+      - apiGroups: [""]
+        kinds: ["Namespace", "Pod"]
+      - apiGroups: ["networking.k8s.io"]
+        kinds: ["Ingress", "NetworkPolicy"]
+    namespaceSelector:
+      matchLabels:
+        environment: production
+    excludedNamespaces: ["kube-system"]
+    labelSelector:
+      matchLabels:
+        managed: "true"
+  parameters:
+    labels: ["owner"]
+"#,
+        );
+
+        let cluster_admission_policy = convert_constraint_to_cluster_admission_policy(
+            MODULE,
+            &template(),
+            constraint,
+            &catalog(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            cluster_admission_policy.metadata,
+            ObjectMeta {
+                name: Some("all-must-have-owner".to_string()),
+                labels: Some(BTreeMap::from([(
+                    "team".to_string(),
+                    "platform".to_string()
+                )])),
+                ..Default::default()
+            }
+        );
+        let spec = cluster_admission_policy.spec;
+        assert_eq!(spec.module, MODULE);
+        assert!(!spec.mutating);
+        assert!(spec.background_audit);
+        assert!(spec.mode.is_none());
+        assert_eq!(
+            serde_yaml::to_value(&spec.settings).unwrap(),
+            serde_yaml::from_str::<serde_yaml::Value>("labels: [owner]").unwrap()
+        );
+        assert_eq!(
+            spec.rules,
+            vec![
+                Rule {
+                    api_groups: vec!["".to_string()],
+                    api_versions: vec!["*".to_string()],
+                    resources: vec!["namespaces".to_string(), "pods".to_string()],
+                    operations: vec![Operation::Create, Operation::Update],
+                },
+                Rule {
+                    api_groups: vec!["networking.k8s.io".to_string()],
+                    api_versions: vec!["*".to_string()],
+                    resources: vec!["ingresses".to_string(), "networkpolicies".to_string()],
+                    operations: vec![Operation::Create, Operation::Update],
+                },
+            ]
+        );
+        assert_eq!(
+            spec.namespace_selector,
+            Some(LabelSelector {
+                match_labels: Some(BTreeMap::from([(
+                    "environment".to_string(),
+                    "production".to_string()
+                )])),
+                match_expressions: Some(vec![LabelSelectorRequirement {
+                    key: NAMESPACE_NAME_LABEL.to_string(),
+                    operator: "NotIn".to_string(),
+                    values: Some(vec!["kube-system".to_string()]),
+                }]),
+            })
+        );
+        assert_eq!(
+            spec.object_selector,
+            Some(LabelSelector {
+                match_labels: Some(BTreeMap::from([(
+                    "managed".to_string(),
+                    "true".to_string()
+                )])),
+                match_expressions: None,
+            })
+        );
+    }
+
+    #[test]
+    fn from_constraint_without_match() {
+        let constraint = constraint(
+            "kind: K8sRequiredLabels\nmetadata:\n  name: everything\nspec:\n  enforcementAction: dryrun\n",
+        );
+
+        let spec = convert_constraint_to_cluster_admission_policy(
+            MODULE,
+            &template(),
+            constraint,
+            &catalog(),
+        )
+        .unwrap()
+        .spec;
+
+        assert_eq!(spec.mode, Some("monitor".to_string()));
+        assert!(spec.settings.is_empty());
+        assert!(spec.namespace_selector.is_none());
+        assert!(spec.object_selector.is_none());
+        assert_eq!(
+            spec.rules,
+            vec![Rule {
+                api_groups: vec!["*".to_string()],
+                api_versions: vec!["*".to_string()],
+                resources: vec!["*".to_string()],
+                operations: vec![Operation::Create, Operation::Update],
+            }]
+        );
+    }
+
+    #[rstest]
+    #[case::kind_mismatch("kind: K8sAllowedRepos\nmetadata:\n  name: test\n")]
+    #[case::unknown_enforcement_action(
+        "kind: K8sRequiredLabels\nmetadata:\n  name: test\nspec:\n  enforcementAction: scoped\n"
+    )]
+    #[case::namespace_pattern(
+        "kind: K8sRequiredLabels\nmetadata:\n  name: test\nspec:\n  match:\n    excludedNamespaces: [\"kube-*\"]\n"
+    )]
+    fn invalid_constraint(#[case] yaml: &str) {
+        assert!(convert_constraint_to_cluster_admission_policy(
+            MODULE,
+            &template(),
+            constraint(yaml),
+            &catalog()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn template_without_rego() {
+        let mut template = template();
+        template.spec.targets[0].rego = None;
+
+        assert!(convert_constraint_to_cluster_admission_policy(
+            MODULE,
+            &template,
+            constraint("kind: K8sRequiredLabels\nmetadata:\n  name: test\n"),
+            &catalog()
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case::irregular_kind(vec![""], "Endpoints", Some(vec!["endpoints"]))]
+    #[case::all_api_groups(vec!["*"], "Ingress", Some(vec!["ingresses"]))]
+    #[case::many_api_groups(vec!["", "networking.k8s.io"], "Pod", Some(vec!["pods"]))]
+    #[case::unknown_kind(vec!["example.com"], "Pod", None)]
+    fn kind_to_resource_from_catalog(
+        #[case] api_groups: Vec<&str>,
+        #[case] kind: &str,
+        #[case] expected: Option<Vec<&str>>,
+    ) {
+        let api_groups: Vec<String> = api_groups.into_iter().map(String::from).collect();
+
+        let resources = resource_names(&catalog(), &api_groups, kind);
+        assert_eq!(
+            resources.ok(),
+            expected.map(|resources| resources.into_iter().map(String::from).collect())
+        );
+    }
+
+    #[test]
+    fn kind_to_resource_without_catalog() {
+        let catalog = load_catalog(serde_json::json!({}));
+
+        assert_eq!(
+            resource_names(&catalog, &["".to_string()], "NetworkPolicy").unwrap(),
+            vec!["networkpolicies".to_string()]
+        );
+    }
+
+    #[rstest]
+    #[case("Pod", "pods")]
+    #[case("Ingress", "ingresses")]
+    #[case("NetworkPolicy", "networkpolicies")]
+    #[case("Gateway", "gateways")]
+    fn kind_to_resource(#[case] kind: &str, #[case] resource: &str) {
+        assert_eq!(resource_of(kind), resource);
+    }
+}
@@ -1,7 +1,10 @@
-use k8s_openapi::apimachinery::pkg::apis::meta::v1::{LabelSelector, ObjectMeta};
+use k8s_openapi::{
+    api::admissionregistration::v1::MatchCondition,
+    apimachinery::pkg::apis::meta::v1::{LabelSelector, ObjectMeta},
+};
 use policy_evaluator::policy_metadata::{ContextAwareResource, Rule};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -32,6 +35,8 @@ pub(crate) struct ClusterAdmissionPolicySpec {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub match_policy: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_conditions: Option<Vec<MatchCondition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace_selector: Option<LabelSelector>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub object_selector: Option<LabelSelector>,
@@ -62,3 +67,59 @@ pub(crate) struct AdmissionPolicySpec {
     #[serde(skip_serializing_if = "is_true")]
     pub background_audit: bool,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClusterAdmissionPolicyGroup {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: ObjectMeta,
+    pub spec: ClusterAdmissionPolicyGroupSpec,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClusterAdmissionPolicyGroupSpec {
+    pub policies: BTreeMap<String, ClusterPolicyGroupMember>,
+    pub expression: String,
+    pub message: String,
+    pub rules: Vec<Rule>,
+    #[serde(skip_serializing_if = "is_true")]
+    pub background_audit: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClusterPolicyGroupMember {
+    pub module: String,
+    pub settings: serde_yaml::Mapping,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub context_aware_resources: BTreeSet<ContextAwareResource>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdmissionPolicyGroup {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: ObjectMeta,
+    pub spec: AdmissionPolicyGroupSpec,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AdmissionPolicyGroupSpec {
+    pub policies: BTreeMap<String, PolicyGroupMember>,
+    pub expression: String,
+    pub message: String,
+    pub rules: Vec<Rule>,
+    #[serde(skip_serializing_if = "is_true")]
+    pub background_audit: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PolicyGroupMember {
+    pub module: String,
+    pub settings: serde_yaml::Mapping,
+}
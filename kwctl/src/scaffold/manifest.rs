@@ -12,18 +12,31 @@ use policy_evaluator::{
         KUBEWARDEN_ANNOTATION_POLICY_CATEGORY, KUBEWARDEN_ANNOTATION_POLICY_SEVERITY,
         KUBEWARDEN_ANNOTATION_POLICY_TITLE,
     },
-    policy_metadata::Metadata,
+    policy_metadata::{ContextAwareResource, Metadata, Rule},
     validator::Validate,
 };
 use tracing::warn;
 
 use crate::scaffold::kubewarden_crds::{
-    AdmissionPolicy, AdmissionPolicySpec, ClusterAdmissionPolicy, ClusterAdmissionPolicySpec,
+    AdmissionPolicy, AdmissionPolicyGroup, AdmissionPolicyGroupSpec, AdmissionPolicySpec,
+    ClusterAdmissionPolicy, ClusterAdmissionPolicyGroup, ClusterAdmissionPolicyGroupSpec,
+    ClusterAdmissionPolicySpec, ClusterPolicyGroupMember, PolicyGroupMember,
 };
 
 pub(crate) enum ManifestType {
     ClusterAdmissionPolicy,
     AdmissionPolicy,
+    ClusterAdmissionPolicyGroup,
+    AdmissionPolicyGroup,
+}
+
+impl ManifestType {
+    pub(crate) fn is_group(&self) -> bool {
+        matches!(
+            self,
+            ManifestType::ClusterAdmissionPolicyGroup | ManifestType::AdmissionPolicyGroup
+        )
+    }
 }
 
 impl FromStr for ManifestType {
@@ -33,6 +46,8 @@ impl FromStr for ManifestType {
         match value {
             "ClusterAdmissionPolicy" => Ok(ManifestType::ClusterAdmissionPolicy),
             "AdmissionPolicy" => Ok(ManifestType::AdmissionPolicy),
+            "ClusterAdmissionPolicyGroup" => Ok(ManifestType::ClusterAdmissionPolicyGroup),
+            "AdmissionPolicyGroup" => Ok(ManifestType::AdmissionPolicyGroup),
             _ => Err(anyhow!("unknown manifest type")),
         }
     }
@@ -142,15 +157,7 @@ pub(crate) fn manifest(
     policy_title: Option<&str>,
     allow_context_aware_resources: bool,
 ) -> Result<()> {
-    let uri = crate::utils::get_uri(&uri_or_sha_prefix.to_owned())?;
-    let wasm_path = crate::utils::wasm_path(&uri)?;
-
-    let metadata = Metadata::from_path(&wasm_path)?
-        .ok_or_else(||
-            anyhow!(
-                "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
-                uri)
-        )?;
+    let (uri, metadata) = load_policy(uri_or_sha_prefix)?;
 
     let settings_yml: serde_yaml::Mapping = serde_yaml::from_str(settings.unwrap_or("{}"))?;
 
@@ -178,6 +185,322 @@ pub(crate) fn manifest(
     Ok(())
 }
 
+/// A policy of a policy group, as given on the command line
+pub(crate) struct GroupMember {
+    /// The name used to reference the policy inside of the group expression
+    pub name: String,
+    pub uri_or_sha_prefix: String,
+    pub settings: Option<String>,
+}
+
+struct ScaffoldGroupMemberData {
+    name: String,
+    uri: String,
+    metadata: Metadata,
+    settings: serde_yaml::Mapping,
+}
+
+struct ScaffoldGroupData {
+    policy_title: Option<String>,
+    expression: String,
+    message: String,
+    members: Vec<ScaffoldGroupMemberData>,
+}
+
+pub(crate) fn manifest_group(
+    members: &[GroupMember],
+    resource_type: ManifestType,
+    expression: &str,
+    message: &str,
+    policy_title: Option<&str>,
+    allow_context_aware_resources: bool,
+) -> Result<()> {
+    if let Some(title) = policy_title {
+        validate_policy_title(title)?;
+    }
+
+    let members = members
+        .iter()
+        .map(|member| {
+            let (uri, metadata) = load_policy(&member.uri_or_sha_prefix)?;
+            let settings: serde_yaml::Mapping =
+                serde_yaml::from_str(member.settings.as_deref().unwrap_or("{}"))?;
+            Ok(ScaffoldGroupMemberData {
+                name: member.name.clone(),
+                uri,
+                metadata,
+                settings,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let scaffold_data = ScaffoldGroupData {
+        policy_title: policy_title.map(|t| t.to_string()),
+        expression: expression.to_string(),
+        message: message.to_string(),
+        members,
+    };
+
+    let resource =
+        generate_group_yaml_resource(scaffold_data, resource_type, allow_context_aware_resources)?;
+
+    let stdout = std::io::stdout();
+    let out = stdout.lock();
+    serde_yaml::to_writer(out, &resource)?;
+
+    Ok(())
+}
+
+fn generate_group_yaml_resource(
+    scaffold_data: ScaffoldGroupData,
+    resource_type: ManifestType,
+    allow_context_aware_resources: bool,
+) -> Result<serde_yaml::Value> {
+    validate_group(&scaffold_data)?;
+
+    let metadata = ObjectMeta {
+        name: scaffold_data.policy_title,
+        ..Default::default()
+    };
+    let rules = merge_rules(scaffold_data.members.iter().map(|m| &m.metadata));
+    let background_audit = scaffold_data
+        .members
+        .iter()
+        .all(|m| m.metadata.background_audit);
+
+    match resource_type {
+        ManifestType::ClusterAdmissionPolicyGroup => {
+            let policies = scaffold_data
+                .members
+                .into_iter()
+                .map(|member| {
+                    let context_aware_resources = granted_context_aware_resources(
+                        member.metadata.context_aware_resources,
+                        allow_context_aware_resources,
+                    );
+                    (
+                        member.name,
+                        ClusterPolicyGroupMember {
+                            module: member.uri,
+                            settings: member.settings,
+                            context_aware_resources,
+                        },
+                    )
+                })
+                .collect();
+
+            serde_yaml::to_value(ClusterAdmissionPolicyGroup {
+                api_version: String::from("policies.kubewarden.io/v1"),
+                kind: String::from("ClusterAdmissionPolicyGroup"),
+                metadata,
+                spec: ClusterAdmissionPolicyGroupSpec {
+                    policies,
+                    expression: scaffold_data.expression,
+                    message: scaffold_data.message,
+                    rules,
+                    background_audit,
+                },
+            })
+            .map_err(|e| anyhow!("{}", e))
+        }
+        ManifestType::AdmissionPolicyGroup => {
+            let policies = scaffold_data
+                .members
+                .into_iter()
+                .map(|member| {
+                    (
+                        member.name,
+                        PolicyGroupMember {
+                            module: member.uri,
+                            settings: member.settings,
+                        },
+                    )
+                })
+                .collect();
+
+            serde_yaml::to_value(AdmissionPolicyGroup {
+                api_version: String::from("policies.kubewarden.io/v1"),
+                kind: String::from("AdmissionPolicyGroup"),
+                metadata,
+                spec: AdmissionPolicyGroupSpec {
+                    policies,
+                    expression: scaffold_data.expression,
+                    message: scaffold_data.message,
+                    rules,
+                    background_audit,
+                },
+            })
+            .map_err(|e| anyhow!("{}", e))
+        }
+        ManifestType::ClusterAdmissionPolicy | ManifestType::AdmissionPolicy => Err(anyhow!(
+            "a policy group can only be scaffolded as ClusterAdmissionPolicyGroup or AdmissionPolicyGroup"
+        )),
+    }
+}
+
+fn validate_group(data: &ScaffoldGroupData) -> Result<()> {
+    if data.policy_title.is_none() {
+        return Err(anyhow!(
+            "a policy group must have a title, it's used as the name of the resource: use the --title flag"
+        ));
+    }
+    if data.members.is_empty() {
+        return Err(anyhow!("a policy group must have at least one policy"));
+    }
+
+    let mut names = BTreeSet::new();
+    for member in &data.members {
+        if !is_valid_member_name(&member.name) {
+            return Err(anyhow!(
+                "Invalid policy group member name '{}'. Must start with a letter or '_', followed by alphanumeric chars or '_'.",
+                member.name
+            ));
+        }
+        if !names.insert(member.name.as_str()) {
+            return Err(anyhow!(
+                "policy group member '{}' is defined more than once",
+                member.name
+            ));
+        }
+        if member.metadata.mutating {
+            return Err(anyhow!(
+                "policy group member '{}' is a mutating policy: policy groups cannot mutate requests",
+                member.name
+            ));
+        }
+        member.metadata.validate()?;
+    }
+
+    if data.expression.trim().is_empty() {
+        return Err(anyhow!("the policy group expression cannot be empty"));
+    }
+    if data.message.trim().is_empty() {
+        return Err(anyhow!("the policy group message cannot be empty"));
+    }
+
+    let referenced = expression_references(&data.expression);
+    for reference in &referenced {
+        if !names.contains(reference.name) {
+            return Err(anyhow!(
+                "the policy group expression references '{}', which is not a member of the group",
+                reference.name
+            ));
+        }
+        if !reference.is_call {
+            return Err(anyhow!(
+                "the policy group expression references '{}' without calling it, use '{}()'",
+                reference.name,
+                reference.name
+            ));
+        }
+    }
+    for name in names {
+        if !referenced.iter().any(|r| r.name == name) {
+            warn!(
+                member = name,
+                "policy group member is not referenced by the expression"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn is_valid_member_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+struct ExpressionReference<'a> {
+    name: &'a str,
+    is_call: bool,
+}
+
+/// Find the identifiers used by a policy group expression, skipping the
+/// string literals and the boolean literals. Members are referenced by
+/// calling them, e.g. `signed() && trusted_registry()`.
+fn expression_references(expression: &str) -> Vec<ExpressionReference<'_>> {
+    let bytes = expression.as_bytes();
+    let mut references = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'"' || c == b'\'' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                if bytes[i] == b'\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            let name = &expression[start..i];
+            if matches!(name, "true" | "false" | "null") {
+                continue;
+            }
+            let is_call = expression[i..].trim_start().starts_with('(');
+            references.push(ExpressionReference { name, is_call });
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+
+    references
+}
+
+/// Merge the rules of the policy group members. Rules targeting the same API
+/// groups, versions and operations are combined into a single rule matching
+/// all their resources.
+fn merge_rules<'a>(metadata: impl Iterator<Item = &'a Metadata>) -> Vec<Rule> {
+    let mut rules: Vec<Rule> = Vec::new();
+
+    for rule in metadata.flat_map(|m| m.rules.iter()) {
+        match rules.iter_mut().find(|r| {
+            r.api_groups == rule.api_groups
+                && r.api_versions == rule.api_versions
+                && r.operations == rule.operations
+        }) {
+            Some(merged) => {
+                for resource in &rule.resources {
+                    if !merged.resources.contains(resource) {
+                        merged.resources.push(resource.clone());
+                    }
+                }
+            }
+            None => rules.push(rule.clone()),
+        }
+    }
+
+    rules
+}
+
+fn load_policy(uri_or_sha_prefix: &str) -> Result<(String, Metadata)> {
+    let uri = crate::utils::get_uri(&uri_or_sha_prefix.to_owned())?;
+    let wasm_path = crate::utils::wasm_path(&uri)?;
+
+    let metadata = Metadata::from_path(&wasm_path)?
+        .ok_or_else(||
+            anyhow!(
+                "No Kubewarden metadata found inside of '{}'.\nPolicies can be annotated with the `kwctl annotate` command.",
+                uri)
+        )?;
+
+    Ok((uri, metadata))
+}
+
 fn get_policy_title_from_cli_or_metadata(
     policy_title: Option<&str>,
     metadata: &Metadata,
@@ -201,20 +524,10 @@ fn generate_yaml_resource(
 
     match resource_type {
         ManifestType::ClusterAdmissionPolicy => {
-            if !scaffold_data.metadata.context_aware_resources.is_empty() {
-                if allow_context_aware_resources {
-                    warn!(
-                        "Policy has been granted access to the Kubernetes resources mentioned by its metadata."
-                    );
-                    warn!("Carefully review the contents of the `contextAwareResources` attribute for abuses.");
-                } else {
-                    warn!("Policy requires access to Kubernetes resources at evaluation time. For safety reasons, the `contextAwareResources` attribute has been left empty.");
-                    warn!("Carefully review which types of Kubernetes resources the policy needs via the `inspect` command an populate the `contextAwareResources` accordingly.");
-                    warn!("Otherwise, invoke the `scaffold` command using the `--allow-context-aware` flag.");
-
-                    scaffold_data.metadata.context_aware_resources = BTreeSet::new();
-                }
-            }
+            scaffold_data.metadata.context_aware_resources = granted_context_aware_resources(
+                scaffold_data.metadata.context_aware_resources,
+                allow_context_aware_resources,
+            );
 
             serde_yaml::to_value(ClusterAdmissionPolicy::try_from(scaffold_data)?)
                 .map_err(|e| anyhow!("{}", e))
@@ -223,6 +536,31 @@ fn generate_yaml_resource(
             serde_yaml::to_value(AdmissionPolicy::try_from(scaffold_data)?)
                 .map_err(|e| anyhow!("{}", e))
         }
+        ManifestType::ClusterAdmissionPolicyGroup | ManifestType::AdmissionPolicyGroup => Err(
+            anyhow!("a policy group must be scaffolded from the policies of its members"),
+        ),
+    }
+}
+
+fn granted_context_aware_resources(
+    context_aware_resources: BTreeSet<ContextAwareResource>,
+    allow_context_aware_resources: bool,
+) -> BTreeSet<ContextAwareResource> {
+    if context_aware_resources.is_empty() {
+        return context_aware_resources;
+    }
+
+    if allow_context_aware_resources {
+        warn!(
+            "Policy has been granted access to the Kubernetes resources mentioned by its metadata."
+        );
+        warn!("Carefully review the contents of the `contextAwareResources` attribute for abuses.");
+        context_aware_resources
+    } else {
+        warn!("Policy requires access to Kubernetes resources at evaluation time. For safety reasons, the `contextAwareResources` attribute has been left empty.");
+        warn!("Carefully review which types of Kubernetes resources the policy needs via the `inspect` command an populate the `contextAwareResources` accordingly.");
+        warn!("Otherwise, invoke the `scaffold` command using the `--allow-context-aware` flag.");
+        BTreeSet::new()
     }
 }
 
@@ -230,7 +568,7 @@ fn generate_yaml_resource(
 mod tests {
     use super::*;
 
-    use policy_evaluator::policy_metadata::ContextAwareResource;
+    use rstest::rstest;
 
     fn mock_metadata_with_no_annotations() -> Metadata {
         Metadata {
@@ -240,6 +578,7 @@ mod tests {
             mutating: false,
             background_audit: true,
             context_aware_resources: BTreeSet::new(),
+            host_capabilities: None,
             execution_mode: Default::default(),
             policy_type: Default::default(),
             minimum_kubewarden_version: None,
@@ -257,6 +596,7 @@ mod tests {
             mutating: false,
             background_audit: true,
             context_aware_resources: BTreeSet::new(),
+            host_capabilities: None,
             execution_mode: Default::default(),
             policy_type: Default::default(),
             minimum_kubewarden_version: None,
@@ -284,6 +624,7 @@ mod tests {
             mutating: false,
             background_audit: true,
             context_aware_resources: BTreeSet::new(),
+            host_capabilities: None,
             execution_mode: Default::default(),
             policy_type: Default::default(),
             minimum_kubewarden_version: None,
@@ -432,6 +773,7 @@ mod tests {
         context_aware_resources.insert(ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Pod".to_string(),
+            projection: None,
         });
 
         let policy_title = "test";
@@ -462,6 +804,7 @@ mod tests {
         context_aware_resources.insert(ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Pod".to_string(),
+            projection: None,
         });
 
         let policy_title = "test";
@@ -494,4 +837,209 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("Invalid title"));
     }
+
+    fn mock_group_member(name: &str, resources: &[&str]) -> ScaffoldGroupMemberData {
+        let mut metadata = mock_metadata_with_no_annotations();
+        metadata.protocol_version = Some(policy_evaluator::ProtocolVersion::V1);
+        metadata.rules = vec![Rule {
+            api_groups: vec![String::from("")],
+            api_versions: vec![String::from("v1")],
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            operations: vec![
+                policy_evaluator::policy_metadata::Operation::Create,
+                policy_evaluator::policy_metadata::Operation::Update,
+            ],
+        }];
+
+        ScaffoldGroupMemberData {
+            name: name.to_string(),
+            uri: format!("registry://ghcr.io/kubewarden/tests/{name}:v1.0.0"),
+            metadata,
+            settings: Default::default(),
+        }
+    }
+
+    fn mock_group(expression: &str, members: Vec<ScaffoldGroupMemberData>) -> ScaffoldGroupData {
+        ScaffoldGroupData {
+            policy_title: Some(String::from("my-group")),
+            expression: expression.to_string(),
+            message: String::from("the request has been rejected"),
+            members,
+        }
+    }
+
+    #[test]
+    fn scaffold_cluster_admission_policy_group() {
+        let mut signed = mock_group_member("signed", &["pods"]);
+        signed
+            .metadata
+            .context_aware_resources
+            .insert(ContextAwareResource {
+                api_version: "v1".to_string(),
+                kind: "Namespace".to_string(),
+                projection: None,
+            });
+        let group = mock_group(
+            "signed() && trusted_registry()",
+            vec![
+                signed,
+                mock_group_member("trusted_registry", &["pods", "deployments"]),
+            ],
+        );
+
+        let resource =
+            generate_group_yaml_resource(group, ManifestType::ClusterAdmissionPolicyGroup, true)
+                .expect("Cannot create yaml resource");
+
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+apiVersion: policies.kubewarden.io/v1
+kind: ClusterAdmissionPolicyGroup
+metadata:
+  name: my-group
+spec:
+  policies:
+    signed:
+      module: registry://ghcr.io/kubewarden/tests/signed:v1.0.0
+      settings: {}
+      contextAwareResources:
+        - apiVersion: v1
+          kind: Namespace
+    trusted_registry:
+      module: registry://ghcr.io/kubewarden/tests/trusted_registry:v1.0.0
+      settings: {}
+  expression: signed() && trusted_registry()
+  message: the request has been rejected
+  rules:
+    - apiGroups: [""]
+      apiVersions: ["v1"]
+      resources: ["pods", "deployments"]
+      operations: ["CREATE", "UPDATE"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(resource, expected);
+    }
+
+    #[test]
+    fn scaffold_admission_policy_group() {
+        let mut trusted_registry = mock_group_member("trusted_registry", &["pods"]);
+        trusted_registry.metadata.background_audit = false;
+        let group = mock_group(
+            "signed() || trusted_registry()",
+            vec![mock_group_member("signed", &["pods"]), trusted_registry],
+        );
+
+        let resource =
+            generate_group_yaml_resource(group, ManifestType::AdmissionPolicyGroup, false)
+                .expect("Cannot create yaml resource");
+
+        assert_eq!(resource["kind"], "AdmissionPolicyGroup");
+        assert_eq!(resource["spec"]["backgroundAudit"], false);
+        assert_eq!(
+            resource["spec"]["rules"]
+                .as_sequence()
+                .expect("rules should be a sequence")
+                .len(),
+            1
+        );
+        assert_eq!(
+            resource["spec"]["policies"]["signed"]["module"],
+            "registry://ghcr.io/kubewarden/tests/signed:v1.0.0"
+        );
+    }
+
+    #[test]
+    fn merge_rules_keeps_rules_with_different_operations() {
+        let pods = mock_group_member("pods", &["pods"]);
+        let mut deployments = mock_group_member("deployments", &["deployments"]);
+        deployments.metadata.rules[0].operations =
+            vec![policy_evaluator::policy_metadata::Operation::Delete];
+
+        let rules = merge_rules([&pods.metadata, &deployments.metadata].into_iter());
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].resources, vec!["pods"]);
+        assert_eq!(rules[1].resources, vec!["deployments"]);
+    }
+
+    #[rstest]
+    #[case::unknown_member("signed() && unknown()", "references 'unknown'")]
+    #[case::member_not_called("signed && trusted_registry()", "without calling it")]
+    #[case::empty_expression("  ", "expression cannot be empty")]
+    fn scaffold_policy_group_with_invalid_expression(
+        #[case] expression: &str,
+        #[case] expected_error: &str,
+    ) {
+        let group = mock_group(
+            expression,
+            vec![
+                mock_group_member("signed", &["pods"]),
+                mock_group_member("trusted_registry", &["pods"]),
+            ],
+        );
+
+        let error =
+            generate_group_yaml_resource(group, ManifestType::ClusterAdmissionPolicyGroup, false)
+                .expect_err("the expression should be rejected");
+        assert!(
+            error.to_string().contains(expected_error),
+            "unexpected error: {error}"
+        );
+    }
+
+    #[test]
+    fn expression_references_skip_literals() {
+        let references = expression_references(
+            r#"signed() && (true || trusted_registry( )) && "unknown()" != 'x'"#,
+        );
+
+        let names: Vec<(&str, bool)> = references.iter().map(|r| (r.name, r.is_call)).collect();
+        assert_eq!(names, vec![("signed", true), ("trusted_registry", true)]);
+    }
+
+    #[rstest]
+    #[case::duplicated_member(
+        vec![mock_group_member("signed", &["pods"]), mock_group_member("signed", &["pods"])],
+        "defined more than once"
+    )]
+    #[case::invalid_member_name(vec![mock_group_member("signed-policy", &["pods"])], "Invalid policy group member name")]
+    #[case::no_member(vec![], "at least one policy")]
+    fn scaffold_policy_group_with_invalid_members(
+        #[case] members: Vec<ScaffoldGroupMemberData>,
+        #[case] expected_error: &str,
+    ) {
+        let group = mock_group("signed()", members);
+
+        let error = generate_group_yaml_resource(group, ManifestType::AdmissionPolicyGroup, false)
+            .expect_err("the members should be rejected");
+        assert!(
+            error.to_string().contains(expected_error),
+            "unexpected error: {error}"
+        );
+    }
+
+    #[test]
+    fn scaffold_policy_group_without_title() {
+        let mut group = mock_group("signed()", vec![mock_group_member("signed", &["pods"])]);
+        group.policy_title = None;
+
+        let error = generate_group_yaml_resource(group, ManifestType::AdmissionPolicyGroup, false)
+            .expect_err("a group without title should be rejected");
+        assert!(error.to_string().contains("--title"));
+    }
+
+    #[test]
+    fn scaffold_policy_group_with_mutating_member() {
+        let mut signed = mock_group_member("signed", &["pods"]);
+        signed.metadata.mutating = true;
+
+        let error = generate_group_yaml_resource(
+            mock_group("signed()", vec![signed]),
+            ManifestType::ClusterAdmissionPolicyGroup,
+            false,
+        )
+        .expect_err("mutating policies should be rejected");
+        assert!(error.to_string().contains("mutating policy"));
+    }
 }
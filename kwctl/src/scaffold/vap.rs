@@ -1,25 +1,53 @@
 use anyhow::{anyhow, Result};
 use k8s_openapi::api::admissionregistration::v1::{
-    ValidatingAdmissionPolicy, ValidatingAdmissionPolicyBinding,
+    ParamKind, ParamRef, ValidatingAdmissionPolicy, ValidatingAdmissionPolicyBinding,
 };
 use policy_evaluator::{policy_fetcher::oci_client::Reference, policy_metadata::Rule};
-use std::{collections::BTreeSet, convert::TryFrom, fs::File, path::Path};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fs::File,
+    path::{Path, PathBuf},
+};
 use tracing::warn;
 
-use crate::scaffold::kubewarden_crds::{ClusterAdmissionPolicy, ClusterAdmissionPolicySpec};
+use crate::{
+    scaffold::kubewarden_crds::{ClusterAdmissionPolicy, ClusterAdmissionPolicySpec},
+    utils::read_yaml_documents,
+};
 
-pub(crate) fn vap(cel_policy_module: &str, vap_path: &Path, binding_path: &Path) -> Result<()> {
+pub(crate) fn vap(
+    cel_policy_module: &str,
+    vap_path: &Path,
+    binding_paths: &[PathBuf],
+    param_paths: &[PathBuf],
+) -> Result<()> {
     let vap_file = File::open(vap_path)
         .map_err(|e| anyhow!("cannot open {}: #{e}", vap_path.to_str().unwrap()))?;
-    let binding_file = File::open(binding_path)
-        .map_err(|e| anyhow!("cannot open {}: #{e}", binding_path.to_str().unwrap()))?;
-
     let vap: ValidatingAdmissionPolicy = serde_yaml::from_reader(vap_file)
         .map_err(|e| anyhow!("cannot convert given data into a ValidatingAdmissionPolicy: #{e}"))?;
-    let vap_binding: ValidatingAdmissionPolicyBinding = serde_yaml::from_reader(binding_file)
-        .map_err(|e| {
-            anyhow!("cannot convert given data into a ValidatingAdmissionPolicyBinding: #{e}")
-        })?;
+
+    // a file can contain multiple bindings
+    let mut vap_bindings = Vec::new();
+    for binding_path in binding_paths {
+        for document in read_yaml_documents(binding_path)? {
+            let vap_binding: ValidatingAdmissionPolicyBinding = serde_yaml::from_value(document)
+                .map_err(|e| {
+                    anyhow!(
+                        "cannot convert given data into a ValidatingAdmissionPolicyBinding: #{e}"
+                    )
+                })?;
+            vap_bindings.push(vap_binding);
+        }
+    }
+    if vap_bindings.is_empty() {
+        return Err(anyhow!("no ValidatingAdmissionPolicyBinding found"));
+    }
+
+    let mut params = Vec::new();
+    for param_path in param_paths {
+        params.extend(read_yaml_documents(param_path)?);
+    }
 
     match cel_policy_module.parse::<Reference>() {
         Ok(cel_policy_ref) => match cel_policy_ref.tag() {
@@ -35,32 +63,32 @@ pub(crate) fn vap(cel_policy_module: &str, vap_path: &Path, binding_path: &Path)
         }
     }
 
-    let cluster_admission_policy =
-        convert_vap_to_cluster_admission_policy(cel_policy_module, vap, vap_binding)?;
+    // one policy is generated for each binding
+    let cluster_admission_policies = vap_bindings
+        .into_iter()
+        .map(|vap_binding| {
+            convert_vap_to_cluster_admission_policy(cel_policy_module, &vap, vap_binding, &params)
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    serde_yaml::to_writer(std::io::stdout(), &cluster_admission_policy)?;
+    let documents = cluster_admission_policies
+        .iter()
+        .map(serde_yaml::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    print!("{}", documents.join("---\n"));
 
     Ok(())
 }
 
 fn convert_vap_to_cluster_admission_policy(
     cel_policy_module: &str,
-    vap: ValidatingAdmissionPolicy,
+    vap: &ValidatingAdmissionPolicy,
     vap_binding: ValidatingAdmissionPolicyBinding,
+    params: &[serde_yaml::Value],
 ) -> anyhow::Result<ClusterAdmissionPolicy> {
-    let vap_spec = vap.spec.unwrap_or_default();
-    if vap_spec.audit_annotations.is_some() {
-        warn!("auditAnnotations are not supported by Kubewarden's CEL policy yet. They will be ignored.");
-    }
-    if vap_spec.match_conditions.is_some() {
-        warn!("matchConditions are not supported by Kubewarden's CEL policy yet. They will be ignored.");
-    }
-    if vap_spec.param_kind.is_some() {
-        // It's not safe to skip this, the policy will definitely not work.
-        return Err(anyhow!(
-            "paramKind is not supported by Kubewarden's CEL policy yet"
-        ));
-    }
+    let vap_spec = vap.spec.clone().unwrap_or_default();
+    let vap_binding_spec = vap_binding.spec.unwrap_or_default();
+    let binding_name = vap_binding.metadata.name.clone().unwrap_or_default();
 
     let mut settings = serde_yaml::Mapping::new();
 
@@ -73,7 +101,7 @@ fn convert_vap_to_cluster_admission_policy(
         settings.insert("variables".into(), vap_variables.into());
     }
 
-    // migrate CEL validations
+    // migrate CEL validations, including their messageExpression
     if let Some(vap_validations) = vap_spec.validations {
         let kw_cel_validations: Vec<serde_yaml::Value> = vap_validations
             .iter()
@@ -82,10 +110,42 @@ fn convert_vap_to_cluster_admission_policy(
         settings.insert("validations".into(), kw_cel_validations.into());
     }
 
+    // migrate CEL audit annotations
+    if let Some(vap_audit_annotations) = vap_spec.audit_annotations {
+        let kw_cel_audit_annotations: Vec<serde_yaml::Value> = vap_audit_annotations
+            .iter()
+            .map(|a| serde_yaml::to_value(a).expect("cannot convert VAP audit annotation to YAML"))
+            .collect();
+        settings.insert("auditAnnotations".into(), kw_cel_audit_annotations.into());
+    }
+
+    // the param referenced by the binding is inlined into the settings
+    if let Some(param_kind) = &vap_spec.param_kind {
+        let param_ref = vap_binding_spec.param_ref.as_ref().ok_or_else(|| {
+            anyhow!("the ValidatingAdmissionPolicy defines a paramKind, but the binding {binding_name} does not define a paramRef")
+        })?;
+        settings.insert("params".into(), find_param(param_kind, param_ref, params)?);
+    } else if vap_binding_spec.param_ref.is_some() {
+        warn!(
+            binding = binding_name,
+            "The binding defines a paramRef, but the ValidatingAdmissionPolicy does not define a paramKind. It will be ignored."
+        );
+    }
+
+    // a binding that does not deny requests is converted into a policy in monitor mode
+    let mode = match &vap_binding_spec.validation_actions {
+        Some(actions) if !actions.iter().any(|action| action == "Deny") => {
+            warn!(
+                binding = binding_name,
+                "The binding does not deny requests, the policy is deployed in monitor mode"
+            );
+            Some("monitor".to_string())
+        }
+        _ => None,
+    };
+
     // VAP specifies the namespace selector inside of the binding
-    let namespace_selector = vap_binding
-        .spec
-        .unwrap_or_default()
+    let namespace_selector = vap_binding_spec
         .match_resources
         .unwrap_or_default()
         .namespace_selector;
@@ -110,13 +170,14 @@ fn convert_vap_to_cluster_admission_policy(
             module: cel_policy_module.to_string(),
             namespace_selector,
             match_policy,
+            match_conditions: vap_spec.match_conditions,
             rules,
             object_selector: vap_match_constraints.object_selector,
             mutating: false,
             background_audit: true,
             context_aware_resources: BTreeSet::new(),
             failure_policy: vap_spec.failure_policy,
-            mode: None, // VAP policies are always in protect mode, which is the default for KW
+            mode,
             settings,
         },
     };
@@ -124,6 +185,46 @@ fn convert_vap_to_cluster_admission_policy(
     Ok(cluster_admission_policy)
 }
 
+/// Find the param object referenced by the binding among the given ones
+fn find_param(
+    param_kind: &ParamKind,
+    param_ref: &ParamRef,
+    params: &[serde_yaml::Value],
+) -> Result<serde_yaml::Value> {
+    let name = param_ref.name.as_deref().ok_or_else(|| {
+        anyhow!("only paramRef referencing a param by name can be converted, paramRef.selector is not supported")
+    })?;
+    let field = |param: &serde_yaml::Value, path: &[&str]| {
+        path.iter()
+            .try_fold(param, |value, key| value.get(*key))
+            .and_then(serde_yaml::Value::as_str)
+            .map(str::to_string)
+    };
+
+    params
+        .iter()
+        .find(|param| {
+            field(param, &["apiVersion"]) == param_kind.api_version
+                && field(param, &["kind"]) == param_kind.kind
+                && field(param, &["metadata", "name"]).as_deref() == Some(name)
+                && (param_ref.namespace.is_none()
+                    || field(param, &["metadata", "namespace"]) == param_ref.namespace)
+        })
+        .cloned()
+        .ok_or_else(|| {
+            anyhow!(
+                "cannot find the {} {}{} param, provide the file defining it",
+                param_kind.kind.as_deref().unwrap_or_default(),
+                param_ref
+                    .namespace
+                    .as_ref()
+                    .map(|namespace| format!("{namespace}/"))
+                    .unwrap_or_default(),
+                name
+            )
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,8 +273,9 @@ mod tests {
 
         let cluster_admission_policy = convert_vap_to_cluster_admission_policy(
             CEL_POLICY_MODULE,
-            vap.clone(),
+            &vap,
             vap_binding.clone(),
+            &[],
         )
         .unwrap();
 
@@ -229,4 +331,110 @@ mod tests {
                 .contains_key("variables"));
         }
     }
+
+    fn vap_bindings_with_params() -> Vec<ValidatingAdmissionPolicyBinding> {
+        read_yaml_documents(Path::new(&test_data("vap/vap-bindings-with-params.yml")))
+            .unwrap()
+            .into_iter()
+            .map(|document| serde_yaml::from_value(document).unwrap())
+            .collect()
+    }
+
+    fn vap_with_params() -> ValidatingAdmissionPolicy {
+        let yaml_file = File::open(test_data("vap/vap-with-params.yml")).unwrap();
+        serde_yaml::from_reader(yaml_file).unwrap()
+    }
+
+    #[test]
+    fn from_vap_with_params_to_cluster_admission_policies() {
+        let vap = vap_with_params();
+        let params = read_yaml_documents(Path::new(&test_data("vap/vap-params.yml"))).unwrap();
+
+        let cluster_admission_policies: Vec<ClusterAdmissionPolicy> = vap_bindings_with_params()
+            .into_iter()
+            .map(|vap_binding| {
+                convert_vap_to_cluster_admission_policy(
+                    CEL_POLICY_MODULE,
+                    &vap,
+                    vap_binding,
+                    &params,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        assert_eq!(cluster_admission_policies.len(), 2);
+        for (cluster_admission_policy, (name, max_replicas, mode)) in
+            cluster_admission_policies.iter().zip([
+                ("replica-limit-production", "5", None),
+                ("replica-limit-test", "2", Some("monitor".to_string())),
+            ])
+        {
+            let spec = &cluster_admission_policy.spec;
+            assert_eq!(
+                cluster_admission_policy.metadata.name.as_deref(),
+                Some(name)
+            );
+            assert_eq!(spec.mode, mode);
+            assert_eq!(
+                spec.match_conditions,
+                vap.clone().spec.unwrap().match_conditions
+            );
+            assert_eq!(
+                serde_yaml::to_value(vap.clone().spec.unwrap().audit_annotations.unwrap()).unwrap(),
+                spec.settings["auditAnnotations"]
+            );
+            assert_eq!(
+                spec.settings["validations"][0]["messageExpression"],
+                "'object.spec.replicas must be no greater than ' + params.data.maxReplicas"
+            );
+            assert_eq!(spec.settings["params"]["metadata"]["name"], name);
+            assert_eq!(spec.settings["params"]["data"]["maxReplicas"], max_replicas);
+        }
+    }
+
+    #[rstest]
+    #[case::param_not_found(
+        Some(ParamRef {
+            name: Some("another-param".to_string()),
+            namespace: Some("default".to_string()),
+            ..Default::default()
+        }),
+        "cannot find the ConfigMap default/another-param param"
+    )]
+    #[case::param_in_another_namespace(
+        Some(ParamRef {
+            name: Some("replica-limit-test".to_string()),
+            namespace: Some("kube-system".to_string()),
+            ..Default::default()
+        }),
+        "cannot find the ConfigMap kube-system/replica-limit-test param"
+    )]
+    #[case::param_selector(
+        Some(ParamRef {
+            selector: Some(Default::default()),
+            ..Default::default()
+        }),
+        "paramRef.selector is not supported"
+    )]
+    #[case::missing_param_ref(None, "does not define a paramRef")]
+    fn unresolved_params(#[case] param_ref: Option<ParamRef>, #[case] expected_error: &str) {
+        let params = read_yaml_documents(Path::new(&test_data("vap/vap-params.yml"))).unwrap();
+        let mut vap_binding = vap_bindings_with_params().remove(0);
+        vap_binding.spec.as_mut().unwrap().param_ref = param_ref;
+
+        let error = convert_vap_to_cluster_admission_policy(
+            CEL_POLICY_MODULE,
+            &vap_with_params(),
+            vap_binding,
+            &params,
+        )
+        .err()
+        .expect("conversion should fail");
+
+        assert!(
+            error.to_string().contains(expected_error),
+            "unexpected error: {error}"
+        );
+    }
 }
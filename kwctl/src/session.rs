@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+use crate::callback_handler::session::Session;
+
+/// Merge the given host capabilities sessions into a single one
+pub(crate) fn merge(inputs: &[PathBuf], output: Option<&Path>) -> Result<()> {
+    let sessions = inputs
+        .iter()
+        .map(|input| Session::from_file(input))
+        .collect::<Result<Vec<Session>>>()?;
+
+    write(&Session::merge(sessions), output)
+}
+
+/// Remove the duplicated exchanges of a session, plus the ones matching the given filters
+pub(crate) fn prune(
+    input: &Path,
+    request_types: &[String],
+    remove_errors: bool,
+    output: Option<&Path>,
+) -> Result<()> {
+    let mut session = Session::from_file(input)?;
+    session.prune(request_types, remove_errors);
+
+    write(&session, output)
+}
+
+fn write(session: &Session, output: Option<&Path>) -> Result<()> {
+    match output {
+        Some(output) => session.to_file(output),
+        None => {
+            print!("{}", serde_yaml::to_string(session)?);
+            Ok(())
+        }
+    }
+}
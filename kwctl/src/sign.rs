@@ -0,0 +1,302 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::policy_fetcher::{
+    oci_client::Reference,
+    registry::{CosignSignatureLayer, Registry, COSIGN_SIGNATURE_ANNOTATION},
+    sigstore::{
+        cosign::{
+            constraint::{AnnotationMarker, PrivateKeySigner},
+            Constraint, SignatureLayer,
+        },
+        crypto::{
+            signing_key::{ecdsa::ECDSAKeys, SigStoreKeyPair},
+            SigStoreSigner, SigningScheme,
+        },
+        registry::oci_reference::OciReference,
+    },
+    sources::Sources,
+};
+use tracing::{debug, info};
+
+use crate::verify::VerificationAnnotations;
+
+/// Environment variable holding the password of the private key. It's the
+/// same one used by cosign.
+pub(crate) const KEY_PASSWORD_ENV_VAR: &str = "COSIGN_PASSWORD";
+
+pub(crate) enum KeyType {
+    EcdsaP256,
+    EcdsaP384,
+    Ed25519,
+}
+
+impl FromStr for KeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value {
+            "ecdsa-p256" => Ok(KeyType::EcdsaP256),
+            "ecdsa-p384" => Ok(KeyType::EcdsaP384),
+            "ed25519" => Ok(KeyType::Ed25519),
+            _ => Err(anyhow!("unknown key type")),
+        }
+    }
+}
+
+impl From<KeyType> for SigningScheme {
+    fn from(key_type: KeyType) -> Self {
+        match key_type {
+            KeyType::EcdsaP256 => SigningScheme::ECDSA_P256_SHA256_ASN1,
+            KeyType::EcdsaP384 => SigningScheme::ECDSA_P384_SHA384_ASN1,
+            KeyType::Ed25519 => SigningScheme::ED25519,
+        }
+    }
+}
+
+/// Generate a cosign compatible key pair. The private key is encrypted with
+/// the given password and written to `<output_prefix>.key`, the public key is
+/// written to `<output_prefix>.pub`.
+///
+/// Returns the paths of the private and the public keys.
+pub(crate) fn generate_key_pair(
+    key_type: KeyType,
+    output_prefix: &str,
+    password: &[u8],
+) -> Result<(PathBuf, PathBuf)> {
+    let private_key_path = PathBuf::from(format!("{output_prefix}.key"));
+    let public_key_path = PathBuf::from(format!("{output_prefix}.pub"));
+    for path in [&private_key_path, &public_key_path] {
+        if path.exists() {
+            return Err(anyhow!(
+                "{} already exists, refusing to overwrite it",
+                path.display()
+            ));
+        }
+    }
+
+    let key_pair = SigningScheme::from(key_type)
+        .create_signer()?
+        .to_sigstore_keypair()?;
+    let private_key = key_pair.private_key_to_encrypted_pem(password)?;
+    let public_key = key_pair.public_key_to_pem()?;
+
+    write_new_file(&private_key_path, private_key.as_bytes(), 0o600)?;
+    write_new_file(&public_key_path, public_key.as_bytes(), 0o644)?;
+
+    Ok((private_key_path, public_key_path))
+}
+
+/// Write the contents to a new file, failing if the file already exists.
+/// On unix systems the file is created with the given permissions.
+fn write_new_file(path: &Path, contents: &[u8], mode: u32) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))?;
+    std::io::Write::write_all(&mut file, contents)
+        .map_err(|e| anyhow!("cannot write {}: {}", path.display(), e))
+}
+
+/// Sign the policy pushed to the OCI registry with the given private key,
+/// then attach the signature to it. The signature can be verified with
+/// `kwctl verify --verification-key` or `cosign verify --key`.
+///
+/// Returns the immutable reference of the signature image.
+pub(crate) async fn sign(
+    url: &str,
+    sources: Option<&Sources>,
+    key_path: &Path,
+    password: &[u8],
+    annotations: &VerificationAnnotations,
+) -> Result<String> {
+    let key =
+        fs::read(key_path).map_err(|e| anyhow!("cannot read key {}: {}", key_path.display(), e))?;
+    let signer = load_signer(&key, password)
+        .map_err(|e| anyhow!("cannot load key {}: {}", key_path.display(), e))?;
+
+    let url = if url.starts_with("registry://") {
+        url.to_owned()
+    } else {
+        format!("registry://{url}")
+    };
+    let reference = Reference::try_from(url.trim_start_matches("registry://"))?;
+
+    let registry = Registry::new();
+    let digest = registry.manifest_digest(&url, sources).await?;
+    debug!(policy = url.as_str(), digest, "signing policy");
+
+    let signature = signature_layer(&reference, &digest, signer, annotations)?;
+    let immutable_ref = format!(
+        "registry://{}/{}@{}",
+        reference.registry(),
+        reference.repository(),
+        digest
+    );
+    let signature_ref = registry
+        .push_cosign_signature(&immutable_ref, sources, &signature)
+        .await?;
+
+    info!(signature = signature_ref.as_str(), "signature pushed");
+    Ok(signature_ref)
+}
+
+fn load_signer(key: &[u8], password: &[u8]) -> Result<SigStoreSigner> {
+    let key_pair = SigStoreKeyPair::from_encrypted_pem(key, password)?;
+    let signing_scheme = match &key_pair {
+        SigStoreKeyPair::ECDSA(ECDSAKeys::P256(_)) => SigningScheme::ECDSA_P256_SHA256_ASN1,
+        SigStoreKeyPair::ECDSA(ECDSAKeys::P384(_)) => SigningScheme::ECDSA_P384_SHA384_ASN1,
+        SigStoreKeyPair::ED25519(_) => SigningScheme::ED25519,
+        SigStoreKeyPair::RSA(_) => {
+            return Err(anyhow!(
+                "RSA keys are not supported, use an ECDSA or Ed25519 key"
+            ))
+        }
+    };
+
+    Ok(key_pair.to_sigstore_signer(&signing_scheme)?)
+}
+
+/// Build the cosign simple signing payload of the image with the given
+/// manifest digest, then sign it
+fn signature_layer(
+    reference: &Reference,
+    digest: &str,
+    signer: SigStoreSigner,
+    annotations: &VerificationAnnotations,
+) -> Result<CosignSignatureLayer> {
+    let image = OciReference::with_digest(
+        reference.registry().to_owned(),
+        reference.repository().to_owned(),
+        digest.to_owned(),
+    );
+    let mut layer = SignatureLayer::new_unsigned(&image, digest)?;
+    // like cosign, identify the repository, not the image
+    layer.simple_signing.critical.identity.docker_reference =
+        format!("{}/{}", reference.registry(), reference.repository());
+
+    if !annotations.is_empty() {
+        let annotations: HashMap<String, String> = annotations.clone().into_iter().collect();
+        AnnotationMarker::new(annotations).add_constraint(&mut layer)?;
+    }
+    PrivateKeySigner::new_with_signer(signer).add_constraint(&mut layer)?;
+
+    let signature = layer
+        .signature
+        .ok_or_else(|| anyhow!("cannot sign the policy"))?;
+
+    Ok(CosignSignatureLayer {
+        payload: layer.raw_data,
+        annotations: BTreeMap::from([(COSIGN_SIGNATURE_ANNOTATION.to_owned(), signature)]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use policy_evaluator::policy_fetcher::sigstore::crypto::{CosignVerificationKey, Signature};
+    use rstest::rstest;
+    use tempfile::tempdir;
+
+    const DIGEST: &str = "sha256:72b4569c3daee67abeaa64192fb53895d0edb2d44fa6e1d9d4c5d3f8ece09f6e";
+
+    #[rstest]
+    #[case::ecdsa_p256("ecdsa-p256")]
+    #[case::ed25519("ed25519")]
+    fn sign_with_generated_key_pair(#[case] key_type: &str) {
+        let tempdir = tempdir().unwrap();
+        let prefix = tempdir.path().join("cosign");
+        let (private_key_path, public_key_path) = generate_key_pair(
+            key_type.parse().unwrap(),
+            prefix.to_str().unwrap(),
+            b"secret",
+        )
+        .expect("cannot generate key pair");
+
+        let private_key = fs::read_to_string(&private_key_path).unwrap();
+        assert!(private_key.contains("ENCRYPTED SIGSTORE PRIVATE KEY"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&private_key_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let signer = load_signer(private_key.as_bytes(), b"secret").expect("cannot load key");
+        let reference = Reference::try_from("ghcr.io/kubewarden/policies/my-policy:v1.0.0")
+            .expect("invalid reference");
+        let annotations = VerificationAnnotations::from([("env".to_owned(), "prod".to_owned())]);
+        let layer =
+            signature_layer(&reference, DIGEST, signer, &annotations).expect("cannot sign payload");
+
+        let verification_key =
+            CosignVerificationKey::try_from_pem(&fs::read(public_key_path).unwrap())
+                .expect("cannot load public key");
+        verification_key
+            .verify_signature(
+                Signature::Base64Encoded(layer.annotations[COSIGN_SIGNATURE_ANNOTATION].as_bytes()),
+                &layer.payload,
+            )
+            .expect("the signature should be valid");
+
+        let payload: serde_json::Value = serde_json::from_slice(&layer.payload).unwrap();
+        assert_eq!(
+            payload["critical"]["image"]["docker-manifest-digest"],
+            DIGEST
+        );
+        assert_eq!(
+            payload["critical"]["identity"]["docker-reference"],
+            "ghcr.io/kubewarden/policies/my-policy"
+        );
+        assert_eq!(payload["optional"]["env"], "prod");
+    }
+
+    #[test]
+    fn load_key_with_wrong_password() {
+        let tempdir = tempdir().unwrap();
+        let prefix = tempdir.path().join("cosign");
+        let (private_key_path, _) =
+            generate_key_pair(KeyType::EcdsaP256, prefix.to_str().unwrap(), b"secret")
+                .expect("cannot generate key pair");
+
+        assert!(load_signer(&fs::read(private_key_path).unwrap(), b"wrong").is_err());
+    }
+
+    #[test]
+    fn generate_key_pair_does_not_overwrite_keys() {
+        let tempdir = tempdir().unwrap();
+        let prefix = tempdir.path().join("cosign");
+        fs::write(tempdir.path().join("cosign.pub"), "existing").unwrap();
+
+        assert!(generate_key_pair(KeyType::Ed25519, prefix.to_str().unwrap(), b"").is_err());
+        assert!(!tempdir.path().join("cosign.key").exists());
+    }
+
+    #[test]
+    fn write_new_file_does_not_overwrite_files() {
+        let tempdir = tempdir().unwrap();
+        let path = tempdir.path().join("cosign.pub");
+        fs::write(&path, "existing").unwrap();
+
+        assert!(write_new_file(&path, b"new", 0o644).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "existing");
+    }
+}
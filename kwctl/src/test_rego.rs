@@ -0,0 +1,170 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use policy_evaluator::burrego::{self, EvaluatorBuilder, HostCallbacks};
+use regex::Regex;
+use serde_json::json;
+use tracing::{debug, error};
+
+use crate::test_report::{OutputType, TestCaseReport, TestOutcome, TestSuiteReport};
+
+/// Run the Rego unit tests compiled into the given Wasm module.
+///
+/// Each entrypoint whose rule name starts with `test_` is considered to be
+/// a test. A test passes when its result is defined and not `false`, the
+/// same semantic used by `opa test`.
+pub(crate) fn test_rego(
+    uri_or_sha_prefix: &str,
+    data_path: Option<&Path>,
+    filter: Option<&Regex>,
+    output: OutputType,
+) -> Result<()> {
+    let uri = crate::utils::map_path_to_uri(uri_or_sha_prefix)?;
+    let wasm_path = crate::utils::wasm_path(&uri)?;
+
+    let data = match data_path {
+        Some(data_path) => {
+            let data: serde_json::Value =
+                serde_yaml::from_reader(std::fs::File::open(data_path).map_err(|e| {
+                    anyhow!("Cannot open data file {}: {}", data_path.display(), e)
+                })?)
+                .map_err(|e| anyhow!("Cannot parse data file {}: {}", data_path.display(), e))?;
+            serde_json::to_vec(&data)?
+        }
+        None => b"{}".to_vec(),
+    };
+
+    let mut evaluator = EvaluatorBuilder::default()
+        .policy_path(&wasm_path)
+        .host_callbacks(HostCallbacks {
+            opa_abort: |msg| error!(msg, "OPA abort"),
+            opa_println: |msg| eprintln!("{msg}"),
+        })
+        .build()
+        .map_err(|e| anyhow!("Cannot load Rego module {}: {}", wasm_path.display(), e))?;
+
+    let mut test_entrypoints: Vec<(String, i32)> = evaluator
+        .entrypoints()
+        .into_iter()
+        .filter(|(name, _)| is_test_entrypoint(name))
+        .filter(|(name, _)| filter.is_none_or(|filter| filter.is_match(name)))
+        .collect();
+    test_entrypoints.sort();
+    if test_entrypoints.is_empty() {
+        return Err(anyhow!(
+            "No test entrypoints found inside of {}. Rego tests must be built as entrypoints, e.g. `opa build -t wasm -e policy/test_allow`",
+            wasm_path.display()
+        ));
+    }
+
+    let mut report = TestSuiteReport {
+        name: uri,
+        test_cases: Vec::with_capacity(test_entrypoints.len()),
+    };
+    for (name, entrypoint_id) in test_entrypoints {
+        debug!(test = name, "running Rego test");
+        let start = Instant::now();
+        let evaluation = evaluator.evaluate(entrypoint_id, &json!({}), &data);
+        let duration: Duration = start.elapsed();
+
+        if evaluation.is_err() {
+            // the Wasm instance might be in an inconsistent state after a failure
+            evaluator.reset()?;
+        }
+
+        report.test_cases.push(TestCaseReport {
+            name,
+            outcome: test_outcome(evaluation),
+            duration,
+        });
+    }
+
+    print!("{}", report.render(&output));
+
+    if report.is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} out of {} Rego tests did not pass",
+            report.test_cases.len() - report.passed(),
+            report.test_cases.len()
+        ))
+    }
+}
+
+/// Rego tests are rules whose name begins with `test_`. The entrypoint
+/// name is the path of the rule, e.g. `kubernetes/admission/test_deny`
+fn is_test_entrypoint(entrypoint: &str) -> bool {
+    entrypoint
+        .rsplit(['/', '.'])
+        .next()
+        .is_some_and(|rule| rule.starts_with("test_"))
+}
+
+fn test_outcome(evaluation: burrego::errors::Result<serde_json::Value>) -> TestOutcome {
+    let evaluation = match evaluation {
+        Ok(evaluation) => evaluation,
+        Err(e) => return TestOutcome::Error(e.to_string()),
+    };
+
+    match evaluation
+        .as_array()
+        .and_then(|results| results.first())
+        .and_then(|result| result.get("result"))
+    {
+        None => TestOutcome::Failed("test result is undefined".to_string()),
+        Some(serde_json::Value::Bool(false)) => {
+            TestOutcome::Failed("test result is false".to_string())
+        }
+        Some(_) => TestOutcome::Passed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("policy/test_allow", true)]
+    #[case("kubernetes/admission/test_deny_privileged", true)]
+    #[case("policy.test_allow", true)]
+    #[case("test_allow", true)]
+    #[case("policy/allow", false)]
+    #[case("test_policy/allow", false)]
+    #[case("policy/testing", false)]
+    fn detect_test_entrypoints(#[case] entrypoint: &str, #[case] is_test: bool) {
+        assert_eq!(is_test_entrypoint(entrypoint), is_test);
+    }
+
+    #[rstest]
+    #[case::true_result(Ok(json!([{"result": true}])), TestOutcome::Passed)]
+    #[case::defined_result(Ok(json!([{"result": {"msg": "ok"}}])), TestOutcome::Passed)]
+    #[case::false_result(
+        Ok(json!([{"result": false}])),
+        TestOutcome::Failed("test result is false".to_string())
+    )]
+    #[case::undefined_result(
+        Ok(json!([])),
+        TestOutcome::Failed("test result is undefined".to_string())
+    )]
+    #[case::evaluation_error(
+        Err(burrego::errors::BurregoError::RegoWasmError("boom".to_string())),
+        TestOutcome::Error("boom".to_string())
+    )]
+    fn interpret_test_result(
+        #[case] evaluation: burrego::errors::Result<serde_json::Value>,
+        #[case] expected: TestOutcome,
+    ) {
+        let outcome = test_outcome(evaluation);
+        match (&outcome, &expected) {
+            (TestOutcome::Error(message), TestOutcome::Error(expected_message)) => {
+                assert!(message.contains(expected_message))
+            }
+            _ => assert_eq!(outcome, expected),
+        }
+    }
+}
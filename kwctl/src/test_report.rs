@@ -0,0 +1,296 @@
+use std::{convert::TryFrom, fmt::Write, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+/// The formats a test report can be rendered with
+pub(crate) enum OutputType {
+    Text,
+    JUnit,
+    Tap,
+}
+
+impl TryFrom<Option<&str>> for OutputType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Option<&str>) -> Result<Self, Self::Error> {
+        match value {
+            Some("text") | None => Ok(Self::Text),
+            Some("junit") => Ok(Self::JUnit),
+            Some("tap") => Ok(Self::Tap),
+            Some(unknown) => Err(anyhow!("Invalid output format '{}'", unknown)),
+        }
+    }
+}
+
+/// The outcome of a single test case
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TestOutcome {
+    Passed,
+    Failed(String),
+    Error(String),
+}
+
+/// The result of a single test case
+#[derive(Debug, Clone)]
+pub(crate) struct TestCaseReport {
+    pub name: String,
+    pub outcome: TestOutcome,
+    pub duration: Duration,
+}
+
+/// The results of all the test cases of a test suite
+#[derive(Debug, Clone)]
+pub(crate) struct TestSuiteReport {
+    pub name: String,
+    pub test_cases: Vec<TestCaseReport>,
+}
+
+impl TestSuiteReport {
+    pub fn passed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, TestOutcome::Passed))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|outcome| matches!(outcome, TestOutcome::Failed(_)))
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(|outcome| matches!(outcome, TestOutcome::Error(_)))
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.passed() == self.test_cases.len()
+    }
+
+    fn count(&self, filter: impl Fn(&TestOutcome) -> bool) -> usize {
+        self.test_cases
+            .iter()
+            .filter(|test_case| filter(&test_case.outcome))
+            .count()
+    }
+
+    fn duration(&self) -> Duration {
+        self.test_cases
+            .iter()
+            .map(|test_case| test_case.duration)
+            .sum()
+    }
+
+    /// Render the report using the given format
+    pub fn render(&self, output: &OutputType) -> String {
+        match output {
+            OutputType::Text => self.to_text(),
+            OutputType::JUnit => self.to_junit(),
+            OutputType::Tap => self.to_tap(),
+        }
+    }
+
+    /// Render the report in a human readable format
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for test_case in &self.test_cases {
+            let (status, reason) = match &test_case.outcome {
+                TestOutcome::Passed => ("PASS", None),
+                TestOutcome::Failed(reason) => ("FAIL", Some(reason)),
+                TestOutcome::Error(reason) => ("ERROR", Some(reason)),
+            };
+            let _ = write!(
+                text,
+                "{status:<5} {} ({:?})",
+                test_case.name, test_case.duration
+            );
+            if let Some(reason) = reason {
+                let _ = write!(text, ": {reason}");
+            }
+            text.push('\n');
+        }
+
+        let total = self.test_cases.len();
+        let _ = writeln!(text, "{}", "-".repeat(80));
+        let _ = writeln!(text, "PASS: {}/{total}", self.passed());
+        if self.failed() > 0 {
+            let _ = writeln!(text, "FAIL: {}/{total}", self.failed());
+        }
+        if self.errors() > 0 {
+            let _ = writeln!(text, "ERROR: {}/{total}", self.errors());
+        }
+
+        text
+    }
+
+    /// Render the report using the JUnit XML format
+    pub fn to_junit(&self) -> String {
+        let tests = self.test_cases.len();
+        let failures = self.failed();
+        let errors = self.errors();
+        let time = self.duration().as_secs_f64();
+        let name = xml_escape(&self.name);
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let _ = writeln!(
+            xml,
+            r#"<testsuites tests="{tests}" failures="{failures}" errors="{errors}" time="{time:.6}">"#
+        );
+        let _ = writeln!(
+            xml,
+            r#"  <testsuite name="{name}" tests="{tests}" failures="{failures}" errors="{errors}" time="{time:.6}">"#
+        );
+        for test_case in &self.test_cases {
+            let _ = write!(
+                xml,
+                r#"    <testcase name="{}" classname="{name}" time="{:.6}""#,
+                xml_escape(&test_case.name),
+                test_case.duration.as_secs_f64()
+            );
+            match &test_case.outcome {
+                TestOutcome::Passed => xml.push_str("/>\n"),
+                TestOutcome::Failed(reason) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\"/>\n    </testcase>",
+                        xml_escape(reason)
+                    );
+                }
+                TestOutcome::Error(reason) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <error message=\"{}\"/>\n    </testcase>",
+                        xml_escape(reason)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+
+        xml
+    }
+    /// Render the report using the Test Anything Protocol, version 13.
+    /// The reason of the failures is reported inside of a YAML diagnostic block
+    pub fn to_tap(&self) -> String {
+        let mut tap = String::from("TAP version 13\n");
+        let _ = writeln!(tap, "1..{}", self.test_cases.len());
+        for (index, test_case) in self.test_cases.iter().enumerate() {
+            let (severity, reason) = match &test_case.outcome {
+                TestOutcome::Passed => {
+                    let _ = writeln!(tap, "ok {} - {}", index + 1, test_case.name);
+                    continue;
+                }
+                TestOutcome::Failed(reason) => ("fail", reason),
+                TestOutcome::Error(reason) => ("error", reason),
+            };
+            let _ = writeln!(tap, "not ok {} - {}", index + 1, test_case.name);
+            let _ = writeln!(tap, "  ---\n  severity: {severity}\n  message: |");
+            for line in reason.lines() {
+                let _ = writeln!(tap, "    {line}");
+            }
+            tap.push_str("  ...\n");
+        }
+
+        tap
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> TestSuiteReport {
+        TestSuiteReport {
+            name: "policy.wasm".to_string(),
+            test_cases: vec![
+                TestCaseReport {
+                    name: "policy/test_allow".to_string(),
+                    outcome: TestOutcome::Passed,
+                    duration: Duration::from_millis(1),
+                },
+                TestCaseReport {
+                    name: "policy/test_deny".to_string(),
+                    outcome: TestOutcome::Failed("result is <false>".to_string()),
+                    duration: Duration::from_millis(2),
+                },
+                TestCaseReport {
+                    name: "policy/test_broken".to_string(),
+                    outcome: TestOutcome::Error("boom".to_string()),
+                    duration: Duration::from_millis(3),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn count_outcomes() {
+        let report = report();
+
+        assert_eq!(report.passed(), 1);
+        assert_eq!(report.failed(), 1);
+        assert_eq!(report.errors(), 1);
+        assert!(!report.is_success());
+    }
+
+    #[test]
+    fn render_junit() {
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites tests="3" failures="1" errors="1" time="0.006000">
+  <testsuite name="policy.wasm" tests="3" failures="1" errors="1" time="0.006000">
+    <testcase name="policy/test_allow" classname="policy.wasm" time="0.001000"/>
+    <testcase name="policy/test_deny" classname="policy.wasm" time="0.002000">
+      <failure message="result is &lt;false&gt;"/>
+    </testcase>
+    <testcase name="policy/test_broken" classname="policy.wasm" time="0.003000">
+      <error message="boom"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+
+        assert_eq!(report().to_junit(), expected);
+    }
+
+    #[test]
+    fn render_tap() {
+        let expected = r#"TAP version 13
+1..3
+ok 1 - policy/test_allow
+not ok 2 - policy/test_deny
+  ---
+  severity: fail
+  message: |
+    result is <false>
+  ...
+not ok 3 - policy/test_broken
+  ---
+  severity: error
+  message: |
+    boom
+  ...
+"#;
+
+        assert_eq!(report().to_tap(), expected);
+    }
+
+    #[test]
+    fn render_text() {
+        let text = report().to_text();
+
+        assert!(text.contains("PASS  policy/test_allow (1ms)\n"));
+        assert!(text.contains("FAIL  policy/test_deny (2ms): result is <false>\n"));
+        assert!(text.contains("ERROR policy/test_broken (3ms): boom\n"));
+        assert!(text.ends_with("PASS: 1/3\nFAIL: 1/3\nERROR: 1/3\n"));
+    }
+}
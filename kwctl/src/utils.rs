@@ -3,8 +3,10 @@ use policy_evaluator::policy_evaluator::PolicyExecutionMode;
 use policy_evaluator::policy_fetcher::oci_client::Reference;
 use policy_evaluator::policy_fetcher::store::{errors::StoreError, Store};
 use regex::Regex;
+use serde::Deserialize;
 use serde_json::json;
-use std::path::PathBuf;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use url::Url;
 
@@ -99,6 +101,22 @@ pub(crate) fn find_file_matching_file(possible_names: &[&str]) -> Option<PathBuf
         .find(|path| path.exists())
 }
 
+/// Read all the documents of a YAML file. Empty documents are skipped
+pub(crate) fn read_yaml_documents(path: &Path) -> Result<Vec<serde_yaml::Value>> {
+    let file = File::open(path).map_err(|e| anyhow!("cannot open {}: #{e}", path.display()))?;
+
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_reader(file) {
+        let value = serde_yaml::Value::deserialize(document)
+            .map_err(|e| anyhow!("cannot parse {}: #{e}", path.display()))?;
+        if !value.is_null() {
+            documents.push(value);
+        }
+    }
+
+    Ok(documents)
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
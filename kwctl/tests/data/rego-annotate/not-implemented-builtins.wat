@@ -0,0 +1,35 @@
+;; Minimal module exposing the OPA Wasm ABI. It declares the usage of a builtin
+;; that is not implemented by Kubewarden, it cannot evaluate any request.
+;; The .wasm file is built with: wasm-tools parse not-implemented-builtins.wat -o not-implemented-builtins.wasm
+(module
+  (import "env" "memory" (memory 5))
+  (global $heap (mut i32) (i32.const 4096))
+  (global (export "opa_wasm_abi_version") i32 (i32.const 1))
+  (global (export "opa_wasm_abi_minor_version") i32 (i32.const 2))
+  (data (i32.const 1024) "{\"not.a.real.builtin\":0,\"time.now_ns\":1}\00")
+  (data (i32.const 1280) "{\"policy\":0}\00")
+  (data (i32.const 1536) "{}\00")
+  (export "memory" (memory 0))
+  (func (export "builtins") (result i32) (i32.const 1))
+  (func (export "entrypoints") (result i32) (i32.const 2))
+  (func (export "opa_json_dump") (param i32) (result i32)
+    (if (result i32) (i32.eq (local.get 0) (i32.const 1))
+      (then (i32.const 1024))
+      (else
+        (if (result i32) (i32.eq (local.get 0) (i32.const 2))
+          (then (i32.const 1280))
+          (else (i32.const 1536))))))
+  (func (export "opa_malloc") (param i32) (result i32)
+    (local i32)
+    (local.set 1 (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+    (local.get 1))
+  (func (export "opa_json_parse") (param i32 i32) (result i32) (i32.const 3))
+  (func (export "opa_heap_ptr_get") (result i32) (global.get $heap))
+  (func (export "opa_heap_ptr_set") (param i32) (global.set $heap (local.get 0)))
+  (func (export "opa_eval_ctx_new") (result i32) (i32.const 0))
+  (func (export "opa_eval_ctx_set_input") (param i32 i32))
+  (func (export "opa_eval_ctx_set_data") (param i32 i32))
+  (func (export "opa_eval_ctx_set_entrypoint") (param i32 i32))
+  (func (export "opa_eval_ctx_get_result") (param i32) (result i32) (i32.const 3))
+  (func (export "eval") (param i32) (result i32) (i32.const 0)))
@@ -0,0 +1,45 @@
+;; Minimal module exposing the OPA Wasm ABI. It exports the `policy/allow` rule
+;; together with two tests: `policy/test_allow`, which evaluates to `true`, and
+;; `policy/test_deny`, which evaluates to `false`.
+;; The .wasm file is built with: wasm-tools parse rego-tests.wat -o rego-tests.wasm
+(module
+  (import "env" "memory" (memory 5))
+  (global $heap (mut i32) (i32.const 4096))
+  (global $entrypoint (mut i32) (i32.const 0))
+  (global (export "opa_wasm_abi_version") i32 (i32.const 1))
+  (global (export "opa_wasm_abi_minor_version") i32 (i32.const 2))
+  (data (i32.const 1024) "{}\00")
+  (data (i32.const 1280) "{\"policy/allow\":0,\"policy/test_allow\":1,\"policy/test_deny\":2}\00")
+  (data (i32.const 1536) "[{\"result\":true}]\00")
+  (data (i32.const 1792) "[{\"result\":false}]\00")
+  (export "memory" (memory 0))
+  (func (export "builtins") (result i32) (i32.const 1))
+  (func (export "entrypoints") (result i32) (i32.const 2))
+  ;; values 1 and 2 are the builtins and the entrypoints, the results of the
+  ;; evaluations are 10 plus the id of the entrypoint
+  (func (export "opa_json_dump") (param i32) (result i32)
+    (if (result i32) (i32.eq (local.get 0) (i32.const 2))
+      (then (i32.const 1280))
+      (else
+        (if (result i32) (i32.eq (local.get 0) (i32.const 12))
+          (then (i32.const 1792))
+          (else
+            (if (result i32) (i32.ge_u (local.get 0) (i32.const 10))
+              (then (i32.const 1536))
+              (else (i32.const 1024))))))))
+  (func (export "opa_malloc") (param i32) (result i32)
+    (local i32)
+    (local.set 1 (global.get $heap))
+    (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+    (local.get 1))
+  (func (export "opa_json_parse") (param i32 i32) (result i32) (i32.const 3))
+  (func (export "opa_heap_ptr_get") (result i32) (global.get $heap))
+  (func (export "opa_heap_ptr_set") (param i32) (global.set $heap (local.get 0)))
+  (func (export "opa_eval_ctx_new") (result i32) (i32.const 0))
+  (func (export "opa_eval_ctx_set_input") (param i32 i32))
+  (func (export "opa_eval_ctx_set_data") (param i32 i32))
+  (func (export "opa_eval_ctx_set_entrypoint") (param i32 i32)
+    (global.set $entrypoint (local.get 1)))
+  (func (export "opa_eval_ctx_get_result") (param i32) (result i32)
+    (i32.add (global.get $entrypoint) (i32.const 10)))
+  (func (export "eval") (param i32) (result i32) (i32.const 0)))
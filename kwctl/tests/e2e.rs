@@ -557,6 +557,101 @@ fn test_bench() {
         .stdout(contains("validate").and(contains("warming up")));
 }
 
+#[rstest]
+fn test_bench_json_report_with_baseline() {
+    let tempdir = tempdir().unwrap();
+    let bench_args = [
+        "bench",
+        "--warm-up-time",
+        "0",
+        "--measurement-time",
+        "0",
+        "--num-samples",
+        "5",
+        "--output",
+        "json",
+        "--request-path",
+    ];
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.args(bench_args)
+        .arg(test_data("unprivileged-pod.json"))
+        .arg("registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5");
+    let output = cmd.output().expect("cannot run bench");
+    assert!(output.status.success());
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("cannot parse bench report");
+    assert_eq!(report["policies"][0]["validate"]["samples"], 5);
+
+    // a baseline reporting impossibly fast evaluations is always regressed
+    let mut baseline = report.clone();
+    baseline["policies"][0]["validate"]["mean_ns"] = serde_json::json!(1.0);
+    let baseline_path = tempdir.path().join("baseline.json");
+    std::fs::write(&baseline_path, baseline.to_string()).unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.args(bench_args)
+        .arg(test_data("unprivileged-pod.json"))
+        .arg("--baseline")
+        .arg(&baseline_path)
+        .arg("registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5");
+    cmd.assert()
+        .failure()
+        .stdout(contains("\"regressed\": true"))
+        .stderr(contains("regressed by more than 10%"));
+}
+
+#[rstest]
+fn test_bench_load_test() {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("bench")
+        .arg("--warm-up-time")
+        .arg("0")
+        .arg("--concurrency")
+        .arg("2")
+        .arg("--duration")
+        .arg("1")
+        .arg("--output")
+        .arg("json")
+        .arg("--request-path")
+        .arg(test_data("unprivileged-pod.json"))
+        .arg("--replay-request-path")
+        .arg(test_data("privileged-pod.json"))
+        .arg("registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5");
+    let output = cmd.output().expect("cannot run bench");
+    assert!(output.status.success());
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("cannot parse bench report");
+    let load_test = &report["load_tests"][0];
+    assert_eq!(load_test["concurrency"], 2);
+    assert_eq!(load_test["errors"], 0);
+    assert!(load_test["requests"].as_u64().unwrap() > 0);
+    assert!(load_test["throughput_rps"].as_f64().unwrap() > 0.0);
+}
+
+#[rstest]
+#[case::text("text", "PASS: 3/3")]
+#[case::junit(
+    "junit",
+    r#"<testsuite name="pod-privileged" tests="3" failures="0" errors="0""#
+)]
+#[case::tap("tap", "ok 2 - reject privileged pod")]
+fn test_policy_test_suite(#[case] output: &str, #[case] expected: &str) {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("test")
+        .arg("--output")
+        .arg(output)
+        .arg(test_data("pod-privileged-tests.yaml"));
+
+    cmd.assert().success();
+    cmd.assert().stdout(contains(expected));
+}
+
 #[rstest]
 #[case(
     "registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5",
@@ -722,6 +817,77 @@ fn test_push() {
         .stdout(contains("my-pod-privileged-policy:v0.1.10"));
 }
 
+#[test]
+fn test_sign() {
+    let registry_image = testcontainers::GenericImage::new("docker.io/library/registry", "2")
+        .with_wait_for(WaitFor::message_on_stderr("listening on "));
+    let testcontainer = registry_image
+        .start()
+        .expect("Failed to start registry container");
+    let port = testcontainer
+        .get_host_port_ipv4(5000)
+        .expect("Failed to get port");
+
+    let tempdir = tempdir().unwrap();
+    pull_policies(tempdir.path(), POLICIES);
+    std::fs::write(
+        tempdir.path().join("sources.yml"),
+        format!("insecure_sources: [\"localhost:{port}\"]"),
+    )
+    .unwrap();
+    let target_image = format!("registry://localhost:{port}/my-pod-privileged-policy:v0.1.10");
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("push")
+        .arg("--sources-path")
+        .arg("sources.yml")
+        .arg("registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5")
+        .arg(&target_image);
+    cmd.assert().success();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.env("COSIGN_PASSWORD", "secret")
+        .arg("generate-key-pair")
+        .arg("--key-type")
+        .arg("ed25519");
+    cmd.assert().success();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.env("COSIGN_PASSWORD", "secret")
+        .arg("sign")
+        .arg("--sources-path")
+        .arg("sources.yml")
+        .arg("--key")
+        .arg("cosign.key")
+        .arg("-a")
+        .arg("env=prod")
+        .arg(&target_image);
+    cmd.assert().success();
+    cmd.assert().stdout(contains("Policy successfully signed"));
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("verify")
+        .arg("--sources-path")
+        .arg("sources.yml")
+        .arg("-k")
+        .arg("cosign.pub")
+        .arg("-a")
+        .arg("env=prod")
+        .arg(&target_image);
+    cmd.assert().success();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("verify")
+        .arg("--sources-path")
+        .arg("sources.yml")
+        .arg("-k")
+        .arg("cosign.pub")
+        .arg("-a")
+        .arg("env=dev")
+        .arg(&target_image);
+    cmd.assert().failure();
+}
+
 #[rstest]
 #[case::pull_policies_before_scaffold(true)]
 #[case::pull_policies_on_demand(false)]
@@ -750,6 +916,66 @@ fn test_scaffold_manifest(#[case] pull_policies_before: bool) {
     cmd.assert().stdout(contains("ClusterAdmissionPolicy"));
 }
 
+#[test]
+fn test_scaffold_manifest_policy_group() {
+    let tempdir = tempdir().unwrap();
+    pull_policies(tempdir.path(), POLICIES);
+    std::fs::write(
+        tempdir.path().join("safe-labels-settings.yml"),
+        "denied_labels: [foo, bar]",
+    )
+    .unwrap();
+    std::fs::write(tempdir.path().join("pod-privileged-settings.yml"), "{}").unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("scaffold")
+        .arg("manifest")
+        .arg("-t")
+        .arg("ClusterAdmissionPolicyGroup")
+        .arg("--title")
+        .arg("safe-pods")
+        .arg("--member-name")
+        .arg("pod_privileged")
+        .arg("--settings-path")
+        .arg("pod-privileged-settings.yml")
+        .arg("--member-name")
+        .arg("safe_labels")
+        .arg("--settings-path")
+        .arg("safe-labels-settings.yml")
+        .arg("--expression")
+        .arg("pod_privileged() && safe_labels()")
+        .arg("--message")
+        .arg("the pod is not safe")
+        .arg("registry://ghcr.io/kubewarden/tests/pod-privileged:v0.2.5")
+        .arg("registry://ghcr.io/kubewarden/tests/safe-labels:v0.1.13");
+
+    cmd.assert().success();
+    cmd.assert()
+        .stdout(contains("kind: ClusterAdmissionPolicyGroup"))
+        .stdout(contains("pod_privileged:"))
+        .stdout(contains(
+            "module: registry://ghcr.io/kubewarden/tests/safe-labels:v0.1.13",
+        ))
+        .stdout(contains("denied_labels"))
+        .stdout(contains("expression: pod_privileged() && safe_labels()"));
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("scaffold")
+        .arg("manifest")
+        .arg("-t")
+        .arg("AdmissionPolicyGroup")
+        .arg("--member-name")
+        .arg("safe_labels")
+        .arg("--expression")
+        .arg("safe_labels() || unknown()")
+        .arg("--message")
+        .arg("rejected")
+        .arg("registry://ghcr.io/kubewarden/tests/safe-labels:v0.1.13");
+
+    cmd.assert().failure();
+    cmd.assert().stderr(contains("references 'unknown'"));
+}
+
 #[rstest]
 #[case::latest_cel_policy(
     Some("vap/vap-with-variables.yml"),
@@ -824,6 +1050,58 @@ fn test_scaffold_from_vap(
     cmd.assert().stderr(stderr_predicate);
 }
 
+#[rstest]
+fn test_scaffold_from_vap_with_params() {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("scaffold")
+        .arg("vap")
+        .arg("--cel-policy")
+        .arg("ghcr.io/kubewarden/tests/cel-policy:1.0.0")
+        .arg("--policy")
+        .arg(test_data("vap/vap-with-params.yml"))
+        .arg("--binding")
+        .arg(test_data("vap/vap-bindings-with-params.yml"))
+        .arg("--param")
+        .arg(test_data("vap/vap-params.yml"));
+
+    cmd.assert().success();
+    cmd.assert().stdout(
+        contains("name: replica-limit-production")
+            .and(contains("name: replica-limit-test"))
+            .and(contains("matchConditions:"))
+            .and(contains("auditAnnotations:"))
+            .and(contains("maxReplicas: '5'"))
+            .and(contains("maxReplicas: '2'")),
+    );
+}
+
+#[rstest]
+fn test_scaffold_from_gatekeeper() {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("scaffold")
+        .arg("gatekeeper")
+        .arg("--module")
+        .arg("registry://ghcr.io/kubewarden/tests/k8srequiredlabels:v1.0.0")
+        .arg("--template")
+        .arg(test_data("gatekeeper/constraint-template.yml"))
+        .arg("--constraint")
+        .arg(test_data("gatekeeper/constraints.yml"));
+
+    cmd.assert().success();
+    cmd.assert().stdout(
+        contains("name: namespaces-must-have-owner")
+            .and(contains("name: pods-must-have-app"))
+            .and(contains("mode: monitor"))
+            .and(contains(
+                "module: registry://ghcr.io/kubewarden/tests/k8srequiredlabels:v1.0.0",
+            )),
+    );
+}
+
 #[rstest]
 #[case::correct("rego-annotate/metadata-correct.yml", true, is_empty())]
 #[case::wrong(
@@ -855,6 +1133,137 @@ fn test_annotate_rego(
     }
 }
 
+#[rstest]
+#[case::compatible("rego-annotate/no-default-namespace-rego.wasm", true)]
+#[case::not_implemented_builtins("rego-annotate/not-implemented-builtins.wasm", false)]
+fn test_inspect_rego_builtins(#[case] wasm_path: &str, #[case] compatible: bool) {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("annotate")
+        .arg("-m")
+        .arg(test_data("rego-annotate/metadata-correct.yml"))
+        .arg(test_data(wasm_path))
+        .arg("-o")
+        .arg("annotated-policy.wasm");
+    cmd.assert().success();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("inspect").arg("annotated-policy.wasm");
+
+    if compatible {
+        cmd.assert().success();
+    } else {
+        cmd.assert().failure().stderr(contains(
+            "these builtins are not implemented: not.a.real.builtin",
+        ));
+    }
+}
+
+#[rstest]
+#[case::all_tests(&[], false, "FAIL: 1/2", "policy/allow")]
+#[case::filtered_tests(&["--run", "test_allow$"], true, "PASS: 1/1", "policy/test_deny")]
+#[case::tap_output(
+    &["--output", "tap"],
+    false,
+    "ok 1 - policy/test_allow\nnot ok 2 - policy/test_deny\n",
+    "policy/allow",
+)]
+fn test_test_rego(
+    #[case] args: &[&str],
+    #[case] success: bool,
+    #[case] expected_output: &str,
+    #[case] unexpected_output: &str,
+) {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("test-rego")
+        .args(args)
+        .arg(test_data("test-rego/rego-tests.wasm"));
+
+    let assert = if success {
+        cmd.assert().success()
+    } else {
+        cmd.assert()
+            .failure()
+            .stderr(contains("1 out of 2 Rego tests did not pass"))
+    };
+    assert
+        .stdout(contains(expected_output))
+        .stdout(contains(unexpected_output).not());
+}
+
+#[test]
+fn test_test_rego_without_tests() {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("test-rego")
+        .arg("--run")
+        .arg("not_a_test")
+        .arg(test_data("test-rego/rego-tests.wasm"));
+
+    cmd.assert()
+        .failure()
+        .stderr(contains("No test entrypoints found"));
+}
+
+#[rstest]
+#[case::clean("lint/metadata.yml", true, "0 error(s), 0 warning(s), 0 note(s)")]
+#[case::mutating_on_delete(
+    "lint/mutating-delete-metadata.yml",
+    false,
+    "error[mutating-without-create-or-update]"
+)]
+fn test_lint_metadata(#[case] metadata_path: &str, #[case] success: bool, #[case] expected: &str) {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("lint").arg("-m").arg(test_data(metadata_path));
+
+    if success {
+        cmd.assert().success();
+    } else {
+        cmd.assert().failure();
+    }
+    cmd.assert().stdout(contains(expected));
+}
+
+#[test]
+fn test_lint_questions_sarif_output() {
+    let tempdir = tempdir().unwrap();
+
+    let mut cmd = setup_command(tempdir.path());
+    cmd.arg("lint")
+        .arg("-m")
+        .arg(test_data("lint/metadata.yml"))
+        .arg("--questions-path")
+        .arg(test_data("lint/questions-ui.yml"))
+        .arg("-s")
+        .arg(test_data("lint/settings.yml"))
+        .arg("-o")
+        .arg("sarif");
+
+    cmd.assert().success();
+    let sarif: serde_json::Value = serde_json::from_slice(&cmd.assert().get_output().stdout)
+        .expect("a valid json document was expected");
+    assert_eq!(sarif["version"], "2.1.0");
+    let rule_ids: HashSet<&str> = sarif["runs"][0]["results"]
+        .as_array()
+        .expect("results not found")
+        .iter()
+        .map(|result| result["ruleId"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        rule_ids,
+        HashSet::from([
+            "questions-ui-missing-setting",
+            "questions-ui-unused-question"
+        ])
+    );
+}
+
 #[rstest]
 #[case::show_signatures(true)]
 #[case::hide_signatures(false)]
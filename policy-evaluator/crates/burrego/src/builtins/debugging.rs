@@ -1,4 +1,5 @@
 use crate::errors::{BurregoError, Result};
+use crate::trace::{self, TraceEventKind};
 
 #[tracing::instrument(skip(args))]
 pub fn trace(args: &[serde_json::Value]) -> Result<serde_json::Value> {
@@ -15,6 +16,73 @@ pub fn trace(args: &[serde_json::Value]) -> Result<serde_json::Value> {
     })?;
 
     tracing::debug!("{}", message_str);
+    trace::record(TraceEventKind::Trace, message_str);
 
     Ok(serde_json::Value::Null)
 }
+
+/// Implementation of `internal.print`, the builtin backing the `print`
+/// function. The only argument is the list of the operands given to `print`,
+/// each one of them is the set of values of the expression. An empty set
+/// means the expression is undefined.
+#[tracing::instrument(skip(args))]
+pub fn print(args: &[serde_json::Value]) -> Result<serde_json::Value> {
+    if args.len() != 1 {
+        return Err(BurregoError::BuiltinError {
+            name: "internal.print".to_string(),
+            message: "Wrong number of arguments".to_string(),
+        });
+    }
+
+    let operands = args[0]
+        .as_array()
+        .ok_or_else(|| BurregoError::BuiltinError {
+            name: "internal.print".to_string(),
+            message: "1st parameter is not an array".to_string(),
+        })?;
+
+    let message = operands
+        .iter()
+        .map(|operand| match operand.as_array() {
+            Some(values) if !values.is_empty() => values
+                .iter()
+                .map(|value| match value {
+                    serde_json::Value::String(s) => s.to_owned(),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(", "),
+            _ => "<undefined>".to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    tracing::debug!("{}", message);
+    trace::record(TraceEventKind::Print, &message);
+
+    Ok(serde_json::Value::Null)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn print_operands() {
+        let (result, events) = trace::collect(|| {
+            print(&[json!([["hello"], [1], [], [{"a": true}]])]).expect("print should not fail")
+        });
+
+        assert_eq!(result, serde_json::Value::Null);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, TraceEventKind::Print);
+        assert_eq!(events[0].message, r#"hello 1 <undefined> {"a":true}"#);
+    }
+
+    #[test]
+    fn print_wrong_arguments() {
+        assert!(print(&[json!("hello")]).is_err());
+        assert!(print(&[]).is_err());
+    }
+}
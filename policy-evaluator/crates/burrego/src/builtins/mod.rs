@@ -21,6 +21,7 @@ pub fn get_builtins() -> BuiltinFunctionsMap {
 
     // debugging
     functions.insert("trace", debugging::trace);
+    functions.insert("internal.print", debugging::print);
 
     // encoding
     functions.insert(
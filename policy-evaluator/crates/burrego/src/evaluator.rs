@@ -4,6 +4,7 @@ use crate::host_callbacks::HostCallbacks;
 use crate::opa_host_functions;
 use crate::policy::Policy;
 use crate::stack_helper::StackHelper;
+use crate::trace::{self, TraceEventKind};
 
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
@@ -101,6 +102,28 @@ impl Evaluator {
         Ok(evaluator)
     }
 
+    pub(crate) fn used_builtins_from_engine_and_module(
+        engine: Engine,
+        module: Module,
+        host_callbacks: HostCallbacks,
+        epoch_deadline: Option<u64>,
+    ) -> Result<HashSet<String>> {
+        let EvaluatorStack {
+            mut store,
+            memory,
+            policy,
+            ..
+        } = Self::setup(engine, module, host_callbacks, epoch_deadline)?;
+
+        set_epoch_deadline_and_call_guest!(epoch_deadline, store, {
+            Ok(policy
+                .builtins(&mut store, &memory)?
+                .keys()
+                .cloned()
+                .collect())
+        })
+    }
+
     fn setup(
         engine: Engine,
         module: Module,
@@ -223,10 +246,6 @@ impl Evaluator {
         self.entrypoints.clone()
     }
 
-    fn has_entrypoint(&self, entrypoint_id: i32) -> bool {
-        self.entrypoints.iter().any(|(_k, &v)| v == entrypoint_id)
-    }
-
     pub fn evaluate(
         &mut self,
         entrypoint_id: i32,
@@ -234,12 +253,18 @@ impl Evaluator {
         data: &[u8],
     ) -> Result<serde_json::Value> {
         set_epoch_deadline_and_call_guest!(self.epoch_deadline, self.store, {
-            if !self.has_entrypoint(entrypoint_id) {
-                return Err(BurregoError::RegoWasmError(format!(
-                    "Cannot find the specified entrypoint {entrypoint_id} inside of {:?}",
-                    self.entrypoints
-                )));
-            }
+            let entrypoint = self
+                .entrypoints
+                .iter()
+                .find(|(_k, &v)| v == entrypoint_id)
+                .map(|(k, _v)| k.to_owned())
+                .ok_or_else(|| {
+                    BurregoError::RegoWasmError(format!(
+                        "Cannot find the specified entrypoint {entrypoint_id} inside of {:?}",
+                        self.entrypoints
+                    ))
+                })?;
+            trace::record(TraceEventKind::Entrypoint, &entrypoint);
 
             debug!(
                 data = serde_json::to_string(&data)
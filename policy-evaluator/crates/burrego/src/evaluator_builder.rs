@@ -1,4 +1,5 @@
 use crate::errors::{BurregoError, Result};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use wasmtime::{Engine, Module};
 
@@ -66,6 +67,28 @@ impl EvaluatorBuilder {
     }
 
     pub fn build(&self) -> Result<Evaluator> {
+        let (engine, module, host_callbacks) = self.prepare()?;
+
+        Evaluator::from_engine_and_module(engine, module, host_callbacks, self.epoch_deadline)
+    }
+
+    /// Returns the names of the builtins used by the policy.
+    ///
+    /// Contrary to `build`, this doesn't fail when the policy relies on
+    /// builtins that are not implemented by burrego. This can be used to
+    /// find out whether a policy is supported before attempting to evaluate it.
+    pub fn used_builtins(&self) -> Result<HashSet<String>> {
+        let (engine, module, host_callbacks) = self.prepare()?;
+
+        Evaluator::used_builtins_from_engine_and_module(
+            engine,
+            module,
+            host_callbacks,
+            self.epoch_deadline,
+        )
+    }
+
+    fn prepare(&self) -> Result<(Engine, Module, HostCallbacks)> {
         self.validate()?;
 
         let engine = match &self.engine {
@@ -97,6 +120,6 @@ impl EvaluatorBuilder {
             .clone()
             .expect("host callbacks should be set");
 
-        Evaluator::from_engine_and_module(engine, module, host_callbacks, self.epoch_deadline)
+        Ok((engine, module, host_callbacks))
     }
 }
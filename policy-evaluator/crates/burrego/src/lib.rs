@@ -6,6 +6,7 @@ pub mod host_callbacks;
 mod opa_host_functions;
 mod policy;
 mod stack_helper;
+pub mod trace;
 
 pub use builtins::get_builtins;
 pub use evaluator::Evaluator;
@@ -4,6 +4,7 @@ use wasmtime::{AsContextMut, Caller, Linker};
 
 use crate::builtins::BUILTINS_HELPER;
 use crate::stack_helper::StackHelper;
+use crate::trace::{self, TraceEventKind};
 
 /// Add OPA host callbacks to the linker.
 /// The callbackes are the one listed at https://www.openpolicyagent.org/docs/latest/wasm/#imports
@@ -66,6 +67,7 @@ fn register_opa_println_func(
                     |e| format!("cannot decode opa_println message: {e:?}"),
                     |data| String::from_utf8(data).unwrap_or_else(|e| format!("cannot decode opa_println message: didn't read a valid string from memory - {e:?}")),
                 );
+            trace::record(TraceEventKind::Println, &msg);
             opa_println_host_callback(&msg);
 
             Ok(())
@@ -0,0 +1,100 @@
+//! Collect the messages produced by a policy while it's being evaluated.
+//!
+//! By default these messages are only sent to the `tracing` subscriber.
+//! Tools that want to show them to the user, like a CLI running a policy,
+//! can wrap the evaluation with [`collect`].
+
+use std::cell::RefCell;
+
+/// The source of a [`TraceEvent`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEventKind {
+    /// Evaluation of an entrypoint started
+    Entrypoint,
+    /// Message produced by the `print` builtin
+    Print,
+    /// Message produced by the `trace` builtin
+    Trace,
+    /// Message sent by the Wasm module through the `opa_println` import
+    Println,
+}
+
+/// A message produced while evaluating a policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub kind: TraceEventKind,
+    pub message: String,
+}
+
+thread_local! {
+    static COLLECTED_EVENTS: RefCell<Option<Vec<TraceEvent>>> = const { RefCell::new(None) };
+}
+
+/// Run `f` and return, together with its result, all the trace events
+/// produced on the current thread while it was running.
+///
+/// Policies are evaluated on the thread invoking `Evaluator::evaluate`, hence
+/// the evaluation must happen inside of `f`.
+pub fn collect<T>(f: impl FnOnce() -> T) -> (T, Vec<TraceEvent>) {
+    let previous = COLLECTED_EVENTS.with(|events| events.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let collected = COLLECTED_EVENTS
+        .with(|events| std::mem::replace(&mut *events.borrow_mut(), previous))
+        .unwrap_or_default();
+
+    (result, collected)
+}
+
+/// Record a trace event, this is a no-op unless a [`collect`] is in progress
+/// on the current thread
+pub(crate) fn record(kind: TraceEventKind, message: &str) {
+    COLLECTED_EVENTS.with(|events| {
+        if let Some(events) = events.borrow_mut().as_mut() {
+            events.push(TraceEvent {
+                kind,
+                message: message.to_string(),
+            });
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_only_while_collecting() {
+        record(TraceEventKind::Trace, "ignored");
+
+        let (result, events) = collect(|| {
+            record(TraceEventKind::Trace, "first");
+            let (_, nested_events) = collect(|| record(TraceEventKind::Print, "nested"));
+            record(TraceEventKind::Println, "second");
+            nested_events
+        });
+
+        assert_eq!(
+            result,
+            vec![TraceEvent {
+                kind: TraceEventKind::Print,
+                message: "nested".to_string(),
+            }]
+        );
+        assert_eq!(
+            events,
+            vec![
+                TraceEvent {
+                    kind: TraceEventKind::Trace,
+                    message: "first".to_string(),
+                },
+                TraceEvent {
+                    kind: TraceEventKind::Println,
+                    message: "second".to_string(),
+                },
+            ]
+        );
+
+        let (_, events) = collect(|| ());
+        assert!(events.is_empty());
+    }
+}
@@ -1,20 +1,24 @@
 use std::sync::Arc;
 
 use anyhow::anyhow;
-use kubewarden_policy_sdk::host_capabilities::net::LookupResponse;
 use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, warn};
 
 use crate::callback_requests::{CallbackRequest, CallbackRequestType, CallbackResponse};
 
+mod attestation;
 mod builder;
+mod cache;
 mod crypto;
 mod kubernetes;
+mod net;
 mod oci;
 mod sigstore_verification;
 
 pub use builder::CallbackHandlerBuilder;
-pub(crate) use crypto::verify_certificate;
+pub use cache::{CacheConfig, CachedCapability};
+pub(crate) use crypto::{verify_certificate, verify_jwt, verify_signature};
+pub use net::{parse_nameserver, DEFAULT_DNS_TIMEOUT};
 
 use sigstore_verification::{
     get_sigstore_certificate_verification_cached, get_sigstore_github_actions_verification_cached,
@@ -28,7 +32,10 @@ use sigstore_verification::{
 pub struct CallbackHandler {
     oci_client: Arc<oci::Client>,
     sigstore_client: sigstore_verification::Client,
+    attestation_verifier: Arc<attestation::Verifier>,
     kubernetes_client: Option<kubernetes::Client>,
+    net_client: Arc<net::Client>,
+    caches: Arc<cache::CallbackCaches>,
     rx: mpsc::Receiver<CallbackRequest>,
     tx: mpsc::Sender<CallbackRequest>,
     shutdown_channel: oneshot::Receiver<()>,
@@ -93,23 +100,58 @@ impl CallbackHandler {
     async fn handle_request(&mut self, req: CallbackRequest) {
         let oci_client = self.oci_client.clone();
         let mut sigstore_client = self.sigstore_client.clone();
+        let attestation_verifier = self.attestation_verifier.clone();
         let mut kubernetes_client = self.kubernetes_client.clone();
+        let net_client = self.net_client.clone();
+        let caches = self.caches.clone();
 
         tokio::spawn(async move {
             match req.request {
                 CallbackRequestType::OciManifestDigest { image } => {
                     handle_callback!(req, image, "Image digest computed", {
-                        oci::get_oci_digest_cached(&oci_client, &image)
+                        oci::get_oci_digest_cached(&oci_client, &caches.oci_digest, &image)
                     });
                 }
                 CallbackRequestType::OciManifest { image } => {
                     handle_callback!(req, image, "Image manifest computed", {
-                        oci::get_oci_manifest_cached(&oci_client, &image)
+                        oci::get_oci_manifest_cached(&oci_client, &caches.oci_manifest, &image)
                     });
                 }
                 CallbackRequestType::OciManifestAndConfig { image } => {
                     handle_callback!(req, image, "Image manifest computed", {
-                        oci::get_oci_manifest_and_config_cached(&oci_client, &image)
+                        oci::get_oci_manifest_and_config_cached(
+                            &oci_client,
+                            &caches.oci_manifest_and_config,
+                            &image,
+                        )
+                    });
+                }
+                CallbackRequestType::OciReferrers {
+                    image,
+                    artifact_type,
+                } => {
+                    handle_callback!(req, image, "Image referrers fetched", {
+                        oci::get_oci_referrers_cached(
+                            &oci_client,
+                            &caches.oci_referrers,
+                            &image,
+                            artifact_type.as_deref(),
+                        )
+                    });
+                }
+                CallbackRequestType::OciReferrerBlob {
+                    image,
+                    digest,
+                    media_type,
+                    max_size,
+                } => {
+                    handle_callback!(req, digest, "Image referrer blob fetched", {
+                        async {
+                            oci_client
+                                .referrer_blob(&image, &digest, media_type.as_deref(), max_size)
+                                .await
+                                .map(cached::Return::new)
+                        }
                     });
                 }
                 CallbackRequestType::SigstorePubKeyVerify {
@@ -120,6 +162,7 @@ impl CallbackHandler {
                     handle_callback!(req, image, "Sigstore pub key verification done", {
                         get_sigstore_pub_key_verification_cached(
                             &mut sigstore_client,
+                            &caches.sigstore,
                             image.clone(),
                             pub_keys,
                             annotations,
@@ -134,6 +177,7 @@ impl CallbackHandler {
                     handle_callback!(req, image, "Sigstore keyless verification done", {
                         get_sigstore_keyless_verification_cached(
                             &mut sigstore_client,
+                            &caches.sigstore,
                             image.clone(),
                             keyless,
                             annotations,
@@ -148,6 +192,7 @@ impl CallbackHandler {
                     handle_callback!(req, image, "Sigstore keyless prefix verification done", {
                         get_sigstore_keyless_prefix_verification_cached(
                             &mut sigstore_client,
+                            &caches.sigstore,
                             image.clone(),
                             keyless_prefix,
                             annotations,
@@ -163,6 +208,7 @@ impl CallbackHandler {
                     handle_callback!(req, image, "Sigstore GitHub Action verification done", {
                         get_sigstore_github_actions_verification_cached(
                             &mut sigstore_client,
+                            &caches.sigstore,
                             image.clone(),
                             owner,
                             repo,
@@ -180,6 +226,7 @@ impl CallbackHandler {
                     handle_callback!(req, image, "Sigstore GitHub Action verification done", {
                         get_sigstore_certificate_verification_cached(
                             &mut sigstore_client,
+                            &caches.sigstore,
                             &image,
                             &certificate,
                             certificate_chain.as_deref(),
@@ -188,21 +235,49 @@ impl CallbackHandler {
                         )
                     })
                 }
+                CallbackRequestType::SigstoreAttestationVerify { request } => {
+                    handle_callback!(
+                        req,
+                        request.image(),
+                        "Sigstore attestation verification done",
+                        {
+                            attestation::get_attestation_verification_cached(
+                                &attestation_verifier,
+                                &caches.sigstore_attestations,
+                                &request,
+                            )
+                        }
+                    );
+                }
                 CallbackRequestType::DNSLookupHost { host } => {
-                    let response = dns_lookup::lookup_host(&host)
-                        .map(|ips| {
-                            let res = LookupResponse {
-                                ips: ips.iter().map(|ip| ip.to_string()).collect(),
-                            };
-                            CallbackResponse {
-                                payload: serde_json::to_vec(&res).unwrap(),
-                            }
-                        })
-                        .map_err(anyhow::Error::new);
-
-                    if let Err(e) = req.response_channel.send(response) {
-                        warn!("callback handler: cannot send response back: {:?}", e);
-                    }
+                    handle_callback!(req, host, "DNS host lookup done", {
+                        net::lookup_host(&net_client, &host)
+                    });
+                }
+                CallbackRequestType::DNSReverseLookup { ip } => {
+                    handle_callback!(req, ip, "DNS reverse lookup done", {
+                        net::get_reverse_lookup_cached(&net_client, &caches.dns_reverse_lookup, &ip)
+                    });
+                }
+                CallbackRequestType::DNSLookupSrv { name } => {
+                    handle_callback!(req, name, "DNS SRV lookup done", {
+                        net::get_srv_lookup_cached(&net_client, &caches.dns_srv_lookup, &name)
+                    });
+                }
+                CallbackRequestType::DNSLookupTxt { name } => {
+                    handle_callback!(req, name, "DNS TXT lookup done", {
+                        net::get_txt_lookup_cached(&net_client, &caches.dns_txt_lookup, &name)
+                    });
+                }
+                CallbackRequestType::DNSLookupCidr { host, cidrs } => {
+                    handle_callback!(req, host, "DNS CIDR lookup done", {
+                        net::get_cidr_lookup_cached(
+                            &net_client,
+                            &caches.dns_ip_lookup,
+                            &host,
+                            &cidrs,
+                        )
+                    });
                 }
                 CallbackRequestType::KubernetesListResourceNamespace {
                     api_version,
@@ -210,6 +285,7 @@ impl CallbackHandler {
                     namespace,
                     label_selector,
                     field_selector,
+                    projection,
                 } => {
                     handle_callback!(
                         req,
@@ -223,6 +299,7 @@ impl CallbackHandler {
                                 &namespace,
                                 label_selector,
                                 field_selector,
+                                projection,
                             )
                         }
                     )
@@ -232,6 +309,7 @@ impl CallbackHandler {
                     kind,
                     label_selector,
                     field_selector,
+                    projection,
                 } => {
                     handle_callback!(
                         req,
@@ -244,6 +322,7 @@ impl CallbackHandler {
                                 &kind,
                                 label_selector,
                                 field_selector,
+                                projection,
                             )
                         }
                     )
@@ -253,6 +332,7 @@ impl CallbackHandler {
                     kind,
                     name,
                     namespace,
+                    projection,
                     disable_cache,
                 } => {
                     if disable_cache {
@@ -267,6 +347,7 @@ impl CallbackHandler {
                                     &kind,
                                     &name,
                                     namespace.as_deref(),
+                                    projection.as_ref(),
                                 )
                             }
                         )
@@ -278,10 +359,12 @@ impl CallbackHandler {
                             {
                                 kubernetes::get_resource_cached(
                                     kubernetes_client.as_mut(),
+                                    &caches.kubernetes_get_resource,
                                     &api_version,
                                     &kind,
                                     &name,
                                     namespace.as_deref(),
+                                    projection.as_ref(),
                                 )
                             }
                         )
@@ -306,6 +389,7 @@ impl CallbackHandler {
                     kind,
                     label_selector,
                     field_selector,
+                    projection,
                     since,
                 } => {
                     handle_callback!(
@@ -319,6 +403,32 @@ impl CallbackHandler {
                                 &kind,
                                 label_selector,
                                 field_selector,
+                                projection,
+                                since,
+                            )
+                        }
+                    )
+                }
+                CallbackRequestType::KubernetesListResourceAllChangesSince {
+                    api_version,
+                    kind,
+                    label_selector,
+                    field_selector,
+                    projection,
+                    since,
+                } => {
+                    handle_callback!(
+                        req,
+                        format!("{api_version}/{kind}"),
+                        "Get the changes of 'Kubernetes list all resources' since a given sequence number",
+                        {
+                            kubernetes::list_resources_all_changes_since(
+                                kubernetes_client.as_mut(),
+                                &api_version,
+                                &kind,
+                                label_selector,
+                                field_selector,
+                                projection,
                                 since,
                             )
                         }
@@ -340,10 +450,37 @@ impl CallbackHandler {
                             req,
                             "can_i".to_owned(),
                             "Check if user or service account has permission to perform operation",
-                            { kubernetes::can_i_cached(kubernetes_client.as_mut(), request) }
+                            {
+                                kubernetes::can_i_cached(
+                                    kubernetes_client.as_mut(),
+                                    &caches.kubernetes_can_i,
+                                    request,
+                                )
+                            }
                         )
                     }
                 }
+                CallbackRequestType::KubernetesDryRun {
+                    object,
+                    operation,
+                    field_manager,
+                } => {
+                    let api_version = object["apiVersion"].as_str().unwrap_or_default();
+                    let kind = object["kind"].as_str().unwrap_or_default();
+                    handle_callback!(
+                        req,
+                        format!("{api_version}/{kind}"),
+                        "Dry-run Kubernetes resource",
+                        {
+                            kubernetes::dry_run(
+                                kubernetes_client.as_mut(),
+                                &object,
+                                operation,
+                                field_manager.as_deref(),
+                            )
+                        }
+                    )
+                }
             }
         });
     }
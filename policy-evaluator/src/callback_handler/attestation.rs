@@ -0,0 +1,811 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use kubewarden_policy_sdk::host_capabilities::{
+    crypto::{BoolWithReason, Certificate, CertificateEncoding},
+    crypto_v1::CertificateVerificationRequest,
+};
+use olpc_cjson::CanonicalFormatter;
+use policy_fetcher::{
+    registry::{CosignAttestationLayer, Registry},
+    sigstore::{
+        cosign::{bundle::Bundle, signature_layers::CertificateSubject},
+        crypto::{CosignVerificationKey, Signature},
+        trust::{ManualTrustRoot, TrustRoot},
+    },
+    sources::Sources,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+use x509_cert::der::{asn1::Utf8StringRef, Decode, DecodePem};
+
+use super::{cache::Cache, crypto::verify_certificate};
+use crate::host_capabilities::oci::{
+    AttestationVerificationRequest, AttestationVerificationResponse,
+};
+
+const IN_TOTO_PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+const SIGSTORE_CERT_ANNOTATION: &str = "dev.sigstore.cosign/certificate";
+const SIGSTORE_BUNDLE_ANNOTATION: &str = "dev.sigstore.cosign/bundle";
+const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+// Fulcio certificate extensions holding the OIDC issuer
+const OIDC_ISSUER_V1_OID: &str = "1.3.6.1.4.1.57264.1.1";
+const OIDC_ISSUER_V2_OID: &str = "1.3.6.1.4.1.57264.1.8";
+
+/// A DSSE envelope, as defined by https://github.com/secure-systems-lab/dsse
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    payload_type: String,
+    payload: String,
+    signatures: Vec<EnvelopeSignature>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+struct EnvelopeSignature {
+    #[serde(default)]
+    keyid: String,
+    sig: String,
+}
+
+/// The in-toto statement carried by the DSSE envelope. Only the fields we
+/// need are parsed
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Statement {
+    predicate_type: String,
+    #[serde(default)]
+    subject: Vec<StatementSubject>,
+    #[serde(default)]
+    predicate: serde_json::Value,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatementSubject {
+    #[serde(default)]
+    digest: std::collections::BTreeMap<String, String>,
+}
+
+/// An attestation about the image being verified, whose signatures have not
+/// been checked yet
+struct Attestation {
+    envelope: Envelope,
+    /// The Pre-Authentication Encoding of the envelope, this is what is signed
+    pae: Vec<u8>,
+    /// Hex encoded SHA-256 digest of the payload of the envelope
+    payload_digest: String,
+    /// Hex encoded SHA-256 digest of the whole envelope, as stored inside of the layer
+    envelope_digest: String,
+    statement: Statement,
+    annotations: std::collections::BTreeMap<String, String>,
+}
+
+/// Who must have signed the attestations
+enum Signer {
+    Key(CosignVerificationKey),
+    Identity {
+        issuer: String,
+        subject: SubjectMatcher,
+    },
+}
+
+enum SubjectMatcher {
+    Equal(String),
+    UrlPrefix(String),
+}
+
+impl SubjectMatcher {
+    fn matches(&self, subject: &str) -> bool {
+        match self {
+            SubjectMatcher::Equal(expected) => subject == expected,
+            SubjectMatcher::UrlPrefix(prefix) => subject.starts_with(prefix),
+        }
+    }
+}
+
+/// Verifies the in-toto attestations attached to images by `cosign attest`
+pub(crate) struct Verifier {
+    registry: Registry,
+    sources: Option<Sources>,
+    fulcio_certs: Vec<Certificate>,
+    rekor_keys: Vec<CosignVerificationKey>,
+}
+
+impl Verifier {
+    pub fn new(
+        sources: Option<Sources>,
+        trust_root: Option<Arc<ManualTrustRoot<'static>>>,
+    ) -> Result<Self> {
+        let (fulcio_certs, rekor_keys) = match trust_root {
+            Some(trust_root) => {
+                let fulcio_certs = trust_root
+                    .fulcio_certs()?
+                    .into_iter()
+                    .map(|cert| Certificate {
+                        encoding: CertificateEncoding::Der,
+                        data: cert.to_vec(),
+                    })
+                    .collect();
+                let rekor_keys = trust_root
+                    .rekor_keys()?
+                    .into_iter()
+                    .map(CosignVerificationKey::try_from_der)
+                    .collect::<Result<Vec<_>, _>>()?;
+                (fulcio_certs, rekor_keys)
+            }
+            None => (Vec::new(), Vec::new()),
+        };
+
+        Ok(Verifier {
+            registry: Registry::new(),
+            sources,
+            fulcio_certs,
+            rekor_keys,
+        })
+    }
+
+    pub async fn verify(
+        &self,
+        request: &AttestationVerificationRequest,
+    ) -> Result<AttestationVerificationResponse> {
+        let signers = signers(request)?;
+        if signers
+            .iter()
+            .any(|signer| matches!(signer, Signer::Identity { .. }))
+            && (self.fulcio_certs.is_empty() || self.rekor_keys.is_empty())
+        {
+            return Err(anyhow!(
+                "keyless attestations cannot be verified: Fulcio and Rekor data are not available"
+            ));
+        }
+
+        let image_ref: policy_fetcher::oci_client::Reference = request.image().parse()?;
+        let attestations = self
+            .registry
+            .cosign_attestations(
+                &format!("registry://{}", image_ref.whole()),
+                self.sources.as_ref(),
+            )
+            .await?;
+
+        let candidates: Vec<Attestation> = attestations
+            .layers
+            .into_iter()
+            .filter_map(|layer| {
+                parse_attestation(layer, &attestations.image_digest, request.predicate_type())
+                    .map_err(|e| debug!(error = %e, "skipping attestation"))
+                    .ok()
+            })
+            .collect();
+
+        let mut verified = vec![false; candidates.len()];
+        for signer in &signers {
+            let mut found = false;
+            for (index, candidate) in candidates.iter().enumerate() {
+                if self.is_signed_by(candidate, signer) {
+                    verified[index] = true;
+                    found = true;
+                }
+            }
+            if !found {
+                return Err(anyhow!(
+                    "no attestation of type {} signed by {} found for {}",
+                    request.predicate_type(),
+                    signer.describe(),
+                    request.image()
+                ));
+            }
+        }
+
+        Ok(AttestationVerificationResponse {
+            digest: attestations.image_digest,
+            predicate_type: request.predicate_type().to_owned(),
+            predicates: candidates
+                .into_iter()
+                .zip(verified)
+                .filter(|(_, verified)| *verified)
+                .map(|(attestation, _)| attestation.statement.predicate)
+                .collect(),
+        })
+    }
+
+    fn is_signed_by(&self, attestation: &Attestation, signer: &Signer) -> bool {
+        match signer {
+            Signer::Key(key) => is_signed_by_key(attestation, key),
+            Signer::Identity { issuer, subject } => {
+                match self.certificate_key(attestation, issuer, subject) {
+                    Ok(key) => is_signed_by_key(attestation, &key),
+                    Err(e) => {
+                        debug!(error = %e, "ignoring keyless signature of attestation");
+                        false
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ensure the certificate used to sign the attestation has been issued by
+    /// Fulcio to the expected identity while it was valid, then return its key
+    fn certificate_key(
+        &self,
+        attestation: &Attestation,
+        issuer: &str,
+        subject: &SubjectMatcher,
+    ) -> Result<CosignVerificationKey> {
+        let cert_pem = attestation
+            .annotations
+            .get(SIGSTORE_CERT_ANNOTATION)
+            .ok_or_else(|| anyhow!("certificate annotation not found"))?;
+        let bundle_raw = attestation
+            .annotations
+            .get(SIGSTORE_BUNDLE_ANNOTATION)
+            .ok_or_else(|| anyhow!("Rekor bundle annotation not found"))?;
+
+        let bundle: Bundle = serde_json::from_str(bundle_raw)?;
+        self.verify_bundle(&bundle, cert_pem, attestation)?;
+        let integrated_time = bundle.payload.integrated_time;
+
+        let not_after = chrono::DateTime::from_timestamp(integrated_time, 0)
+            .ok_or_else(|| anyhow!("invalid integrated time {integrated_time}"))?;
+        let trusted = verify_certificate(CertificateVerificationRequest {
+            cert: Certificate {
+                encoding: CertificateEncoding::Pem,
+                data: cert_pem.as_bytes().to_vec(),
+            },
+            cert_chain: Some(self.fulcio_certs.clone()),
+            not_after: Some(not_after.to_rfc3339()),
+        })?;
+        if let BoolWithReason::False(reason) = trusted {
+            return Err(anyhow!("certificate is not trusted: {reason}"));
+        }
+
+        let cert = x509_cert::Certificate::from_pem(cert_pem.as_bytes())
+            .map_err(|e| anyhow!("cannot parse certificate: {e}"))?;
+        let not_before = cert
+            .tbs_certificate
+            .validity
+            .not_before
+            .to_unix_duration()
+            .as_secs();
+        if integrated_time < i64::try_from(not_before)? {
+            return Err(anyhow!(
+                "the attestation was signed before the certificate was valid"
+            ));
+        }
+
+        let cert_issuer = certificate_issuer(&cert)?;
+        if cert_issuer != issuer {
+            return Err(anyhow!(
+                "certificate issuer {cert_issuer} does not match {issuer}"
+            ));
+        }
+        let cert_subject = match CertificateSubject::from_certificate(&cert)? {
+            CertificateSubject::Email(email) => email,
+            CertificateSubject::Uri(uri) => uri,
+        };
+        if !subject.matches(&cert_subject) {
+            return Err(anyhow!("certificate subject {cert_subject} does not match"));
+        }
+
+        CosignVerificationKey::try_from(&cert.tbs_certificate.subject_public_key_info)
+            .map_err(|e| anyhow!("cannot extract public key from certificate: {e}"))
+    }
+
+    /// Ensure the Rekor bundle has been signed by one of the trusted Rekor keys
+    /// and that its entry is about the given certificate and attestation
+    fn verify_bundle(
+        &self,
+        bundle: &Bundle,
+        cert_pem: &str,
+        attestation: &Attestation,
+    ) -> Result<()> {
+        let mut payload = Vec::new();
+        let mut ser =
+            serde_json::Serializer::with_formatter(&mut payload, CanonicalFormatter::new());
+        bundle.payload.serialize(&mut ser)?;
+
+        if !self.rekor_keys.iter().any(|key| {
+            key.verify_signature(
+                Signature::Base64Encoded(bundle.signed_entry_timestamp.as_bytes()),
+                &payload,
+            )
+            .is_ok()
+        }) {
+            return Err(anyhow!("Rekor bundle not signed by a trusted key"));
+        }
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&general_purpose::STANDARD.decode(&bundle.payload.body)?)?;
+        let encoded_cert = general_purpose::STANDARD.encode(cert_pem);
+        if !contains_string(&body, &encoded_cert) {
+            return Err(anyhow!("Rekor entry does not reference the certificate"));
+        }
+
+        verify_rekor_entry(&body, attestation)
+    }
+}
+
+impl Signer {
+    fn describe(&self) -> String {
+        match self {
+            Signer::Key(_) => "the given public key".to_owned(),
+            Signer::Identity {
+                issuer,
+                subject: SubjectMatcher::Equal(subject),
+            } => format!("{subject} ({issuer})"),
+            Signer::Identity {
+                issuer,
+                subject: SubjectMatcher::UrlPrefix(prefix),
+            } => format!("{prefix}* ({issuer})"),
+        }
+    }
+}
+
+pub(crate) async fn get_attestation_verification_cached(
+    verifier: &Verifier,
+    cache: &Cache<String, AttestationVerificationResponse>,
+    request: &AttestationVerificationRequest,
+) -> Result<cached::Return<AttestationVerificationResponse>> {
+    cache
+        .get_or_insert_with(format!("{request:?}"), || verifier.verify(request))
+        .await
+}
+
+fn signers(request: &AttestationVerificationRequest) -> Result<Vec<Signer>> {
+    let signers: Vec<Signer> = match request {
+        AttestationVerificationRequest::SigstorePubKeyVerify { pub_keys, .. } => pub_keys
+            .iter()
+            .map(|key| {
+                CosignVerificationKey::try_from_pem(key.as_bytes())
+                    .map(Signer::Key)
+                    .map_err(|e| anyhow!("invalid public key: {e}"))
+            })
+            .collect::<Result<_>>()?,
+        AttestationVerificationRequest::SigstoreKeylessVerify { keyless, .. } => keyless
+            .iter()
+            .map(|k| Signer::Identity {
+                issuer: k.issuer.clone(),
+                subject: SubjectMatcher::Equal(k.subject.clone()),
+            })
+            .collect(),
+        AttestationVerificationRequest::SigstoreKeylessPrefixVerify { keyless_prefix, .. } => {
+            keyless_prefix
+                .iter()
+                .map(|k| {
+                    // sanitize the prefix like the signature verification does,
+                    // to protect against typosquatting
+                    let prefix = url::Url::parse(&k.url_prefix)
+                        .map_err(|e| anyhow!("invalid url prefix {}: {e}", k.url_prefix))?;
+                    let mut prefix = prefix.to_string();
+                    if !prefix.ends_with('/') {
+                        prefix.push('/');
+                    }
+                    Ok(Signer::Identity {
+                        issuer: k.issuer.clone(),
+                        subject: SubjectMatcher::UrlPrefix(prefix),
+                    })
+                })
+                .collect::<Result<_>>()?
+        }
+        AttestationVerificationRequest::SigstoreGithubActionsVerify { owner, repo, .. } => {
+            let prefix = match repo {
+                Some(repo) => format!("https://github.com/{owner}/{repo}/"),
+                None => format!("https://github.com/{owner}/"),
+            };
+            vec![Signer::Identity {
+                issuer: GITHUB_ACTIONS_ISSUER.to_owned(),
+                subject: SubjectMatcher::UrlPrefix(prefix),
+            }]
+        }
+    };
+
+    if signers.is_empty() {
+        return Err(anyhow!("Must provide at least one signer"));
+    }
+    Ok(signers)
+}
+
+/// Parse the attestation layer, ensuring it is an in-toto statement of the
+/// requested type about the given image
+fn parse_attestation(
+    layer: CosignAttestationLayer,
+    image_digest: &str,
+    predicate_type: &str,
+) -> Result<Attestation> {
+    let envelope: Envelope = serde_json::from_slice(&layer.envelope)?;
+    if envelope.payload_type != IN_TOTO_PAYLOAD_TYPE {
+        return Err(anyhow!("unexpected payload type {}", envelope.payload_type));
+    }
+    let payload = general_purpose::STANDARD.decode(&envelope.payload)?;
+    let statement: Statement = serde_json::from_slice(&payload)?;
+    if statement.predicate_type != predicate_type {
+        return Err(anyhow!(
+            "predicate type {} is not the requested one",
+            statement.predicate_type
+        ));
+    }
+
+    let (algorithm, digest) = image_digest
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid image digest {image_digest}"))?;
+    if !statement
+        .subject
+        .iter()
+        .any(|subject| subject.digest.get(algorithm).map(String::as_str) == Some(digest))
+    {
+        return Err(anyhow!("the statement is not about {image_digest}"));
+    }
+
+    Ok(Attestation {
+        pae: pre_authentication_encoding(&envelope.payload_type, &payload),
+        payload_digest: format!("{:x}", Sha256::digest(&payload)),
+        envelope_digest: format!("{:x}", Sha256::digest(&layer.envelope)),
+        envelope,
+        statement,
+        annotations: layer.annotations,
+    })
+}
+
+/// The DSSE Pre-Authentication Encoding, which is what gets signed
+fn pre_authentication_encoding(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut pae = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    pae.extend_from_slice(payload);
+    pae
+}
+
+fn is_signed_by_key(attestation: &Attestation, key: &CosignVerificationKey) -> bool {
+    attestation.envelope.signatures.iter().any(|signature| {
+        key.verify_signature(
+            Signature::Base64Encoded(signature.sig.as_bytes()),
+            &attestation.pae,
+        )
+        .is_ok()
+    })
+}
+
+/// Ensure the intoto entry of Rekor is about the given attestation: it must record
+/// the digest of its payload and its signature
+fn verify_rekor_entry(body: &serde_json::Value, attestation: &Attestation) -> Result<()> {
+    if body.get("kind").and_then(serde_json::Value::as_str) != Some("intoto") {
+        return Err(anyhow!("Rekor entry is not an intoto one"));
+    }
+    let content = body
+        .pointer("/spec/content")
+        .ok_or_else(|| anyhow!("Rekor entry does not have any content"))?;
+
+    if sha256_digest(content, "payloadHash") != Some(attestation.payload_digest.as_str()) {
+        return Err(anyhow!(
+            "Rekor entry does not match the payload of the attestation"
+        ));
+    }
+
+    let signed = match content
+        .pointer("/envelope/signatures")
+        .and_then(serde_json::Value::as_array)
+    {
+        // intoto v0.0.2 entries record the signatures of the envelope. They are
+        // base64 encoded once more by some Rekor versions
+        Some(signatures) => signatures
+            .iter()
+            .filter_map(|signature| signature.get("sig").and_then(serde_json::Value::as_str))
+            .any(|entry_sig| {
+                attestation.envelope.signatures.iter().any(|signature| {
+                    entry_sig == signature.sig
+                        || entry_sig == general_purpose::STANDARD.encode(&signature.sig)
+                })
+            }),
+        // intoto v0.0.1 entries record the digest of the whole envelope, signatures included
+        None => sha256_digest(content, "hash") == Some(attestation.envelope_digest.as_str()),
+    };
+    if !signed {
+        return Err(anyhow!(
+            "Rekor entry does not match the signature of the attestation"
+        ));
+    }
+
+    Ok(())
+}
+
+/// The hex encoded SHA-256 digest stored inside of the given field of a Rekor entry
+fn sha256_digest<'a>(content: &'a serde_json::Value, field: &str) -> Option<&'a str> {
+    let hash = content.get(field)?;
+    if hash.get("algorithm").and_then(serde_json::Value::as_str) != Some("sha256") {
+        return None;
+    }
+    hash.get("value").and_then(serde_json::Value::as_str)
+}
+
+fn certificate_issuer(cert: &x509_cert::Certificate) -> Result<String> {
+    for extension in cert.tbs_certificate.extensions.iter().flatten() {
+        let oid = extension.extn_id.to_string();
+        if oid == OIDC_ISSUER_V2_OID {
+            let value = Utf8StringRef::from_der(extension.extn_value.as_bytes())
+                .map_err(|e| anyhow!("cannot decode issuer extension: {e}"))?;
+            return Ok(value.as_str().to_owned());
+        }
+        if oid == OIDC_ISSUER_V1_OID {
+            return Ok(String::from_utf8(extension.extn_value.as_bytes().to_vec())?);
+        }
+    }
+    warn!("certificate without OIDC issuer extension");
+    Err(anyhow!("the certificate does not have an OIDC issuer"))
+}
+
+fn contains_string(value: &serde_json::Value, expected: &str) -> bool {
+    match value {
+        serde_json::Value::String(s) => s == expected,
+        serde_json::Value::Array(values) => values.iter().any(|v| contains_string(v, expected)),
+        serde_json::Value::Object(map) => map.values().any(|v| contains_string(v, expected)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use policy_fetcher::sigstore::crypto::SigningScheme;
+    use rstest::rstest;
+    use serde_json::json;
+
+    const IMAGE_DIGEST: &str =
+        "sha256:0f6f6f9ef9dd2a3a0a0fc7e0d1e7d3a7b4a2a0c0b5d1b8c2a1f6e3d9c4b7a6e5";
+
+    fn statement(predicate_type: &str, digest: &str) -> serde_json::Value {
+        let (algorithm, hex) = digest.split_once(':').unwrap();
+        json!({
+            "_type": "https://in-toto.io/Statement/v0.1",
+            "predicateType": predicate_type,
+            "subject": [{ "name": "ghcr.io/kubewarden/policy", "digest": { algorithm: hex } }],
+            "predicate": { "builder": { "id": "test" } },
+        })
+    }
+
+    /// Build a layer holding a DSSE envelope signed with the returned key
+    fn signed_layer(
+        payload_type: &str,
+        statement: &serde_json::Value,
+    ) -> (CosignAttestationLayer, String) {
+        let signer = SigningScheme::ECDSA_P256_SHA256_ASN1
+            .create_signer()
+            .unwrap();
+        let pub_key = signer
+            .to_sigstore_keypair()
+            .unwrap()
+            .public_key_to_pem()
+            .unwrap();
+        let payload = serde_json::to_vec(statement).unwrap();
+        let signature = signer
+            .sign(&pre_authentication_encoding(payload_type, &payload))
+            .unwrap();
+        let envelope = Envelope {
+            payload_type: payload_type.to_owned(),
+            payload: general_purpose::STANDARD.encode(payload),
+            signatures: vec![EnvelopeSignature {
+                keyid: String::new(),
+                sig: general_purpose::STANDARD.encode(signature),
+            }],
+        };
+        let layer = CosignAttestationLayer {
+            envelope: serde_json::to_vec(&envelope).unwrap(),
+            annotations: Default::default(),
+        };
+        (layer, pub_key)
+    }
+
+    #[test]
+    fn pae_encoding() {
+        assert_eq!(
+            pre_authentication_encoding("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world".to_vec()
+        );
+    }
+
+    #[test]
+    fn attestation_signed_by_key() {
+        let predicate_type = "https://slsa.dev/provenance/v0.2";
+        let (layer, pub_key) = signed_layer(
+            IN_TOTO_PAYLOAD_TYPE,
+            &statement(predicate_type, IMAGE_DIGEST),
+        );
+        let attestation = parse_attestation(layer, IMAGE_DIGEST, predicate_type).unwrap();
+        assert_eq!(
+            attestation.statement.predicate,
+            json!({ "builder": { "id": "test" } })
+        );
+
+        let key = CosignVerificationKey::try_from_pem(pub_key.as_bytes()).unwrap();
+        assert!(is_signed_by_key(&attestation, &key));
+
+        let (_, other_pub_key) = signed_layer(
+            IN_TOTO_PAYLOAD_TYPE,
+            &statement(predicate_type, IMAGE_DIGEST),
+        );
+        let other_key = CosignVerificationKey::try_from_pem(other_pub_key.as_bytes()).unwrap();
+        assert!(!is_signed_by_key(&attestation, &other_key));
+    }
+
+    #[rstest]
+    #[case::wrong_payload_type(
+        "application/json",
+        "https://slsa.dev/provenance/v0.2",
+        IMAGE_DIGEST
+    )]
+    #[case::wrong_predicate_type(IN_TOTO_PAYLOAD_TYPE, "https://spdx.dev/Document", IMAGE_DIGEST)]
+    #[case::wrong_subject(
+        IN_TOTO_PAYLOAD_TYPE,
+        "https://slsa.dev/provenance/v0.2",
+        "sha256:1111111111111111111111111111111111111111111111111111111111111111"
+    )]
+    fn attestation_rejected(
+        #[case] payload_type: &str,
+        #[case] predicate_type: &str,
+        #[case] subject_digest: &str,
+    ) {
+        let (layer, _) = signed_layer(payload_type, &statement(predicate_type, subject_digest));
+        assert!(
+            parse_attestation(layer, IMAGE_DIGEST, "https://slsa.dev/provenance/v0.2").is_err()
+        );
+    }
+
+    fn sha256_hex(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    /// An attestation, together with the intoto v0.0.1 and v0.0.2 Rekor entries about it
+    fn attestation_and_rekor_entries() -> (Attestation, serde_json::Value, serde_json::Value) {
+        let predicate_type = "https://slsa.dev/provenance/v0.2";
+        let (layer, _) = signed_layer(
+            IN_TOTO_PAYLOAD_TYPE,
+            &statement(predicate_type, IMAGE_DIGEST),
+        );
+        let envelope_digest = sha256_hex(&layer.envelope);
+        let attestation = parse_attestation(layer, IMAGE_DIGEST, predicate_type).unwrap();
+        let payload_digest = sha256_hex(
+            &general_purpose::STANDARD
+                .decode(&attestation.envelope.payload)
+                .unwrap(),
+        );
+        let payload_hash = json!({ "algorithm": "sha256", "value": payload_digest });
+
+        let v1 = json!({
+            "apiVersion": "0.0.1",
+            "kind": "intoto",
+            "spec": {
+                "content": {
+                    "hash": { "algorithm": "sha256", "value": envelope_digest },
+                    "payloadHash": payload_hash,
+                },
+                "publicKey": "cert",
+            },
+        });
+        let v2 = json!({
+            "apiVersion": "0.0.2",
+            "kind": "intoto",
+            "spec": {
+                "content": {
+                    "envelope": {
+                        "payloadType": IN_TOTO_PAYLOAD_TYPE,
+                        "signatures": [{
+                            "sig": general_purpose::STANDARD
+                                .encode(&attestation.envelope.signatures[0].sig),
+                            "publicKey": "cert",
+                        }],
+                    },
+                    "payloadHash": payload_hash,
+                },
+            },
+        });
+
+        (attestation, v1, v2)
+    }
+
+    #[test]
+    fn rekor_entry_matches_attestation() {
+        let (attestation, v1, v2) = attestation_and_rekor_entries();
+
+        verify_rekor_entry(&v1, &attestation).expect("v0.0.1 entry should match");
+        verify_rekor_entry(&v2, &attestation).expect("v0.0.2 entry should match");
+    }
+
+    #[rstest]
+    #[case::other_kind("/kind", json!("hashedrekord"))]
+    #[case::other_payload("/spec/content/payloadHash/value", json!("0000"))]
+    #[case::payload_hash_algorithm("/spec/content/payloadHash/algorithm", json!("sha512"))]
+    #[case::other_signature("/spec/content/envelope/signatures/0/sig", json!("c2lnbmF0dXJl"))]
+    fn rekor_entry_does_not_match_attestation(
+        #[case] pointer: &str,
+        #[case] value: serde_json::Value,
+    ) {
+        let (attestation, _, mut v2) = attestation_and_rekor_entries();
+        *v2.pointer_mut(pointer).unwrap() = value;
+
+        assert!(verify_rekor_entry(&v2, &attestation).is_err());
+    }
+
+    #[test]
+    fn rekor_v1_entry_of_other_envelope() {
+        let (attestation, mut v1, _) = attestation_and_rekor_entries();
+        *v1.pointer_mut("/spec/content/hash/value").unwrap() = json!("0000");
+
+        assert!(verify_rekor_entry(&v1, &attestation).is_err());
+    }
+
+    #[rstest]
+    #[case::owner_only(
+        None,
+        "https://github.com/kubewarden/policy/.github/workflows/release.yml@refs/tags/v1.0.0",
+        true
+    )]
+    #[case::owner_and_repo(
+        Some("policy"),
+        "https://github.com/kubewarden/policy/.github/workflows/release.yml@refs/tags/v1.0.0",
+        true
+    )]
+    #[case::other_repo(
+        Some("policy"),
+        "https://github.com/kubewarden/policy-evil/.github/workflows/release.yml@refs/tags/v1.0.0",
+        false
+    )]
+    #[case::other_owner(
+        None,
+        "https://github.com/kubewarden-evil/policy/.github/workflows/release.yml@refs/tags/v1.0.0",
+        false
+    )]
+    fn github_actions_signer(
+        #[case] repo: Option<&str>,
+        #[case] subject: &str,
+        #[case] expected: bool,
+    ) {
+        let request = AttestationVerificationRequest::SigstoreGithubActionsVerify {
+            image: "ghcr.io/kubewarden/policy:v1.0.0".to_owned(),
+            predicate_type: "https://slsa.dev/provenance/v0.2".to_owned(),
+            owner: "kubewarden".to_owned(),
+            repo: repo.map(str::to_owned),
+        };
+        let signers = signers(&request).unwrap();
+        assert_eq!(signers.len(), 1);
+        match &signers[0] {
+            Signer::Identity {
+                issuer,
+                subject: matcher,
+            } => {
+                assert_eq!(issuer, GITHUB_ACTIONS_ISSUER);
+                assert_eq!(matcher.matches(subject), expected);
+            }
+            Signer::Key(_) => panic!("unexpected signer"),
+        }
+    }
+
+    #[test]
+    fn keyless_without_trust_root() {
+        let verifier = Verifier::new(None, None).unwrap();
+        let request = AttestationVerificationRequest::SigstoreGithubActionsVerify {
+            image: "ghcr.io/kubewarden/policy:v1.0.0".to_owned(),
+            predicate_type: "https://slsa.dev/provenance/v0.2".to_owned(),
+            owner: "kubewarden".to_owned(),
+            repo: None,
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let error = rt.block_on(verifier.verify(&request)).unwrap_err();
+        assert!(error.to_string().contains("keyless attestations"));
+    }
+
+    #[test]
+    fn no_signers() {
+        let request = AttestationVerificationRequest::SigstorePubKeyVerify {
+            image: "ghcr.io/kubewarden/policy:v1.0.0".to_owned(),
+            predicate_type: "https://slsa.dev/provenance/v0.2".to_owned(),
+            pub_keys: Vec::new(),
+        };
+        assert!(signers(&request).is_err());
+    }
+}
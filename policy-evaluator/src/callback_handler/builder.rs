@@ -1,11 +1,12 @@
 use anyhow::Result;
 use policy_fetcher::sigstore::trust::ManualTrustRoot;
 use policy_fetcher::sources::Sources;
-use std::sync::Arc;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 use tokio::sync::{mpsc, oneshot};
 
+use super::cache::{CacheConfig, CachedCapability, CallbackCaches};
 use super::CallbackHandler;
-use super::{oci, sigstore_verification};
+use super::{attestation, net, oci, sigstore_verification};
 use crate::callback_requests::CallbackRequest;
 
 const DEFAULT_CHANNEL_BUFF_SIZE: usize = 100;
@@ -17,6 +18,9 @@ pub struct CallbackHandlerBuilder {
     shutdown_channel: oneshot::Receiver<()>,
     trust_root: Option<Arc<ManualTrustRoot<'static>>>,
     kube_client: Option<kube::Client>,
+    cache_configs: HashMap<CachedCapability, CacheConfig>,
+    dns_nameservers: Option<Vec<SocketAddr>>,
+    dns_timeout: Duration,
 }
 
 impl CallbackHandlerBuilder {
@@ -27,6 +31,9 @@ impl CallbackHandlerBuilder {
             channel_buffer_size: DEFAULT_CHANNEL_BUFF_SIZE,
             trust_root: None,
             kube_client: None,
+            cache_configs: HashMap::new(),
+            dns_nameservers: None,
+            dns_timeout: net::DEFAULT_DNS_TIMEOUT,
         }
     }
 
@@ -56,6 +63,28 @@ impl CallbackHandlerBuilder {
         self
     }
 
+    /// Set how the results of the given host capability are cached.
+    /// Optional, `CacheConfig::default_for` is used for the capabilities
+    /// that are not configured
+    pub fn cache_config(mut self, capability: CachedCapability, config: CacheConfig) -> Self {
+        self.cache_configs.insert(capability, config);
+        self
+    }
+
+    /// Set the name servers used by the DNS host capabilities.
+    /// Optional, the ones configured on the system are used by default
+    pub fn dns_nameservers(mut self, nameservers: Vec<SocketAddr>) -> Self {
+        self.dns_nameservers = Some(nameservers);
+        self
+    }
+
+    /// Set the timeout of the queries done by the DNS host capabilities.
+    /// Optional, defaults to 5 seconds
+    pub fn dns_timeout(mut self, timeout: Duration) -> Self {
+        self.dns_timeout = timeout;
+        self
+    }
+
     /// Create a CallbackHandler object
     pub async fn build(self) -> Result<CallbackHandler> {
         let (tx, rx) = mpsc::channel::<CallbackRequest>(self.channel_buffer_size);
@@ -65,12 +94,25 @@ impl CallbackHandlerBuilder {
                 .await?
                 .to_owned();
 
+        let attestation_verifier = Arc::new(attestation::Verifier::new(
+            self.oci_sources.clone(),
+            self.trust_root.clone(),
+        )?);
+
+        let net_client = Arc::new(net::Client::new(
+            self.dns_nameservers.as_deref(),
+            self.dns_timeout,
+        ));
+
         let kubernetes_client = self.kube_client.map(super::kubernetes::Client::new);
 
         Ok(CallbackHandler {
             oci_client,
             sigstore_client,
+            attestation_verifier,
             kubernetes_client,
+            net_client,
+            caches: Arc::new(CallbackCaches::new(&self.cache_configs)),
             tx,
             rx,
             shutdown_channel: self.shutdown_channel,
@@ -0,0 +1,439 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    hash::Hash,
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use cached::{Cached, SizedCache};
+use k8s_openapi::api::authorization::v1::SubjectAccessReviewStatus;
+use kubewarden_policy_sdk::host_capabilities::{
+    kubernetes::SubjectAccessReview as KWSubjectAccessReview, oci::ManifestDigestResponse,
+    verification::VerificationResponse,
+};
+use policy_fetcher::oci_client::manifest::OciManifest;
+use tracing::debug;
+
+use super::oci::ManifestAndConfigResponse;
+use crate::host_capabilities::{
+    net::{ReverseLookupResponse, SrvLookupResponse, TxtLookupResponse},
+    oci::{AttestationVerificationResponse, ReferrersResponse},
+};
+use crate::metrics::{record_callback_cache_eviction, record_callback_cache_lookup};
+
+/// The host capabilities whose results are cached by the CallbackHandler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CachedCapability {
+    /// OCI manifest digest, manifest and manifest+config lookups
+    Oci,
+    /// Sigstore verifications
+    Sigstore,
+    /// Kubernetes `get_resource` requests
+    KubernetesGetResource,
+    /// Kubernetes `can_i` requests
+    KubernetesCanI,
+    /// DNS reverse, SRV, TXT and CIDR lookups
+    Net,
+}
+
+impl CachedCapability {
+    pub const ALL: [CachedCapability; 5] = [
+        CachedCapability::Oci,
+        CachedCapability::Sigstore,
+        CachedCapability::KubernetesGetResource,
+        CachedCapability::KubernetesCanI,
+        CachedCapability::Net,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CachedCapability::Oci => "oci",
+            CachedCapability::Sigstore => "sigstore",
+            CachedCapability::KubernetesGetResource => "kubernetes_get_resource",
+            CachedCapability::KubernetesCanI => "kubernetes_can_i",
+            CachedCapability::Net => "net",
+        }
+    }
+}
+
+impl fmt::Display for CachedCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for CachedCapability {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CachedCapability::ALL
+            .into_iter()
+            .find(|capability| capability.as_str() == s)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown cached capability '{s}', valid values are: {}",
+                    CachedCapability::ALL
+                        .iter()
+                        .map(|c| c.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+}
+
+/// Settings of the cache used for a host capability
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// How long a successful result is kept
+    pub ttl: Duration,
+    /// Maximum number of entries kept, the least recently used ones are evicted
+    /// first. Setting this to `0` disables the cache.
+    pub max_entries: usize,
+    /// How long a failure is kept. When `None`, failures are not cached
+    pub negative_ttl: Option<Duration>,
+}
+
+impl CacheConfig {
+    /// The settings used when nothing is specified for the given capability
+    pub fn default_for(capability: CachedCapability) -> Self {
+        let ttl = match capability {
+            CachedCapability::Oci | CachedCapability::Sigstore | CachedCapability::Net => {
+                Duration::from_secs(60)
+            }
+            CachedCapability::KubernetesGetResource | CachedCapability::KubernetesCanI => {
+                Duration::from_secs(5)
+            }
+        };
+
+        CacheConfig {
+            ttl,
+            max_entries: 1000,
+            negative_ttl: None,
+        }
+    }
+}
+
+struct CacheEntry<V> {
+    value: Result<V, String>,
+    expires_at: Instant,
+}
+
+/// Time and size bound cache of the results of a host capability.
+///
+/// The lock is never held while the value is being computed: concurrent
+/// misses of the same key will all compute the value, the last one wins.
+pub(crate) struct Cache<K, V> {
+    capability: CachedCapability,
+    config: CacheConfig,
+    store: Option<Mutex<SizedCache<K, CacheEntry<V>>>>,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new(capability: CachedCapability, config: CacheConfig) -> Self {
+        let store =
+            (config.max_entries > 0).then(|| Mutex::new(SizedCache::with_size(config.max_entries)));
+
+        Cache {
+            capability,
+            config,
+            store,
+        }
+    }
+
+    /// Return the cached result for `key`, computing it via `f` when missing
+    /// or expired
+    pub async fn get_or_insert_with<F, Fut>(&self, key: K, f: F) -> Result<cached::Return<V>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return f().await.map(cached::Return::new),
+        };
+
+        if let Some(value) = self.lookup(store, &key) {
+            return match value {
+                Ok(value) => Ok(cached::Return {
+                    was_cached: true,
+                    value,
+                }),
+                Err(e) => Err(anyhow!(e)),
+            };
+        }
+
+        let result = f().await;
+        let (value, ttl) = match &result {
+            Ok(value) => (Ok(value.clone()), self.config.ttl),
+            Err(e) => match self.config.negative_ttl {
+                Some(negative_ttl) => (Err(format!("{e:#}")), negative_ttl),
+                None => return result.map(cached::Return::new),
+            },
+        };
+        self.insert(store, key, value, ttl);
+
+        result.map(cached::Return::new)
+    }
+
+    fn lookup(
+        &self,
+        store: &Mutex<SizedCache<K, CacheEntry<V>>>,
+        key: &K,
+    ) -> Option<Result<V, String>> {
+        let mut store = store.lock().expect("cannot lock callback cache");
+
+        let expired = match store.cache_get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                let negative = entry.value.is_err();
+                record_callback_cache_lookup(self.capability, true, negative);
+                debug!(capability = self.capability.as_str(), negative, "cache hit");
+                return Some(entry.value.clone());
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            store.cache_remove(key);
+            record_callback_cache_eviction(self.capability, "expired");
+        }
+        record_callback_cache_lookup(self.capability, false, false);
+
+        None
+    }
+
+    fn insert(
+        &self,
+        store: &Mutex<SizedCache<K, CacheEntry<V>>>,
+        key: K,
+        value: Result<V, String>,
+        ttl: Duration,
+    ) {
+        let mut store = store.lock().expect("cannot lock callback cache");
+
+        if store.cache_get(&key).is_none() && store.cache_size() >= self.config.max_entries {
+            record_callback_cache_eviction(self.capability, "capacity");
+        }
+        store.cache_set(
+            key,
+            CacheEntry {
+                value,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+}
+
+/// The caches used by the CallbackHandler, one for each kind of cached lookup.
+/// The OCI limits apply to each kind of OCI lookup, while all the Sigstore
+/// verifications share the same cache.
+pub(crate) struct CallbackCaches {
+    pub oci_digest: Cache<String, ManifestDigestResponse>,
+    pub oci_manifest: Cache<String, OciManifest>,
+    pub oci_manifest_and_config: Cache<String, ManifestAndConfigResponse>,
+    pub oci_referrers: Cache<String, ReferrersResponse>,
+    pub sigstore: Cache<String, VerificationResponse>,
+    pub sigstore_attestations: Cache<String, AttestationVerificationResponse>,
+    pub kubernetes_get_resource: Cache<String, kube::core::DynamicObject>,
+    pub kubernetes_can_i: Cache<KWSubjectAccessReview, SubjectAccessReviewStatus>,
+    pub dns_reverse_lookup: Cache<String, ReverseLookupResponse>,
+    pub dns_srv_lookup: Cache<String, SrvLookupResponse>,
+    pub dns_txt_lookup: Cache<String, TxtLookupResponse>,
+    pub dns_ip_lookup: Cache<String, Vec<IpAddr>>,
+}
+
+impl CallbackCaches {
+    pub fn new(configs: &HashMap<CachedCapability, CacheConfig>) -> Self {
+        let config = |capability| {
+            configs
+                .get(&capability)
+                .copied()
+                .unwrap_or_else(|| CacheConfig::default_for(capability))
+        };
+
+        CallbackCaches {
+            oci_digest: Cache::new(CachedCapability::Oci, config(CachedCapability::Oci)),
+            oci_manifest: Cache::new(CachedCapability::Oci, config(CachedCapability::Oci)),
+            oci_manifest_and_config: Cache::new(
+                CachedCapability::Oci,
+                config(CachedCapability::Oci),
+            ),
+            oci_referrers: Cache::new(CachedCapability::Oci, config(CachedCapability::Oci)),
+            sigstore: Cache::new(
+                CachedCapability::Sigstore,
+                config(CachedCapability::Sigstore),
+            ),
+            sigstore_attestations: Cache::new(
+                CachedCapability::Sigstore,
+                config(CachedCapability::Sigstore),
+            ),
+            kubernetes_get_resource: Cache::new(
+                CachedCapability::KubernetesGetResource,
+                config(CachedCapability::KubernetesGetResource),
+            ),
+            kubernetes_can_i: Cache::new(
+                CachedCapability::KubernetesCanI,
+                config(CachedCapability::KubernetesCanI),
+            ),
+            dns_reverse_lookup: Cache::new(CachedCapability::Net, config(CachedCapability::Net)),
+            dns_srv_lookup: Cache::new(CachedCapability::Net, config(CachedCapability::Net)),
+            dns_txt_lookup: Cache::new(CachedCapability::Net, config(CachedCapability::Net)),
+            dns_ip_lookup: Cache::new(CachedCapability::Net, config(CachedCapability::Net)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(ttl: Duration, max_entries: usize, negative_ttl: Option<Duration>) -> CacheConfig {
+        CacheConfig {
+            ttl,
+            max_entries,
+            negative_ttl,
+        }
+    }
+
+    async fn lookup(
+        cache: &Cache<String, usize>,
+        key: &str,
+        calls: &AtomicUsize,
+        fail: bool,
+    ) -> Result<cached::Return<usize>> {
+        cache
+            .get_or_insert_with(key.to_owned(), || async {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                if fail {
+                    Err(anyhow!("failure {call}"))
+                } else {
+                    Ok(call)
+                }
+            })
+            .await
+    }
+
+    #[rstest]
+    #[case::oci("oci", CachedCapability::Oci)]
+    #[case::sigstore("sigstore", CachedCapability::Sigstore)]
+    #[case::get_resource("kubernetes_get_resource", CachedCapability::KubernetesGetResource)]
+    #[case::can_i("kubernetes_can_i", CachedCapability::KubernetesCanI)]
+    #[case::net("net", CachedCapability::Net)]
+    fn parse_capability(#[case] input: &str, #[case] expected: CachedCapability) {
+        let capability: CachedCapability = input.parse().unwrap();
+        assert_eq!(capability, expected);
+        assert_eq!(capability.to_string(), input);
+    }
+
+    #[test]
+    fn parse_unknown_capability() {
+        assert!("dns".parse::<CachedCapability>().is_err());
+    }
+
+    #[tokio::test]
+    async fn hit_and_miss() {
+        let cache = Cache::new(
+            CachedCapability::Oci,
+            config(Duration::from_secs(60), 10, None),
+        );
+        let calls = AtomicUsize::new(0);
+
+        let first = lookup(&cache, "a", &calls, false).await.unwrap();
+        assert!(!first.was_cached);
+        assert_eq!(first.value, 1);
+
+        let second = lookup(&cache, "a", &calls, false).await.unwrap();
+        assert!(second.was_cached);
+        assert_eq!(second.value, 1);
+
+        let other = lookup(&cache, "b", &calls, false).await.unwrap();
+        assert!(!other.was_cached);
+        assert_eq!(other.value, 2);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_computed_again() {
+        let cache = Cache::new(
+            CachedCapability::Oci,
+            config(Duration::from_millis(10), 10, None),
+        );
+        let calls = AtomicUsize::new(0);
+
+        lookup(&cache, "a", &calls, false).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let value = lookup(&cache, "a", &calls, false).await.unwrap();
+        assert!(!value.was_cached);
+        assert_eq!(value.value, 2);
+    }
+
+    #[rstest]
+    #[case::not_cached(None, 2)]
+    #[case::cached(Some(Duration::from_secs(60)), 1)]
+    #[tokio::test]
+    async fn negative_caching(
+        #[case] negative_ttl: Option<Duration>,
+        #[case] expected_calls: usize,
+    ) {
+        let cache = Cache::new(
+            CachedCapability::Sigstore,
+            config(Duration::from_secs(60), 10, negative_ttl),
+        );
+        let calls = AtomicUsize::new(0);
+
+        let first = lookup(&cache, "a", &calls, true).await.err().unwrap();
+        let second = lookup(&cache, "a", &calls, true).await.err().unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), expected_calls);
+        assert_eq!(first.to_string(), "failure 1");
+        assert_eq!(
+            second.to_string(),
+            format!("failure {expected_calls}"),
+            "a cached failure must return the original error"
+        );
+    }
+
+    #[tokio::test]
+    async fn least_recently_used_entries_are_evicted() {
+        let cache = Cache::new(
+            CachedCapability::KubernetesGetResource,
+            config(Duration::from_secs(60), 2, None),
+        );
+        let calls = AtomicUsize::new(0);
+
+        lookup(&cache, "a", &calls, false).await.unwrap();
+        lookup(&cache, "b", &calls, false).await.unwrap();
+        // refresh "a", making "b" the least recently used entry
+        assert!(lookup(&cache, "a", &calls, false).await.unwrap().was_cached);
+        lookup(&cache, "c", &calls, false).await.unwrap();
+
+        assert!(lookup(&cache, "a", &calls, false).await.unwrap().was_cached);
+        assert!(!lookup(&cache, "b", &calls, false).await.unwrap().was_cached);
+    }
+
+    #[tokio::test]
+    async fn zero_max_entries_disables_the_cache() {
+        let cache = Cache::new(
+            CachedCapability::KubernetesCanI,
+            config(Duration::from_secs(60), 0, Some(Duration::from_secs(60))),
+        );
+        let calls = AtomicUsize::new(0);
+
+        lookup(&cache, "a", &calls, false).await.unwrap();
+        let value = lookup(&cache, "a", &calls, false).await.unwrap();
+
+        assert!(!value.was_cached);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
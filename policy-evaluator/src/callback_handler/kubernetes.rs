@@ -1,15 +1,17 @@
-use std::time::Duration;
-
 mod client;
 mod reflector;
 
 use anyhow::{anyhow, Result};
-use cached::proc_macro::cached;
 use k8s_openapi::api::authorization::v1::SubjectAccessReviewStatus;
 use kube::core::ObjectList;
 use kubewarden_policy_sdk::host_capabilities::kubernetes::SubjectAccessReview as KWSubjectAccessReview;
 use serde::Serialize;
 
+use super::cache::Cache;
+use crate::callback_requests::KubernetesResourceChanges;
+use crate::host_capabilities::kubernetes::{DryRunOperation, DryRunResponse};
+use crate::policy_metadata::ResourceProjection;
+
 pub(crate) use client::Client;
 
 #[derive(Eq, Hash, PartialEq)]
@@ -31,6 +33,7 @@ pub(crate) async fn list_resources_by_namespace(
     namespace: &str,
     label_selector: Option<String>,
     field_selector: Option<String>,
+    projection: Option<ResourceProjection>,
 ) -> Result<cached::Return<ObjectList<kube::core::DynamicObject>>> {
     if client.is_none() {
         return Err(anyhow!("kube::Client was not initialized properly")).map(cached::Return::new);
@@ -38,7 +41,14 @@ pub(crate) async fn list_resources_by_namespace(
 
     client
         .unwrap()
-        .list_resources_by_namespace(api_version, kind, namespace, label_selector, field_selector)
+        .list_resources_by_namespace(
+            api_version,
+            kind,
+            namespace,
+            label_selector,
+            field_selector,
+            projection,
+        )
         .await
         .map(cached::Return::new)
 }
@@ -49,6 +59,7 @@ pub(crate) async fn list_resources_all(
     kind: &str,
     label_selector: Option<String>,
     field_selector: Option<String>,
+    projection: Option<ResourceProjection>,
 ) -> Result<cached::Return<ObjectList<kube::core::DynamicObject>>> {
     if client.is_none() {
         return Err(anyhow!("kube::Client was not initialized properly")).map(cached::Return::new);
@@ -56,7 +67,13 @@ pub(crate) async fn list_resources_all(
 
     client
         .unwrap()
-        .list_resources_all(api_version, kind, label_selector, field_selector)
+        .list_resources_all(
+            api_version,
+            kind,
+            label_selector,
+            field_selector,
+            projection,
+        )
         .await
         .map(cached::Return::new)
 }
@@ -67,6 +84,7 @@ pub(crate) async fn get_resource(
     kind: &str,
     name: &str,
     namespace: Option<&str>,
+    projection: Option<&ResourceProjection>,
 ) -> Result<cached::Return<kube::core::DynamicObject>> {
     if client.is_none() {
         return Err(anyhow!("kube::Client was not initialized properly"));
@@ -74,7 +92,7 @@ pub(crate) async fn get_resource(
 
     client
         .unwrap()
-        .get_resource(api_version, kind, name, namespace)
+        .get_resource(api_version, kind, name, namespace, projection)
         .await
         .map(|value| cached::Return {
             was_cached: false,
@@ -82,22 +100,24 @@ pub(crate) async fn get_resource(
         })
 }
 
-#[cached(
-    time = 5,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("get_resource_cached({},{}),{},{:?}", api_version, kind, name, namespace) }"#,
-    with_cached_flag = true
-)]
 pub(crate) async fn get_resource_cached(
     client: Option<&mut Client>,
+    cache: &Cache<String, kube::core::DynamicObject>,
     api_version: &str,
     kind: &str,
     name: &str,
     namespace: Option<&str>,
+    projection: Option<&ResourceProjection>,
 ) -> Result<cached::Return<kube::core::DynamicObject>> {
-    get_resource(client, api_version, kind, name, namespace).await
+    let key =
+        format!("get_resource_cached({api_version},{kind}),{name},{namespace:?},{projection:?}");
+    cache
+        .get_or_insert_with(key, || async {
+            get_resource(client, api_version, kind, name, namespace, projection)
+                .await
+                .map(|response| response.value)
+        })
+        .await
 }
 
 pub(crate) async fn get_resource_plural_name(
@@ -129,6 +149,7 @@ pub(crate) async fn has_list_resources_all_result_changed_since_instant(
     kind: &str,
     label_selector: Option<String>,
     field_selector: Option<String>,
+    projection: Option<ResourceProjection>,
     since: tokio::time::Instant,
 ) -> Result<cached::Return<bool>> {
     if client.is_none() {
@@ -142,12 +163,64 @@ pub(crate) async fn has_list_resources_all_result_changed_since_instant(
             kind,
             label_selector,
             field_selector,
+            projection,
             since,
         )
         .await
         .map(cached::Return::new)
 }
 
+/// Get the changes to the results of the "list all resources" query since the provided
+/// sequence number. This is done by querying the reflector that keeps track of this query
+pub(crate) async fn list_resources_all_changes_since(
+    client: Option<&mut Client>,
+    api_version: &str,
+    kind: &str,
+    label_selector: Option<String>,
+    field_selector: Option<String>,
+    projection: Option<ResourceProjection>,
+    since: Option<u64>,
+) -> Result<cached::Return<KubernetesResourceChanges>> {
+    if client.is_none() {
+        return Err(anyhow!("kube::Client was not initialized properly")).map(cached::Return::new);
+    }
+
+    client
+        .unwrap()
+        .list_resources_all_changes_since(
+            api_version,
+            kind,
+            label_selector,
+            field_selector,
+            projection,
+            since,
+        )
+        .await
+        .map(cached::Return::new)
+}
+
+pub(crate) async fn dry_run(
+    client: Option<&mut Client>,
+    object: &serde_json::Value,
+    operation: DryRunOperation,
+    field_manager: Option<&str>,
+) -> Result<cached::Return<DryRunResponse>> {
+    if client.is_none() {
+        return Err(anyhow!("kube::Client was not initialized properly"));
+    }
+
+    client
+        .unwrap()
+        .dry_run(object, operation, field_manager)
+        .await
+        .map(|value| cached::Return {
+            // dry-run results are never cached, the objects usually change
+            // from one request to the other
+            was_cached: false,
+            value,
+        })
+}
+
 pub(crate) async fn can_i(
     client: Option<&mut Client>,
     request: KWSubjectAccessReview,
@@ -166,21 +239,16 @@ pub(crate) async fn can_i(
         })
 }
 
-#[cached(
-    time = 5,
-    result = true,
-    // We can use the request type as key because cached requires the key to implement Hash + Eq
-    // traits. As we already implement these traits, there is no need to have a custom logic for key
-    // generation. If we do that, we will only convert it into a type (e.g. string)  that
-    // implements the traits as well. 
-    key = "KWSubjectAccessReview",
-    convert = r#"{request.clone()}"#,
-    sync_writes = "default",
-    with_cached_flag = true
-)]
 pub(crate) async fn can_i_cached(
     client: Option<&mut Client>,
+    cache: &Cache<KWSubjectAccessReview, SubjectAccessReviewStatus>,
     request: KWSubjectAccessReview,
 ) -> Result<cached::Return<SubjectAccessReviewStatus>> {
-    can_i(client, request).await
+    // The request is used as key: it already implements the Hash + Eq traits
+    // required by the cache
+    cache
+        .get_or_insert_with(request.clone(), || async {
+            can_i(client, request).await.map(|response| response.value)
+        })
+        .await
 }
@@ -1,7 +1,7 @@
 use anyhow::{anyhow, Result};
 use k8s_openapi::api::authorization::v1::{SubjectAccessReview, SubjectAccessReviewStatus};
 use kube::{
-    api::PostParams,
+    api::{Patch, PatchParams, PostParams},
     core::{DynamicObject, ObjectList},
     Api,
 };
@@ -9,7 +9,18 @@ use kubewarden_policy_sdk::host_capabilities::kubernetes::SubjectAccessReview as
 use std::{collections::HashMap, sync::Arc};
 use tokio::{sync::RwLock, time::Instant};
 
-use crate::callback_handler::kubernetes::{reflector::Reflector, ApiVersionKind, KubeResource};
+use crate::{
+    callback_handler::kubernetes::{
+        reflector::{self, Reflector},
+        ApiVersionKind, KubeResource,
+    },
+    callback_requests::KubernetesResourceChanges,
+    host_capabilities::kubernetes::{DryRunOperation, DryRunResponse},
+    policy_metadata::ResourceProjection,
+};
+
+/// The field manager used when a policy doesn't provide one
+const DEFAULT_DRY_RUN_FIELD_MANAGER: &str = "kubewarden";
 
 #[derive(Clone)]
 pub(crate) struct Client {
@@ -99,6 +110,7 @@ impl Client {
         namespace: Option<String>,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
     ) -> Result<kube::runtime::reflector::Store<kube::core::DynamicObject>> {
         let reader = {
             let reflectors = self.reflectors.read().await;
@@ -116,6 +128,7 @@ impl Client {
             namespace,
             label_selector,
             field_selector,
+            projection,
         )
         .await?;
         let reader = reflector.reader.clone();
@@ -135,6 +148,7 @@ impl Client {
         namespace: &str,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
     ) -> Result<ObjectList<kube::core::DynamicObject>> {
         let resource = self.build_kube_resource(api_version, kind).await?;
         if !resource.namespaced {
@@ -146,6 +160,7 @@ impl Client {
             Some(namespace.to_owned()),
             label_selector,
             field_selector,
+            projection,
         )
         .await
     }
@@ -156,11 +171,18 @@ impl Client {
         kind: &str,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
     ) -> Result<ObjectList<kube::core::DynamicObject>> {
         let resource = self.build_kube_resource(api_version, kind).await?;
 
-        self.list_resources_from_reflector(resource, None, label_selector, field_selector)
-            .await
+        self.list_resources_from_reflector(
+            resource,
+            None,
+            label_selector,
+            field_selector,
+            projection,
+        )
+        .await
     }
 
     pub async fn has_list_resources_all_result_changed_since_instant(
@@ -169,6 +191,7 @@ impl Client {
         kind: &str,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
         since: Instant,
     ) -> Result<bool> {
         let resource = self.build_kube_resource(api_version, kind).await?;
@@ -179,17 +202,45 @@ impl Client {
                 None,
                 label_selector,
                 field_selector,
+                projection.as_ref(),
                 since,
             )
             .await)
     }
 
+    pub async fn list_resources_all_changes_since(
+        &mut self,
+        api_version: &str,
+        kind: &str,
+        label_selector: Option<String>,
+        field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
+        since: Option<u64>,
+    ) -> Result<KubernetesResourceChanges> {
+        let resource = self.build_kube_resource(api_version, kind).await?;
+
+        let reflector_id = Reflector::compute_id(
+            &resource,
+            None,
+            label_selector.as_deref(),
+            field_selector.as_deref(),
+            projection.as_ref(),
+        );
+
+        let reflectors = self.reflectors.read().await;
+        Ok(match reflectors.get(&reflector_id) {
+            Some(reflector) => reflector.changes_since(since),
+            None => KubernetesResourceChanges::Resync,
+        })
+    }
+
     async fn list_resources_from_reflector(
         &mut self,
         resource: KubeResource,
         namespace: Option<String>,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
     ) -> Result<ObjectList<kube::core::DynamicObject>> {
         let api_version = resource.resource.api_version.clone();
         let kind = resource.resource.kind.clone();
@@ -199,6 +250,7 @@ impl Client {
             namespace.as_deref(),
             label_selector.as_deref(),
             field_selector.as_deref(),
+            projection.as_ref(),
         );
 
         let reader = self
@@ -208,6 +260,7 @@ impl Client {
                 namespace,
                 label_selector,
                 field_selector,
+                projection,
             )
             .await?;
 
@@ -232,6 +285,7 @@ impl Client {
         namespace: Option<String>,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<&ResourceProjection>,
         since: Instant,
     ) -> bool {
         let reflector_id = Reflector::compute_id(
@@ -239,6 +293,7 @@ impl Client {
             namespace.as_deref(),
             label_selector.as_deref(),
             field_selector.as_deref(),
+            projection,
         );
 
         let last_change_seen_at = {
@@ -258,6 +313,7 @@ impl Client {
         kind: &str,
         name: &str,
         namespace: Option<&str>,
+        projection: Option<&ResourceProjection>,
     ) -> Result<kube::core::DynamicObject> {
         let resource = self.build_kube_resource(api_version, kind).await?;
 
@@ -279,10 +335,20 @@ impl Client {
             ),
         };
 
-        api.get_opt(name)
-            .await
-            .map_err(anyhow::Error::new)?
-            .ok_or_else(|| anyhow!("Cannot find {api_version}/{kind} named '{name}' inside of namespace '{namespace:?}'"))
+        let obj = match projection {
+            Some(ResourceProjection::MetadataOnly) => api
+                .get_metadata_opt(name)
+                .await
+                .map_err(anyhow::Error::new)?
+                .map(|obj| reflector::object_from_metadata(obj, &resource.resource)),
+            _ => api.get_opt(name).await.map_err(anyhow::Error::new)?,
+        }
+        .ok_or_else(|| anyhow!("Cannot find {api_version}/{kind} named '{name}' inside of namespace '{namespace:?}'"))?;
+
+        Ok(match projection {
+            Some(projection) => projection.project(&obj),
+            None => obj,
+        })
     }
 
     pub async fn get_resource_plural_name(
@@ -294,6 +360,73 @@ impl Client {
         Ok(resource.resource.plural)
     }
 
+    /// Create or server-side apply the object in dry-run mode. The object
+    /// returned by the API Server is given back, together with the JSON patch
+    /// describing the changes done to the submitted object.
+    /// Namespaced objects must have their namespace set
+    pub async fn dry_run(
+        &mut self,
+        object: &serde_json::Value,
+        operation: DryRunOperation,
+        field_manager: Option<&str>,
+    ) -> Result<DryRunResponse> {
+        let obj: DynamicObject = serde_json::from_value(object.to_owned())
+            .map_err(|e| anyhow!("Cannot parse the object to dry-run: {e}"))?;
+        let types = obj
+            .types
+            .as_ref()
+            .ok_or_else(|| anyhow!("The object to dry-run has no apiVersion and kind"))?;
+        let resource = self
+            .build_kube_resource(&types.api_version, &types.kind)
+            .await?;
+
+        let api = match (resource.namespaced, obj.metadata.namespace.as_deref()) {
+            (true, Some(namespace)) => Api::<DynamicObject>::namespaced_with(
+                self.kube_client.clone(),
+                namespace,
+                &resource.resource,
+            ),
+            // Defaulting to the namespace of the client would dry-run the
+            // object somewhere else than where it's going to be created
+            (true, None) => {
+                return Err(anyhow!(
+                    "The {} object to dry-run is namespaced, but it has no namespace",
+                    types.kind
+                ))
+            }
+            (false, _) => {
+                Api::<DynamicObject>::all_with(self.kube_client.clone(), &resource.resource)
+            }
+        };
+        let field_manager = field_manager.unwrap_or(DEFAULT_DRY_RUN_FIELD_MANAGER);
+
+        let result = match operation {
+            DryRunOperation::Create => {
+                let params = PostParams {
+                    dry_run: true,
+                    field_manager: Some(field_manager.to_owned()),
+                };
+                api.create(&params, &obj).await
+            }
+            DryRunOperation::Apply => {
+                let name = obj
+                    .metadata
+                    .name
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("The object to apply has no name"))?;
+                let params = PatchParams::apply(field_manager).dry_run();
+                api.patch(name, &params, &Patch::Apply(&obj)).await
+            }
+        }
+        .map_err(anyhow::Error::new)?;
+
+        let patch = json_patch::diff(object, &serde_json::to_value(&result)?);
+        Ok(DryRunResponse {
+            object: result,
+            patch,
+        })
+    }
+
     pub async fn can_i(
         &mut self,
         request: KWSubjectAccessReview,
@@ -314,3 +447,147 @@ impl Client {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{http, Request, Response};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIResource, APIResourceList};
+    use kube::client::Body;
+    use rstest::rstest;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use tower_test::mock::Handle;
+
+    fn pod() -> serde_json::Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {"name": "nginx", "namespace": "default"},
+            "spec": {"containers": [{"name": "nginx", "image": "nginx"}]}
+        })
+    }
+
+    /// Answer the discovery request of the core API group
+    async fn serve_discovery(handle: &mut Handle<Request<Body>, Response<Body>>) {
+        let (request, send) = handle.next_request().await.expect("service not called");
+        assert_eq!(request.uri().path(), "/api/v1");
+        let resources = APIResourceList {
+            group_version: "v1".to_owned(),
+            resources: vec![APIResource {
+                name: "pods".to_owned(),
+                singular_name: "pod".to_owned(),
+                namespaced: true,
+                kind: "Pod".to_owned(),
+                ..Default::default()
+            }],
+        };
+        send.send_response(
+            Response::builder()
+                .body(Body::from(serde_json::to_vec(&resources).unwrap()))
+                .unwrap(),
+        );
+    }
+
+    /// Answer the discovery request, then the dry-run one by returning the
+    /// submitted object with some defaults applied
+    async fn dry_run_scenario(
+        mut handle: Handle<Request<Body>, Response<Body>>,
+        expected_method: http::Method,
+        expected_path: &'static str,
+    ) {
+        tokio::spawn(async move {
+            serve_discovery(&mut handle).await;
+
+            let (request, send) = handle.next_request().await.expect("service not called");
+            assert_eq!(request.method(), expected_method);
+            assert_eq!(request.uri().path(), expected_path);
+            let query: HashMap<String, String> =
+                url::form_urlencoded::parse(request.uri().query().unwrap_or_default().as_bytes())
+                    .into_owned()
+                    .collect();
+            assert_eq!(query.get("dryRun").map(String::as_str), Some("All"));
+            assert_eq!(
+                query.get("fieldManager").map(String::as_str),
+                Some("kubewarden")
+            );
+
+            let mut result = pod();
+            result["metadata"]["uid"] = json!("d3f4b1a2-0000-4000-8000-000000000000");
+            result["spec"]["restartPolicy"] = json!("Always");
+            send.send_response(
+                Response::builder()
+                    .body(Body::from(serde_json::to_vec(&result).unwrap()))
+                    .unwrap(),
+            );
+        });
+    }
+
+    #[rstest]
+    #[case::create(
+        DryRunOperation::Create,
+        http::Method::POST,
+        "/api/v1/namespaces/default/pods"
+    )]
+    #[case::apply(
+        DryRunOperation::Apply,
+        http::Method::PATCH,
+        "/api/v1/namespaces/default/pods/nginx"
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dry_run(
+        #[case] operation: DryRunOperation,
+        #[case] expected_method: http::Method,
+        #[case] expected_path: &'static str,
+    ) {
+        let (mocksvc, handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        dry_run_scenario(handle, expected_method, expected_path).await;
+        let mut client = Client::new(kube::Client::new(mocksvc, "kube-system"));
+
+        let response = client
+            .dry_run(&pod(), operation, None)
+            .await
+            .expect("dry-run failed");
+
+        assert_eq!(
+            response.object.metadata.namespace.as_deref(),
+            Some("default")
+        );
+        assert_eq!(
+            serde_json::to_value(&response.patch).unwrap(),
+            json!([
+                {"op": "add", "path": "/metadata/uid", "value": "d3f4b1a2-0000-4000-8000-000000000000"},
+                {"op": "add", "path": "/spec/restartPolicy", "value": "Always"},
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::create(DryRunOperation::Create)]
+    #[case::apply(DryRunOperation::Apply)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dry_run_namespaced_object_without_namespace(#[case] operation: DryRunOperation) {
+        let (mocksvc, mut handle) = tower_test::mock::pair::<Request<Body>, Response<Body>>();
+        tokio::spawn(async move {
+            serve_discovery(&mut handle).await;
+            // no other request must reach the API server
+            assert!(handle.next_request().await.is_none());
+        });
+        let mut client = Client::new(kube::Client::new(mocksvc, "default"));
+        let mut object = pod();
+        object["metadata"]
+            .as_object_mut()
+            .unwrap()
+            .remove("namespace");
+
+        let error = client
+            .dry_run(&object, operation, None)
+            .await
+            .expect_err("dry-run should have failed");
+
+        assert_eq!(
+            error.to_string(),
+            "The Pod object to dry-run is namespaced, but it has no namespace"
+        );
+    }
+}
@@ -1,40 +1,394 @@
 use anyhow::Result;
 use futures::{future::ready, Stream, StreamExt, TryStreamExt};
-use kube::{runtime::reflector::store, Resource};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ManagedFieldsEntry, OwnerReference};
 use kube::{
-    runtime::{reflector::store::Writer, watcher, WatchStreamExt},
+    api::ApiResource,
+    core::{DynamicObject, PartialObjectMeta, TypeMeta},
+    runtime::{
+        metadata_watcher,
+        reflector::{
+            store::{self, Writer},
+            ObjectRef, Store,
+        },
+        watcher, WatchStreamExt,
+    },
     ResourceExt,
 };
-use std::hash::Hash;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
 use tokio::{sync::watch, time::Instant};
 use tracing::{debug, info, warn};
 
-use crate::callback_handler::kubernetes::KubeResource;
+use crate::{
+    callback_handler::kubernetes::KubeResource,
+    callback_requests::{KubernetesResourceChange, KubernetesResourceChanges},
+    metrics::record_reflector_memory_footprint,
+    policy_metadata::ResourceProjection,
+};
+
+/// Maximum number of changes kept in memory by each change log. When this limit is
+/// reached the oldest changes are dropped, consumers asking for them have to
+/// perform a full resync.
+const MAX_TRACKED_CHANGES: usize = 4096;
+
+/// Namespace and name of an object
+type ObjectKey = (Option<String>, String);
+
+fn object_key(obj: &DynamicObject) -> ObjectKey {
+    (obj.namespace(), obj.name_any())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChangeKind {
+    Applied,
+    Deleted,
+}
+
+/// The list of changes seen by the watch of a reflector. Each change gets a
+/// sequence number, which consumers use to ask for the changes they have not seen yet.
+/// Only the keys of the changed objects are kept, their contents are taken from the
+/// store of the reflector when the changes are requested
+#[derive(Debug)]
+struct ChangeLog {
+    changes: VecDeque<(u64, ChangeKind, ObjectKey)>,
+    /// The sequence number of the next change
+    next_sequence: u64,
+    /// All the changes with a sequence number equal or greater than this one are
+    /// part of `changes`
+    complete_since: u64,
+    max_changes: usize,
+}
+
+impl ChangeLog {
+    fn new(max_changes: usize) -> Self {
+        ChangeLog {
+            changes: VecDeque::new(),
+            next_sequence: 0,
+            complete_since: 0,
+            max_changes,
+        }
+    }
+
+    fn record_event(&mut self, event: &watcher::Event<DynamicObject>) {
+        match event {
+            watcher::Event::Init | watcher::Event::InitApply(_) => self.changes.clear(),
+            watcher::Event::InitDone => {
+                self.changes.clear();
+                self.complete_since = self.next_sequence;
+            }
+            watcher::Event::Apply(obj) => self.push(ChangeKind::Applied, object_key(obj)),
+            watcher::Event::Delete(obj) => self.push(ChangeKind::Deleted, object_key(obj)),
+        }
+    }
+
+    fn push(&mut self, kind: ChangeKind, key: ObjectKey) {
+        if self.changes.len() >= self.max_changes {
+            if let Some((dropped, _, _)) = self.changes.pop_front() {
+                self.complete_since = dropped + 1;
+            }
+        }
+        self.changes.push_back((self.next_sequence, kind, key));
+        self.next_sequence += 1;
+    }
+
+    /// Get all the changes with a sequence number equal or greater than the given one.
+    /// When no sequence number is given, only the sequence number of the next change
+    /// is returned.
+    ///
+    /// Applied objects are reported with their current contents, objects that are no
+    /// longer part of the store are skipped: a later change reports their deletion
+    fn changes_since(
+        &self,
+        since: Option<u64>,
+        store: &Store<DynamicObject>,
+        resource: &ApiResource,
+    ) -> KubernetesResourceChanges {
+        let changes = match since {
+            None => Vec::new(),
+            Some(since) if since < self.complete_since => return KubernetesResourceChanges::Resync,
+            Some(since) => self
+                .changes
+                .iter()
+                .filter(|(sequence, _, _)| *sequence >= since)
+                .filter_map(|(_, kind, (namespace, name))| match kind {
+                    ChangeKind::Applied => {
+                        let mut obj_ref = ObjectRef::new_with(name, resource.clone());
+                        obj_ref.namespace = namespace.clone();
+                        store
+                            .get(&obj_ref)
+                            .map(|obj| KubernetesResourceChange::Applied(obj.as_ref().clone()))
+                    }
+                    ChangeKind::Deleted => {
+                        let mut obj = DynamicObject::new(name, resource);
+                        obj.metadata.namespace = namespace.clone();
+                        obj.data = serde_json::Value::Object(serde_json::Map::new());
+                        Some(KubernetesResourceChange::Deleted(obj))
+                    }
+                })
+                .collect(),
+        };
+        KubernetesResourceChanges::Changes {
+            changes,
+            next_sequence: self.next_sequence,
+        }
+    }
+}
+
+/// Tracks the changes seen by the watch of a reflector. Most reflectors are never
+/// asked for their changes, hence the change log is created only when a consumer
+/// asks for them the first time
+#[derive(Debug)]
+pub(crate) struct ChangeTracker {
+    /// Set while the watch is being (re)initialized
+    initializing: bool,
+    change_log: Option<ChangeLog>,
+    max_changes: usize,
+}
+
+impl ChangeTracker {
+    fn new(max_changes: usize) -> Self {
+        ChangeTracker {
+            initializing: false,
+            change_log: None,
+            max_changes,
+        }
+    }
+
+    fn record_event(&mut self, event: &watcher::Event<DynamicObject>) {
+        match event {
+            watcher::Event::Init | watcher::Event::InitApply(_) => self.initializing = true,
+            watcher::Event::InitDone => self.initializing = false,
+            watcher::Event::Apply(_) | watcher::Event::Delete(_) => {}
+        }
+        if let Some(change_log) = &mut self.change_log {
+            change_log.record_event(event);
+        }
+    }
+
+    /// Get the changes since the given sequence number, see `ChangeLog::changes_since`.
+    /// The change log is created by the first request: when it asks for the changes
+    /// since a given sequence number, a full resync is required
+    fn changes_since(
+        &mut self,
+        since: Option<u64>,
+        store: &Store<DynamicObject>,
+        resource: &ApiResource,
+    ) -> KubernetesResourceChanges {
+        if self.change_log.is_none() {
+            self.change_log = Some(ChangeLog::new(self.max_changes));
+            if since.is_some() {
+                return KubernetesResourceChanges::Resync;
+            }
+        }
+        if self.initializing {
+            return KubernetesResourceChanges::Resync;
+        }
+
+        self.change_log
+            .as_ref()
+            .expect("the change log has just been created")
+            .changes_since(since, store, resource)
+    }
+}
+
+/// Estimate of the memory used by the objects stored by a reflector, based on the
+/// strings and the JSON values they hold
+#[derive(Debug)]
+pub(crate) struct MemoryFootprint {
+    reflector_id: String,
+    projection: &'static str,
+    /// Size of each object, indexed by namespace and name
+    sizes: HashMap<ObjectKey, usize>,
+    /// The sizes of the objects seen while the watch is being (re)initialized
+    init_sizes: Option<HashMap<ObjectKey, usize>>,
+    total: usize,
+}
+
+impl MemoryFootprint {
+    fn new(reflector_id: String, projection: Option<&ResourceProjection>) -> Self {
+        MemoryFootprint {
+            reflector_id,
+            projection: projection.map_or("none", ResourceProjection::as_str),
+            sizes: HashMap::new(),
+            init_sizes: None,
+            total: 0,
+        }
+    }
+
+    fn record_event(&mut self, event: &watcher::Event<DynamicObject>) {
+        match event {
+            watcher::Event::Init => self.init_sizes = Some(HashMap::new()),
+            watcher::Event::InitApply(obj) => {
+                self.init_sizes
+                    .get_or_insert_with(HashMap::new)
+                    .insert(object_key(obj), Self::size(obj));
+            }
+            watcher::Event::InitDone => {
+                self.sizes = self.init_sizes.take().unwrap_or_default();
+                self.total = self.sizes.values().sum();
+            }
+            watcher::Event::Apply(obj) => {
+                let size = Self::size(obj);
+                let previous_size = self.sizes.insert(object_key(obj), size).unwrap_or_default();
+                self.total = self.total + size - previous_size;
+            }
+            watcher::Event::Delete(obj) => {
+                let previous_size = self.sizes.remove(&object_key(obj)).unwrap_or_default();
+                self.total -= previous_size;
+            }
+        }
+    }
+
+    /// A cheap estimate of the memory used by the object. It's computed on every
+    /// watch event, hence the object is walked without being serialized
+    fn size(obj: &DynamicObject) -> usize {
+        let metadata = &obj.metadata;
+        let strings = [
+            &metadata.name,
+            &metadata.namespace,
+            &metadata.generate_name,
+            &metadata.uid,
+            &metadata.resource_version,
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::len)
+        .sum::<usize>();
+        let maps = [&metadata.labels, &metadata.annotations]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|(key, value)| key.len() + value.len())
+            .sum::<usize>();
+        let finalizers = metadata
+            .finalizers
+            .iter()
+            .flatten()
+            .map(String::len)
+            .sum::<usize>();
+        let owner_references = metadata
+            .owner_references
+            .as_ref()
+            .map_or(0, |refs| refs.len() * std::mem::size_of::<OwnerReference>());
+        let managed_fields = metadata
+            .managed_fields
+            .iter()
+            .flatten()
+            .map(|entry| {
+                std::mem::size_of::<ManagedFieldsEntry>()
+                    + entry
+                        .fields_v1
+                        .as_ref()
+                        .map_or(0, |fields| json_value_size(&fields.0))
+            })
+            .sum::<usize>();
+
+        std::mem::size_of::<DynamicObject>()
+            + strings
+            + maps
+            + finalizers
+            + owner_references
+            + managed_fields
+            + json_value_size(&obj.data)
+    }
+
+    fn report(&self) {
+        record_reflector_memory_footprint(
+            &self.reflector_id,
+            self.projection,
+            self.total,
+            self.sizes.len(),
+        );
+    }
+}
+
+/// Estimate of the memory used by a JSON value
+fn json_value_size(value: &serde_json::Value) -> usize {
+    let children = match value {
+        serde_json::Value::Null | serde_json::Value::Bool(_) | serde_json::Value::Number(_) => 0,
+        serde_json::Value::String(value) => value.len(),
+        serde_json::Value::Array(values) => values.iter().map(json_value_size).sum(),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| key.len() + json_value_size(value))
+            .sum(),
+    };
+    std::mem::size_of::<serde_json::Value>() + children
+}
 
 /// Like `kube::runtime::reflector::reflector`, but also sends the time of the last change to a
-/// watch channel
-pub fn reflector_tracking_changes_instant<K, W>(
-    mut writer: store::Writer<K>,
+/// watch channel, records the changes inside of the given `ChangeTracker` and reports the memory
+/// footprint of the stored objects
+pub fn reflector_tracking_changes_instant<W>(
+    mut writer: store::Writer<DynamicObject>,
     stream: W,
     last_change_seen_at: watch::Sender<Instant>,
+    change_tracker: Arc<Mutex<ChangeTracker>>,
+    mut memory_footprint: MemoryFootprint,
 ) -> impl Stream<Item = W::Item>
 where
-    K: Resource + Clone,
-    K::DynamicType: Eq + Hash + Clone,
-    W: Stream<Item = watcher::Result<watcher::Event<K>>>,
+    W: Stream<Item = watcher::Result<watcher::Event<DynamicObject>>>,
 {
     stream.inspect_ok(move |event| {
+        // The store, the change log and the instant of the last change are updated
+        // while holding the lock: whoever reads the change log sees either all of
+        // them or none, and the instant is never older than a change it is about
+        let mut change_tracker = change_tracker
+            .lock()
+            .expect("cannot lock reflector change tracker");
+        writer.apply_watcher_event(event);
+        change_tracker.record_event(event);
         if let Err(err) = last_change_seen_at.send(Instant::now()) {
             warn!(error = ?err, "failed to set last_change_seen_at");
         }
-        writer.apply_watcher_event(event)
+        drop(change_tracker);
+
+        memory_footprint.record_event(event);
+        if !matches!(event, watcher::Event::Init | watcher::Event::InitApply(_)) {
+            memory_footprint.report();
+        }
     })
 }
 
+/// Convert the metadata of an object, as returned by the PartialObjectMetadata API, into
+/// a `DynamicObject` of the given resource
+pub(crate) fn object_from_metadata(
+    obj: PartialObjectMeta<DynamicObject>,
+    resource: &ApiResource,
+) -> DynamicObject {
+    DynamicObject {
+        types: Some(TypeMeta {
+            api_version: resource.api_version.clone(),
+            kind: resource.kind.clone(),
+        }),
+        metadata: obj.metadata,
+        data: serde_json::Value::Object(serde_json::Map::new()),
+    }
+}
+
+fn event_from_metadata(
+    event: watcher::Event<PartialObjectMeta<DynamicObject>>,
+    resource: &ApiResource,
+) -> watcher::Event<DynamicObject> {
+    match event {
+        watcher::Event::Apply(obj) => watcher::Event::Apply(object_from_metadata(obj, resource)),
+        watcher::Event::Delete(obj) => watcher::Event::Delete(object_from_metadata(obj, resource)),
+        watcher::Event::Init => watcher::Event::Init,
+        watcher::Event::InitApply(obj) => {
+            watcher::Event::InitApply(object_from_metadata(obj, resource))
+        }
+        watcher::Event::InitDone => watcher::Event::InitDone,
+    }
+}
+
 /// A reflector fetches kubernetes objects based on filtering criteria.
 /// When created, the list is populated slowly, to prevent hammering the Kubernetes API server.
 /// The items are stored in-memory. The `managedFields` attribute is stripped from all the objects
-/// to reduce memory consumption. All the other fields are retained.
+/// to reduce memory consumption. All the other fields are retained, unless a projection is
+/// given. When projecting to the metadata only, the objects are fetched using the
+/// PartialObjectMetadata API.
 /// A Kubernetes Watch is then created to keep the contents of the list updated.
 ///
 /// This is code relies heavily on the `kube::runtime::reflector` module.
@@ -52,6 +406,8 @@ pub(crate) struct Reflector {
     /// Read-only access to the data cached by the Reflector
     pub reader: kube::runtime::reflector::Store<kube::core::DynamicObject>,
     last_change_seen_at: watch::Receiver<Instant>,
+    change_tracker: Arc<Mutex<ChangeTracker>>,
+    resource: ApiResource,
 }
 
 impl Reflector {
@@ -62,9 +418,10 @@ impl Reflector {
         namespace: Option<&str>,
         label_selector: Option<&str>,
         field_selector: Option<&str>,
+        projection: Option<&ResourceProjection>,
     ) -> String {
         format!(
-            "{}|{}|{namespace:?}|{label_selector:?}|{field_selector:?}",
+            "{}|{}|{namespace:?}|{label_selector:?}|{field_selector:?}|{projection:?}",
             resource.resource.api_version, resource.resource.kind
         )
     }
@@ -77,6 +434,7 @@ impl Reflector {
         namespace: Option<String>,
         label_selector: Option<String>,
         field_selector: Option<String>,
+        projection: Option<ResourceProjection>,
     ) -> Result<Self> {
         let group = resource.resource.group.clone();
         let version = resource.resource.version.clone();
@@ -89,9 +447,19 @@ impl Reflector {
             ?namespace,
             ?label_selector,
             ?field_selector,
+            ?projection,
             "creating new reflector"
         );
 
+        let reflector_id = Self::compute_id(
+            &resource,
+            namespace.as_deref(),
+            label_selector.as_deref(),
+            field_selector.as_deref(),
+            projection.as_ref(),
+        );
+        let memory_footprint = MemoryFootprint::new(reflector_id, projection.as_ref());
+
         let api = match namespace {
             Some(ref ns) => kube::api::Api::<kube::core::DynamicObject>::namespaced_with(
                 kube_client,
@@ -104,7 +472,8 @@ impl Reflector {
             ),
         };
 
-        let writer = Writer::new(resource.resource);
+        let api_resource = resource.resource.clone();
+        let writer = Writer::new(api_resource.clone());
         let reader = writer.as_reader();
 
         let filter = watcher::Config {
@@ -112,20 +481,37 @@ impl Reflector {
             field_selector: field_selector.clone(),
             ..Default::default()
         };
-        let stream = watcher(api, filter).map_ok(|ev| {
+        let stream = match projection {
+            Some(ResourceProjection::MetadataOnly) => metadata_watcher(api, filter)
+                .map_ok(move |ev| event_from_metadata(ev, &resource.resource))
+                .boxed(),
+            _ => watcher(api, filter).boxed(),
+        };
+        let stream = stream.map_ok(move |ev| {
             ev.modify(|obj| {
                 // clear managed fields to reduce memory usage
                 obj.managed_fields_mut().clear();
                 // clear last-applied-configuration to reduce memory usage
                 obj.annotations_mut()
                     .remove("kubectl.kubernetes.io/last-applied-configuration");
+                if let Some(projection) = &projection {
+                    *obj = projection.project(obj);
+                }
             })
         });
 
         // this is a watch channel that tracks the last time the reflector saw a change
         let (updated_at_watch_tx, updated_at_watch_rx) = watch::channel(Instant::now());
 
-        let rf = reflector_tracking_changes_instant(writer, stream, updated_at_watch_tx);
+        let change_tracker = Arc::new(Mutex::new(ChangeTracker::new(MAX_TRACKED_CHANGES)));
+
+        let rf = reflector_tracking_changes_instant(
+            writer,
+            stream,
+            updated_at_watch_tx,
+            change_tracker.clone(),
+            memory_footprint,
+        );
 
         tokio::spawn(async move {
             let infinite_watch = rf.default_backoff().touched_objects().for_each(|obj| {
@@ -161,6 +547,8 @@ impl Reflector {
         Ok(Reflector {
             reader,
             last_change_seen_at: updated_at_watch_rx,
+            change_tracker,
+            resource: api_resource,
         })
     }
 
@@ -168,4 +556,288 @@ impl Reflector {
     pub async fn last_change_seen_at(&self) -> Instant {
         *self.last_change_seen_at.borrow()
     }
+
+    /// Get the changes seen by the reflector since the given sequence number
+    pub fn changes_since(&self, since: Option<u64>) -> KubernetesResourceChanges {
+        self.change_tracker
+            .lock()
+            .expect("cannot lock reflector change tracker")
+            .changes_since(since, &self.reader, &self.resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(name: &str) -> DynamicObject {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": { "name": name }
+        }))
+        .unwrap()
+    }
+
+    fn namespace_resource() -> ApiResource {
+        ApiResource::erase::<k8s_openapi::api::core::v1::Namespace>(&())
+    }
+
+    /// A reflector without the watch: the events are applied to its store and to its
+    /// change tracker like `reflector_tracking_changes_instant` does
+    struct TestReflector {
+        writer: Writer<DynamicObject>,
+        reader: Store<DynamicObject>,
+        change_tracker: ChangeTracker,
+    }
+
+    impl TestReflector {
+        fn new(max_changes: usize) -> Self {
+            let writer = Writer::new(namespace_resource());
+            let reader = writer.as_reader();
+            TestReflector {
+                writer,
+                reader,
+                change_tracker: ChangeTracker::new(max_changes),
+            }
+        }
+
+        fn apply(&mut self, event: watcher::Event<DynamicObject>) {
+            self.writer.apply_watcher_event(&event);
+            self.change_tracker.record_event(&event);
+        }
+
+        fn changes_since(&mut self, since: Option<u64>) -> KubernetesResourceChanges {
+            self.change_tracker
+                .changes_since(since, &self.reader, &namespace_resource())
+        }
+    }
+
+    #[test]
+    fn change_log_is_created_by_the_first_request() {
+        let mut reflector = TestReflector::new(10);
+        reflector.apply(watcher::Event::InitDone);
+        reflector.apply(watcher::Event::Apply(object("foo")));
+        assert!(reflector.change_tracker.change_log.is_none());
+
+        // the changes seen before the creation of the change log are lost
+        assert_eq!(
+            reflector.changes_since(Some(0)),
+            KubernetesResourceChanges::Resync
+        );
+        assert!(reflector.change_tracker.change_log.is_some());
+        assert_eq!(
+            reflector.changes_since(None),
+            KubernetesResourceChanges::Changes {
+                changes: vec![],
+                next_sequence: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn change_log_returns_changes_since_sequence() {
+        let mut reflector = TestReflector::new(10);
+        reflector.apply(watcher::Event::Init);
+        assert_eq!(
+            reflector.changes_since(None),
+            KubernetesResourceChanges::Resync
+        );
+        reflector.apply(watcher::Event::InitApply(object("bar")));
+        reflector.apply(watcher::Event::InitDone);
+
+        let start = match reflector.changes_since(None) {
+            KubernetesResourceChanges::Changes {
+                changes,
+                next_sequence,
+            } => {
+                assert!(changes.is_empty());
+                next_sequence
+            }
+            KubernetesResourceChanges::Resync => panic!("the change log is complete"),
+        };
+
+        reflector.apply(watcher::Event::Apply(object("foo")));
+        reflector.apply(watcher::Event::Delete(object("bar")));
+
+        assert_eq!(
+            reflector.changes_since(Some(start)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![
+                    KubernetesResourceChange::Applied(object("foo")),
+                    KubernetesResourceChange::Deleted(object("bar")),
+                ],
+                next_sequence: start + 2,
+            }
+        );
+        assert_eq!(
+            reflector.changes_since(Some(start + 1)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![KubernetesResourceChange::Deleted(object("bar"))],
+                next_sequence: start + 2,
+            }
+        );
+        assert_eq!(
+            reflector.changes_since(Some(start + 2)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![],
+                next_sequence: start + 2,
+            }
+        );
+
+        // objects deleted afterwards are only reported by their deletion
+        reflector.apply(watcher::Event::Delete(object("foo")));
+        assert_eq!(
+            reflector.changes_since(Some(start)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![
+                    KubernetesResourceChange::Deleted(object("bar")),
+                    KubernetesResourceChange::Deleted(object("foo")),
+                ],
+                next_sequence: start + 3,
+            }
+        );
+    }
+
+    #[test]
+    fn change_log_requires_resync() {
+        let mut reflector = TestReflector::new(1);
+        reflector.apply(watcher::Event::InitDone);
+        reflector.changes_since(None);
+
+        reflector.apply(watcher::Event::Apply(object("foo")));
+        reflector.apply(watcher::Event::Apply(object("bar")));
+
+        // the first change has been dropped
+        assert_eq!(
+            reflector.changes_since(Some(0)),
+            KubernetesResourceChanges::Resync
+        );
+        assert_eq!(
+            reflector.changes_since(Some(1)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![KubernetesResourceChange::Applied(object("bar"))],
+                next_sequence: 2,
+            }
+        );
+
+        // the watch has been restarted, the changes seen before are no longer valid
+        reflector.apply(watcher::Event::Init);
+        assert_eq!(
+            reflector.changes_since(Some(2)),
+            KubernetesResourceChanges::Resync
+        );
+        reflector.apply(watcher::Event::InitDone);
+        assert_eq!(
+            reflector.changes_since(Some(1)),
+            KubernetesResourceChanges::Resync
+        );
+        assert_eq!(
+            reflector.changes_since(Some(2)),
+            KubernetesResourceChanges::Changes {
+                changes: vec![],
+                next_sequence: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn change_log_does_not_lose_changes_recorded_while_reading() {
+        const EVENTS: usize = 1000;
+
+        let reflector = Arc::new(Mutex::new(TestReflector::new(EVENTS)));
+        {
+            let mut reflector = reflector.lock().unwrap();
+            reflector.apply(watcher::Event::InitDone);
+            reflector.changes_since(None);
+        }
+
+        let writer = {
+            let reflector = reflector.clone();
+            std::thread::spawn(move || {
+                for i in 0..EVENTS {
+                    reflector
+                        .lock()
+                        .unwrap()
+                        .apply(watcher::Event::Apply(object(&format!("obj-{i}"))));
+                    std::thread::yield_now();
+                }
+            })
+        };
+
+        // read the changes while they are being recorded, like the Gatekeeper
+        // inventory does, always asking for the ones after the last answer
+        let mut seen = Vec::new();
+        let mut since = 0;
+        loop {
+            let writer_done = writer.is_finished();
+            match reflector.lock().unwrap().changes_since(Some(since)) {
+                KubernetesResourceChanges::Changes {
+                    changes,
+                    next_sequence,
+                } => {
+                    seen.extend(changes.into_iter().map(|change| match change {
+                        KubernetesResourceChange::Applied(obj) => obj.name_any(),
+                        KubernetesResourceChange::Deleted(obj) => obj.name_any(),
+                    }));
+                    since = next_sequence;
+                }
+                KubernetesResourceChanges::Resync => panic!("the change log is complete"),
+            }
+            if writer_done {
+                break;
+            }
+            std::thread::yield_now();
+        }
+        writer.join().unwrap();
+
+        let expected: Vec<String> = (0..EVENTS).map(|i| format!("obj-{i}")).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn memory_footprint_estimates_object_size() {
+        let empty = MemoryFootprint::size(&object(""));
+        assert_eq!(MemoryFootprint::size(&object("foo")), empty + 3);
+
+        let obj: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "foo",
+                "labels": { "app": "nginx" }
+            },
+            "data": { "key": "value" }
+        }))
+        .unwrap();
+        let data = 2 * std::mem::size_of::<serde_json::Value>() + "key".len() + "value".len();
+        assert!(MemoryFootprint::size(&obj) >= empty + 3 + "app".len() + "nginx".len() + data);
+    }
+
+    #[test]
+    fn memory_footprint_tracks_stored_objects() {
+        let foo_size = MemoryFootprint::size(&object("foo"));
+        let bar_size = MemoryFootprint::size(&object("bar"));
+        let mut memory_footprint = MemoryFootprint::new("test".to_string(), None);
+
+        memory_footprint.record_event(&watcher::Event::Init);
+        memory_footprint.record_event(&watcher::Event::InitApply(object("foo")));
+        assert_eq!(memory_footprint.total, 0);
+        memory_footprint.record_event(&watcher::Event::InitDone);
+        assert_eq!(memory_footprint.total, foo_size);
+
+        memory_footprint.record_event(&watcher::Event::Apply(object("bar")));
+        memory_footprint.record_event(&watcher::Event::Apply(object("foo")));
+        assert_eq!(memory_footprint.total, foo_size + bar_size);
+        assert_eq!(memory_footprint.sizes.len(), 2);
+
+        memory_footprint.record_event(&watcher::Event::Delete(object("foo")));
+        assert_eq!(memory_footprint.total, bar_size);
+
+        // the watch has been restarted and no object is left
+        memory_footprint.record_event(&watcher::Event::Init);
+        memory_footprint.record_event(&watcher::Event::InitDone);
+        assert_eq!(memory_footprint.total, 0);
+        assert!(memory_footprint.sizes.is_empty());
+    }
 }
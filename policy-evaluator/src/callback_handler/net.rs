@@ -0,0 +1,433 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    name_server::TokioConnectionProvider,
+    Name, ResolveError, TokioResolver,
+};
+use ipnet::IpNet;
+
+use super::cache::Cache;
+use kubewarden_policy_sdk::host_capabilities::net::LookupResponse;
+
+use crate::host_capabilities::net::{
+    CidrLookupAddress, CidrLookupResponse, ReverseLookupResponse, SrvLookupResponse, SrvRecord,
+    TxtLookupResponse,
+};
+
+/// Default timeout of a DNS query
+pub const DEFAULT_DNS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Parse the address of a name server, given as `IP` or `IP:PORT`. The port
+/// defaults to 53
+pub fn parse_nameserver(address: &str) -> Result<SocketAddr> {
+    address
+        .parse::<SocketAddr>()
+        .or_else(|_| address.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| anyhow!("invalid name server '{address}', expected IP or IP:PORT"))
+}
+
+/// Helper struct to perform DNS queries
+pub(crate) struct Client {
+    /// The resolver is built eagerly, but a failure is reported only when a
+    /// query is performed: policies not doing DNS queries must keep working
+    /// even when the system configuration cannot be read
+    resolver: std::result::Result<TokioResolver, String>,
+}
+
+impl Client {
+    /// Create a new client. The name servers of the system are used when
+    /// `nameservers` is `None`
+    pub fn new(nameservers: Option<&[SocketAddr]>, timeout: Duration) -> Self {
+        let builder = match nameservers {
+            Some(nameservers) => {
+                let mut group = NameServerConfigGroup::new();
+                for nameserver in nameservers {
+                    group.merge(NameServerConfigGroup::from_ips_clear(
+                        &[nameserver.ip()],
+                        nameserver.port(),
+                        true,
+                    ));
+                }
+                Ok(TokioResolver::builder_with_config(
+                    ResolverConfig::from_parts(None, vec![], group),
+                    TokioConnectionProvider::default(),
+                ))
+            }
+            None => TokioResolver::builder_tokio(),
+        };
+
+        let resolver = builder
+            .map(|mut builder| {
+                let options: &mut ResolverOpts = builder.options_mut();
+                options.timeout = timeout;
+                // results are cached by the CallbackHandler
+                options.cache_size = 0;
+                builder.build()
+            })
+            .map_err(|e| format!("cannot configure the DNS resolver: {e}"));
+
+        Client { resolver }
+    }
+
+    fn resolver(&self) -> Result<&TokioResolver> {
+        self.resolver.as_ref().map_err(|e| anyhow!("{e}"))
+    }
+
+    /// Find the host names of the given address
+    pub async fn reverse_lookup(&self, ip: &str) -> Result<ReverseLookupResponse> {
+        let ip: IpAddr = ip
+            .parse()
+            .map_err(|e| anyhow!("invalid IP address {ip}: {e}"))?;
+        let hosts = match self.resolver()?.reverse_lookup(ip).await {
+            Ok(lookup) => lookup.iter().map(|ptr| host_name(&ptr.0)).collect(),
+            Err(e) if is_not_found(&e) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(ReverseLookupResponse { hosts })
+    }
+
+    /// Find the SRV records of the given name
+    pub async fn srv_lookup(&self, name: &str) -> Result<SrvLookupResponse> {
+        let records = match self.resolver()?.srv_lookup(name).await {
+            Ok(lookup) => lookup
+                .iter()
+                .map(|srv| SrvRecord {
+                    priority: srv.priority(),
+                    weight: srv.weight(),
+                    port: srv.port(),
+                    target: host_name(srv.target()),
+                })
+                .collect(),
+            Err(e) if is_not_found(&e) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(SrvLookupResponse { records })
+    }
+
+    /// Find the TXT records of the given name
+    pub async fn txt_lookup(&self, name: &str) -> Result<TxtLookupResponse> {
+        let records = match self.resolver()?.txt_lookup(name).await {
+            Ok(lookup) => lookup
+                .iter()
+                .map(|txt| {
+                    txt.txt_data()
+                        .iter()
+                        .map(|data| String::from_utf8_lossy(data))
+                        .collect::<String>()
+                })
+                .collect(),
+            Err(e) if is_not_found(&e) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(TxtLookupResponse { records })
+    }
+
+    /// Resolve the given host, failing when it has no address
+    pub async fn lookup_host(&self, host: &str) -> Result<LookupResponse> {
+        let ips = self.lookup_ip(host).await?;
+        if ips.is_empty() {
+            return Err(anyhow!("cannot resolve {host}: no address found"));
+        }
+
+        Ok(LookupResponse {
+            ips: ips.iter().map(IpAddr::to_string).collect(),
+        })
+    }
+
+    /// Resolve the given host, an IP address is returned as it is
+    pub async fn lookup_ip(&self, host: &str) -> Result<Vec<IpAddr>> {
+        match self.resolver()?.lookup_ip(host).await {
+            Ok(lookup) => Ok(lookup.iter().collect()),
+            Err(e) if is_not_found(&e) => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A missing domain, or a domain without records of the requested type, is
+/// not an error: an empty response is returned
+fn is_not_found(error: &ResolveError) -> bool {
+    error.is_no_records_found() || error.is_nx_domain()
+}
+
+fn host_name(name: &Name) -> String {
+    name.to_utf8().trim_end_matches('.').to_owned()
+}
+
+/// Match the given addresses against the given networks
+pub(crate) fn match_cidrs(ips: &[IpAddr], cidrs: &[String]) -> Result<CidrLookupResponse> {
+    let networks = cidrs
+        .iter()
+        .map(|cidr| {
+            cidr.parse::<IpNet>()
+                .map(|net| (cidr, net))
+                .map_err(|e| anyhow!("invalid CIDR {cidr}: {e}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CidrLookupResponse {
+        addresses: ips
+            .iter()
+            .map(|ip| CidrLookupAddress {
+                ip: ip.to_string(),
+                cidrs: networks
+                    .iter()
+                    .filter(|(_, net)| net.contains(ip))
+                    .map(|(cidr, _)| cidr.to_string())
+                    .collect(),
+            })
+            .collect(),
+    })
+}
+
+/// The results of the host lookups are not cached, the policies relying on
+/// them expect fresh answers
+pub(crate) async fn lookup_host(
+    client: &Client,
+    host: &str,
+) -> Result<cached::Return<LookupResponse>> {
+    client.lookup_host(host).await.map(cached::Return::new)
+}
+
+pub(crate) async fn get_reverse_lookup_cached(
+    client: &Client,
+    cache: &Cache<String, ReverseLookupResponse>,
+    ip: &str,
+) -> Result<cached::Return<ReverseLookupResponse>> {
+    cache
+        .get_or_insert_with(ip.to_owned(), || client.reverse_lookup(ip))
+        .await
+}
+
+pub(crate) async fn get_srv_lookup_cached(
+    client: &Client,
+    cache: &Cache<String, SrvLookupResponse>,
+    name: &str,
+) -> Result<cached::Return<SrvLookupResponse>> {
+    cache
+        .get_or_insert_with(name.to_owned(), || client.srv_lookup(name))
+        .await
+}
+
+pub(crate) async fn get_txt_lookup_cached(
+    client: &Client,
+    cache: &Cache<String, TxtLookupResponse>,
+    name: &str,
+) -> Result<cached::Return<TxtLookupResponse>> {
+    cache
+        .get_or_insert_with(name.to_owned(), || client.txt_lookup(name))
+        .await
+}
+
+pub(crate) async fn get_cidr_lookup_cached(
+    client: &Client,
+    cache: &Cache<String, Vec<IpAddr>>,
+    host: &str,
+    cidrs: &[String],
+) -> Result<cached::Return<CidrLookupResponse>> {
+    let ips = cache
+        .get_or_insert_with(host.to_owned(), || client.lookup_ip(host))
+        .await?;
+    let response = match_cidrs(&ips.value, cidrs)?;
+
+    let mut ret = cached::Return::new(response);
+    ret.was_cached = ips.was_cached;
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::{
+        op::{Message, MessageType, ResponseCode},
+        rr::{
+            rdata::{PTR, SRV, TXT},
+            RData, Record, RecordType,
+        },
+    };
+    use rstest::rstest;
+    use std::str::FromStr;
+    use tokio::net::UdpSocket;
+
+    /// Start a DNS server answering with fixed records, returns its address
+    async fn stub_resolver() -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                let request = Message::from_vec(&buf[..len]).unwrap();
+                let query = request.queries()[0].clone();
+                let name = query.name().clone();
+
+                let mut response = Message::new();
+                response
+                    .set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_op_code(request.op_code())
+                    .set_recursion_desired(true)
+                    .set_recursion_available(true)
+                    .add_query(query.clone());
+
+                let rdata = match (name.to_utf8().as_str(), query.query_type()) {
+                    ("ownership.example.com.", RecordType::TXT) => {
+                        Some(RData::TXT(TXT::new(vec![
+                            "kubewarden-".to_owned(),
+                            "team-a".to_owned(),
+                        ])))
+                    }
+                    ("_ldap._tcp.example.com.", RecordType::SRV) => Some(RData::SRV(SRV::new(
+                        10,
+                        5,
+                        389,
+                        Name::from_str("ldap.example.com.").unwrap(),
+                    ))),
+                    ("4.3.2.10.in-addr.arpa.", RecordType::PTR) => Some(RData::PTR(PTR(
+                        Name::from_str("host.example.com.").unwrap(),
+                    ))),
+                    ("external.example.com.", RecordType::A) => {
+                        Some(RData::A("10.2.3.4".parse().unwrap()))
+                    }
+                    _ => None,
+                };
+                match rdata {
+                    Some(rdata) => {
+                        response.add_answer(Record::from_rdata(name, 60, rdata));
+                    }
+                    None => {
+                        response.set_response_code(ResponseCode::NXDomain);
+                    }
+                }
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn lookup_host() {
+        let client = Client::new(Some(&[stub_resolver().await]), DEFAULT_DNS_TIMEOUT);
+
+        let response = client.lookup_host("external.example.com").await.unwrap();
+        assert_eq!(response.ips, vec!["10.2.3.4".to_owned()]);
+
+        assert!(client.lookup_host("missing.example.com").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn txt_lookup() {
+        let client = Client::new(Some(&[stub_resolver().await]), DEFAULT_DNS_TIMEOUT);
+
+        let response = client.txt_lookup("ownership.example.com").await.unwrap();
+        assert_eq!(response.records, vec!["kubewarden-team-a".to_owned()]);
+
+        let response = client.txt_lookup("missing.example.com").await.unwrap();
+        assert!(response.records.is_empty());
+    }
+
+    #[tokio::test]
+    async fn srv_lookup() {
+        let client = Client::new(Some(&[stub_resolver().await]), DEFAULT_DNS_TIMEOUT);
+
+        let response = client.srv_lookup("_ldap._tcp.example.com").await.unwrap();
+        assert_eq!(
+            response.records,
+            vec![SrvRecord {
+                priority: 10,
+                weight: 5,
+                port: 389,
+                target: "ldap.example.com".to_owned(),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn reverse_lookup() {
+        let client = Client::new(Some(&[stub_resolver().await]), DEFAULT_DNS_TIMEOUT);
+
+        let response = client.reverse_lookup("10.2.3.4").await.unwrap();
+        assert_eq!(response.hosts, vec!["host.example.com".to_owned()]);
+
+        assert!(client.reverse_lookup("not-an-ip").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cidr_lookup_is_cached() {
+        let client = Client::new(Some(&[stub_resolver().await]), DEFAULT_DNS_TIMEOUT);
+        let cache = Cache::new(
+            super::super::CachedCapability::Net,
+            super::super::CacheConfig::default_for(super::super::CachedCapability::Net),
+        );
+        let cidrs = vec!["10.0.0.0/8".to_owned(), "192.168.0.0/16".to_owned()];
+
+        let response = get_cidr_lookup_cached(&client, &cache, "external.example.com", &cidrs)
+            .await
+            .unwrap();
+        assert!(!response.was_cached);
+        assert_eq!(
+            response.value.addresses,
+            vec![CidrLookupAddress {
+                ip: "10.2.3.4".to_owned(),
+                cidrs: vec!["10.0.0.0/8".to_owned()],
+            }]
+        );
+
+        let response = get_cidr_lookup_cached(&client, &cache, "external.example.com", &cidrs)
+            .await
+            .unwrap();
+        assert!(response.was_cached);
+    }
+
+    #[rstest]
+    #[case::ipv4("192.168.1.10", vec!["192.168.0.0/16", "10.0.0.0/8"], vec!["192.168.0.0/16"])]
+    #[case::ipv6("fd00::1", vec!["fd00::/8", "10.0.0.0/8"], vec!["fd00::/8"])]
+    #[case::no_match("8.8.8.8", vec!["192.168.0.0/16"], vec![])]
+    fn cidr_matching(#[case] ip: &str, #[case] cidrs: Vec<&str>, #[case] expected: Vec<&str>) {
+        let cidrs: Vec<String> = cidrs.into_iter().map(str::to_owned).collect();
+        let response = match_cidrs(&[ip.parse().unwrap()], &cidrs).unwrap();
+        assert_eq!(
+            response.addresses,
+            vec![CidrLookupAddress {
+                ip: ip.to_owned(),
+                cidrs: expected.into_iter().map(str::to_owned).collect(),
+            }]
+        );
+    }
+
+    #[rstest]
+    #[case::ipv4("10.0.0.10", "10.0.0.10:53")]
+    #[case::ipv4_with_port("10.0.0.10:5353", "10.0.0.10:5353")]
+    #[case::ipv6("fd00::1", "[fd00::1]:53")]
+    #[case::ipv6_with_port("[fd00::1]:5353", "[fd00::1]:5353")]
+    fn nameserver_address(#[case] address: &str, #[case] expected: &str) {
+        assert_eq!(
+            parse_nameserver(address).unwrap(),
+            expected.parse::<SocketAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_nameserver_address() {
+        assert!(parse_nameserver("dns.example.com").is_err());
+    }
+
+    #[test]
+    fn invalid_cidr() {
+        assert!(match_cidrs(&[], &["10.0.0.0/33".to_owned()]).is_err());
+    }
+}
@@ -1,7 +1,5 @@
-use std::time::Duration;
-
 use anyhow::Result;
-use cached::proc_macro::cached;
+use base64::{engine::general_purpose, Engine as _};
 use kubewarden_policy_sdk::host_capabilities::oci::ManifestDigestResponse;
 use policy_fetcher::{
     oci_client::{
@@ -13,6 +11,9 @@ use policy_fetcher::{
 };
 use serde::{Deserialize, Serialize};
 
+use super::cache::Cache;
+use crate::host_capabilities::oci::{ReferrerBlobResponse, ReferrersResponse};
+
 /// Helper struct to interact with an OCI registry
 pub(crate) struct Client {
     sources: Option<Sources>,
@@ -75,6 +76,49 @@ impl Client {
             config,
         })
     }
+
+    /// Fetch the artifacts referencing the OCI resource referenced via `image`
+    pub async fn referrers(
+        &self,
+        image: &str,
+        artifact_type: Option<&str>,
+    ) -> Result<ReferrersResponse> {
+        let image_ref: Reference = image.parse()?;
+        let image_with_proto = format!("registry://{}", image_ref.whole());
+        let referrers = self
+            .registry
+            .referrers(&image_with_proto, artifact_type, self.sources.as_ref())
+            .await?;
+        Ok(ReferrersResponse { referrers })
+    }
+
+    /// Fetch a layer of the referrer artifact identified by `digest`, which
+    /// lives in the same repository of `image`
+    pub async fn referrer_blob(
+        &self,
+        image: &str,
+        digest: &str,
+        media_type: Option<&str>,
+        max_size: u64,
+    ) -> Result<ReferrerBlobResponse> {
+        let image_ref: Reference = image.parse()?;
+        let image_with_proto = format!("registry://{}", image_ref.whole());
+        let blob = self
+            .registry
+            .referrer_blob(
+                &image_with_proto,
+                digest,
+                media_type,
+                max_size,
+                self.sources.as_ref(),
+            )
+            .await?;
+        Ok(ReferrerBlobResponse {
+            media_type: blob.media_type,
+            digest: blob.digest,
+            data: general_purpose::STANDARD.encode(blob.data),
+        })
+    }
 }
 
 // Interacting with a remote OCI registry is time expensive, this can cause a massive slow down
@@ -84,25 +128,20 @@ impl Client {
 // Details about this cache:
 //   * only the image "url" is used as key. oci::Client is not hashable, plus
 //     the client is always the same
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}", img) }"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
 pub(crate) async fn get_oci_digest_cached(
     oci_client: &Client,
+    cache: &Cache<String, ManifestDigestResponse>,
     img: &str,
 ) -> Result<cached::Return<ManifestDigestResponse>> {
-    oci_client
-        .digest(img)
+    cache
+        .get_or_insert_with(img.to_owned(), || async {
+            oci_client
+                .digest(img)
+                .await
+                .map(|digest| ManifestDigestResponse { digest })
+        })
         .await
-        .map(|digest| ManifestDigestResponse { digest })
-        .map(cached::Return::new)
 }
 
 // Interacting with a remote OCI registry is time expensive, this can cause a massive slow down
@@ -112,37 +151,41 @@ pub(crate) async fn get_oci_digest_cached(
 // Details about this cache:
 //   * only the image "url" is used as key. oci::Client is not hashable, plus
 //     the client is always the same
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}", img) }"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
 pub(crate) async fn get_oci_manifest_cached(
     oci_client: &Client,
+    cache: &Cache<String, OciManifest>,
     img: &str,
 ) -> Result<cached::Return<OciManifest>> {
-    oci_client.manifest(img).await.map(cached::Return::new)
+    cache
+        .get_or_insert_with(img.to_owned(), || oci_client.manifest(img))
+        .await
 }
 
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}", img) }"#,
-    with_cached_flag = true
-)]
 pub(crate) async fn get_oci_manifest_and_config_cached(
     oci_client: &Client,
+    cache: &Cache<String, ManifestAndConfigResponse>,
     img: &str,
 ) -> Result<cached::Return<ManifestAndConfigResponse>> {
-    oci_client
-        .manifest_and_config(img)
+    cache
+        .get_or_insert_with(img.to_owned(), || oci_client.manifest_and_config(img))
+        .await
+}
+
+// The referrers of an image change over time, new attestations can be pushed
+// at any moment. They are cached like the other OCI lookups, using the image
+// "url" and the artifact type as key.
+//
+// The referrer blobs are not cached: they can be quite big.
+pub(crate) async fn get_oci_referrers_cached(
+    oci_client: &Client,
+    cache: &Cache<String, ReferrersResponse>,
+    img: &str,
+    artifact_type: Option<&str>,
+) -> Result<cached::Return<ReferrersResponse>> {
+    cache
+        .get_or_insert_with(format!("{img}{artifact_type:?}"), || {
+            oci_client.referrers(img, artifact_type)
+        })
         .await
-        .map(cached::Return::new)
 }
@@ -1,7 +1,6 @@
-use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::{anyhow, Result};
-use cached::proc_macro::cached;
 use itertools::Itertools;
 use kubewarden_policy_sdk::host_capabilities::verification::{
     KeylessInfo, KeylessPrefixInfo, VerificationResponse,
@@ -24,6 +23,8 @@ use sigstore::{
 use tokio::sync::Mutex;
 use tracing::warn;
 
+use super::cache::Cache;
+
 #[derive(Clone)]
 pub(crate) struct Client {
     cosign_client: Arc<Mutex<sigstore::cosign::Client>>,
@@ -258,26 +259,22 @@ impl Client {
 // Because of that we will keep a cache of the digests results.
 //
 // Details about this cache:
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}{:?}{:?}", image, pub_keys, annotations)}"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
+//   * all the kinds of verification share the same cache, the key is
+//     prefixed with the kind of verification
 pub(crate) async fn get_sigstore_pub_key_verification_cached(
     client: &mut Client,
+    cache: &Cache<String, VerificationResponse>,
     image: String,
     pub_keys: Vec<String>,
     annotations: Option<BTreeMap<String, String>>,
 ) -> Result<cached::Return<VerificationResponse>> {
-    client
-        .verify_public_key(image, pub_keys, annotations)
+    let key = format!("pub_key:{image}{pub_keys:?}{annotations:?}");
+    cache
+        .get_or_insert_with(key, || {
+            client.verify_public_key(image, pub_keys, annotations)
+        })
         .await
-        .map(cached::Return::new)
 }
 
 // Sigstore verifications are time expensive, this can cause a massive slow down
@@ -285,26 +282,20 @@ pub(crate) async fn get_sigstore_pub_key_verification_cached(
 // Because of that we will keep a cache of the digests results.
 //
 // Details about this cache:
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}{:?}{:?}", image, keyless, annotations)}"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
+//   * all the kinds of verification share the same cache, the key is
+//     prefixed with the kind of verification
 pub(crate) async fn get_sigstore_keyless_verification_cached(
     client: &mut Client,
+    cache: &Cache<String, VerificationResponse>,
     image: String,
     keyless: Vec<KeylessInfo>,
     annotations: Option<BTreeMap<String, String>>,
 ) -> Result<cached::Return<VerificationResponse>> {
-    client
-        .verify_keyless(image, keyless, annotations)
+    let key = format!("keyless:{image}{keyless:?}{annotations:?}");
+    cache
+        .get_or_insert_with(key, || client.verify_keyless(image, keyless, annotations))
         .await
-        .map(cached::Return::new)
 }
 
 // Sigstore verifications are time expensive, this can cause a massive slow down
@@ -312,26 +303,22 @@ pub(crate) async fn get_sigstore_keyless_verification_cached(
 // Because of that we will keep a cache of the digests results.
 //
 // Details about this cache:
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}{:?}{:?}", image, keyless_prefix, annotations)}"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
+//   * all the kinds of verification share the same cache, the key is
+//     prefixed with the kind of verification
 pub(crate) async fn get_sigstore_keyless_prefix_verification_cached(
     client: &mut Client,
+    cache: &Cache<String, VerificationResponse>,
     image: String,
     keyless_prefix: Vec<KeylessPrefixInfo>,
     annotations: Option<BTreeMap<String, String>>,
 ) -> Result<cached::Return<VerificationResponse>> {
-    client
-        .verify_keyless_prefix(image, keyless_prefix, annotations)
+    let key = format!("keyless_prefix:{image}{keyless_prefix:?}{annotations:?}");
+    cache
+        .get_or_insert_with(key, || {
+            client.verify_keyless_prefix(image, keyless_prefix, annotations)
+        })
         .await
-        .map(cached::Return::new)
 }
 
 // Sigstore verifications are time expensive, this can cause a massive slow down
@@ -339,27 +326,23 @@ pub(crate) async fn get_sigstore_keyless_prefix_verification_cached(
 // Because of that we will keep a cache of the digests results.
 //
 // Details about this cache:
-//   * the cache is time bound: cached values are purged after 60 seconds
-//   * only successful results are cached
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}{:?}{:?}{:?}", image, owner, repo, annotations)}"#,
-    with_cached_flag = true
-)]
+//   * the cache is time and size bound, see `CacheConfig`
+//   * all the kinds of verification share the same cache, the key is
+//     prefixed with the kind of verification
 pub(crate) async fn get_sigstore_github_actions_verification_cached(
     client: &mut Client,
+    cache: &Cache<String, VerificationResponse>,
     image: String,
     owner: String,
     repo: Option<String>,
     annotations: Option<BTreeMap<String, String>>,
 ) -> Result<cached::Return<VerificationResponse>> {
-    client
-        .verify_github_actions(image, owner, repo, annotations)
+    let key = format!("github_actions:{image}{owner:?}{repo:?}{annotations:?}");
+    cache
+        .get_or_insert_with(key, || {
+            client.verify_github_actions(image, owner, repo, annotations)
+        })
         .await
-        .map(cached::Return::new)
 }
 
 fn get_sigstore_certificate_verification_cache_key(
@@ -397,30 +380,34 @@ fn get_sigstore_certificate_verification_cache_key(
     format!("{:x}", hasher.finalize())
 }
 
-#[cached(
-    time = 60,
-    result = true,
-    sync_writes = "default",
-    key = "String",
-    convert = r#"{ format!("{}", get_sigstore_certificate_verification_cache_key(image, certificate, certificate_chain, require_rekor_bundle, annotations.as_ref()))}"#,
-    with_cached_flag = true
-)]
 pub(crate) async fn get_sigstore_certificate_verification_cached(
     client: &mut Client,
+    cache: &Cache<String, VerificationResponse>,
     image: &str,
     certificate: &[u8],
     certificate_chain: Option<&[Vec<u8>]>,
     require_rekor_bundle: bool,
     annotations: Option<BTreeMap<String, String>>,
 ) -> Result<cached::Return<VerificationResponse>> {
-    client
-        .verify_certificate(
+    let key = format!(
+        "certificate:{}",
+        get_sigstore_certificate_verification_cache_key(
             image,
             certificate,
             certificate_chain,
             require_rekor_bundle,
-            annotations,
+            annotations.as_ref(),
         )
+    );
+    cache
+        .get_or_insert_with(key, || {
+            client.verify_certificate(
+                image,
+                certificate,
+                certificate_chain,
+                require_rekor_bundle,
+                annotations,
+            )
+        })
         .await
-        .map(cached::Return::new)
 }
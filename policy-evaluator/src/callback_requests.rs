@@ -9,6 +9,12 @@ use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use tokio::{sync::oneshot, time::Instant};
 
+use crate::host_capabilities::kubernetes::{DryRunOperation, DryRunRequest};
+use crate::host_capabilities::oci::{
+    AttestationVerificationRequest, ReferrerBlobRequest, ReferrersRequest, MAX_REFERRER_BLOB_SIZE,
+};
+use crate::policy_metadata::ResourceProjection;
+
 /// Holds the response to a waPC evaluation request
 #[derive(Debug, Clone)]
 pub struct CallbackResponse {
@@ -16,6 +22,34 @@ pub struct CallbackResponse {
     pub payload: Vec<u8>,
 }
 
+/// A change to a Kubernetes object, as seen by the watch of a reflector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KubernetesResourceChange {
+    /// The object has been created or updated
+    Applied(kube::core::DynamicObject),
+    /// The object has been deleted
+    Deleted(kube::core::DynamicObject),
+}
+
+/// The changes that happened to a list of Kubernetes objects since a given position
+/// of the change log of the reflector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KubernetesResourceChanges {
+    /// Not all the changes are known, for example because the watch has been
+    /// restarted. The whole list of objects has to be fetched again
+    Resync,
+    #[serde(rename_all = "camelCase")]
+    Changes {
+        /// The changes, sorted from the oldest to the newest one
+        changes: Vec<KubernetesResourceChange>,
+        /// The sequence number the next change will get. All the changes recorded
+        /// before answering have a smaller one, this is the value to ask for next time
+        next_sequence: u64,
+    },
+}
+
 /// A request sent by some synchronous code (usually waPC's host_callback)
 /// that can be evaluated only inside of asynchronous code.
 #[derive(Debug)]
@@ -51,6 +85,27 @@ pub enum CallbackRequestType {
         image: String,
     },
 
+    /// Require the list of the artifacts referencing an OCI object, like SBOMs
+    /// or attestations
+    OciReferrers {
+        /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+        image: String,
+        /// Return only the referrers of the given artifact type
+        artifact_type: Option<String>,
+    },
+
+    /// Require the contents of a layer of an artifact referencing an OCI object
+    OciReferrerBlob {
+        /// String pointing to the object referenced by the artifact
+        image: String,
+        /// Digest of the referrer manifest
+        digest: String,
+        /// Media type of the layer, the first one is used when not set
+        media_type: Option<String>,
+        /// Maximum size of the layer, in bytes
+        max_size: u64,
+    },
+
     /// Require the verification of the manifest digest of an OCI object (be
     /// it an image or anything else that can be stored into an OCI registry)
     /// to be signed by Sigstore, using public keys mode
@@ -121,9 +176,29 @@ pub enum CallbackRequestType {
         annotations: Option<BTreeMap<String, String>>,
     },
 
+    /// Require the verification of the in-toto attestations attached by
+    /// Sigstore to an OCI object. The predicates of the verified attestations
+    /// are returned
+    SigstoreAttestationVerify {
+        request: AttestationVerificationRequest,
+    },
+
     /// Lookup the addresses for a given hostname via DNS
     DNSLookupHost { host: String },
 
+    /// Lookup the host names of the given IP address via DNS
+    DNSReverseLookup { ip: String },
+
+    /// Lookup the SRV records of the given name via DNS
+    DNSLookupSrv { name: String },
+
+    /// Lookup the TXT records of the given name via DNS
+    DNSLookupTxt { name: String },
+
+    /// Resolve the given host via DNS and find which of the given networks
+    /// contain its addresses
+    DNSLookupCidr { host: String, cidrs: Vec<String> },
+
     /// Get all the Kubernetes resources defined inside of the given
     /// namespace
     /// Note: cannot be used with cluster-wide resources
@@ -140,6 +215,9 @@ pub enum CallbackRequestType {
         /// A selector to restrict the list of returned objects by their fields.
         /// Defaults to everything if `None`
         field_selector: Option<String>,
+        /// The projection applied to the objects. All the fields are returned
+        /// when `None`
+        projection: Option<ResourceProjection>,
     },
 
     /// Get all the Kubernetes resources defined inside of the given
@@ -156,6 +234,9 @@ pub enum CallbackRequestType {
         /// A selector to restrict the list of returned objects by their fields.
         /// Defaults to everything if `None`
         field_selector: Option<String>,
+        /// The projection applied to the objects. All the fields are returned
+        /// when `None`
+        projection: Option<ResourceProjection>,
     },
 
     /// Get a Kubernetes resource with the specified `name`.
@@ -170,6 +251,9 @@ pub enum CallbackRequestType {
         /// The namespace used to search namespaced resources. Cluster level resources
         /// must set this parameter to `None`
         namespace: Option<String>,
+        /// The projection applied to the objects. All the fields are returned
+        /// when `None`
+        projection: Option<ResourceProjection>,
 
         /// Disable caching of results obtained from Kubernetes API Server
         /// By default query results are cached for 5 seconds, that might cause
@@ -199,11 +283,33 @@ pub enum CallbackRequestType {
         /// A selector to restrict the list of returned objects by their fields.
         /// Defaults to everything if `None`
         field_selector: Option<String>,
+        /// The projection applied by the reflector tracking this query
+        projection: Option<ResourceProjection>,
         /// The instant in time to compare the last change of the resources
         #[serde(with = "tokio_instant_serializer")]
         since: Instant,
     },
 
+    /// Get the changes seen by the reflector tracking this query since the given sequence
+    /// number. The response is a `KubernetesResourceChanges` object
+    KubernetesListResourceAllChangesSince {
+        /// apiVersion of the resource (v1 for core group, groupName/groupVersions for other).
+        api_version: String,
+        /// Singular PascalCase name of the resource
+        kind: String,
+        /// A selector to restrict the list of returned objects by their labels.
+        /// Defaults to everything if `None`
+        label_selector: Option<String>,
+        /// A selector to restrict the list of returned objects by their fields.
+        /// Defaults to everything if `None`
+        field_selector: Option<String>,
+        /// The projection applied by the reflector tracking this query
+        projection: Option<ResourceProjection>,
+        /// Only the changes with a sequence number equal or greater than this one are
+        /// returned. When `None`, no change is returned, only the current sequence number
+        since: Option<u64>,
+    },
+
     /// Check if the user can permissions to perform some operations
     KubernetesCanI {
         /// Describe the set of parameters used by the `can_i` function. The values in this struct
@@ -221,6 +327,18 @@ pub enum CallbackRequestType {
         /// the value here to keep the same pattern used by the other Kubernetes requests
         disable_cache: bool,
     },
+
+    /// Create or server-side apply an object in dry-run mode: the Kubernetes
+    /// API Server runs defaulting and admission, but the object is not persisted.
+    /// The response is a `DryRunResponse` object
+    KubernetesDryRun {
+        /// The object to create or apply
+        object: serde_json::Value,
+        /// The write operation to simulate
+        operation: DryRunOperation,
+        /// The field manager used when applying the object
+        field_manager: Option<String>,
+    },
 }
 mod tokio_instant_serializer {
     use serde::de::Error;
@@ -249,6 +367,35 @@ mod tokio_instant_serializer {
     }
 }
 
+impl From<ReferrersRequest> for CallbackRequestType {
+    fn from(req: ReferrersRequest) -> Self {
+        CallbackRequestType::OciReferrers {
+            image: req.image,
+            artifact_type: req.artifact_type,
+        }
+    }
+}
+
+impl From<ReferrerBlobRequest> for CallbackRequestType {
+    fn from(req: ReferrerBlobRequest) -> Self {
+        CallbackRequestType::OciReferrerBlob {
+            image: req.image,
+            digest: req.digest,
+            media_type: req.media_type,
+            max_size: req
+                .max_size
+                .unwrap_or(MAX_REFERRER_BLOB_SIZE)
+                .min(MAX_REFERRER_BLOB_SIZE),
+        }
+    }
+}
+
+impl From<AttestationVerificationRequest> for CallbackRequestType {
+    fn from(request: AttestationVerificationRequest) -> Self {
+        CallbackRequestType::SigstoreAttestationVerify { request }
+    }
+}
+
 impl From<SigstoreVerificationInputV2> for CallbackRequestType {
     fn from(val: SigstoreVerificationInputV2) -> Self {
         match val {
@@ -344,6 +491,7 @@ impl From<kubewarden_policy_sdk::host_capabilities::kubernetes::ListResourcesByN
             namespace: req.namespace,
             label_selector: req.label_selector,
             field_selector: req.field_selector,
+            projection: None,
         }
     }
 }
@@ -359,6 +507,7 @@ impl From<kubewarden_policy_sdk::host_capabilities::kubernetes::ListAllResources
             kind: req.kind,
             label_selector: req.label_selector,
             field_selector: req.field_selector,
+            projection: None,
         }
     }
 }
@@ -372,11 +521,22 @@ impl From<kubewarden_policy_sdk::host_capabilities::kubernetes::GetResourceReque
             kind: req.kind,
             name: req.name,
             namespace: req.namespace,
+            projection: None,
             disable_cache: req.disable_cache,
         }
     }
 }
 
+impl From<DryRunRequest> for CallbackRequestType {
+    fn from(req: DryRunRequest) -> Self {
+        CallbackRequestType::KubernetesDryRun {
+            object: req.object,
+            operation: req.operation,
+            field_manager: req.field_manager,
+        }
+    }
+}
+
 impl From<CanIRequest> for CallbackRequestType {
     fn from(req: CanIRequest) -> Self {
         CallbackRequestType::KubernetesCanI {
@@ -93,6 +93,12 @@ pub enum MetadataError {
     },
 }
 
+#[derive(Error, Debug)]
+pub enum RegoBuiltinsError {
+    #[error("cannot find the builtins used by the Rego policy: {0}")]
+    UsedBuiltins(#[source] burrego::errors::BurregoError),
+}
+
 #[derive(Error, Debug)]
 pub enum ResponseError {
     #[error("cannot deserialize JSONPatch: {0}")]
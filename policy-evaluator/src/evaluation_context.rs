@@ -1,9 +1,10 @@
 use std::collections::BTreeSet;
 use std::fmt;
+use std::sync::Arc;
 use tokio::sync::mpsc;
 
 use crate::callback_requests::CallbackRequest;
-use crate::policy_metadata::ContextAwareResource;
+use crate::policy_metadata::{ContextAwareResource, HostCapabilityNamespace, ResourceProjection};
 
 /// A struct that holds metadata and other data that are needed when a policy
 /// is being evaluated
@@ -22,19 +23,64 @@ pub struct EvaluationContext {
 
     /// List of ContextAwareResource the policy is granted access to.
     pub ctx_aware_resources_allow_list: BTreeSet<ContextAwareResource>,
+
+    /// List of Kubernetes resources the policy is allowed to create or apply
+    /// in dry-run mode. The projections of these resources are ignored.
+    pub dry_run_resources_allow_list: BTreeSet<ContextAwareResource>,
+
+    /// The namespaces of host capabilities the policy is granted access to.
+    /// When `None`, all the host capabilities can be used
+    pub host_capabilities_allow_list: Option<BTreeSet<HostCapabilityNamespace>>,
+
+    /// External data document made available to Rego policies under their
+    /// `data` object. This is ignored by policies that are not Rego based
+    pub rego_data: Option<Arc<serde_json::Map<String, serde_json::Value>>>,
 }
 
 impl EvaluationContext {
     /// Checks if a policy has access to a Kubernetes resource, based on the privileges
     /// that have been granted by the user
     pub(crate) fn can_access_kubernetes_resource(&self, api_version: &str, kind: &str) -> bool {
-        let wanted_resource = ContextAwareResource {
-            api_version: api_version.to_string(),
-            kind: kind.to_string(),
-        };
+        self.allowed_kubernetes_resource(api_version, kind)
+            .is_some()
+    }
+
+    /// Get the projection to apply to the Kubernetes resources the policy has access to.
+    /// When the same resource is granted more than once, the least restrictive grant wins
+    pub(crate) fn kubernetes_resource_projection(
+        &self,
+        api_version: &str,
+        kind: &str,
+    ) -> Option<ResourceProjection> {
+        self.allowed_kubernetes_resource(api_version, kind)
+            .and_then(|resource| resource.projection.clone())
+    }
 
+    fn allowed_kubernetes_resource(
+        &self,
+        api_version: &str,
+        kind: &str,
+    ) -> Option<&ContextAwareResource> {
+        // The resources without a projection come first
         self.ctx_aware_resources_allow_list
-            .contains(&wanted_resource)
+            .iter()
+            .find(|resource| resource.api_version == api_version && resource.kind == kind)
+    }
+
+    /// Checks if a policy can dry-run a Kubernetes resource, based on the privileges
+    /// that have been granted by the user
+    pub(crate) fn can_dry_run_kubernetes_resource(&self, api_version: &str, kind: &str) -> bool {
+        self.dry_run_resources_allow_list
+            .iter()
+            .any(|resource| resource.api_version == api_version && resource.kind == kind)
+    }
+
+    /// Checks if a policy can use the host capabilities of the given namespace,
+    /// based on the privileges that have been granted by the user
+    pub(crate) fn can_use_host_capability(&self, namespace: HostCapabilityNamespace) -> bool {
+        self.host_capabilities_allow_list
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&namespace))
     }
 }
 
@@ -44,11 +90,20 @@ impl fmt::Debug for EvaluationContext {
             Some(_) => "Some(...)",
             None => "None",
         };
+        let rego_data = match self.rego_data {
+            Some(_) => "Some(...)",
+            None => "None",
+        };
 
         write!(
             f,
-            r#"EvaluationContext {{ policy_id: "{}", callback_channel: {}, allowed_kubernetes_resources: {:?} }}"#,
-            self.policy_id, callback_channel, self.ctx_aware_resources_allow_list,
+            r#"EvaluationContext {{ policy_id: "{}", callback_channel: {}, allowed_kubernetes_resources: {:?}, allowed_dry_run_resources: {:?}, allowed_host_capabilities: {:?}, rego_data: {} }}"#,
+            self.policy_id,
+            callback_channel,
+            self.ctx_aware_resources_allow_list,
+            self.dry_run_resources_allow_list,
+            self.host_capabilities_allow_list,
+            rego_data,
         )
     }
 }
@@ -66,6 +121,7 @@ mod tests {
             ContextAwareResource{
                 api_version: "v1".to_string(),
                 kind: "ConfigMap".to_string(),
+                projection: None,
             }]),
         "v1",
         "Secret",
@@ -77,6 +133,7 @@ mod tests {
             ContextAwareResource{
                 api_version: "v1".to_string(),
                 kind: "ConfigMap".to_string(),
+                projection: None,
             }]),
         "v1",
         "ConfigMap",
@@ -94,11 +151,15 @@ mod tests {
             policy_id: name.to_string(),
             callback_channel: None,
             ctx_aware_resources_allow_list: allowed_resources,
+            dry_run_resources_allow_list: BTreeSet::new(),
+            host_capabilities_allow_list: None,
+            rego_data: None,
         };
 
         let requested_resource = ContextAwareResource {
             api_version: api_version.to_string(),
             kind: kind.to_string(),
+            projection: None,
         };
 
         assert_eq!(
@@ -109,4 +170,75 @@ mod tests {
             )
         );
     }
+
+    #[rstest]
+    #[case::unrestricted(None, HostCapabilityNamespace::Net, true)]
+    #[case::nothing_allowed(Some(BTreeSet::new()), HostCapabilityNamespace::Oci, false)]
+    #[case::denied(
+        Some(BTreeSet::from([HostCapabilityNamespace::Oci, HostCapabilityNamespace::Crypto])),
+        HostCapabilityNamespace::Net,
+        false
+    )]
+    #[case::allowed(
+        Some(BTreeSet::from([HostCapabilityNamespace::Oci, HostCapabilityNamespace::Crypto])),
+        HostCapabilityNamespace::Crypto,
+        true
+    )]
+    fn can_use_host_capability(
+        #[case] allow_list: Option<BTreeSet<HostCapabilityNamespace>>,
+        #[case] namespace: HostCapabilityNamespace,
+        #[case] allowed: bool,
+    ) {
+        let ctx = EvaluationContext {
+            host_capabilities_allow_list: allow_list,
+            ..Default::default()
+        };
+
+        assert_eq!(allowed, ctx.can_use_host_capability(namespace));
+    }
+
+    #[rstest]
+    #[case::not_projected(
+        BTreeSet::from([ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Secret".to_string(),
+            projection: None,
+        }]),
+        None
+    )]
+    #[case::projected(
+        BTreeSet::from([ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Secret".to_string(),
+            projection: Some(ResourceProjection::MetadataOnly),
+        }]),
+        Some(ResourceProjection::MetadataOnly)
+    )]
+    #[case::least_restrictive_grant_wins(
+        BTreeSet::from([
+            ContextAwareResource {
+                api_version: "v1".to_string(),
+                kind: "Secret".to_string(),
+                projection: Some(ResourceProjection::MetadataOnly),
+            },
+            ContextAwareResource {
+                api_version: "v1".to_string(),
+                kind: "Secret".to_string(),
+                projection: None,
+            },
+        ]),
+        None
+    )]
+    fn kubernetes_resource_projection(
+        #[case] allowed_resources: BTreeSet<ContextAwareResource>,
+        #[case] expected: Option<ResourceProjection>,
+    ) {
+        let ctx = EvaluationContext {
+            ctx_aware_resources_allow_list: allowed_resources,
+            ..Default::default()
+        };
+
+        assert!(ctx.can_access_kubernetes_resource("v1", "Secret"));
+        assert_eq!(expected, ctx.kubernetes_resource_projection("v1", "Secret"));
+    }
 }
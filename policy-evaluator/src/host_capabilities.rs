@@ -0,0 +1,7 @@
+//! Payloads of the host capabilities that are not yet part of the Kubewarden
+//! policy SDK. Policies exchange them as JSON documents.
+
+pub mod crypto;
+pub mod kubernetes;
+pub mod net;
+pub mod oci;
@@ -0,0 +1,113 @@
+use kubewarden_policy_sdk::host_capabilities::crypto::{BoolWithReason, Certificate};
+use serde::{Deserialize, Serialize};
+
+/// Request of the `crypto/v1/verify_signature` host capability: verify a
+/// detached signature over an arbitrary payload.
+///
+/// The signature is checked with either a PEM encoded public key or with the
+/// key of a certificate. The supported keys are RSA (PKCS#1 v1.5 with SHA-256),
+/// ECDSA P-256 and P-384 (ASN.1 DER encoded signatures) and Ed25519.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureVerificationRequest {
+    /// The signed payload, base64 encoded
+    pub payload: String,
+    /// The signature, base64 encoded
+    pub signature: String,
+    /// PEM encoded public key used to verify the signature. Cannot be used
+    /// together with `certificate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Certificate holding the key used to verify the signature. Cannot be
+    /// used together with `public_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<Certificate>,
+    /// The certificate must be trusted by this chain. The certificate is
+    /// trusted when no chain is provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_chain: Option<Vec<Certificate>>,
+    /// RFC 3339 time used to check the expiration of the certificate. The
+    /// expiration is not checked when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<String>,
+}
+
+/// Response of the `crypto/v1/verify_signature` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignatureVerificationResponse {
+    pub trusted: bool,
+    /// Why the signature is not trusted, empty when it is
+    pub reason: String,
+}
+
+impl From<BoolWithReason> for SignatureVerificationResponse {
+    fn from(b: BoolWithReason) -> Self {
+        match b {
+            BoolWithReason::True => SignatureVerificationResponse {
+                trusted: true,
+                reason: String::new(),
+            },
+            BoolWithReason::False(reason) => SignatureVerificationResponse {
+                trusted: false,
+                reason,
+            },
+        }
+    }
+}
+
+/// Request of the `crypto/v1/verify_jwt` host capability: verify the signature
+/// of a JWT against a JSON Web Key Set and validate its registered claims
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct JwtVerificationRequest {
+    /// The JWT, in compact serialization
+    pub token: String,
+    /// The JSON Web Key Set holding the keys that can sign the token. The key
+    /// is chosen by the `kid` header of the token, which can be omitted when
+    /// the set has only one key
+    pub jwks: serde_json::Value,
+    /// The `iss` claim must be one of these values
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuers: Option<Vec<String>>,
+    /// The `aud` claim must contain one of these values. The audience is not
+    /// checked when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audiences: Option<Vec<String>>,
+    /// The algorithms the token can be signed with (e.g.: `RS256`, `ES256`,
+    /// `EdDSA`). Defaults to all the asymmetric algorithms, HMAC based ones
+    /// must be listed explicitly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithms: Option<Vec<String>>,
+    /// Clock skew tolerated when checking the `exp` and `nbf` claims, in
+    /// seconds. Defaults to 60 seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leeway: Option<u64>,
+}
+
+/// Response of the `crypto/v1/verify_jwt` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct JwtVerificationResponse {
+    pub trusted: bool,
+    /// Why the token is not trusted, empty when it is
+    pub reason: String,
+    /// The header of the token, set only when the token is trusted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<serde_json::Value>,
+    /// The claims of the token, set only when the token is trusted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claims: Option<serde_json::Value>,
+}
+
+impl From<BoolWithReason> for JwtVerificationResponse {
+    fn from(b: BoolWithReason) -> Self {
+        let SignatureVerificationResponse { trusted, reason } = b.into();
+        JwtVerificationResponse {
+            trusted,
+            reason,
+            header: None,
+            claims: None,
+        }
+    }
+}
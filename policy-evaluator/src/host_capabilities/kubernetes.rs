@@ -0,0 +1,45 @@
+use kube::core::DynamicObject;
+use serde::{Deserialize, Serialize};
+
+/// The write operation simulated by the `kubernetes/dry_run` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DryRunOperation {
+    /// Create the object, like `kubectl create --dry-run=server` does
+    #[default]
+    Create,
+    /// Server-side apply the object, like `kubectl apply --server-side --dry-run=server` does
+    Apply,
+}
+
+/// Request of the `kubernetes/dry_run` host capability: ask the Kubernetes API
+/// server to create or apply an object without persisting it.
+///
+/// The dry-run request goes through the admission webhooks of the cluster,
+/// Kubewarden ones included. To prevent policies from re-entering themselves,
+/// the request is refused while evaluating an admission request that has
+/// `dryRun` set
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunRequest {
+    /// The object to create or apply. Its `apiVersion` and `kind` must be
+    /// among the resources the policy is allowed to dry-run. Namespaced
+    /// objects must have their namespace set
+    pub object: serde_json::Value,
+    /// The operation to simulate. Defaults to `create`
+    #[serde(default)]
+    pub operation: DryRunOperation,
+    /// The field manager used to apply the object. Defaults to `kubewarden`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field_manager: Option<String>,
+}
+
+/// Response of the `kubernetes/dry_run` host capability
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResponse {
+    /// The object returned by the API server, after defaulting and admission
+    pub object: DynamicObject,
+    /// JSON patch turning the submitted object into the returned one
+    pub patch: json_patch::Patch,
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// Request of the `net/v1/dns_reverse_lookup` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseLookupRequest {
+    /// The IPv4 or IPv6 address to look up
+    pub ip: String,
+}
+
+/// Response of the `net/v1/dns_reverse_lookup` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReverseLookupResponse {
+    /// The host names the address resolves to, without the trailing dot
+    pub hosts: Vec<String>,
+}
+
+/// Request of the `net/v1/dns_lookup_srv` and `net/v1/dns_lookup_txt` host capabilities
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordLookupRequest {
+    /// The DNS name to look up (e.g.: `_ldap._tcp.example.com`)
+    pub name: String,
+}
+
+/// A DNS SRV record
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    /// The host providing the service, without the trailing dot
+    pub target: String,
+}
+
+/// Response of the `net/v1/dns_lookup_srv` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SrvLookupResponse {
+    pub records: Vec<SrvRecord>,
+}
+
+/// Response of the `net/v1/dns_lookup_txt` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TxtLookupResponse {
+    /// The TXT records, the character strings of each record are concatenated
+    pub records: Vec<String>,
+}
+
+/// Request of the `net/v1/dns_lookup_cidr` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CidrLookupRequest {
+    /// The host to resolve. An IP address can be provided too
+    pub host: String,
+    /// The networks the addresses of the host are checked against (e.g.: `10.0.0.0/8`)
+    pub cidrs: Vec<String>,
+}
+
+/// An address of the host looked up via `net/v1/dns_lookup_cidr`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CidrLookupAddress {
+    pub ip: String,
+    /// The requested networks containing the address
+    pub cidrs: Vec<String>,
+}
+
+/// Response of the `net/v1/dns_lookup_cidr` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CidrLookupResponse {
+    pub addresses: Vec<CidrLookupAddress>,
+}
@@ -0,0 +1,141 @@
+use kubewarden_policy_sdk::host_capabilities::verification::{KeylessInfo, KeylessPrefixInfo};
+use serde::{Deserialize, Serialize};
+
+pub use policy_fetcher::registry::Referrer;
+
+/// Hard limit, in bytes, of the referrer blobs that can be fetched by a policy
+pub const MAX_REFERRER_BLOB_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Request of the `oci/v1/referrers` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrersRequest {
+    /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+    pub image: String,
+    /// Return only the referrers of the given artifact type (e.g.: `application/spdx+json`)
+    #[serde(default)]
+    pub artifact_type: Option<String>,
+}
+
+/// Response of the `oci/v1/referrers` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrersResponse {
+    /// The artifacts referencing the image
+    pub referrers: Vec<Referrer>,
+}
+
+/// Request of the `oci/v1/referrer_blob` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrerBlobRequest {
+    /// String pointing to the object referenced by the artifact, used to
+    /// find the registry and repository of the referrer
+    pub image: String,
+    /// Digest of the referrer manifest, as returned by `oci/v1/referrers`
+    pub digest: String,
+    /// Media type of the layer to fetch. The first layer is fetched when not set
+    #[serde(default)]
+    pub media_type: Option<String>,
+    /// Maximum size of the layer, in bytes. Cannot exceed `MAX_REFERRER_BLOB_SIZE`,
+    /// which is also the default value
+    #[serde(default)]
+    pub max_size: Option<u64>,
+}
+
+/// Response of the `oci/v1/referrer_blob` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrerBlobResponse {
+    /// Media type of the layer
+    pub media_type: String,
+    /// Digest of the layer
+    pub digest: String,
+    /// Contents of the layer, base64 encoded
+    pub data: String,
+}
+
+/// Request of the `oci/v1/verify_attestation` host capability: verify the
+/// in-toto attestations attached to an image with `cosign attest`. The same
+/// signature options of `oci/v2/verify` are available.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type")]
+pub enum AttestationVerificationRequest {
+    /// The attestations must be signed by all the given public keys
+    SigstorePubKeyVerify {
+        /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+        image: String,
+        /// Type of the predicate of the attestations (e.g.: `https://slsa.dev/provenance/v1`)
+        predicate_type: String,
+        /// List of PEM encoded keys that must have been used to sign the attestations
+        pub_keys: Vec<String>,
+    },
+
+    /// The attestations must be signed in keyless mode by all the given identities
+    SigstoreKeylessVerify {
+        /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+        image: String,
+        /// Type of the predicate of the attestations (e.g.: `https://slsa.dev/provenance/v1`)
+        predicate_type: String,
+        /// List of keyless signatures that must be found
+        keyless: Vec<KeylessInfo>,
+    },
+
+    /// The attestations must be signed in keyless mode by all the given
+    /// identities, whose subject is a URL prefix
+    SigstoreKeylessPrefixVerify {
+        /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+        image: String,
+        /// Type of the predicate of the attestations (e.g.: `https://slsa.dev/provenance/v1`)
+        predicate_type: String,
+        /// List of keyless signatures that must be found
+        keyless_prefix: Vec<KeylessPrefixInfo>,
+    },
+
+    /// The attestations must be signed in keyless mode inside of GitHub Actions
+    SigstoreGithubActionsVerify {
+        /// String pointing to the object (e.g.: `registry.testing.lan/busybox:1.0.0`)
+        image: String,
+        /// Type of the predicate of the attestations (e.g.: `https://slsa.dev/provenance/v1`)
+        predicate_type: String,
+        /// Owner of the repository. E.g: octocat
+        owner: String,
+        /// Optional - Repo of the GH Action workflow that signed the attestations. E.g: example-repo
+        repo: Option<String>,
+    },
+}
+
+impl AttestationVerificationRequest {
+    pub fn image(&self) -> &str {
+        match self {
+            AttestationVerificationRequest::SigstorePubKeyVerify { image, .. }
+            | AttestationVerificationRequest::SigstoreKeylessVerify { image, .. }
+            | AttestationVerificationRequest::SigstoreKeylessPrefixVerify { image, .. }
+            | AttestationVerificationRequest::SigstoreGithubActionsVerify { image, .. } => image,
+        }
+    }
+
+    pub fn predicate_type(&self) -> &str {
+        match self {
+            AttestationVerificationRequest::SigstorePubKeyVerify { predicate_type, .. }
+            | AttestationVerificationRequest::SigstoreKeylessVerify { predicate_type, .. }
+            | AttestationVerificationRequest::SigstoreKeylessPrefixVerify {
+                predicate_type, ..
+            }
+            | AttestationVerificationRequest::SigstoreGithubActionsVerify {
+                predicate_type, ..
+            } => predicate_type,
+        }
+    }
+}
+
+/// Response of the `oci/v1/verify_attestation` host capability
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttestationVerificationResponse {
+    /// Digest of the image that was verified
+    pub digest: String,
+    /// Type of the predicates
+    pub predicate_type: String,
+    /// The predicates of the attestations whose signature has been verified
+    pub predicates: Vec<serde_json::Value>,
+}
@@ -9,11 +9,14 @@ pub mod callback_requests;
 pub mod constants;
 pub mod errors;
 pub mod evaluation_context;
+pub mod host_capabilities;
+mod metrics;
 pub mod policy_artifacthub;
 pub mod policy_evaluator;
 pub mod policy_group_evaluator;
 pub mod policy_metadata;
 mod policy_tracing;
+pub mod rego_builtins;
 pub mod runtimes;
 
 // API's that expose other crate types (such as Kubewarden Policy SDK
@@ -0,0 +1,141 @@
+use lazy_static::lazy_static;
+use opentelemetry::{
+    metrics::{Counter, Gauge, Histogram},
+    KeyValue,
+};
+use std::time::Duration;
+
+use crate::callback_handler::CachedCapability;
+use crate::policy_metadata::HostCapabilityNamespace;
+
+const METER_NAME: &str = "kubewarden";
+
+lazy_static! {
+    static ref GATEKEEPER_INVENTORY_BUILD_DURATION: Histogram<u64> =
+        opentelemetry::global::meter(METER_NAME)
+            .u64_histogram("kubewarden_gatekeeper_inventory_build_duration_milliseconds")
+            .build();
+    static ref GATEKEEPER_INVENTORY_SIZE: Gauge<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_gauge("kubewarden_gatekeeper_inventory_size_bytes")
+        .build();
+    static ref CALLBACK_CACHE_HITS: Counter<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_counter("kubewarden_callback_cache_hits_total")
+        .build();
+    static ref CALLBACK_CACHE_MISSES: Counter<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_counter("kubewarden_callback_cache_misses_total")
+        .build();
+    static ref CALLBACK_CACHE_EVICTIONS: Counter<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_counter("kubewarden_callback_cache_evictions_total")
+        .build();
+    static ref HOST_CAPABILITY_CALLS: Counter<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_counter("kubewarden_host_capability_calls_total")
+        .build();
+    static ref REFLECTOR_MEMORY_SIZE: Gauge<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_gauge("kubewarden_reflector_memory_size_bytes")
+        .build();
+    static ref REFLECTOR_OBJECTS: Gauge<u64> = opentelemetry::global::meter(METER_NAME)
+        .u64_gauge("kubewarden_reflector_objects")
+        .build();
+}
+
+/// The way a Gatekeeper inventory has been built
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum GatekeeperInventoryBuildType {
+    /// All the Kubernetes resources have been fetched again
+    Full,
+    /// The inventory has been updated with the changes seen by the reflectors
+    Incremental,
+}
+
+impl GatekeeperInventoryBuildType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            GatekeeperInventoryBuildType::Full => "full",
+            GatekeeperInventoryBuildType::Incremental => "incremental",
+        }
+    }
+}
+
+/// Record the time taken to build and serialize a Gatekeeper inventory, together with
+/// the size of the serialized inventory
+pub(crate) fn record_gatekeeper_inventory_build(
+    duration: Duration,
+    size: usize,
+    build_type: GatekeeperInventoryBuildType,
+    resources: String,
+) {
+    let attributes = [
+        KeyValue::new("build_type", build_type.as_str()),
+        KeyValue::new("resources", resources),
+    ];
+    let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+    GATEKEEPER_INVENTORY_BUILD_DURATION.record(millis, &attributes);
+    GATEKEEPER_INVENTORY_SIZE.record(u64::try_from(size).unwrap_or(u64::MAX), &attributes);
+}
+
+/// Record a lookup inside of the cache of a host capability. `negative` is
+/// true when the hit returned a cached failure
+pub(crate) fn record_callback_cache_lookup(
+    capability: CachedCapability,
+    hit: bool,
+    negative: bool,
+) {
+    if hit {
+        CALLBACK_CACHE_HITS.add(
+            1,
+            &[
+                KeyValue::new("capability", capability.as_str()),
+                KeyValue::new("negative", negative),
+            ],
+        );
+    } else {
+        CALLBACK_CACHE_MISSES.add(1, &[KeyValue::new("capability", capability.as_str())]);
+    }
+}
+
+/// Record the removal of an entry from the cache of a host capability, either
+/// because it `expired` or to make room for a new one (`capacity`)
+pub(crate) fn record_callback_cache_eviction(capability: CachedCapability, reason: &'static str) {
+    CALLBACK_CACHE_EVICTIONS.add(
+        1,
+        &[
+            KeyValue::new("capability", capability.as_str()),
+            KeyValue::new("reason", reason),
+        ],
+    );
+}
+
+/// Record a call made by a policy to a host capability. `allowed` is false when
+/// the policy has not been granted access to the capability
+pub(crate) fn record_host_capability_call(
+    policy_id: &str,
+    namespace: HostCapabilityNamespace,
+    operation: &'static str,
+    allowed: bool,
+) {
+    HOST_CAPABILITY_CALLS.add(
+        1,
+        &[
+            KeyValue::new("policy_name", policy_id.to_owned()),
+            KeyValue::new("namespace", namespace.as_str()),
+            KeyValue::new("operation", operation),
+            KeyValue::new("allowed", allowed),
+        ],
+    );
+}
+
+/// Record the estimated memory used by the objects stored by a reflector, together
+/// with the number of objects
+pub(crate) fn record_reflector_memory_footprint(
+    reflector_id: &str,
+    projection: &'static str,
+    size: usize,
+    objects: usize,
+) {
+    let attributes = [
+        KeyValue::new("reflector", reflector_id.to_owned()),
+        KeyValue::new("projection", projection),
+    ];
+    REFLECTOR_MEMORY_SIZE.record(u64::try_from(size).unwrap_or(u64::MAX), &attributes);
+    REFLECTOR_OBJECTS.record(u64::try_from(objects).unwrap_or(u64::MAX), &attributes);
+}
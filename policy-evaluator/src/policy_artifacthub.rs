@@ -530,6 +530,7 @@ mod tests {
             mutating: false,
             background_audit: true,
             context_aware_resources: BTreeSet::new(),
+            host_capabilities: None,
             execution_mode: Default::default(),
             policy_type: PolicyType::Kubernetes,
             minimum_kubewarden_version: None,
@@ -541,6 +542,7 @@ mod tests {
         context_aware_resources.insert(ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Pod".to_string(),
+            projection: None,
         });
 
         Metadata {
@@ -599,6 +601,7 @@ mod tests {
             mutating: false,
             background_audit: true,
             context_aware_resources,
+            host_capabilities: None,
             execution_mode: Default::default(),
             minimum_kubewarden_version: None,
             policy_type: Default::default(),
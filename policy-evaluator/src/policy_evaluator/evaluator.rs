@@ -1,10 +1,12 @@
 use kubewarden_policy_sdk::{metadata::ProtocolVersion, settings::SettingsValidationResponse};
-use std::fmt;
+use std::{collections::BTreeSet, fmt};
 
 use crate::admission_response::AdmissionResponse;
 use crate::errors::PolicyEvaluatorError;
 use crate::evaluation_context::EvaluationContext;
 use crate::policy_evaluator::{PolicySettings, ValidateRequest};
+use crate::policy_metadata::HostCapabilityNamespace;
+use crate::runtimes::callback::EvaluatedRequestGuard;
 use crate::runtimes::rego::Runtime as BurregoRuntime;
 use crate::runtimes::wapc::Runtime as WapcRuntime;
 use crate::runtimes::wasi_cli::Runtime as WasiRuntime;
@@ -29,17 +31,34 @@ impl PolicyEvaluator {
         request: ValidateRequest,
         settings: &PolicySettings,
     ) -> AdmissionResponse {
+        let _evaluated_request = EvaluatedRequestGuard::new(&request);
         match self.runtime {
             Runtime::Wapc(ref mut wapc_stack) => {
                 WapcRuntime(wapc_stack).validate(settings, &request)
             }
             Runtime::Rego(ref mut burrego_evaluator) => {
+                // Rego policies get the Kubernetes resources they are allowed to
+                // access only when they can use the Kubernetes host capabilities
+                let no_resources = BTreeSet::new();
+                let ctx_aware_resources_allow_list = if self
+                    .eval_ctx
+                    .can_use_host_capability(HostCapabilityNamespace::Kubernetes)
+                {
+                    &self.eval_ctx.ctx_aware_resources_allow_list
+                } else {
+                    &no_resources
+                };
                 let kube_ctx = burrego_evaluator.build_kubernetes_context(
                     self.eval_ctx.callback_channel.as_ref(),
-                    &self.eval_ctx.ctx_aware_resources_allow_list,
+                    ctx_aware_resources_allow_list,
                 );
                 match kube_ctx {
-                    Ok(ctx) => BurregoRuntime(burrego_evaluator).validate(settings, &request, &ctx),
+                    Ok(ctx) => BurregoRuntime(burrego_evaluator).validate(
+                        settings,
+                        &request,
+                        &ctx,
+                        self.eval_ctx.rego_data.as_ref(),
+                    ),
                     Err(e) => {
                         AdmissionResponse::reject(request.uid().to_string(), e.to_string(), 500)
                     }
@@ -1,4 +1,4 @@
-use std::{collections::BTreeSet, fmt};
+use std::{collections::BTreeSet, fmt, sync::Arc};
 
 use kubewarden_policy_sdk::crd::policies::{
     admission_policy_group::PolicyGroupMember,
@@ -10,7 +10,7 @@ pub mod evaluator;
 
 use crate::admission_response::AdmissionResponse;
 use crate::policy_evaluator::PolicySettings;
-use crate::policy_metadata::ContextAwareResource;
+use crate::policy_metadata::{ContextAwareResource, HostCapabilityNamespace};
 
 /// The settings of a policy group member
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -19,6 +19,14 @@ pub struct PolicyGroupMemberSettings {
     pub settings: PolicySettings,
     /// The list of kubernetes resources that are allowed to be accessed by the policy member
     pub ctx_aware_resources_allow_list: BTreeSet<ContextAwareResource>,
+    /// The list of kubernetes resources the policy member is allowed to dry-run
+    pub dry_run_resources_allow_list: BTreeSet<ContextAwareResource>,
+    /// The namespaces of host capabilities the policy member is allowed to use.
+    /// When `None`, all of them can be used
+    pub host_capabilities_allow_list: Option<BTreeSet<HostCapabilityNamespace>>,
+    /// External data document made available to the policy member, when it's
+    /// Rego based
+    pub rego_data: Option<Arc<serde_json::Map<String, serde_json::Value>>>,
 }
 
 /// This holds the a summary of the evaluation results of a policy group member
@@ -67,6 +75,9 @@ impl TryFrom<&PolicyGroupMemberWithContext> for PolicyGroupMemberSettings {
         Ok(Self {
             settings,
             ctx_aware_resources_allow_list,
+            dry_run_resources_allow_list: BTreeSet::new(),
+            host_capabilities_allow_list: None,
+            rego_data: None,
         })
     }
 }
@@ -80,6 +91,9 @@ impl TryFrom<&PolicyGroupMember> for PolicyGroupMemberSettings {
         Ok(Self {
             settings,
             ctx_aware_resources_allow_list: BTreeSet::new(),
+            dry_run_resources_allow_list: BTreeSet::new(),
+            host_capabilities_allow_list: None,
+            rego_data: None,
         })
     }
 }
@@ -115,10 +129,12 @@ mod tests {
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Pod".to_string(),
+                projection: None,
             },
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Service".to_string(),
+                projection: None,
             },
         ]);
 
@@ -259,6 +259,9 @@ impl PolicyGroupEvaluator {
             policy_id: policy_id.to_owned(),
             callback_channel: self.callback_channel.clone(),
             ctx_aware_resources_allow_list: settings.ctx_aware_resources_allow_list.clone(),
+            dry_run_resources_allow_list: settings.dry_run_resources_allow_list.clone(),
+            host_capabilities_allow_list: settings.host_capabilities_allow_list.clone(),
+            rego_data: settings.rego_data.clone(),
         };
         let mut evaluator = evaluator_pre.rehydrate(&eval_ctx).map_err(|e| {
             EvaluationError::CannotRehydratePolicyGroupMember(policy_id.to_owned(), e)
@@ -332,6 +335,9 @@ impl PolicyGroupEvaluator {
             policy_id: policy_id.to_owned(),
             callback_channel: self.callback_channel.clone(),
             ctx_aware_resources_allow_list: settings.ctx_aware_resources_allow_list.clone(),
+            dry_run_resources_allow_list: settings.dry_run_resources_allow_list.clone(),
+            host_capabilities_allow_list: settings.host_capabilities_allow_list.clone(),
+            rego_data: settings.rego_data.clone(),
         };
         let mut evaluator = evaluator_pre.rehydrate(&eval_ctx).map_err(|e| {
             EvaluationError::CannotRehydratePolicyGroupMember(policy_id.to_owned(), e)
@@ -470,6 +476,9 @@ mod tests {
                 PolicyGroupMemberSettings {
                     settings: Default::default(),
                     ctx_aware_resources_allow_list: Default::default(),
+                    dry_run_resources_allow_list: Default::default(),
+                    host_capabilities_allow_list: None,
+                    rego_data: None,
                 },
             );
         }
@@ -547,6 +556,9 @@ mod tests {
                 PolicyGroupMemberSettings {
                     settings: Default::default(),
                     ctx_aware_resources_allow_list: Default::default(),
+                    dry_run_resources_allow_list: Default::default(),
+                    host_capabilities_allow_list: None,
+                    rego_data: None,
                 },
             );
         }
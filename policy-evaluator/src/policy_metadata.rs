@@ -4,7 +4,11 @@ use std::{
     path::Path,
 };
 
-use k8s_openapi::api::admissionregistration::v1::NamedRuleWithOperations;
+use k8s_openapi::{
+    api::admissionregistration::v1::NamedRuleWithOperations,
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+};
+use kube::core::DynamicObject;
 use kubewarden_policy_sdk::metadata::ProtocolVersion;
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -166,6 +170,128 @@ pub struct ContextAwareResource {
     pub api_version: String,
     #[validate(length(min = 1))]
     pub kind: String,
+    /// Restrict the fields of the objects made available to the policy.
+    /// All the fields are available when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(custom(function = "validate_projection"))]
+    pub projection: Option<ResourceProjection>,
+}
+
+/// The fields of the Kubernetes objects that are kept in memory and handed over
+/// to the policy
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum ResourceProjection {
+    /// Only the `metadata` of the objects is kept. The objects are fetched using
+    /// the PartialObjectMetadata API, hence the rest of the object never reaches
+    /// the host
+    MetadataOnly,
+    /// Only the fields referenced by the given JSON pointers (e.g. `/spec/nodeName`)
+    /// are kept, together with the name, namespace, uid and resourceVersion of
+    /// the object. Arrays are not traversed: a pointer going through an array
+    /// keeps the whole array
+    Fields(Vec<String>),
+}
+
+impl ResourceProjection {
+    /// Build a copy of the given object that contains only the projected fields
+    pub fn project(&self, obj: &DynamicObject) -> DynamicObject {
+        match self {
+            ResourceProjection::MetadataOnly => DynamicObject {
+                types: obj.types.clone(),
+                metadata: obj.metadata.clone(),
+                data: serde_json::Value::Object(serde_json::Map::new()),
+            },
+            ResourceProjection::Fields(pointers) => {
+                let mut projected = DynamicObject {
+                    types: obj.types.clone(),
+                    metadata: ObjectMeta {
+                        name: obj.metadata.name.clone(),
+                        namespace: obj.metadata.namespace.clone(),
+                        uid: obj.metadata.uid.clone(),
+                        resource_version: obj.metadata.resource_version.clone(),
+                        ..Default::default()
+                    },
+                    data: serde_json::Value::Object(serde_json::Map::new()),
+                };
+
+                let metadata = serde_json::to_value(&obj.metadata).unwrap_or_default();
+                let mut projected_metadata =
+                    serde_json::to_value(&projected.metadata).unwrap_or_default();
+                for pointer in pointers {
+                    match pointer.strip_prefix("/metadata") {
+                        Some(metadata_pointer)
+                            if metadata_pointer.is_empty() || metadata_pointer.starts_with('/') =>
+                        {
+                            copy_json_pointer(&metadata, &mut projected_metadata, metadata_pointer)
+                        }
+                        _ => copy_json_pointer(&obj.data, &mut projected.data, pointer),
+                    }
+                }
+                if let Ok(metadata) = serde_json::from_value(projected_metadata) {
+                    projected.metadata = metadata;
+                }
+
+                projected
+            }
+        }
+    }
+
+    /// A short name of the projection, used when reporting metrics
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceProjection::MetadataOnly => "metadataOnly",
+            ResourceProjection::Fields(_) => "fields",
+        }
+    }
+}
+
+/// Copy the value referenced by the JSON `pointer` from `source` to `destination`,
+/// creating the parent objects when needed
+fn copy_json_pointer(
+    source: &serde_json::Value,
+    destination: &mut serde_json::Value,
+    pointer: &str,
+) {
+    if source.pointer(pointer).is_none() {
+        return;
+    }
+
+    let mut source = source;
+    let mut destination = destination;
+    for token in pointer.split('/').skip(1) {
+        let serde_json::Value::Object(source_map) = source else {
+            // arrays are copied as a whole
+            break;
+        };
+        let serde_json::Value::Object(destination_map) = destination else {
+            // the value has already been copied by another pointer
+            return;
+        };
+        let token = token.replace("~1", "/").replace("~0", "~");
+        let Some(value) = source_map.get(&token) else {
+            return;
+        };
+        source = value;
+        destination = destination_map
+            .entry(token)
+            .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+    }
+    *destination = source.clone();
+}
+
+fn validate_projection(projection: &ResourceProjection) -> Result<(), ValidationError> {
+    if let ResourceProjection::Fields(pointers) = projection {
+        if pointers.is_empty() {
+            return Err(ValidationError::new("At least one field must be projected"));
+        }
+        if pointers.iter().any(|pointer| !pointer.starts_with('/')) {
+            return Err(ValidationError::new(
+                "Projected fields must be JSON pointers starting with '/'",
+            ));
+        }
+    }
+    Ok(())
 }
 
 impl From<&kubewarden_policy_sdk::crd::policies::common::ContextAwareResource>
@@ -175,10 +301,38 @@ impl From<&kubewarden_policy_sdk::crd::policies::common::ContextAwareResource>
         Self {
             api_version: resource.api_version.clone(),
             kind: resource.kind.clone(),
+            projection: None,
+        }
+    }
+}
+
+/// The namespaces of the host capabilities that can be used by a policy
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum HostCapabilityNamespace {
+    Oci,
+    Net,
+    Crypto,
+    Kubernetes,
+}
+
+impl HostCapabilityNamespace {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HostCapabilityNamespace::Oci => "oci",
+            HostCapabilityNamespace::Net => "net",
+            HostCapabilityNamespace::Crypto => "crypto",
+            HostCapabilityNamespace::Kubernetes => "kubernetes",
         }
     }
 }
 
+impl Display for HostCapabilityNamespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
 pub enum PolicyType {
     #[default]
@@ -215,6 +369,10 @@ pub struct Metadata {
     #[serde(default)]
     #[validate(nested)]
     pub context_aware_resources: BTreeSet<ContextAwareResource>,
+    /// The namespaces of the host capabilities used by the policy. When not
+    /// set, the policy can use all of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_capabilities: Option<BTreeSet<HostCapabilityNamespace>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub minimum_kubewarden_version: Option<Version>,
 }
@@ -234,6 +392,7 @@ impl Default for Metadata {
             execution_mode: PolicyExecutionMode::KubewardenWapc,
             policy_type: PolicyType::Kubernetes,
             context_aware_resources: BTreeSet::new(),
+            host_capabilities: None,
             minimum_kubewarden_version: None,
         }
     }
@@ -276,6 +435,7 @@ fn validate_metadata(metadata: &Metadata) -> Result<(), ValidationError> {
 mod tests {
     use super::*;
     use assert_json_diff::assert_json_eq;
+    use rstest::rstest;
     use serde_json::json;
 
     #[test]
@@ -454,6 +614,26 @@ mod tests {
         assert_json_eq!(expected, actual);
     }
 
+    #[test]
+    fn metadata_with_host_capabilities() {
+        let json_metadata = json!({
+            "protocolVersion": "v1",
+            "rules": [ ],
+            "mutating": false,
+            "hostCapabilities": ["oci", "crypto"],
+        });
+
+        let actual: Metadata =
+            serde_json::from_value(json_metadata).expect("cannot deserialize Metadata");
+        assert_eq!(
+            actual.host_capabilities,
+            Some(BTreeSet::from([
+                HostCapabilityNamespace::Oci,
+                HostCapabilityNamespace::Crypto
+            ]))
+        );
+    }
+
     #[test]
     fn metadata_init() -> Result<(), ()> {
         let pod_rule = Rule {
@@ -678,6 +858,7 @@ mod tests {
         context_aware_resources.insert(ContextAwareResource {
             api_version: "".to_string(),
             kind: "Pod".to_string(),
+            projection: None,
         });
 
         let metadata = Metadata {
@@ -702,6 +883,7 @@ mod tests {
         context_aware_resources.insert(ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "".to_string(),
+            projection: None,
         });
 
         let metadata = Metadata {
@@ -713,4 +895,133 @@ mod tests {
 
         assert!(metadata.validate().is_err());
     }
+
+    #[test]
+    fn metadata_with_projected_context_aware_resources() {
+        let json_metadata = json!({
+            "protocolVersion": "v1",
+            "rules": [ ],
+            "mutating": false,
+            "contextAwareResources": [
+                {
+                    "apiVersion": "v1",
+                    "kind": "Secret",
+                    "projection": "metadataOnly",
+                },
+                {
+                    "apiVersion": "v1",
+                    "kind": "Pod",
+                    "projection": { "fields": ["/spec/nodeName"] },
+                },
+            ],
+        });
+
+        let actual: Metadata =
+            serde_json::from_value(json_metadata).expect("cannot deserialize Metadata");
+        assert_eq!(
+            actual.context_aware_resources,
+            BTreeSet::from([
+                ContextAwareResource {
+                    api_version: "v1".to_string(),
+                    kind: "Secret".to_string(),
+                    projection: Some(ResourceProjection::MetadataOnly),
+                },
+                ContextAwareResource {
+                    api_version: "v1".to_string(),
+                    kind: "Pod".to_string(),
+                    projection: Some(ResourceProjection::Fields(vec![
+                        "/spec/nodeName".to_string()
+                    ])),
+                },
+            ])
+        );
+    }
+
+    #[rstest]
+    #[case::metadata_only(ResourceProjection::MetadataOnly, true)]
+    #[case::fields(ResourceProjection::Fields(vec!["/spec/nodeName".to_string()]), true)]
+    #[case::no_fields(ResourceProjection::Fields(vec![]), false)]
+    #[case::not_a_pointer(ResourceProjection::Fields(vec!["spec.nodeName".to_string()]), false)]
+    fn validate_context_aware_resource_projection(
+        #[case] projection: ResourceProjection,
+        #[case] valid: bool,
+    ) {
+        let resource = ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            projection: Some(projection),
+        };
+
+        assert_eq!(resource.validate().is_ok(), valid);
+    }
+
+    fn pod() -> DynamicObject {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx",
+                "namespace": "default",
+                "uid": "8c5e0f2a",
+                "resourceVersion": "42",
+                "labels": { "app": "nginx", "tier": "frontend" },
+                "ownerReferences": [
+                    { "apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "nginx-1", "uid": "1234" }
+                ],
+            },
+            "spec": {
+                "nodeName": "node-1",
+                "containers": [{ "name": "nginx", "image": "nginx:latest" }],
+            },
+            "status": { "phase": "Running" },
+        }))
+        .expect("cannot build pod")
+    }
+
+    #[rstest]
+    #[case::metadata_only(
+        ResourceProjection::MetadataOnly,
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx",
+                "namespace": "default",
+                "uid": "8c5e0f2a",
+                "resourceVersion": "42",
+                "labels": { "app": "nginx", "tier": "frontend" },
+                "ownerReferences": [
+                    { "apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "nginx-1", "uid": "1234" }
+                ],
+            },
+        })
+    )]
+    #[case::fields(
+        ResourceProjection::Fields(vec![
+            "/metadata/labels/app".to_string(),
+            "/spec/nodeName".to_string(),
+            "/spec/containers/0/image".to_string(),
+            "/status/missing".to_string(),
+        ]),
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": "nginx",
+                "namespace": "default",
+                "uid": "8c5e0f2a",
+                "resourceVersion": "42",
+                "labels": { "app": "nginx" },
+            },
+            "spec": {
+                "nodeName": "node-1",
+                "containers": [{ "name": "nginx", "image": "nginx:latest" }],
+            },
+        })
+    )]
+    fn project_object(#[case] projection: ResourceProjection, #[case] expected: serde_json::Value) {
+        let projected = projection.project(&pod());
+
+        assert_json_eq!(expected, projected);
+    }
 }
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::RegoBuiltinsError;
+
+/// A builtin function used by a Rego policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegoBuiltin {
+    pub name: String,
+    /// `true` when the builtin is implemented by burrego
+    pub implemented: bool,
+}
+
+/// Compatibility report of the builtins used by a Rego policy compiled to
+/// WebAssembly.
+///
+/// OPA Wasm modules can delegate the evaluation of some builtins to the host.
+/// Policies relying on builtins that are not implemented by burrego cannot be
+/// loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegoBuiltinsReport {
+    /// `true` when all the builtins used by the policy are implemented
+    pub compatible: bool,
+    /// The builtins used by the policy, sorted by name
+    pub builtins: Vec<RegoBuiltin>,
+}
+
+impl RegoBuiltinsReport {
+    /// Build the report of the Rego policy stored at the given path
+    pub fn from_path(path: &Path) -> Result<Self, RegoBuiltinsError> {
+        let used_builtins = burrego::EvaluatorBuilder::default()
+            .policy_path(path)
+            .host_callbacks(crate::runtimes::rego::new_host_callbacks())
+            .used_builtins()
+            .map_err(RegoBuiltinsError::UsedBuiltins)?;
+
+        Ok(Self::from_used_builtins(used_builtins))
+    }
+
+    /// Build the report starting from the names of the builtins used by a policy
+    pub fn from_used_builtins(used_builtins: impl IntoIterator<Item = String>) -> Self {
+        let implemented_builtins = burrego::Evaluator::implemented_builtins();
+
+        let mut builtins: Vec<RegoBuiltin> = used_builtins
+            .into_iter()
+            .map(|name| RegoBuiltin {
+                implemented: implemented_builtins.contains(&name),
+                name,
+            })
+            .collect();
+        builtins.sort_by(|a, b| a.name.cmp(&b.name));
+        builtins.dedup();
+
+        RegoBuiltinsReport {
+            compatible: builtins.iter().all(|builtin| builtin.implemented),
+            builtins,
+        }
+    }
+
+    /// The names of the builtins used by the policy that are not implemented
+    pub fn not_implemented(&self) -> Vec<&str> {
+        self.builtins
+            .iter()
+            .filter(|builtin| !builtin.implemented)
+            .map(|builtin| builtin.name.as_str())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_not_implemented_builtins() {
+        let report = RegoBuiltinsReport::from_used_builtins([
+            "time.now_ns".to_string(),
+            "not.a.real.builtin".to_string(),
+            "json.patch".to_string(),
+        ]);
+
+        assert!(!report.compatible);
+        assert_eq!(
+            report.builtins,
+            vec![
+                RegoBuiltin {
+                    name: "json.patch".to_string(),
+                    implemented: true,
+                },
+                RegoBuiltin {
+                    name: "not.a.real.builtin".to_string(),
+                    implemented: false,
+                },
+                RegoBuiltin {
+                    name: "time.now_ns".to_string(),
+                    implemented: true,
+                },
+            ]
+        );
+        assert_eq!(report.not_implemented(), vec!["not.a.real.builtin"]);
+    }
+
+    #[test]
+    fn report_from_policy() {
+        let report = RegoBuiltinsReport::from_path(Path::new(
+            "tests/data/gatekeeper_always_happy_policy.wasm",
+        ))
+        .expect("cannot build builtins report");
+
+        assert!(report.compatible);
+        assert!(report.not_implemented().is_empty());
+    }
+}
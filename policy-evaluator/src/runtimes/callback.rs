@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{cell::Cell, sync::Arc};
 
 use anyhow::{anyhow, Result};
 use kubewarden_policy_sdk::host_capabilities::{
@@ -12,7 +12,117 @@ use tokio::sync::{mpsc, oneshot, oneshot::Receiver};
 use tracing::{debug, error, warn};
 
 use crate::callback_requests::{CallbackRequest, CallbackRequestType, CallbackResponse};
-use crate::{callback_handler::verify_certificate, evaluation_context::EvaluationContext};
+use crate::host_capabilities::{
+    crypto::{JwtVerificationRequest, SignatureVerificationRequest, SignatureVerificationResponse},
+    kubernetes::DryRunRequest,
+    net::{CidrLookupRequest, RecordLookupRequest, ReverseLookupRequest},
+    oci::{AttestationVerificationRequest, ReferrerBlobRequest, ReferrersRequest},
+};
+use crate::{
+    callback_handler::{verify_certificate, verify_jwt, verify_signature},
+    evaluation_context::EvaluationContext,
+    metrics::record_host_capability_call,
+    policy_evaluator::ValidateRequest,
+    policy_metadata::HostCapabilityNamespace,
+};
+
+thread_local! {
+    /// Set while the thread evaluates an admission request that has `dryRun` set.
+    /// The host callbacks run on the thread evaluating the policy
+    static EVALUATING_DRY_RUN_REQUEST: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the current thread as evaluating the given request, until dropped.
+///
+/// A dry-run request goes through the admission webhooks of the cluster, Kubewarden
+/// ones included. Policies cannot issue one while evaluating a dry-run admission
+/// request, otherwise they could keep re-entering themselves
+pub(crate) struct EvaluatedRequestGuard {
+    previous: bool,
+}
+
+impl EvaluatedRequestGuard {
+    pub(crate) fn new(request: &ValidateRequest) -> Self {
+        let dry_run = match request {
+            ValidateRequest::AdmissionRequest(adm_req) => adm_req.dry_run.unwrap_or_default(),
+            ValidateRequest::Raw(_) => false,
+        };
+        EvaluatedRequestGuard {
+            previous: EVALUATING_DRY_RUN_REQUEST.replace(dry_run),
+        }
+    }
+}
+
+impl Drop for EvaluatedRequestGuard {
+    fn drop(&mut self) {
+        EVALUATING_DRY_RUN_REQUEST.set(self.previous);
+    }
+}
+
+/// Returns the namespace of the host capability being invoked, `None` when the
+/// call is not about a host capability (like logging)
+fn host_capability_namespace(binding: &str, namespace: &str) -> Option<HostCapabilityNamespace> {
+    match (binding, namespace) {
+        ("kubewarden", "oci") => Some(HostCapabilityNamespace::Oci),
+        ("kubewarden", "net") => Some(HostCapabilityNamespace::Net),
+        ("kubewarden", "crypto") => Some(HostCapabilityNamespace::Crypto),
+        ("kubewarden", "kubernetes") | ("kubernetes", _) => {
+            Some(HostCapabilityNamespace::Kubernetes)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the name of the host capability operation being invoked, used when
+/// reporting metrics. The operation is chosen by the policy, hence anything not
+/// known by the host is reported as `unknown`
+fn host_capability_operation(
+    binding: &str,
+    capability: HostCapabilityNamespace,
+    operation: &str,
+) -> &'static str {
+    // The deprecated `kubernetes` binding lists all the resources of a given kind
+    if binding == "kubernetes" {
+        return "list_resources_all";
+    }
+
+    let known_operations: &[&'static str] = match capability {
+        HostCapabilityNamespace::Oci => &[
+            "v1/verify",
+            "v2/verify",
+            "v1/manifest_digest",
+            "v1/oci_manifest",
+            "v1/oci_manifest_config",
+            "v1/referrers",
+            "v1/referrer_blob",
+            "v1/verify_attestation",
+        ],
+        HostCapabilityNamespace::Net => &[
+            "v1/dns_lookup_host",
+            "v1/dns_reverse_lookup",
+            "v1/dns_lookup_srv",
+            "v1/dns_lookup_txt",
+            "v1/dns_lookup_cidr",
+        ],
+        HostCapabilityNamespace::Crypto => &[
+            "v1/is_certificate_trusted",
+            "v1/verify_signature",
+            "v1/verify_jwt",
+        ],
+        HostCapabilityNamespace::Kubernetes => &[
+            "list_resources_by_namespace",
+            "list_resources_all",
+            "get_resource",
+            "can_i",
+            "dry_run",
+        ],
+    };
+    known_operations
+        .iter()
+        .find(|known_operation| **known_operation == operation)
+        .copied()
+        .unwrap_or("unknown")
+}
 
 /// The callback function used by waPC and Wasi policies to use host capabilities
 pub(crate) fn host_callback(
@@ -22,6 +132,30 @@ pub(crate) fn host_callback(
     payload: &[u8],
     eval_ctx: &Arc<EvaluationContext>,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(capability) = host_capability_namespace(binding, namespace) {
+        let allowed = eval_ctx.can_use_host_capability(capability);
+        record_host_capability_call(
+            &eval_ctx.policy_id,
+            capability,
+            host_capability_operation(binding, capability, operation),
+            allowed,
+        );
+        if !allowed {
+            error!(
+                policy = eval_ctx.policy_id,
+                binding,
+                namespace,
+                operation,
+                capabilities_allowed = ?eval_ctx.host_capabilities_allow_list,
+                "Policy tried to use a host capability it doesn't have access to"
+            );
+            return Err(format!(
+                "Policy has not been granted access to the {capability} host capabilities. The violation has been reported."
+            )
+            .into());
+        }
+    }
+
     match binding {
         "kubewarden" => match namespace {
             "tracing" => match operation {
@@ -150,24 +284,94 @@ pub(crate) fn host_callback(
                         eval_ctx,
                     )
                 }
+                "v1/referrers" => {
+                    let req: ReferrersRequest = serde_json::from_slice(payload.to_vec().as_ref())?;
+                    debug!(
+                        eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        ?req,
+                        "Sending request via callback channel"
+                    );
+                    let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
+                    let req = CallbackRequest {
+                        request: CallbackRequestType::from(req),
+                        response_channel: tx,
+                    };
+                    send_request_and_wait_for_response(
+                        &eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        req,
+                        rx,
+                        eval_ctx,
+                    )
+                }
+                "v1/referrer_blob" => {
+                    let req: ReferrerBlobRequest =
+                        serde_json::from_slice(payload.to_vec().as_ref())?;
+                    debug!(
+                        eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        ?req,
+                        "Sending request via callback channel"
+                    );
+                    let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
+                    let req = CallbackRequest {
+                        request: CallbackRequestType::from(req),
+                        response_channel: tx,
+                    };
+                    send_request_and_wait_for_response(
+                        &eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        req,
+                        rx,
+                        eval_ctx,
+                    )
+                }
+                "v1/verify_attestation" => {
+                    let req: AttestationVerificationRequest =
+                        serde_json::from_slice(payload.to_vec().as_ref())?;
+                    debug!(
+                        eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        ?req,
+                        "Sending request via callback channel"
+                    );
+                    let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
+                    let req = CallbackRequest {
+                        request: CallbackRequestType::from(req),
+                        response_channel: tx,
+                    };
+                    send_request_and_wait_for_response(
+                        &eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        req,
+                        rx,
+                        eval_ctx,
+                    )
+                }
                 _ => {
                     error!("unknown operation: {}", operation);
                     Err(format!("unknown operation: {operation}").into())
                 }
             },
-            "net" => match operation {
-                "v1/dns_lookup_host" => {
-                    let host: String = serde_json::from_slice(payload.to_vec().as_ref())?;
+            "net" => match dns_request(operation, payload)? {
+                Some(request) => {
                     debug!(
                         eval_ctx.policy_id,
                         binding,
                         operation,
-                        ?host,
+                        ?request,
                         "Sending request via callback channel"
                     );
                     let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
                     let req = CallbackRequest {
-                        request: CallbackRequestType::DNSLookupHost { host },
+                        request,
                         response_channel: tx,
                     };
                     send_request_and_wait_for_response(
@@ -179,7 +383,7 @@ pub(crate) fn host_callback(
                         eval_ctx,
                     )
                 }
-                _ => {
+                None => {
                     error!("unknown operation: {}", operation);
                     Err(format!("unknown operation: {operation}").into())
                 }
@@ -196,6 +400,24 @@ pub(crate) fn host_callback(
                     };
                     Ok(serde_json::to_vec(&response)?)
                 }
+                "v1/verify_signature" => {
+                    let req: SignatureVerificationRequest =
+                        serde_json::from_slice(payload.to_vec().as_ref())?;
+                    let response: SignatureVerificationResponse = match verify_signature(req) {
+                        Ok(b) => b.into(),
+                        Err(e) => return Err(format!("Error when verifying signature: {e}").into()),
+                    };
+                    Ok(serde_json::to_vec(&response)?)
+                }
+                "v1/verify_jwt" => {
+                    let req: JwtVerificationRequest =
+                        serde_json::from_slice(payload.to_vec().as_ref())?;
+                    let response = match verify_jwt(req) {
+                        Ok(response) => response,
+                        Err(e) => return Err(format!("Error when verifying JWT: {e}").into()),
+                    };
+                    Ok(serde_json::to_vec(&response)?)
+                }
                 _ => {
                     error!(namespace, operation, "unknown operation");
                     Err(format!("unknown operation: {operation}").into())
@@ -218,6 +440,8 @@ pub(crate) fn host_callback(
                                 req.kind).into());
                     }
 
+                    let projection =
+                        eval_ctx.kubernetes_resource_projection(&req.api_version, &req.kind);
                     debug!(
                         eval_ctx.policy_id,
                         binding,
@@ -227,7 +451,14 @@ pub(crate) fn host_callback(
                     );
                     let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
                     let req = CallbackRequest {
-                        request: CallbackRequestType::from(req),
+                        request: CallbackRequestType::KubernetesListResourceNamespace {
+                            api_version: req.api_version,
+                            kind: req.kind,
+                            namespace: req.namespace,
+                            label_selector: req.label_selector,
+                            field_selector: req.field_selector,
+                            projection,
+                        },
                         response_channel: tx,
                     };
                     send_request_and_wait_for_response(
@@ -254,6 +485,8 @@ pub(crate) fn host_callback(
                                 req.kind).into());
                     }
 
+                    let projection =
+                        eval_ctx.kubernetes_resource_projection(&req.api_version, &req.kind);
                     debug!(
                         eval_ctx.policy_id,
                         binding,
@@ -263,7 +496,13 @@ pub(crate) fn host_callback(
                     );
                     let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
                     let req = CallbackRequest {
-                        request: CallbackRequestType::from(req),
+                        request: CallbackRequestType::KubernetesListResourceAll {
+                            api_version: req.api_version,
+                            kind: req.kind,
+                            label_selector: req.label_selector,
+                            field_selector: req.field_selector,
+                            projection,
+                        },
                         response_channel: tx,
                     };
                     send_request_and_wait_for_response(
@@ -290,6 +529,8 @@ pub(crate) fn host_callback(
                                 req.kind).into());
                     }
 
+                    let projection =
+                        eval_ctx.kubernetes_resource_projection(&req.api_version, &req.kind);
                     debug!(
                         eval_ctx.policy_id,
                         binding,
@@ -299,7 +540,14 @@ pub(crate) fn host_callback(
                     );
                     let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
                     let req = CallbackRequest {
-                        request: CallbackRequestType::from(req),
+                        request: CallbackRequestType::KubernetesGetResource {
+                            api_version: req.api_version,
+                            kind: req.kind,
+                            name: req.name,
+                            namespace: req.namespace,
+                            projection,
+                            disable_cache: req.disable_cache,
+                        },
                         response_channel: tx,
                     };
                     send_request_and_wait_for_response(
@@ -336,6 +584,50 @@ pub(crate) fn host_callback(
                         eval_ctx,
                     )
                 }
+                "dry_run" => {
+                    let req: DryRunRequest = serde_json::from_slice(payload.to_vec().as_ref())?;
+                    let api_version = req.object["apiVersion"].as_str().unwrap_or_default();
+                    let kind = req.object["kind"].as_str().unwrap_or_default();
+
+                    if !eval_ctx.can_dry_run_kubernetes_resource(api_version, kind) {
+                        error!(
+                            policy = eval_ctx.policy_id,
+                            resource_requested = format!("{api_version}/{kind}"),
+                            resources_allowed = ?eval_ctx.dry_run_resources_allow_list,
+                            "Policy tried to dry-run a Kubernetes resource it doesn't have access to");
+                        return Err(format!(
+                                "Policy has not been granted dry-run access to Kubernetes {api_version}/{kind} resources. The violation has been reported.").into());
+                    }
+                    if EVALUATING_DRY_RUN_REQUEST.get() {
+                        error!(
+                            policy = eval_ctx.policy_id,
+                            resource_requested = format!("{api_version}/{kind}"),
+                            "Policy tried to dry-run a Kubernetes resource while evaluating a dry-run request");
+                        return Err("Policies cannot dry-run Kubernetes resources while evaluating a dry-run request. The violation has been reported.".into());
+                    }
+
+                    debug!(
+                        eval_ctx.policy_id,
+                        binding,
+                        namespace,
+                        operation,
+                        resource = format!("{api_version}/{kind}"),
+                        "Sending request via callback channel"
+                    );
+                    let (tx, rx) = oneshot::channel::<Result<CallbackResponse>>();
+                    let req = CallbackRequest {
+                        request: CallbackRequestType::from(req),
+                        response_channel: tx,
+                    };
+                    send_request_and_wait_for_response(
+                        &eval_ctx.policy_id,
+                        binding,
+                        operation,
+                        req,
+                        rx,
+                        eval_ctx,
+                    )
+                }
                 _ => {
                     error!(namespace, operation, "unknown operation");
                     Err(format!("unknown operation: {operation}").into())
@@ -353,6 +645,7 @@ pub(crate) fn host_callback(
                     kind: "Ingress".to_string(),
                     label_selector: None,
                     field_selector: None,
+                    projection: None,
                 };
 
                 warn!(
@@ -380,6 +673,7 @@ pub(crate) fn host_callback(
                     kind: "Namespace".to_string(),
                     label_selector: None,
                     field_selector: None,
+                    projection: None,
                 };
 
                 warn!(
@@ -407,6 +701,7 @@ pub(crate) fn host_callback(
                     kind: "Service".to_string(),
                     label_selector: None,
                     field_selector: None,
+                    projection: None,
                 };
 
                 warn!(
@@ -440,6 +735,38 @@ pub(crate) fn host_callback(
     }
 }
 
+/// Build the request of a DNS host capability out of the payload sent by the
+/// policy, `None` is returned when the operation is not known
+fn dns_request(operation: &str, payload: &[u8]) -> serde_json::Result<Option<CallbackRequestType>> {
+    let request = match operation {
+        "v1/dns_lookup_host" => CallbackRequestType::DNSLookupHost {
+            host: serde_json::from_slice(payload)?,
+        },
+        "v1/dns_reverse_lookup" => {
+            let req: ReverseLookupRequest = serde_json::from_slice(payload)?;
+            CallbackRequestType::DNSReverseLookup { ip: req.ip }
+        }
+        "v1/dns_lookup_srv" => {
+            let req: RecordLookupRequest = serde_json::from_slice(payload)?;
+            CallbackRequestType::DNSLookupSrv { name: req.name }
+        }
+        "v1/dns_lookup_txt" => {
+            let req: RecordLookupRequest = serde_json::from_slice(payload)?;
+            CallbackRequestType::DNSLookupTxt { name: req.name }
+        }
+        "v1/dns_lookup_cidr" => {
+            let req: CidrLookupRequest = serde_json::from_slice(payload)?;
+            CallbackRequestType::DNSLookupCidr {
+                host: req.host,
+                cidrs: req.cidrs,
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(request))
+}
+
 fn send_request_and_wait_for_response(
     policy_id: &str,
     binding: &str,
@@ -494,3 +821,218 @@ fn send_request_and_wait_for_response(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy_metadata::ContextAwareResource;
+    use rstest::rstest;
+    use std::collections::BTreeSet;
+
+    #[rstest]
+    #[case::dns_lookup("kubewarden", "net", "v1/dns_lookup_host")]
+    #[case::oci_manifest("kubewarden", "oci", "v1/oci_manifest")]
+    #[case::crypto("kubewarden", "crypto", "v1/is_certificate_trusted")]
+    #[case::kubernetes("kubewarden", "kubernetes", "get_resource")]
+    #[case::legacy_kubernetes("kubernetes", "ingresses", "list")]
+    fn host_capability_not_granted(
+        #[case] binding: &str,
+        #[case] namespace: &str,
+        #[case] operation: &str,
+    ) {
+        let eval_ctx = Arc::new(EvaluationContext {
+            policy_id: "test".to_owned(),
+            host_capabilities_allow_list: Some(BTreeSet::new()),
+            ..Default::default()
+        });
+
+        let error = host_callback(binding, namespace, operation, b"{}", &eval_ctx).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("The violation has been reported"));
+    }
+
+    #[rstest]
+    #[case::known_operation("kubewarden", HostCapabilityNamespace::Oci, "v1/verify", "v1/verify")]
+    #[case::dns_operation(
+        "kubewarden",
+        HostCapabilityNamespace::Net,
+        "v1/dns_lookup_cidr",
+        "v1/dns_lookup_cidr"
+    )]
+    #[case::operation_of_another_namespace(
+        "kubewarden",
+        HostCapabilityNamespace::Crypto,
+        "dry_run",
+        "unknown"
+    )]
+    #[case::unknown_operation(
+        "kubewarden",
+        HostCapabilityNamespace::Kubernetes,
+        "some-random-string",
+        "unknown"
+    )]
+    #[case::legacy_kubernetes(
+        "kubernetes",
+        HostCapabilityNamespace::Kubernetes,
+        "list",
+        "list_resources_all"
+    )]
+    fn host_capability_operation_label(
+        #[case] binding: &str,
+        #[case] capability: HostCapabilityNamespace,
+        #[case] operation: &str,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            host_capability_operation(binding, capability, operation),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::nothing_allowed(BTreeSet::new())]
+    #[case::other_resource(BTreeSet::from([ContextAwareResource {
+        api_version: "v1".to_owned(),
+        kind: "ConfigMap".to_owned(),
+        projection: None,
+    }]))]
+    fn dry_run_not_granted(#[case] allow_list: BTreeSet<ContextAwareResource>) {
+        let eval_ctx = Arc::new(EvaluationContext {
+            policy_id: "test".to_owned(),
+            // read access doesn't grant dry-run access
+            ctx_aware_resources_allow_list: BTreeSet::from([ContextAwareResource {
+                api_version: "v1".to_owned(),
+                kind: "Pod".to_owned(),
+                projection: None,
+            }]),
+            dry_run_resources_allow_list: allow_list,
+            ..Default::default()
+        });
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "object": {
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {"name": "nginx"}
+            }
+        }))
+        .unwrap();
+
+        let error =
+            host_callback("kubewarden", "kubernetes", "dry_run", &payload, &eval_ctx).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Policy has not been granted dry-run access to Kubernetes v1/Pod resources. The violation has been reported."
+        );
+    }
+
+    #[test]
+    fn dry_run_refused_while_evaluating_dry_run_request() {
+        let eval_ctx = Arc::new(EvaluationContext {
+            policy_id: "test".to_owned(),
+            dry_run_resources_allow_list: BTreeSet::from([ContextAwareResource {
+                api_version: "v1".to_owned(),
+                kind: "Pod".to_owned(),
+                projection: None,
+            }]),
+            ..Default::default()
+        });
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "object": {
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {"name": "nginx", "namespace": "default"}
+            }
+        }))
+        .unwrap();
+        let refusal = "Policies cannot dry-run Kubernetes resources while evaluating a dry-run request. The violation has been reported.";
+        let request = |dry_run: bool| {
+            ValidateRequest::AdmissionRequest(Box::new(
+                serde_json::from_value(serde_json::json!({
+                    "uid": "uid",
+                    "kind": {"group": "", "version": "v1", "kind": "Pod"},
+                    "resource": {"group": "", "version": "v1", "resource": "pods"},
+                    "operation": "CREATE",
+                    "userInfo": {},
+                    "dryRun": dry_run,
+                }))
+                .unwrap(),
+            ))
+        };
+
+        {
+            let _guard = EvaluatedRequestGuard::new(&request(true));
+            let error = host_callback("kubewarden", "kubernetes", "dry_run", &payload, &eval_ctx)
+                .unwrap_err();
+            assert_eq!(error.to_string(), refusal);
+        }
+
+        // the dry-run request is no longer being evaluated: the request goes
+        // further and fails because there's no callback channel
+        let error =
+            host_callback("kubewarden", "kubernetes", "dry_run", &payload, &eval_ctx).unwrap_err();
+        assert_ne!(error.to_string(), refusal);
+
+        let _guard = EvaluatedRequestGuard::new(&request(false));
+        let error =
+            host_callback("kubewarden", "kubernetes", "dry_run", &payload, &eval_ctx).unwrap_err();
+        assert_ne!(error.to_string(), refusal);
+    }
+
+    #[test]
+    fn tracing_is_always_allowed() {
+        let eval_ctx = Arc::new(EvaluationContext {
+            policy_id: "test".to_owned(),
+            host_capabilities_allow_list: Some(BTreeSet::new()),
+            ..Default::default()
+        });
+
+        assert!(host_callback("kubewarden", "tracing", "log", b"{}", &eval_ctx).is_ok());
+    }
+
+    #[rstest]
+    #[case::lookup_host(
+        "v1/dns_lookup_host",
+        r#""kubewarden.io""#,
+        CallbackRequestType::DNSLookupHost { host: "kubewarden.io".to_owned() }
+    )]
+    #[case::reverse_lookup(
+        "v1/dns_reverse_lookup",
+        r#"{"ip": "10.2.3.4"}"#,
+        CallbackRequestType::DNSReverseLookup { ip: "10.2.3.4".to_owned() }
+    )]
+    #[case::srv_lookup(
+        "v1/dns_lookup_srv",
+        r#"{"name": "_ldap._tcp.example.com"}"#,
+        CallbackRequestType::DNSLookupSrv { name: "_ldap._tcp.example.com".to_owned() }
+    )]
+    #[case::txt_lookup(
+        "v1/dns_lookup_txt",
+        r#"{"name": "example.com"}"#,
+        CallbackRequestType::DNSLookupTxt { name: "example.com".to_owned() }
+    )]
+    #[case::cidr_lookup(
+        "v1/dns_lookup_cidr",
+        r#"{"host": "example.com", "cidrs": ["10.0.0.0/8"]}"#,
+        CallbackRequestType::DNSLookupCidr {
+            host: "example.com".to_owned(),
+            cidrs: vec!["10.0.0.0/8".to_owned()],
+        }
+    )]
+    fn dns_requests(
+        #[case] operation: &str,
+        #[case] payload: &str,
+        #[case] expected: CallbackRequestType,
+    ) {
+        assert_eq!(
+            dns_request(operation, payload.as_bytes()).unwrap(),
+            Some(expected)
+        );
+    }
+
+    #[test]
+    fn dns_request_errors() {
+        assert_eq!(dns_request("v1/unknown", b"{}").unwrap(), None);
+        assert!(dns_request("v1/dns_lookup_srv", b"{}").is_err());
+    }
+}
@@ -1,22 +1,27 @@
 use kube::api::ObjectList;
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::{
-    callback_requests::{CallbackRequest, CallbackRequestType, CallbackResponse},
+    callback_requests::{
+        CallbackRequest, CallbackRequestType, CallbackResponse, KubernetesResourceChange,
+        KubernetesResourceChanges,
+    },
     policy_metadata::ContextAwareResource,
     runtimes::rego::{
         errors::{RegoRuntimeError, Result},
+        gatekeeper_inventory_cache::CachedInventory,
         opa_inventory::OpaInventory,
     },
 };
 
-#[derive(serde::Serialize)]
-#[serde(untagged)]
 pub(crate) enum KubernetesContext {
     Empty,
     Opa(OpaInventory),
-    Gatekeeper(Vec<u8>),
+    Gatekeeper(Arc<CachedInventory>),
 }
 
 /// Uses the callback channel to get all the Kubernetes resources defined inside of
@@ -48,6 +53,7 @@ fn get_all_resources_by_type(
         kind: resource_type.kind.to_owned(),
         label_selector: None,
         field_selector: None,
+        projection: resource_type.projection.to_owned(),
     };
 
     let response = make_request_via_callback_channel(req_type, callback_channel)?;
@@ -83,6 +89,7 @@ fn has_resource_changed_since(
         kind: resource_type.kind.to_owned(),
         label_selector: None,
         field_selector: None,
+        projection: resource_type.projection.to_owned(),
         since,
     };
 
@@ -90,6 +97,91 @@ fn has_resource_changed_since(
     serde_json::from_slice::<bool>(&response.payload).map_err(RegoRuntimeError::CallbackConvertBool)
 }
 
+/// The changes to the "list all resources" result of an allowed resource
+#[derive(Debug)]
+pub(crate) struct AllowedResourceChanges {
+    /// The changes, sorted from the oldest to the newest one
+    pub changes: Vec<KubernetesResourceChange>,
+    /// The sequence number to ask the next changes from
+    pub next_sequence: u64,
+}
+
+/// For each allowed resource, get the changes to the "list all resources" result since
+/// the given sequence number of the change log of the reflector tracking the resource.
+///
+/// `None` is returned when the changes of at least one of the resources are not known,
+/// in that case the whole list of resources has to be fetched again.
+pub(crate) fn get_allowed_resources_changes_since(
+    callback_channel: &mpsc::Sender<CallbackRequest>,
+    allowed_resources: &BTreeSet<ContextAwareResource>,
+    since: &BTreeMap<ContextAwareResource, u64>,
+) -> Result<Option<BTreeMap<ContextAwareResource, AllowedResourceChanges>>> {
+    let mut changes_by_resource = BTreeMap::new();
+
+    for resource in allowed_resources {
+        let Some(since) = since.get(resource) else {
+            return Ok(None);
+        };
+        match get_resource_changes_since(callback_channel, resource, Some(*since))? {
+            KubernetesResourceChanges::Resync => return Ok(None),
+            KubernetesResourceChanges::Changes {
+                changes,
+                next_sequence,
+            } => {
+                changes_by_resource.insert(
+                    resource.to_owned(),
+                    AllowedResourceChanges {
+                        changes,
+                        next_sequence,
+                    },
+                );
+            }
+        }
+    }
+
+    Ok(Some(changes_by_resource))
+}
+
+/// For each allowed resource, get the sequence number the next change of the
+/// "list all resources" result will get.
+///
+/// The resources whose changes are not tracked yet are left out.
+pub(crate) fn get_allowed_resources_next_sequences(
+    callback_channel: &mpsc::Sender<CallbackRequest>,
+    allowed_resources: &BTreeSet<ContextAwareResource>,
+) -> Result<BTreeMap<ContextAwareResource, u64>> {
+    let mut sequences = BTreeMap::new();
+
+    for resource in allowed_resources {
+        if let KubernetesResourceChanges::Changes { next_sequence, .. } =
+            get_resource_changes_since(callback_channel, resource, None)?
+        {
+            sequences.insert(resource.to_owned(), next_sequence);
+        }
+    }
+
+    Ok(sequences)
+}
+
+fn get_resource_changes_since(
+    callback_channel: &mpsc::Sender<CallbackRequest>,
+    resource: &ContextAwareResource,
+    since: Option<u64>,
+) -> Result<KubernetesResourceChanges> {
+    let req_type = CallbackRequestType::KubernetesListResourceAllChangesSince {
+        api_version: resource.api_version.to_owned(),
+        kind: resource.kind.to_owned(),
+        label_selector: None,
+        field_selector: None,
+        projection: resource.projection.to_owned(),
+        since,
+    };
+
+    let response = make_request_via_callback_channel(req_type, callback_channel)?;
+    serde_json::from_slice::<KubernetesResourceChanges>(&response.payload)
+        .map_err(RegoRuntimeError::CallbackConvertResourceChanges)
+}
+
 /// Creates a map that has ContextAwareResource as key, and its plural name as value.
 /// For example, the key for {`apps/v1`, `Deployment`} will have `deployments` as value.
 /// The map is built by making request via the given callback channel.
@@ -177,6 +269,7 @@ pub(crate) mod tests {
         let resource = ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Service".to_string(),
+            projection: None,
         };
         let expected_resource = resource.clone();
         let services = [
@@ -196,11 +289,13 @@ pub(crate) mod tests {
                     kind,
                     label_selector,
                     field_selector,
+                    projection,
                 } => {
                     assert_eq!(api_version, expected_resource.api_version);
                     assert_eq!(kind, expected_resource.kind);
                     assert!(label_selector.is_none());
                     assert!(field_selector.is_none());
+                    assert!(projection.is_none());
                 }
                 _ => {
                     panic!("not the expected request type");
@@ -231,6 +326,7 @@ pub(crate) mod tests {
         let resource = ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Service".to_string(),
+            projection: None,
         };
         let plural_name = "services";
 
@@ -273,24 +369,24 @@ pub(crate) mod tests {
     }
     #[rstest]
     #[case(
-        HashMap::<ContextAwareResource, bool>::from([(ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string()}, true)]),
+        HashMap::<ContextAwareResource, bool>::from([(ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string(), projection: None}, true)]),
         true,
     )]
     #[case(
-        HashMap::<ContextAwareResource, bool>::from([(ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string()}, false)]),
+        HashMap::<ContextAwareResource, bool>::from([(ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string(), projection: None}, false)]),
         false,
     )]
     #[case(
         HashMap::<ContextAwareResource, bool>::from([
-            (ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string()}, true),
-            (ContextAwareResource{api_version: "v1".to_string(), kind: "Pod".to_string()}, false),
+            (ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string(), projection: None}, true),
+            (ContextAwareResource{api_version: "v1".to_string(), kind: "Pod".to_string(), projection: None}, false),
         ]),
         true,
     )]
     #[case(
         HashMap::<ContextAwareResource, bool>::from([
-            (ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string()}, false),
-            (ContextAwareResource{api_version: "v1".to_string(), kind: "Pod".to_string()}, false),
+            (ContextAwareResource{api_version: "v1".to_string(), kind: "Service".to_string(), projection: None}, false),
+            (ContextAwareResource{api_version: "v1".to_string(), kind: "Pod".to_string(), projection: None}, false),
         ]),
         false,
     )]
@@ -315,14 +411,17 @@ pub(crate) mod tests {
                     kind,
                     label_selector,
                     field_selector,
+                    projection,
                     since: _,
                 } => {
                     let resource = ContextAwareResource {
                         api_version: api_version.clone(),
                         kind: kind.clone(),
+                        projection: None,
                     };
                     assert!(label_selector.is_none());
                     assert!(field_selector.is_none());
+                    assert!(projection.is_none());
 
                     expected_resources_with_change_status
                         .get(&resource)
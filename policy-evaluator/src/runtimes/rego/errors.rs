@@ -13,6 +13,9 @@ pub enum RegoRuntimeError {
     #[error("cannot convert callback response into a boolean: {0}")]
     CallbackConvertBool(#[source] serde_json::Error),
 
+    #[error("cannot convert callback response into a list of resource changes: {0}")]
+    CallbackConvertResourceChanges(#[source] serde_json::Error),
+
     #[error("error sending request over callback channel: {0}")]
     CallbackSend(String), // TODO same as CallbackRequest?
 
@@ -59,6 +59,7 @@ use kube::api::ObjectList;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+use crate::callback_requests::KubernetesResourceChange;
 use crate::policy_metadata::ContextAwareResource;
 use crate::runtimes::rego::errors::{RegoRuntimeError, Result};
 
@@ -77,6 +78,20 @@ impl ResourcesByName {
         self.0.insert(name, obj.to_owned());
         Ok(())
     }
+
+    fn unregister(&mut self, obj: &kube::core::DynamicObject) -> Result<()> {
+        let name = obj
+            .metadata
+            .name
+            .as_ref()
+            .ok_or(RegoRuntimeError::GatekeeperInventoryMissingName)?;
+        self.0.remove(name);
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// A wrapper around a dictionary that has a Kubernetes Kind (e.g. `Pod`)
@@ -95,6 +110,24 @@ impl ResourcesByKind {
             .or_default()
             .register(obj)
     }
+
+    fn unregister(
+        &mut self,
+        obj: &kube::core::DynamicObject,
+        resource: &ContextAwareResource,
+    ) -> Result<()> {
+        if let Some(by_name) = self.0.get_mut(&resource.kind) {
+            by_name.unregister(obj)?;
+            if by_name.is_empty() {
+                self.0.remove(&resource.kind);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// A wrapper around a dictionary that has a Kubernetes GroupVersion (e.g. `apps/v1`)
@@ -113,6 +146,24 @@ impl ResourcesByGroupVersion {
             .or_default()
             .register(obj, resource)
     }
+
+    fn unregister(
+        &mut self,
+        obj: &kube::core::DynamicObject,
+        resource: &ContextAwareResource,
+    ) -> Result<()> {
+        if let Some(by_kind) = self.0.get_mut(&resource.api_version) {
+            by_kind.unregister(obj, resource)?;
+            if by_kind.is_empty() {
+                self.0.remove(&resource.api_version);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// A wrapper around a dictionary that has
@@ -134,6 +185,25 @@ impl ResourcesByNamespace {
             .ok_or(RegoRuntimeError::GatekeeperInventoryMissingNamespace)?;
         self.0.entry(namespace).or_default().register(obj, resource)
     }
+
+    fn unregister(
+        &mut self,
+        obj: &kube::core::DynamicObject,
+        resource: &ContextAwareResource,
+    ) -> Result<()> {
+        let namespace = obj
+            .metadata
+            .namespace
+            .as_ref()
+            .ok_or(RegoRuntimeError::GatekeeperInventoryMissingNamespace)?;
+        if let Some(by_group_version) = self.0.get_mut(namespace) {
+            by_group_version.unregister(obj, resource)?;
+            if by_group_version.is_empty() {
+                self.0.remove(namespace);
+            }
+        }
+        Ok(())
+    }
 }
 
 /// A struct holding the Kubernetes context aware data in a format that is compabible with what
@@ -179,6 +249,29 @@ impl GatekeeperInventory {
             }
         }
     }
+
+    fn unregister(
+        &mut self,
+        obj: &kube::core::DynamicObject,
+        resource: &ContextAwareResource,
+    ) -> Result<()> {
+        match &obj.metadata.namespace {
+            Some(_) => self.namespaced_resources.unregister(obj, resource),
+            None => self.cluster_resources.unregister(obj, resource),
+        }
+    }
+
+    /// Update the inventory with a change seen by the watch of the given resource type
+    pub(crate) fn apply_change(
+        &mut self,
+        change: &KubernetesResourceChange,
+        resource: &ContextAwareResource,
+    ) -> Result<()> {
+        match change {
+            KubernetesResourceChange::Applied(obj) => self.register(obj, resource),
+            KubernetesResourceChange::Deleted(obj) => self.unregister(obj, resource),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -206,6 +299,7 @@ mod tests {
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Service".to_string(),
+                projection: None,
             },
             services_list,
         );
@@ -225,6 +319,7 @@ mod tests {
             ContextAwareResource {
                 api_version: "apps/v1".to_string(),
                 kind: "Deployment".to_string(),
+                projection: None,
             },
             deployments_list,
         );
@@ -238,6 +333,7 @@ mod tests {
             ContextAwareResource {
                 api_version: "v1".to_string(),
                 kind: "Namespace".to_string(),
+                projection: None,
             },
             namespaces_list,
         );
@@ -280,4 +376,72 @@ mod tests {
         let inventory_json = serde_json::to_value(inventory).unwrap();
         assert_json_eq!(inventory_json, expected);
     }
+
+    #[test]
+    fn apply_changes() {
+        let service = ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Service".to_string(),
+            projection: None,
+        };
+        let namespace = ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Namespace".to_string(),
+            projection: None,
+        };
+        let kube_dns =
+            dynamic_object_from_fixture("services", Some("kube-system"), "kube-dns").unwrap();
+        let metrics_server =
+            dynamic_object_from_fixture("services", Some("kube-system"), "metrics-server").unwrap();
+        let kube_system = dynamic_object_from_fixture("namespaces", None, "kube-system").unwrap();
+
+        let kube_resources = BTreeMap::from([(
+            service.clone(),
+            object_list_from_dynamic_objects(std::slice::from_ref(&kube_dns)).unwrap(),
+        )]);
+        let mut inventory = GatekeeperInventory::new(&kube_resources).unwrap();
+
+        for (change, resource) in [
+            (
+                KubernetesResourceChange::Applied(metrics_server.clone()),
+                &service,
+            ),
+            (KubernetesResourceChange::Deleted(kube_dns), &service),
+            (
+                KubernetesResourceChange::Applied(kube_system.clone()),
+                &namespace,
+            ),
+        ] {
+            inventory.apply_change(&change, resource).unwrap();
+        }
+
+        let expected = serde_json::json!({
+            "cluster": {
+                "v1": {
+                    "Namespace": {
+                        "kube-system": kube_system.clone(),
+                    }
+                }
+            },
+            "namespace": {
+                "kube-system": {
+                    "v1": {
+                        "Service": {
+                            "metrics-server": metrics_server.clone(),
+                        }
+                    }
+                }
+            }
+        });
+        assert_json_eq!(serde_json::to_value(&inventory).unwrap(), expected);
+
+        // removing the last object of a namespace prunes the whole namespace
+        inventory
+            .apply_change(&KubernetesResourceChange::Deleted(metrics_server), &service)
+            .unwrap();
+        inventory
+            .apply_change(&KubernetesResourceChange::Deleted(kube_system), &namespace)
+            .unwrap();
+        assert_eq!(inventory, GatekeeperInventory::default());
+    }
 }
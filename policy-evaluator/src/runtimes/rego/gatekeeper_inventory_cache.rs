@@ -1,16 +1,22 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{BTreeSet, HashMap},
-    sync::{Arc, RwLock},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
 };
 use tokio::{sync::mpsc, time::Instant};
+use tracing::warn;
 
 use crate::runtimes::rego::context_aware::{
-    get_allowed_resources, have_allowed_resources_changed_since_instant,
+    get_allowed_resources, get_allowed_resources_changes_since,
+    get_allowed_resources_next_sequences, have_allowed_resources_changed_since_instant,
 };
 use crate::{
     callback_requests::CallbackRequest,
+    metrics::{record_gatekeeper_inventory_build, GatekeeperInventoryBuildType},
     policy_metadata::ContextAwareResource,
     runtimes::rego::{
         errors::{RegoRuntimeError, Result},
@@ -22,6 +28,28 @@ lazy_static! {
     /// Global cache for the Gatekeeper inventories
     pub(crate) static ref GATEKEEPER_INVENTORY_CACHE: GateKeeperInventoryCache =
         GateKeeperInventoryCache::new();
+
+    /// Serializes the inventories that have been updated incrementally. A single
+    /// worker takes care of all of them, one at a time
+    static ref INVENTORY_SERIALIZER: std::sync::mpsc::Sender<SerializationJob> = {
+        let (tx, rx) = std::sync::mpsc::channel::<SerializationJob>();
+        std::thread::Builder::new()
+            .name("gatekeeper-inventory-serializer".to_string())
+            .spawn(move || {
+                for job in rx {
+                    job.entry.serialize(job.resources);
+                }
+            })
+            .expect("cannot spawn the Gatekeeper inventory serializer");
+        tx
+    };
+}
+
+/// An inventory waiting to be serialized again
+struct SerializationJob {
+    entry: Arc<InventoryEntry>,
+    /// The value of the metric attribute that identifies the inventory
+    resources: String,
 }
 
 /// A serialized Gatekeeper inventory. Building and serializing the inventory can
@@ -36,12 +64,82 @@ pub(crate) struct CachedInventory {
 }
 
 /// This defines how Gatekeeper policy expects the `input` attribute to be structured.
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Default)]
 struct GatekeeperInput {
     /// The actual inventory
     inventory: GatekeeperInventory,
 }
 
+/// The inventory kept in sync with the changes seen by the reflectors
+struct SyncedInventory {
+    input: GatekeeperInput,
+    /// For each resource, the sequence number of the first change not applied yet,
+    /// as returned by the change log of its reflector. The inventory must be
+    /// created again when a resource is missing
+    synced_at: BTreeMap<ContextAwareResource, u64>,
+    /// The instant right before the changes have been last fetched
+    cache_time: Instant,
+}
+
+/// An entry of the cache.
+///
+/// The deserialized inventory is kept around, this allows to apply the changes
+/// seen by the reflectors without having to fetch all the Kubernetes resources again.
+/// The serialized version is regenerated in the background, while that happens the
+/// previous serialized version is served to the policies.
+struct InventoryEntry {
+    synced: Mutex<SyncedInventory>,
+    serialized: RwLock<Arc<CachedInventory>>,
+    /// Set while the serialized version is being regenerated
+    regenerating: AtomicBool,
+}
+
+impl InventoryEntry {
+    fn new(
+        input: GatekeeperInput,
+        synced_at: BTreeMap<ContextAwareResource, u64>,
+        cached_inventory: Arc<CachedInventory>,
+    ) -> Self {
+        Self {
+            synced: Mutex::new(SyncedInventory {
+                input,
+                synced_at,
+                cache_time: cached_inventory.cache_time,
+            }),
+            serialized: RwLock::new(cached_inventory),
+            regenerating: AtomicBool::new(false),
+        }
+    }
+
+    fn cached_inventory(&self) -> Arc<CachedInventory> {
+        self.serialized.read().unwrap().clone()
+    }
+
+    /// Serialize the synced inventory and serve it from now on
+    fn serialize(&self, resources: String) {
+        let synced = self.synced.lock().unwrap();
+        // the changes applied from now on need another serialization
+        self.regenerating.store(false, Ordering::Release);
+
+        let start = std::time::Instant::now();
+        match serde_json::to_vec(&synced.input) {
+            Ok(data) => {
+                record_gatekeeper_inventory_build(
+                    start.elapsed(),
+                    data.len(),
+                    GatekeeperInventoryBuildType::Incremental,
+                    resources,
+                );
+                *self.serialized.write().unwrap() = Arc::new(CachedInventory {
+                    data,
+                    cache_time: synced.cache_time,
+                });
+            }
+            Err(error) => warn!(?error, "cannot serialize Gatekeeper inventory"),
+        }
+    }
+}
+
 /// Hold all the inventories for the Gatekeeper runtime
 ///
 /// The inventories are stored inside of a dictionary that has the list of resources
@@ -56,7 +154,7 @@ struct GatekeeperInput {
 pub(crate) struct GateKeeperInventoryCache {
     // Note: the Arc is used to make some `clone` invocation faster. The `clone` operations
     // are required because the whole `inventories` variable is located inside of a RwLock
-    inventories: RwLock<HashMap<BTreeSet<ContextAwareResource>, Arc<CachedInventory>>>,
+    inventories: RwLock<HashMap<BTreeSet<ContextAwareResource>, Arc<InventoryEntry>>>,
 }
 
 impl GateKeeperInventoryCache {
@@ -68,32 +166,82 @@ impl GateKeeperInventoryCache {
 
     /// This function returns the serialized inventory for the given set of resources.
     /// The inventory is computed and serialized only if it's not already present in the cache.
-    /// The inventory is also recreated if the set of resources has changed since the time
-    /// the inventory was computed
+    ///
+    /// When the resources changed since the time the inventory was computed, the changes
+    /// seen by the reflectors are applied to the inventory, which is then serialized again
+    /// in the background. Meanwhile the previous version of the inventory is returned.
+    /// The inventory is recreated from scratch only when the changes are not known.
     pub fn get_inventory(
         &self,
         callback_channel: &mpsc::Sender<CallbackRequest>,
         ctx_aware_resources: &BTreeSet<ContextAwareResource>,
-    ) -> Result<Vec<u8>> {
-        let inventory = {
+    ) -> Result<Arc<CachedInventory>> {
+        let entry = {
             let inventories = self.inventories.read().unwrap();
             inventories.get(ctx_aware_resources).cloned()
         };
-        let inventory = match inventory {
-            None => self.create_and_register_inventory(ctx_aware_resources, callback_channel),
-            Some(cached_inventory) => {
-                if have_allowed_resources_changed_since_instant(
-                    callback_channel,
-                    ctx_aware_resources,
-                    cached_inventory.cache_time,
-                )? {
-                    self.create_and_register_inventory(ctx_aware_resources, callback_channel)
-                } else {
-                    Ok(cached_inventory)
-                }
+        let entry = match entry {
+            None => {
+                return self.create_and_register_inventory(ctx_aware_resources, callback_channel)
             }
-        }?;
-        Ok(inventory.data.clone())
+            Some(entry) => entry,
+        };
+
+        let cached_inventory = entry.cached_inventory();
+        if entry.regenerating.load(Ordering::Acquire)
+            || !have_allowed_resources_changed_since_instant(
+                callback_channel,
+                ctx_aware_resources,
+                cached_inventory.cache_time,
+            )?
+        {
+            return Ok(cached_inventory);
+        }
+
+        let mut synced = match entry.synced.try_lock() {
+            Ok(synced) => synced,
+            // somebody else is already updating the inventory
+            Err(_) => return Ok(cached_inventory),
+        };
+
+        let now = Instant::now();
+        let changes = match get_allowed_resources_changes_since(
+            callback_channel,
+            ctx_aware_resources,
+            &synced.synced_at,
+        )? {
+            Some(changes) => changes,
+            None => {
+                drop(synced);
+                return self.create_and_register_inventory(ctx_aware_resources, callback_channel);
+            }
+        };
+
+        for (resource, resource_changes) in &changes {
+            for change in &resource_changes.changes {
+                synced.input.inventory.apply_change(change, resource)?;
+            }
+        }
+        synced.synced_at = changes
+            .into_iter()
+            .map(|(resource, resource_changes)| (resource, resource_changes.next_sequence))
+            .collect();
+        synced.cache_time = now;
+
+        // when the flag is already set, the pending serialization has not started
+        // yet and it will pick up these changes too
+        if !entry.regenerating.swap(true, Ordering::AcqRel) {
+            drop(synced);
+            let job = SerializationJob {
+                entry,
+                resources: resources_label(ctx_aware_resources),
+            };
+            if INVENTORY_SERIALIZER.send(job).is_err() {
+                warn!("the Gatekeeper inventory serializer is not running");
+            }
+        }
+
+        Ok(cached_inventory)
     }
 
     /// Create the inventory and register it in the cache. A prior entry of the inventory is
@@ -104,6 +252,12 @@ impl GateKeeperInventoryCache {
         callback_channel: &mpsc::Sender<CallbackRequest>,
     ) -> Result<Arc<CachedInventory>> {
         let now = Instant::now();
+        // The sequence numbers are fetched before the resources: the changes
+        // recorded in between are applied again later, which is harmless. The
+        // resources that are not tracked yet get a reflector while being listed,
+        // the next update of the inventory creates it again
+        let synced_at =
+            get_allowed_resources_next_sequences(callback_channel, ctx_aware_resources)?;
         let cluster_resources = get_allowed_resources(callback_channel, ctx_aware_resources)?;
         let inventory = GatekeeperInput {
             inventory: GatekeeperInventory::new(&cluster_resources)?,
@@ -113,19 +267,40 @@ impl GateKeeperInventoryCache {
                 .map_err(RegoRuntimeError::GatekeeperInventorySerializationError)?,
             cache_time: now,
         });
-
-        self.inventories
-            .write()
-            .unwrap()
-            .insert(ctx_aware_resources.to_owned(), cached_inventory.clone());
+        record_gatekeeper_inventory_build(
+            now.elapsed(),
+            cached_inventory.data.len(),
+            GatekeeperInventoryBuildType::Full,
+            resources_label(ctx_aware_resources),
+        );
+
+        self.inventories.write().unwrap().insert(
+            ctx_aware_resources.to_owned(),
+            Arc::new(InventoryEntry::new(
+                inventory,
+                synced_at,
+                cached_inventory.clone(),
+            )),
+        );
         Ok(cached_inventory)
     }
 }
 
+/// Build the value of the metric attribute that identifies an inventory
+fn resources_label(ctx_aware_resources: &BTreeSet<ContextAwareResource>) -> String {
+    ctx_aware_resources
+        .iter()
+        .map(|resource| format!("{}/{}", resource.api_version, resource.kind))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
-    use crate::callback_requests::{CallbackRequestType, CallbackResponse};
+    use crate::callback_requests::{
+        CallbackRequestType, CallbackResponse, KubernetesResourceChange, KubernetesResourceChanges,
+    };
     use serial_test::serial;
     use std::collections::BTreeMap;
 
@@ -133,6 +308,27 @@ pub(crate) mod tests {
         dynamic_object_from_fixture, object_list_from_dynamic_objects,
     };
 
+    fn insert_cached_inventory(
+        resources: &BTreeSet<ContextAwareResource>,
+        synced_at: BTreeMap<ContextAwareResource, u64>,
+        cached_inventory: CachedInventory,
+    ) {
+        let mut inventories = GATEKEEPER_INVENTORY_CACHE.inventories.write().unwrap();
+        inventories.insert(
+            resources.clone(),
+            Arc::new(InventoryEntry::new(
+                GatekeeperInput::default(),
+                synced_at,
+                Arc::new(cached_inventory),
+            )),
+        );
+    }
+
+    fn cached_inventory_of(resources: &BTreeSet<ContextAwareResource>) -> Arc<CachedInventory> {
+        let inventories = GATEKEEPER_INVENTORY_CACHE.inventories.read().unwrap();
+        inventories.get(resources).unwrap().cached_inventory()
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[serial]
     async fn test_create_entry_because_cache_does_not_exist() {
@@ -140,6 +336,7 @@ pub(crate) mod tests {
         let resource = ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Service".to_string(),
+            projection: None,
         };
         let expected_resource = resource.clone();
         let services = [
@@ -162,15 +359,33 @@ pub(crate) mod tests {
                         kind,
                         label_selector,
                         field_selector,
+                        projection,
                     } => {
                         assert_eq!(api_version, expected_resource.api_version);
                         assert_eq!(kind, expected_resource.kind);
                         assert!(label_selector.is_none());
                         assert!(field_selector.is_none());
+                        assert!(projection.is_none());
                         CallbackResponse {
                             payload: serde_json::to_vec(&services_list).unwrap(),
                         }
                     }
+                    CallbackRequestType::KubernetesListResourceAllChangesSince {
+                        api_version,
+                        kind,
+                        since,
+                        ..
+                    } => {
+                        assert_eq!(api_version, expected_resource.api_version);
+                        assert_eq!(kind, expected_resource.kind);
+                        assert!(since.is_none());
+
+                        // the resource is not tracked yet
+                        CallbackResponse {
+                            payload: serde_json::to_vec(&KubernetesResourceChanges::Resync)
+                                .unwrap(),
+                        }
+                    }
                     _ => {
                         panic!("not the expected request type");
                     }
@@ -192,11 +407,10 @@ pub(crate) mod tests {
             let cached_inventory = GATEKEEPER_INVENTORY_CACHE
                 .get_inventory(&callback_tx, &resources)
                 .unwrap();
-            assert!(!cached_inventory.is_empty());
+            assert!(!cached_inventory.data.is_empty());
 
             {
-                let inventories = GATEKEEPER_INVENTORY_CACHE.inventories.read().unwrap();
-                let cached_input_json = inventories.get(&resources).unwrap();
+                let cached_input_json = cached_inventory_of(&resources);
                 let actual_inventory =
                     serde_json::from_slice::<GatekeeperInput>(&cached_input_json.data)
                         .unwrap()
@@ -215,6 +429,7 @@ pub(crate) mod tests {
         let resource = ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Service".to_string(),
+            projection: None,
         };
         let expected_resource = resource.clone();
 
@@ -226,13 +441,11 @@ pub(crate) mod tests {
                 .checked_sub(tokio::time::Duration::from_secs(60))
                 .unwrap(),
         };
-        {
-            let mut inventories = GATEKEEPER_INVENTORY_CACHE.inventories.write().unwrap();
-            inventories.insert(
-                resources.clone(),
-                Arc::new(expected_cached_inventory.clone()),
-            );
-        }
+        insert_cached_inventory(
+            &resources,
+            BTreeMap::from([(resource.clone(), 0)]),
+            expected_cached_inventory.clone(),
+        );
 
         tokio::spawn(async move {
             loop {
@@ -246,12 +459,14 @@ pub(crate) mod tests {
                         kind,
                         label_selector,
                         field_selector,
+                        projection,
                         since: _,
                     } => {
                         assert_eq!(api_version, expected_resource.api_version);
                         assert_eq!(kind, expected_resource.kind);
                         assert!(label_selector.is_none());
                         assert!(field_selector.is_none());
+                        assert!(projection.is_none());
 
                         CallbackResponse {
                             payload: serde_json::to_vec(&false).unwrap(),
@@ -270,7 +485,7 @@ pub(crate) mod tests {
             let actual = GATEKEEPER_INVENTORY_CACHE
                 .get_inventory(&callback_tx, &resources)
                 .unwrap();
-            assert_eq!(expected_cached_inventory.data, actual);
+            assert_eq!(expected_cached_inventory.data, actual.data);
         })
         .await
         .unwrap();
@@ -283,6 +498,7 @@ pub(crate) mod tests {
         let resource = ContextAwareResource {
             api_version: "v1".to_string(),
             kind: "Service".to_string(),
+            projection: None,
         };
         let expected_resource = resource.clone();
 
@@ -294,7 +510,7 @@ pub(crate) mod tests {
         ];
         let services_list = object_list_from_dynamic_objects(&services).unwrap();
 
-        let kube_resources = BTreeMap::from([(resource, services_list.clone())]);
+        let kube_resources = BTreeMap::from([(resource.clone(), services_list.clone())]);
         let expected_inventory = GatekeeperInventory::new(&kube_resources).unwrap();
 
         let stale_cached_inventory = CachedInventory {
@@ -304,10 +520,11 @@ pub(crate) mod tests {
                 .unwrap(),
         };
 
-        {
-            let mut inventories = GATEKEEPER_INVENTORY_CACHE.inventories.write().unwrap();
-            inventories.insert(resources.clone(), Arc::new(stale_cached_inventory.clone()));
-        }
+        insert_cached_inventory(
+            &resources,
+            BTreeMap::from([(resource.clone(), 0)]),
+            stale_cached_inventory.clone(),
+        );
 
         tokio::spawn(async move {
             loop {
@@ -321,11 +538,13 @@ pub(crate) mod tests {
                         kind,
                         label_selector,
                         field_selector,
+                        projection,
                     } => {
                         assert_eq!(api_version, expected_resource.api_version);
                         assert_eq!(kind, expected_resource.kind);
                         assert!(label_selector.is_none());
                         assert!(field_selector.is_none());
+                        assert!(projection.is_none());
                         CallbackResponse {
                             payload: serde_json::to_vec(&services_list).unwrap(),
                         }
@@ -335,17 +554,33 @@ pub(crate) mod tests {
                         kind,
                         label_selector,
                         field_selector,
+                        projection,
                         since: _,
                     } => {
                         assert_eq!(api_version, expected_resource.api_version);
                         assert_eq!(kind, expected_resource.kind);
                         assert!(label_selector.is_none());
                         assert!(field_selector.is_none());
+                        assert!(projection.is_none());
 
                         CallbackResponse {
                             payload: serde_json::to_vec(&true).unwrap(),
                         }
                     }
+                    CallbackRequestType::KubernetesListResourceAllChangesSince {
+                        api_version,
+                        kind,
+                        ..
+                    } => {
+                        assert_eq!(api_version, expected_resource.api_version);
+                        assert_eq!(kind, expected_resource.kind);
+
+                        // the changes are not known, a full rebuild is required
+                        CallbackResponse {
+                            payload: serde_json::to_vec(&KubernetesResourceChanges::Resync)
+                                .unwrap(),
+                        }
+                    }
                     _ => {
                         panic!("not the expected request type");
                     }
@@ -359,23 +594,131 @@ pub(crate) mod tests {
             let actual = GATEKEEPER_INVENTORY_CACHE
                 .get_inventory(&callback_tx, &resources)
                 .unwrap();
-            assert!(actual != stale_cached_inventory.data);
-            let actual_inventory = serde_json::from_slice::<GatekeeperInput>(&actual).unwrap();
+            assert!(actual.data != stale_cached_inventory.data);
+            let actual_inventory = serde_json::from_slice::<GatekeeperInput>(&actual.data).unwrap();
             assert_eq!(expected_inventory, actual_inventory.inventory);
 
             {
-                let inventories = GATEKEEPER_INVENTORY_CACHE.inventories.read().unwrap();
-                let actual_inventory = inventories.get(&resources).unwrap();
+                let actual_inventory = cached_inventory_of(&resources);
                 assert!(actual_inventory.cache_time > stale_cached_inventory.cache_time);
             }
 
             {
-                let inventories = GATEKEEPER_INVENTORY_CACHE.inventories.read().unwrap();
-                let actual_inventory = inventories.get(&resources).unwrap();
+                let actual_inventory = cached_inventory_of(&resources);
                 assert!(actual_inventory.cache_time > stale_cached_inventory.cache_time);
             }
         })
         .await
         .unwrap();
     }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_cached_entry_is_updated_incrementally() {
+        let (callback_tx, mut callback_rx) = mpsc::channel::<CallbackRequest>(10);
+        let resource = ContextAwareResource {
+            api_version: "v1".to_string(),
+            kind: "Service".to_string(),
+            projection: None,
+        };
+        let resources: BTreeSet<ContextAwareResource> = BTreeSet::from([resource.clone()]);
+
+        let kube_dns =
+            dynamic_object_from_fixture("services", Some("kube-system"), "kube-dns").unwrap();
+        let metrics_server =
+            dynamic_object_from_fixture("services", Some("kube-system"), "metrics-server").unwrap();
+
+        let stale_cached_inventory = CachedInventory {
+            data: b"cached_inventory_stale".to_vec(),
+            cache_time: Instant::now()
+                .checked_sub(tokio::time::Duration::from_secs(60))
+                .unwrap(),
+        };
+        insert_cached_inventory(
+            &resources,
+            BTreeMap::from([(resource.clone(), 5)]),
+            stale_cached_inventory.clone(),
+        );
+
+        let expected_inventory = GatekeeperInventory::new(&BTreeMap::from([(
+            resource.clone(),
+            object_list_from_dynamic_objects(&[metrics_server.clone()]).unwrap(),
+        )]))
+        .unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let req = match callback_rx.recv().await {
+                    Some(r) => r,
+                    None => return,
+                };
+                let callback_response = match req.request {
+                    CallbackRequestType::HasKubernetesListResourceAllResultChangedSinceInstant {
+                        ..
+                    } => CallbackResponse {
+                        payload: serde_json::to_vec(&true).unwrap(),
+                    },
+                    CallbackRequestType::KubernetesListResourceAllChangesSince {
+                        since, ..
+                    } => {
+                        assert_eq!(since, Some(5));
+                        CallbackResponse {
+                            payload: serde_json::to_vec(&KubernetesResourceChanges::Changes {
+                                changes: vec![
+                                    KubernetesResourceChange::Applied(kube_dns.clone()),
+                                    KubernetesResourceChange::Applied(metrics_server.clone()),
+                                    KubernetesResourceChange::Deleted(kube_dns.clone()),
+                                ],
+                                next_sequence: 8,
+                            })
+                            .unwrap(),
+                        }
+                    }
+                    _ => {
+                        panic!("not the expected request type");
+                    }
+                };
+
+                req.response_channel.send(Ok(callback_response)).unwrap();
+            }
+        });
+
+        tokio::task::spawn_blocking(move || {
+            // the stale inventory is served while the new one is serialized
+            let actual = GATEKEEPER_INVENTORY_CACHE
+                .get_inventory(&callback_tx, &resources)
+                .unwrap();
+            assert_eq!(stale_cached_inventory.data, actual.data);
+
+            let entry = GATEKEEPER_INVENTORY_CACHE
+                .inventories
+                .read()
+                .unwrap()
+                .get(&resources)
+                .cloned()
+                .unwrap();
+            assert_eq!(
+                entry.synced.lock().unwrap().synced_at,
+                BTreeMap::from([(resource, 8)])
+            );
+
+            let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+            let cached_inventory = loop {
+                let cached_inventory = cached_inventory_of(&resources);
+                if cached_inventory.cache_time > stale_cached_inventory.cache_time {
+                    break cached_inventory;
+                }
+                assert!(
+                    std::time::Instant::now() < deadline,
+                    "the inventory has not been serialized"
+                );
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            };
+            let actual_inventory =
+                serde_json::from_slice::<GatekeeperInput>(&cached_inventory.data).unwrap();
+            assert_eq!(expected_inventory, actual_inventory.inventory);
+        })
+        .await
+        .unwrap();
+    }
 }
@@ -1,11 +1,17 @@
 use burrego::errors::BurregoError;
 use kubewarden_policy_sdk::settings::SettingsValidationResponse;
+use lazy_static::lazy_static;
 use serde::Deserialize;
 use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, Weak},
+};
 use tracing::{error, warn};
 
 use crate::runtimes::rego::{
-    context_aware, context_aware::KubernetesContext, errors::RegoRuntimeError, Stack,
+    context_aware, context_aware::KubernetesContext, errors::RegoRuntimeError,
+    gatekeeper_inventory_cache::CachedInventory, Stack,
 };
 use crate::{
     admission_request,
@@ -13,6 +19,46 @@ use crate::{
     policy_evaluator::{PolicySettings, RegoPolicyExecutionMode, ValidateRequest},
 };
 
+type RegoData = serde_json::Map<String, serde_json::Value>;
+
+lazy_static! {
+    /// The data of the Gatekeeper policies that use an external data document. The
+    /// data is merged only once for each version of the inventory and of the document
+    static ref GATEKEEPER_MERGED_DATA: Mutex<Vec<MergedGatekeeperData>> = Mutex::new(Vec::new());
+}
+
+/// An inventory merged with an external data document. The entry is dropped once
+/// any of them is no longer in use
+struct MergedGatekeeperData {
+    /// `None` when the policy doesn't have access to Kubernetes resources
+    inventory: Option<Weak<CachedInventory>>,
+    external_data: Weak<RegoData>,
+    data: Arc<Vec<u8>>,
+}
+
+impl MergedGatekeeperData {
+    fn is_merge_of(
+        &self,
+        inventory: Option<&Arc<CachedInventory>>,
+        external_data: &Arc<RegoData>,
+    ) -> bool {
+        let same_inventory = match (&self.inventory, inventory) {
+            (Some(merged), Some(inventory)) => merged.as_ptr() == Arc::as_ptr(inventory),
+            (None, None) => true,
+            _ => false,
+        };
+        same_inventory && self.external_data.as_ptr() == Arc::as_ptr(external_data)
+    }
+
+    fn is_in_use(&self) -> bool {
+        self.external_data.strong_count() > 0
+            && self
+                .inventory
+                .as_ref()
+                .is_none_or(|inventory| inventory.strong_count() > 0)
+    }
+}
+
 pub(crate) struct Runtime<'a>(pub(crate) &'a mut Stack);
 
 impl Runtime<'_> {
@@ -21,12 +67,15 @@ impl Runtime<'_> {
         settings: &PolicySettings,
         request: &ValidateRequest,
         ctx_data: &context_aware::KubernetesContext,
+        external_data: Option<&Arc<RegoData>>,
     ) -> AdmissionResponse {
         let uid = request.uid();
 
         // OPA and Gatekeeper expect arguments in different ways
         let burrego_evaluation = match self.0.policy_execution_mode {
-            RegoPolicyExecutionMode::Opa => self.evaluate_opa(settings, request, ctx_data),
+            RegoPolicyExecutionMode::Opa => {
+                self.evaluate_opa(settings, request, ctx_data, external_data)
+            }
             RegoPolicyExecutionMode::Gatekeeper => {
                 // Gatekeeper policies expect the `AdmissionRequest` variant only.
                 let request = match request {
@@ -38,7 +87,7 @@ impl Runtime<'_> {
                         );
                     }
                 };
-                self.evaluate_gatekeeper(settings, request, ctx_data)
+                self.evaluate_gatekeeper(settings, request, ctx_data, external_data)
             }
         };
 
@@ -149,6 +198,7 @@ impl Runtime<'_> {
         settings: &PolicySettings,
         request: &ValidateRequest,
         ctx_data: &context_aware::KubernetesContext,
+        external_data: Option<&Arc<RegoData>>,
     ) -> Result<serde_json::Value, BurregoError> {
         let input = json!({
             "request": &request,
@@ -157,23 +207,35 @@ impl Runtime<'_> {
         // OPA data seems to be free-form, except for the
         // Kubernetes context aware data that must be under the
         // `kubernetes` key
+        // The external data document is loaded first, then the settings
+        // provided by the user are added on top of it.
         // We don't know the data that is provided by the users via
         // their settings, hence set the context aware data, to
         // ensure we overwrite what a user might have set.
-        let data = match ctx_data {
-            KubernetesContext::Opa(ctx) => {
-                let mut data = settings.clone();
-                if data
-                    .0
-                    .insert("kubernetes".to_string(), json!(ctx))
-                    .is_some()
-                {
-                    warn!("OPA policy had user provided setting with key `kubernetes`. This value has been overwritten with the actual kubernetes context data");
-                }
-                json!(data)
+        // The data is only borrowed, the document can be big.
+        let mut data: BTreeMap<&str, &serde_json::Value> = external_data
+            .map(|external_data| {
+                external_data
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value))
+                    .collect()
+            })
+            .unwrap_or_default();
+        for (key, value) in settings.0.iter() {
+            if data.insert(key, value).is_some() {
+                warn!(
+                    key,
+                    "OPA policy external data has been overwritten by the user provided setting with the same key"
+                );
             }
-            _ => json!(settings),
-        };
+        }
+        let kubernetes_ctx;
+        if let KubernetesContext::Opa(ctx) = ctx_data {
+            kubernetes_ctx = json!(ctx);
+            if data.insert("kubernetes", &kubernetes_ctx).is_some() {
+                warn!("OPA policy had user provided setting with key `kubernetes`. This value has been overwritten with the actual kubernetes context data");
+            }
+        }
 
         let data_raw = serde_json::to_vec(&data).map_err(|e| BurregoError::JSONError {
             msg: "cannot convert OPA data to JSON".to_string(),
@@ -190,6 +252,7 @@ impl Runtime<'_> {
         settings: &PolicySettings,
         request: &admission_request::AdmissionRequest,
         ctx_data: &context_aware::KubernetesContext,
+        external_data: Option<&Arc<RegoData>>,
     ) -> Result<serde_json::Value, BurregoError> {
         // Gatekeeper policies include a toplevel `review`
         // object that contains the AdmissionRequest to be
@@ -201,15 +264,25 @@ impl Runtime<'_> {
             "review": request,
         });
 
-        let data_raw = match ctx_data {
-            KubernetesContext::Gatekeeper(ctx) => ctx,
-            KubernetesContext::Empty => "{}".as_bytes(),
+        let inventory = match ctx_data {
+            KubernetesContext::Gatekeeper(inventory) => Some(inventory),
+            KubernetesContext::Empty => None,
             KubernetesContext::Opa(_) => unreachable!(),
         };
 
-        self.0
-            .evaluator
-            .evaluate(self.0.entrypoint_id, &input, data_raw)
+        match external_data {
+            Some(external_data) => {
+                let data_raw = gatekeeper_data(inventory, external_data)?;
+                self.0
+                    .evaluator
+                    .evaluate(self.0.entrypoint_id, &input, &data_raw)
+            }
+            None => self.0.evaluator.evaluate(
+                self.0.entrypoint_id,
+                &input,
+                inventory.map_or("{}".as_bytes(), |inventory| &inventory.data),
+            ),
+        }
     }
 
     pub fn validate_settings(&mut self, _settings: String) -> SettingsValidationResponse {
@@ -222,3 +295,155 @@ impl Runtime<'_> {
         }
     }
 }
+
+/// Get the data of a Gatekeeper policy using an external data document. The
+/// inventory and the document are merged only when one of them changed
+fn gatekeeper_data(
+    inventory: Option<&Arc<CachedInventory>>,
+    external_data: &Arc<RegoData>,
+) -> Result<Arc<Vec<u8>>, BurregoError> {
+    if let Some(merged) = GATEKEEPER_MERGED_DATA
+        .lock()
+        .expect("cannot lock Gatekeeper merged data")
+        .iter()
+        .find(|merged| merged.is_merge_of(inventory, external_data))
+    {
+        return Ok(merged.data.clone());
+    }
+
+    let data = Arc::new(merge_gatekeeper_data(
+        inventory.map_or("{}".as_bytes(), |inventory| &inventory.data),
+        external_data,
+    )?);
+
+    let mut merged_data = GATEKEEPER_MERGED_DATA
+        .lock()
+        .expect("cannot lock Gatekeeper merged data");
+    merged_data.retain(MergedGatekeeperData::is_in_use);
+    if !merged_data
+        .iter()
+        .any(|merged| merged.is_merge_of(inventory, external_data))
+    {
+        merged_data.push(MergedGatekeeperData {
+            inventory: inventory.map(Arc::downgrade),
+            external_data: Arc::downgrade(external_data),
+            data: data.clone(),
+        });
+    }
+
+    Ok(data)
+}
+
+/// Add the external data document to the data built by Gatekeeper.
+/// The Kubernetes inventory, stored under the `inventory` key, always takes
+/// precedence over the external data.
+fn merge_gatekeeper_data(
+    data_raw: &[u8],
+    external_data: &RegoData,
+) -> Result<Vec<u8>, BurregoError> {
+    let mut data: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(data_raw)
+        .map_err(|e| BurregoError::JSONError {
+            msg: "cannot parse Gatekeeper data".to_string(),
+            source: e,
+        })?;
+    for (key, value) in external_data.iter() {
+        if data.contains_key(key) {
+            warn!(
+                key,
+                "Gatekeeper policy external data has a key that clashes with the kubernetes context data. The external value has been ignored"
+            );
+            continue;
+        }
+        data.insert(key.to_owned(), value.to_owned());
+    }
+
+    serde_json::to_vec(&data).map_err(|e| BurregoError::JSONError {
+        msg: "cannot convert Gatekeeper data to JSON".to_string(),
+        source: e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_gatekeeper_data_keeps_inventory() {
+        let inventory = json!({
+            "inventory": {
+                "namespace": {}
+            }
+        });
+        let external_data = json!({
+            "inventory": "ignored",
+            "allowed_registries": ["registry.example.com"]
+        });
+
+        let data_raw = merge_gatekeeper_data(
+            &serde_json::to_vec(&inventory).unwrap(),
+            external_data.as_object().unwrap(),
+        )
+        .expect("cannot merge data");
+        let data: serde_json::Value = serde_json::from_slice(&data_raw).unwrap();
+
+        assert_eq!(
+            data,
+            json!({
+                "inventory": {
+                    "namespace": {}
+                },
+                "allowed_registries": ["registry.example.com"]
+            })
+        );
+    }
+
+    #[test]
+    fn gatekeeper_data_is_merged_once() {
+        let inventory = Arc::new(CachedInventory {
+            data: serde_json::to_vec(&json!({"inventory": {"cluster": {}}})).unwrap(),
+            cache_time: tokio::time::Instant::now(),
+        });
+        let external_data = Arc::new(
+            json!({"allowed_registries": ["registry.example.com"]})
+                .as_object()
+                .unwrap()
+                .to_owned(),
+        );
+
+        let data = gatekeeper_data(Some(&inventory), &external_data).expect("cannot merge data");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&data).unwrap(),
+            json!({
+                "inventory": {"cluster": {}},
+                "allowed_registries": ["registry.example.com"]
+            })
+        );
+        let cached = gatekeeper_data(Some(&inventory), &external_data).expect("cannot merge data");
+        assert!(Arc::ptr_eq(&data, &cached));
+
+        // policies without access to Kubernetes resources get another merge
+        let without_inventory = gatekeeper_data(None, &external_data).expect("cannot merge data");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&without_inventory).unwrap(),
+            json!({"allowed_registries": ["registry.example.com"]})
+        );
+
+        // a new version of the document is merged again
+        let refreshed_external_data = Arc::new(
+            json!({"allowed_registries": ["other.example.com"]})
+                .as_object()
+                .unwrap()
+                .to_owned(),
+        );
+        let refreshed =
+            gatekeeper_data(Some(&inventory), &refreshed_external_data).expect("cannot merge data");
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&refreshed).unwrap(),
+            json!({
+                "inventory": {"cluster": {}},
+                "allowed_registries": ["other.example.com"]
+            })
+        );
+    }
+}
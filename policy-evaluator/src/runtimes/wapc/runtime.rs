@@ -199,6 +199,9 @@ mod tests {
             policy_id: "wapc_endless_loop".to_string(),
             callback_channel: None,
             ctx_aware_resources_allow_list: Default::default(),
+            dry_run_resources_allow_list: Default::default(),
+            host_capabilities_allow_list: None,
+            rego_data: None,
         };
 
         let eval_ctx = Arc::new(eval_ctx);
@@ -200,6 +200,9 @@ async fn test_policy_evaluator(
         policy_id: "test".to_owned(),
         callback_channel: None,
         ctx_aware_resources_allow_list: Default::default(),
+        dry_run_resources_allow_list: Default::default(),
+        host_capabilities_allow_list: None,
+        rego_data: None,
     };
 
     let mut policy_evaluator = build_policy_evaluator(execution_mode, &policy, &eval_ctx);
@@ -302,16 +305,22 @@ async fn test_runtime_context_aware<F, Fut>(
             ContextAwareResource {
                 api_version: "v1".to_owned(),
                 kind: "Namespace".to_owned(),
+                projection: None,
             },
             ContextAwareResource {
                 api_version: "apps/v1".to_owned(),
                 kind: "Deployment".to_owned(),
+                projection: None,
             },
             ContextAwareResource {
                 api_version: "v1".to_owned(),
                 kind: "Service".to_owned(),
+                projection: None,
             },
         ]),
+        dry_run_resources_allow_list: Default::default(),
+        host_capabilities_allow_list: None,
+        rego_data: None,
     };
 
     let request_data = load_request_data(request_file_path);
@@ -358,6 +367,9 @@ async fn test_oci_manifest_capability(
         policy_id: "test".to_owned(),
         callback_channel: Some(callback_handler_channel),
         ctx_aware_resources_allow_list: Default::default(),
+        dry_run_resources_allow_list: Default::default(),
+        host_capabilities_allow_list: None,
+        rego_data: None,
     };
 
     let cb_channel: mpsc::Sender<CallbackRequest> = eval_ctx
@@ -448,6 +460,9 @@ async fn test_oci_manifest_and_config_capability(
         policy_id: "test".to_owned(),
         callback_channel: Some(callback_handler_channel),
         ctx_aware_resources_allow_list: Default::default(),
+        dry_run_resources_allow_list: Default::default(),
+        host_capabilities_allow_list: None,
+        rego_data: None,
     };
 
     let cb_channel: mpsc::Sender<CallbackRequest> = eval_ctx
@@ -511,6 +526,9 @@ async fn test_oci_digest_capability() {
         policy_id: "test".to_owned(),
         callback_channel: Some(callback_handler_channel),
         ctx_aware_resources_allow_list: Default::default(),
+        dry_run_resources_allow_list: Default::default(),
+        host_capabilities_allow_list: None,
+        rego_data: None,
     };
 
     let cb_channel: mpsc::Sender<CallbackRequest> = eval_ctx
@@ -8,6 +8,10 @@ pub enum FetcherError {
     InvalidFilePathError(String),
     #[error("invalid wasm file")]
     InvalidWasmFileError,
+    #[error("invalid OPA bundle: not a gzipped tarball")]
+    InvalidOpaBundleError,
+    #[error("cannot read OPA bundle file {0:?}: {1}")]
+    CannotReadOpaBundleFile(String, #[source] std::io::Error),
     #[error("wasm module cannot be save to {0:?}: {1}")]
     CannotWriteWasmModuleFile(String, #[source] std::io::Error),
     #[error(transparent)]
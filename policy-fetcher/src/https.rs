@@ -39,30 +39,35 @@ impl TryFrom<&Certificate> for reqwest::Certificate {
     }
 }
 
-#[async_trait]
-impl PolicyFetcher for Https {
-    async fn fetch(&self, url: &Url, client_protocol: ClientProtocol) -> SourceResult<Vec<u8>> {
-        let mut client_builder = reqwest::Client::builder();
-        match client_protocol {
-            ClientProtocol::Http => {}
-            ClientProtocol::Https(ref tls_fetch_mode) => {
-                client_builder = client_builder.https_only(true);
-                match tls_fetch_mode {
-                    TlsVerificationMode::SystemCa => {}
-                    TlsVerificationMode::CustomCaCertificates(ca_certificates) => {
-                        for certificate in ca_certificates.iter() {
-                            client_builder =
-                                client_builder.add_root_certificate(certificate.try_into()?);
-                        }
-                    }
-                    TlsVerificationMode::NoTlsVerification => {
-                        client_builder = client_builder.danger_accept_invalid_certs(true);
+/// Build a HTTP client honoring the given protocol and TLS verification mode
+pub(crate) fn build_client(client_protocol: &ClientProtocol) -> SourceResult<reqwest::Client> {
+    let mut client_builder = reqwest::Client::builder();
+    match client_protocol {
+        ClientProtocol::Http => {}
+        ClientProtocol::Https(tls_fetch_mode) => {
+            client_builder = client_builder.https_only(true);
+            match tls_fetch_mode {
+                TlsVerificationMode::SystemCa => {}
+                TlsVerificationMode::CustomCaCertificates(ca_certificates) => {
+                    for certificate in ca_certificates.iter() {
+                        client_builder =
+                            client_builder.add_root_certificate(certificate.try_into()?);
                     }
                 }
+                TlsVerificationMode::NoTlsVerification => {
+                    client_builder = client_builder.danger_accept_invalid_certs(true);
+                }
             }
-        };
+        }
+    };
 
-        let client = client_builder.build()?;
+    Ok(client_builder.build()?)
+}
+
+#[async_trait]
+impl PolicyFetcher for Https {
+    async fn fetch(&self, url: &Url, client_protocol: ClientProtocol) -> SourceResult<Vec<u8>> {
+        let client = build_client(&client_protocol)?;
         Ok(client
             .get(url.as_ref())
             .send()
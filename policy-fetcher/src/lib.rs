@@ -23,7 +23,7 @@ use crate::fetcher::{ClientProtocol, PolicyFetcher, TlsVerificationMode};
 use crate::https::Https;
 use crate::policy::Policy;
 use crate::registry::build_fully_resolved_reference;
-use crate::registry::Registry;
+use crate::registry::{OpaBundleRegistry, Registry};
 use crate::sources::Sources;
 use crate::store::Store;
 
@@ -127,35 +127,68 @@ pub async fn fetch_policy(
         _ => unreachable!(),
     }
     debug!(?url, "pulling policy");
-    let policy_fetcher = url_fetcher(url.scheme())?;
+    let bytes = fetch_with_fallback(url_fetcher(url.scheme())?, &url, sources).await?;
+    create_file_if_valid(&bytes, &destination, url.to_string())
+}
+
+/// Download the Open Policy Agent bundle referenced by the given url and
+/// return its raw contents.
+///
+/// Contrary to policies, bundles are not saved to disk: they are meant to
+/// be fetched again whenever their contents have to be refreshed.
+pub async fn fetch_opa_bundle(url: &str, sources: Option<&Sources>) -> FetcherResult<Vec<u8>> {
+    let url = parse_url(url)?;
+    let bundle_fetcher: Box<dyn PolicyFetcher + Send> = match url.scheme() {
+        "file" => {
+            let path = url
+                .to_file_path()
+                .map_err(|_| FetcherError::InvalidFilePathError(url.to_string()))?;
+            let bytes = fs::read(&path).map_err(|e| {
+                FetcherError::CannotReadOpaBundleFile(path.to_string_lossy().to_string(), e)
+            })?;
+            return validate_opa_bundle(bytes);
+        }
+        "http" | "https" => Box::new(Https::default()),
+        "registry" => Box::new(OpaBundleRegistry::default()),
+        _ => return Err(StoreError::UnknownSchemeError(url.scheme().to_owned()).into()),
+    };
+    debug!(?url, "pulling OPA bundle");
+    let bytes = fetch_with_fallback(bundle_fetcher, &url, sources).await?;
+    validate_opa_bundle(bytes)
+}
+
+// Fetch the contents referenced by the url. When the source is marked as
+// insecure, retry first without TLS verification and then using plain HTTP.
+async fn fetch_with_fallback(
+    fetcher: Box<dyn PolicyFetcher + Send>,
+    url: &Url,
+    sources: Option<&Sources>,
+) -> FetcherResult<Vec<u8>> {
     let sources_default = Sources::default();
     let sources = sources.unwrap_or(&sources_default);
 
-    match policy_fetcher
-        .fetch(&url, client_protocol(&url, sources)?)
-        .await
-    {
+    match fetcher.fetch(url, client_protocol(url, sources)?).await {
         Err(err) => {
-            if !sources.is_insecure_source(&host_and_port(&url)?) {
+            if !sources.is_insecure_source(&host_and_port(url)?) {
                 return Err(FetcherError::SourceError(err));
             }
         }
-        Ok(bytes) => return create_file_if_valid(&bytes, &destination, url.to_string()),
+        Ok(bytes) => return Ok(bytes),
     }
-    if let Ok(bytes) = policy_fetcher
+    if let Ok(bytes) = fetcher
         .fetch(
-            &url,
+            url,
             ClientProtocol::Https(TlsVerificationMode::NoTlsVerification),
         )
         .await
     {
-        return create_file_if_valid(&bytes, &destination, url.to_string());
+        return Ok(bytes);
     }
 
-    match policy_fetcher.fetch(&url, ClientProtocol::Http).await {
-        Ok(bytes) => create_file_if_valid(&bytes, &destination, url.to_string()),
-        Err(e) => Err(FetcherError::SourceError(e)),
-    }
+    fetcher
+        .fetch(url, ClientProtocol::Http)
+        .await
+        .map_err(FetcherError::SourceError)
 }
 
 fn client_protocol(
@@ -241,6 +274,17 @@ fn create_file_if_valid(bytes: &[u8], destination: &Path, url: String) -> Fetche
     })
 }
 
+// OPA bundles are gzipped tarballs, hence they begin with the gzip
+// magic bytes sequence.
+const GZIP_MAGIC_NUMBER: [u8; 2] = [0x1f, 0x8b];
+
+fn validate_opa_bundle(bytes: Vec<u8>) -> FetcherResult<Vec<u8>> {
+    if !bytes.starts_with(&GZIP_MAGIC_NUMBER) {
+        return Err(FetcherError::InvalidOpaBundleError);
+    }
+    Ok(bytes)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -554,4 +598,17 @@ mod tests {
             !success
         );
     }
+
+    #[rstest]
+    #[case::gzip_data(vec![0x1f, 0x8b, 0x08, 0x00], true)]
+    #[case::wasm_module(WASM_MAGIC_NUMBER.to_vec(), false)]
+    #[case::empty(Vec::new(), false)]
+    fn accept_only_gzipped_opa_bundles(#[case] contents: Vec<u8>, #[case] success: bool) {
+        let outcome = validate_opa_bundle(contents);
+        assert_eq!(outcome.is_ok(), success);
+        assert_eq!(
+            matches!(outcome, Err(FetcherError::InvalidOpaBundleError)),
+            !success
+        );
+    }
 }
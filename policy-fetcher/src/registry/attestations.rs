@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use oci_client::Reference;
+use url::Url;
+
+use super::{
+    build_fully_resolved_reference, referrers::is_not_found, referrers::referrers_tag,
+    try_with_protocols, Registry,
+};
+use crate::{registry::errors::RegistryResult, sources::Sources};
+
+/// Media type of the layers holding the DSSE envelopes of the attestations
+/// attached to an image by cosign
+pub const DSSE_ENVELOPE_MEDIA_TYPE: &str = "application/vnd.dsse.envelope.v1+json";
+
+/// The attestations attached to an image by `cosign attest`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosignAttestations {
+    /// Digest of the attested image
+    pub image_digest: String,
+    /// One entry per attestation
+    pub layers: Vec<CosignAttestationLayer>,
+}
+
+/// A layer of the cosign attestation image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosignAttestationLayer {
+    /// The DSSE envelope, as a JSON document
+    pub envelope: Vec<u8>,
+    /// The annotations of the layer: they hold the signing certificate and
+    /// the Rekor bundle when the attestation has been signed in keyless mode
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Registry {
+    /// Fetch the attestations attached by cosign to the OCI object referenced
+    /// by the given url. They live under the `<alg>-<digest>.att` tag of the
+    /// same repository.
+    ///
+    /// The attestations are returned as they are, they are **not** verified.
+    pub async fn cosign_attestations(
+        &self,
+        url: &str,
+        sources: Option<&Sources>,
+    ) -> RegistryResult<CosignAttestations> {
+        let reference = build_fully_resolved_reference(url)?;
+        let url: Url = Url::parse(format!("registry://{reference}").as_str())?;
+        let registry_auth = Registry::auth(reference.registry());
+        let sources: Sources = sources.cloned().unwrap_or_default();
+
+        try_with_protocols(&url, &sources, |client_protocol| {
+            Box::pin({
+                let reference = reference.clone();
+                let registry_auth = registry_auth.clone();
+                async move {
+                    let client = Registry::client(client_protocol);
+                    let image_digest = match reference.digest() {
+                        Some(digest) => digest.to_owned(),
+                        None => {
+                            client
+                                .fetch_manifest_digest(&reference, &registry_auth)
+                                .await?
+                        }
+                    };
+                    let attestations_reference = Reference::with_tag(
+                        reference.registry().to_owned(),
+                        reference.repository().to_owned(),
+                        format!("{}.att", referrers_tag(&image_digest)),
+                    );
+
+                    let layers = match client
+                        .pull(
+                            &attestations_reference,
+                            &registry_auth,
+                            vec![DSSE_ENVELOPE_MEDIA_TYPE],
+                        )
+                        .await
+                    {
+                        Ok(image) => image
+                            .layers
+                            .into_iter()
+                            .map(|layer| CosignAttestationLayer {
+                                envelope: layer.data,
+                                annotations: layer.annotations.unwrap_or_default(),
+                            })
+                            .collect(),
+                        Err(error) if is_not_found(&error) => Vec::new(),
+                        Err(error) => return Err(error.into()),
+                    };
+
+                    Ok(CosignAttestations {
+                        image_digest,
+                        layers,
+                    })
+                }
+            })
+        })
+        .await
+    }
+}
@@ -20,4 +20,8 @@ pub enum RegistryError {
     InvalidURLError(#[from] InvalidURLError),
     #[error(transparent)]
     JSONParseError(#[from] serde_json::Error),
+    #[error(transparent)]
+    SourceError(#[from] crate::sources::SourceError),
+    #[error("Cannot fetch referrer blob: {0}")]
+    ReferrerBlobError(String),
 }
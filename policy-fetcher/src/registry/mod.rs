@@ -24,7 +24,20 @@ use crate::{
     sources::{Certificate, SourceError, SourceResult, Sources},
 };
 
+mod attestations;
 pub mod errors;
+mod referrers;
+mod signatures;
+
+pub use attestations::{CosignAttestationLayer, CosignAttestations, DSSE_ENVELOPE_MEDIA_TYPE};
+pub use referrers::{Referrer, ReferrerBlob};
+pub use signatures::{
+    CosignSignatureLayer, COSIGN_SIGNATURE_ANNOTATION, SIMPLE_SIGNING_MEDIA_TYPE,
+};
+
+/// Media type of the layer holding an Open Policy Agent bundle pushed to an
+/// OCI registry
+pub const OPA_BUNDLE_LAYER_MEDIA_TYPE: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
 
 lazy_static! {
     static ref SHA256_DIGEST_RE: Regex = Regex::new(r"[A-Fa-f0-9]{64}").unwrap();
@@ -335,18 +348,23 @@ pub(crate) fn build_fully_resolved_reference(url: &str) -> RegistryResult<Refere
     Ok(Reference::try_from(image)?)
 }
 
-#[async_trait]
-impl PolicyFetcher for Registry {
-    async fn fetch(&self, url: &Url, client_protocol: ClientProtocol) -> SourceResult<Vec<u8>> {
+impl Registry {
+    // Pull the OCI object referenced by the url and return the contents of
+    // its first layer of the given media type
+    async fn pull_layer(
+        url: &Url,
+        client_protocol: ClientProtocol,
+        media_type: &str,
+    ) -> SourceResult<Vec<u8>> {
         let reference =
             Reference::from_str(url.as_ref().strip_prefix("registry://").unwrap_or_default())?;
-        debug!(image=?reference, ?client_protocol, "fetching policy");
+        debug!(image=?reference, ?client_protocol, media_type, "pulling layer");
 
-        let image_content = Registry::client(client_protocol)
+        let layer_content = Registry::client(client_protocol)
             .pull(
                 &reference,
                 &Registry::auth(&crate::host_and_port(url)?),
-                vec![manifest::WASM_LAYER_MEDIA_TYPE],
+                vec![media_type],
             )
             .await?
             .layers
@@ -354,13 +372,31 @@ impl PolicyFetcher for Registry {
             .next()
             .map(|layer| layer.data);
 
-        match image_content {
-            Some(image_content) => Ok(image_content),
+        match layer_content {
+            Some(layer_content) => Ok(layer_content),
             None => Err(SourceError::EmptyLayersError(url.to_string())),
         }
     }
 }
 
+#[async_trait]
+impl PolicyFetcher for Registry {
+    async fn fetch(&self, url: &Url, client_protocol: ClientProtocol) -> SourceResult<Vec<u8>> {
+        Registry::pull_layer(url, client_protocol, manifest::WASM_LAYER_MEDIA_TYPE).await
+    }
+}
+
+/// Fetches Open Policy Agent bundles stored inside of OCI registries
+#[derive(Default)]
+pub(crate) struct OpaBundleRegistry {}
+
+#[async_trait]
+impl PolicyFetcher for OpaBundleRegistry {
+    async fn fetch(&self, url: &Url, client_protocol: ClientProtocol) -> SourceResult<Vec<u8>> {
+        Registry::pull_layer(url, client_protocol, OPA_BUNDLE_LAYER_MEDIA_TYPE).await
+    }
+}
+
 /// Builds an immutable OCI reference for the given image
 ///
 /// * `image ref`: the mutable image reference. For example: `ghcr.io/kubewarden/secure-policy:latest`
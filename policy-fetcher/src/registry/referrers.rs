@@ -0,0 +1,566 @@
+use std::collections::BTreeMap;
+
+use oci_client::{
+    client::Client,
+    errors::{OciDistributionError, OciEnvelope, OciErrorCode},
+    manifest::{OciImageManifest, OCI_IMAGE_INDEX_MEDIA_TYPE},
+    secrets::RegistryAuth,
+    Reference, RegistryOperation,
+};
+use reqwest::{header::ACCEPT, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use url::Url;
+
+use super::{build_fully_resolved_reference, errors::RegistryError, try_with_protocols, Registry};
+use crate::{
+    fetcher::ClientProtocol, https::build_client, registry::errors::RegistryResult,
+    sources::Sources,
+};
+
+/// An artifact referencing an OCI object through its `subject` field, like
+/// an SBOM or an attestation, as defined by the OCI 1.1 specification
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Referrer {
+    /// Media type of the referrer manifest
+    pub media_type: String,
+    /// Digest of the referrer manifest
+    pub digest: String,
+    /// Size of the referrer manifest
+    pub size: i64,
+    /// Type of the artifact, e.g. `application/spdx+json`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<BTreeMap<String, String>>,
+}
+
+/// The contents of a layer of a referrer artifact
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrerBlob {
+    /// Media type of the layer
+    pub media_type: String,
+    /// Digest of the layer
+    pub digest: String,
+    /// Contents of the layer
+    pub data: Vec<u8>,
+}
+
+/// The image index returned by the referrers API or stored under the
+/// referrers tag schema. Only the fields we need are parsed, `oci_client`'s
+/// image index drops the `artifactType` of the entries
+#[derive(Deserialize, Debug)]
+struct ReferrersIndex {
+    #[serde(default)]
+    manifests: Vec<Referrer>,
+}
+
+impl Registry {
+    /// Fetch the artifacts referencing the OCI object referenced by the given url,
+    /// optionally keeping only the ones of the given artifact type.
+    ///
+    /// The OCI 1.1 referrers API is used, falling back to the referrers tag
+    /// schema when the registry does not support it.
+    pub async fn referrers(
+        &self,
+        url: &str,
+        artifact_type: Option<&str>,
+        sources: Option<&Sources>,
+    ) -> RegistryResult<Vec<Referrer>> {
+        let reference = build_fully_resolved_reference(url)?;
+        let url: Url = Url::parse(format!("registry://{reference}").as_str())?;
+        let registry_auth = Registry::auth(reference.registry());
+        let sources: Sources = sources.cloned().unwrap_or_default();
+
+        let referrers = try_with_protocols(&url, &sources, |client_protocol| {
+            Box::pin({
+                let reference = reference.clone();
+                let registry_auth = registry_auth.clone();
+                async move {
+                    let client = Registry::client(client_protocol.clone());
+                    let reference = digest_reference(&client, &reference, &registry_auth).await?;
+                    fetch_referrers(
+                        &client,
+                        &client_protocol,
+                        &reference,
+                        &registry_auth,
+                        artifact_type,
+                    )
+                    .await
+                }
+            })
+        })
+        .await?;
+
+        Ok(referrers
+            .into_iter()
+            .filter(|referrer| {
+                artifact_type.is_none() || referrer.artifact_type.as_deref() == artifact_type
+            })
+            .collect())
+    }
+
+    /// Fetch the contents of a layer of the referrer artifact with the given
+    /// manifest digest. The first layer is used, unless a media type is provided.
+    ///
+    /// The layer is not downloaded when its size is bigger than `max_size` bytes.
+    pub async fn referrer_blob(
+        &self,
+        url: &str,
+        digest: &str,
+        media_type: Option<&str>,
+        max_size: u64,
+        sources: Option<&Sources>,
+    ) -> RegistryResult<ReferrerBlob> {
+        let reference = build_fully_resolved_reference(url)?;
+        let reference = Reference::with_digest(
+            reference.registry().to_owned(),
+            reference.repository().to_owned(),
+            digest.to_owned(),
+        );
+        let url: Url = Url::parse(format!("registry://{reference}").as_str())?;
+        let registry_auth = Registry::auth(reference.registry());
+        let sources: Sources = sources.cloned().unwrap_or_default();
+
+        try_with_protocols(&url, &sources, |client_protocol| {
+            Box::pin({
+                let reference = reference.clone();
+                let registry_auth = registry_auth.clone();
+                async move {
+                    let client = Registry::client(client_protocol);
+                    let (manifest, _) = client
+                        .pull_image_manifest(&reference, &registry_auth)
+                        .await?;
+                    let layer = manifest
+                        .layers
+                        .iter()
+                        .find(|layer| media_type.is_none_or(|mt| layer.media_type == mt))
+                        .ok_or_else(|| {
+                            RegistryError::ReferrerBlobError(format!(
+                                "{reference} has no layer of media type {}",
+                                media_type.unwrap_or("any")
+                            ))
+                        })?;
+                    if u64::try_from(layer.size).unwrap_or(u64::MAX) > max_size {
+                        return Err(RegistryError::ReferrerBlobError(format!(
+                            "layer {} of {reference} is {} bytes, more than the allowed {max_size}",
+                            layer.digest, layer.size
+                        )));
+                    }
+
+                    // pull_blob verifies the contents against the digest of the
+                    // layer, hence their size matches the one we checked
+                    let mut data = Vec::with_capacity(usize::try_from(layer.size).unwrap_or(0));
+                    client.pull_blob(&reference, layer, &mut data).await?;
+
+                    Ok(ReferrerBlob {
+                        media_type: layer.media_type.clone(),
+                        digest: layer.digest.clone(),
+                        data,
+                    })
+                }
+            })
+        })
+        .await
+    }
+}
+
+/// Return a reference pointing to the digest of the given one, referrers can
+/// be looked up only by digest
+async fn digest_reference(
+    client: &Client,
+    reference: &Reference,
+    registry_auth: &RegistryAuth,
+) -> RegistryResult<Reference> {
+    let digest = match reference.digest() {
+        Some(digest) => digest.to_owned(),
+        None => {
+            client
+                .fetch_manifest_digest(reference, registry_auth)
+                .await?
+        }
+    };
+    Ok(Reference::with_digest(
+        reference.registry().to_owned(),
+        reference.repository().to_owned(),
+        digest,
+    ))
+}
+
+async fn fetch_referrers(
+    client: &Client,
+    client_protocol: &ClientProtocol,
+    reference: &Reference,
+    registry_auth: &RegistryAuth,
+    artifact_type: Option<&str>,
+) -> RegistryResult<Vec<Referrer>> {
+    let url = referrers_url(client_protocol, reference, artifact_type)?;
+    let http_client = build_client(client_protocol)?;
+    let referrers =
+        match pull_referrers_index(client, &http_client, reference, registry_auth, &url).await {
+            Ok(index) => index.manifests,
+            Err(error) if is_not_found(&error) || is_unsupported(&error) => {
+                debug!(
+                    %error,
+                    %reference,
+                    "referrers API not available, falling back to the referrers tag schema"
+                );
+                fetch_referrers_tag_schema(client, reference, registry_auth).await?
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+    let mut complete_referrers = Vec::with_capacity(referrers.len());
+    for mut referrer in referrers {
+        // The artifact type of the index entries is optional, we have to
+        // look at the manifest when it's missing
+        if referrer.artifact_type.is_none() {
+            let referrer_reference = Reference::with_digest(
+                reference.registry().to_owned(),
+                reference.repository().to_owned(),
+                referrer.digest.clone(),
+            );
+            let (manifest, _) = client
+                .pull_image_manifest(&referrer_reference, registry_auth)
+                .await?;
+            referrer.artifact_type = Some(artifact_type_of(&manifest));
+            referrer.annotations = referrer.annotations.or(manifest.annotations);
+        }
+        complete_referrers.push(referrer);
+    }
+    Ok(complete_referrers)
+}
+
+/// The url of the referrers API for the given reference, which must point
+/// to a digest
+fn referrers_url(
+    client_protocol: &ClientProtocol,
+    reference: &Reference,
+    artifact_type: Option<&str>,
+) -> RegistryResult<Url> {
+    let scheme = match client_protocol {
+        ClientProtocol::Http => "http",
+        ClientProtocol::Https(_) => "https",
+    };
+    let digest = reference
+        .digest()
+        .expect("the reference has been resolved to a digest");
+    let mut url = Url::parse(&format!(
+        "{scheme}://{}/v2/{}/referrers/{digest}",
+        reference.resolve_registry(),
+        reference.repository()
+    ))?;
+    if let Some(artifact_type) = artifact_type {
+        url.query_pairs_mut()
+            .append_pair("artifactType", artifact_type);
+    }
+    Ok(url)
+}
+
+/// Query the referrers API. The request is done by hand because the image
+/// index returned by `oci_client` drops the `artifactType` of its entries
+async fn pull_referrers_index(
+    client: &Client,
+    http_client: &reqwest::Client,
+    reference: &Reference,
+    registry_auth: &RegistryAuth,
+    url: &Url,
+) -> Result<ReferrersIndex, OciDistributionError> {
+    let token = client
+        .auth(reference, registry_auth, RegistryOperation::Pull)
+        .await?;
+
+    let mut request = http_client
+        .get(url.as_str())
+        .header(ACCEPT, OCI_IMAGE_INDEX_MEDIA_TYPE);
+    request = match (token, registry_auth) {
+        (Some(token), _) => request.bearer_auth(token),
+        (None, RegistryAuth::Basic(username, password)) => {
+            request.basic_auth(username, Some(password))
+        }
+        (None, _) => request,
+    };
+
+    let response = request.send().await?;
+    let status = response.status();
+    let body = response.bytes().await?;
+    parse_referrers_response(status, &body, url)
+}
+
+/// Turn the answer of the referrers API into an image index, or into the
+/// error `oci_client` would have returned for it
+fn parse_referrers_response(
+    status: StatusCode,
+    body: &[u8],
+    url: &Url,
+) -> Result<ReferrersIndex, OciDistributionError> {
+    match status {
+        StatusCode::OK => serde_json::from_slice(body)
+            .map_err(|e| OciDistributionError::ManifestParsingError(e.to_string())),
+        StatusCode::UNAUTHORIZED => Err(OciDistributionError::UnauthorizedError {
+            url: url.to_string(),
+        }),
+        status if status.is_client_error() => match serde_json::from_slice::<OciEnvelope>(body) {
+            Ok(envelope) => Err(OciDistributionError::RegistryError {
+                envelope,
+                url: url.to_string(),
+            }),
+            Err(_) => Err(OciDistributionError::ServerError {
+                code: status.as_u16(),
+                url: url.to_string(),
+                message: String::from_utf8_lossy(body).to_string(),
+            }),
+        },
+        status => Err(OciDistributionError::ServerError {
+            code: status.as_u16(),
+            url: url.to_string(),
+            message: String::from_utf8_lossy(body).to_string(),
+        }),
+    }
+}
+
+/// Look up the referrers using the tag schema described by the OCI distribution
+/// specification: the image index is stored under the `<alg>-<digest>` tag
+async fn fetch_referrers_tag_schema(
+    client: &Client,
+    reference: &Reference,
+    registry_auth: &RegistryAuth,
+) -> RegistryResult<Vec<Referrer>> {
+    let digest = reference
+        .digest()
+        .expect("the reference has been resolved to a digest");
+    let tag_reference = Reference::with_tag(
+        reference.registry().to_owned(),
+        reference.repository().to_owned(),
+        referrers_tag(digest),
+    );
+
+    match client
+        .pull_manifest_raw(&tag_reference, registry_auth, &[OCI_IMAGE_INDEX_MEDIA_TYPE])
+        .await
+    {
+        Ok((body, _)) => {
+            let index: ReferrersIndex = serde_json::from_slice(&body)?;
+            Ok(index.manifests)
+        }
+        Err(error) if is_not_found(&error) => Ok(Vec::new()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// The artifact type of a manifest: its `artifactType`, falling back to the
+/// media type of its config, as the OCI specification mandates
+fn artifact_type_of(manifest: &OciImageManifest) -> String {
+    manifest
+        .artifact_type
+        .clone()
+        .unwrap_or_else(|| manifest.config.media_type.clone())
+}
+
+pub(super) fn referrers_tag(digest: &str) -> String {
+    digest.replacen(':', "-", 1)
+}
+
+/// Whether the registry answered that it does not implement the referrers API
+fn is_unsupported(error: &OciDistributionError) -> bool {
+    match error {
+        OciDistributionError::ServerError { code, .. } => *code == 405,
+        OciDistributionError::RegistryError { envelope, .. } => envelope
+            .errors
+            .iter()
+            .any(|e| matches!(e.code, OciErrorCode::Unsupported)),
+        _ => false,
+    }
+}
+
+pub(super) fn is_not_found(error: &OciDistributionError) -> bool {
+    match error {
+        OciDistributionError::ImageManifestNotFoundError(_) => true,
+        OciDistributionError::ServerError { code, .. } => *code == 404,
+        OciDistributionError::RegistryError { envelope, .. } => envelope.errors.iter().any(|e| {
+            matches!(
+                e.code,
+                OciErrorCode::ManifestUnknown | OciErrorCode::NotFound
+            )
+        }),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oci_client::{errors::OciEnvelope, manifest::OciDescriptor};
+    use rstest::rstest;
+
+    #[test]
+    fn referrers_tag_schema() {
+        assert_eq!(
+            referrers_tag(
+                "sha256:72b4569c3daee67abeaa64192fb53895d0edb2d44fa6e1d9d4c5d3f8ece09f6e"
+            ),
+            "sha256-72b4569c3daee67abeaa64192fb53895d0edb2d44fa6e1d9d4c5d3f8ece09f6e"
+        );
+    }
+
+    #[rstest]
+    #[case::artifact_type(Some("application/spdx+json"), "application/spdx+json")]
+    #[case::config_media_type(None, "application/vnd.example.config+json")]
+    fn manifest_artifact_type(#[case] artifact_type: Option<&str>, #[case] expected: &str) {
+        let manifest = OciImageManifest {
+            artifact_type: artifact_type.map(|at| at.to_owned()),
+            config: OciDescriptor {
+                media_type: "application/vnd.example.config+json".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(artifact_type_of(&manifest), expected);
+    }
+
+    #[test]
+    fn parse_referrers_index() {
+        let index = r#"{
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.index.v1+json",
+            "manifests": [
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:a",
+                    "size": 1234,
+                    "artifactType": "application/spdx+json",
+                    "annotations": {"org.opencontainers.image.created": "2024-01-01T00:00:00Z"}
+                },
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:b",
+                    "size": 42
+                }
+            ]
+        }"#;
+
+        let index: ReferrersIndex = serde_json::from_str(index).unwrap();
+        assert_eq!(index.manifests.len(), 2);
+        assert_eq!(
+            index.manifests[0].artifact_type.as_deref(),
+            Some("application/spdx+json")
+        );
+        assert_eq!(index.manifests[1].artifact_type, None);
+        assert_eq!(index.manifests[1].size, 42);
+    }
+
+    #[rstest]
+    #[case::manifest_not_found(
+        OciDistributionError::ImageManifestNotFoundError("not found".to_owned()),
+        true
+    )]
+    #[case::http_404(
+        OciDistributionError::ServerError { code: 404, url: String::new(), message: String::new() },
+        true
+    )]
+    #[case::http_500(
+        OciDistributionError::ServerError { code: 500, url: String::new(), message: String::new() },
+        false
+    )]
+    #[case::manifest_unknown(
+        OciDistributionError::RegistryError {
+            envelope: serde_json::from_str::<OciEnvelope>(
+                r#"{"errors": [{"code": "MANIFEST_UNKNOWN", "message": "unknown"}]}"#
+            ).unwrap(),
+            url: String::new(),
+        },
+        true
+    )]
+    #[case::denied(
+        OciDistributionError::RegistryError {
+            envelope: serde_json::from_str::<OciEnvelope>(
+                r#"{"errors": [{"code": "DENIED", "message": "denied"}]}"#
+            ).unwrap(),
+            url: String::new(),
+        },
+        false
+    )]
+    fn not_found_errors(#[case] error: OciDistributionError, #[case] expected: bool) {
+        assert_eq!(is_not_found(&error), expected);
+    }
+
+    #[rstest]
+    #[case::http(
+        ClientProtocol::Http,
+        None,
+        "http://registry.example.com:5000/v2/kubewarden/policy/referrers/sha256:abc"
+    )]
+    #[case::https_artifact_type(
+        ClientProtocol::Https(crate::fetcher::TlsVerificationMode::SystemCa),
+        Some("application/spdx+json"),
+        "https://registry.example.com:5000/v2/kubewarden/policy/referrers/sha256:abc?artifactType=application%2Fspdx%2Bjson"
+    )]
+    fn build_referrers_url(
+        #[case] client_protocol: ClientProtocol,
+        #[case] artifact_type: Option<&str>,
+        #[case] expected: &str,
+    ) {
+        let reference = Reference::with_digest(
+            "registry.example.com:5000".to_owned(),
+            "kubewarden/policy".to_owned(),
+            "sha256:abc".to_owned(),
+        );
+
+        let url = referrers_url(&client_protocol, &reference, artifact_type).unwrap();
+        assert_eq!(url.as_str(), expected);
+    }
+
+    #[rstest]
+    #[case::not_found(StatusCode::NOT_FOUND, "404 page not found", true)]
+    #[case::method_not_allowed(StatusCode::METHOD_NOT_ALLOWED, "", true)]
+    #[case::unsupported(
+        StatusCode::BAD_REQUEST,
+        r#"{"errors": [{"code": "UNSUPPORTED", "message": "unsupported"}]}"#,
+        true
+    )]
+    #[case::unauthorized(StatusCode::UNAUTHORIZED, "", false)]
+    #[case::denied(
+        StatusCode::FORBIDDEN,
+        r#"{"errors": [{"code": "DENIED", "message": "denied"}]}"#,
+        false
+    )]
+    #[case::server_error(StatusCode::INTERNAL_SERVER_ERROR, "boom", false)]
+    fn referrers_api_errors(
+        #[case] status: StatusCode,
+        #[case] body: &str,
+        #[case] falls_back: bool,
+    ) {
+        let url =
+            Url::parse("https://registry.example.com/v2/policy/referrers/sha256:abc").unwrap();
+
+        let error = parse_referrers_response(status, body.as_bytes(), &url).unwrap_err();
+        assert_eq!(is_not_found(&error) || is_unsupported(&error), falls_back);
+    }
+
+    #[test]
+    fn referrers_api_index() {
+        let url =
+            Url::parse("https://registry.example.com/v2/policy/referrers/sha256:abc").unwrap();
+        let body = r#"{
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.index.v1+json",
+            "manifests": [
+                {
+                    "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                    "digest": "sha256:a",
+                    "size": 1234,
+                    "artifactType": "application/spdx+json"
+                }
+            ]
+        }"#;
+
+        let index = parse_referrers_response(StatusCode::OK, body.as_bytes(), &url).unwrap();
+        assert_eq!(
+            index.manifests[0].artifact_type.as_deref(),
+            Some("application/spdx+json")
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+
+use oci_client::{
+    client::{Config, ImageLayer},
+    manifest::{OciImageManifest, OCI_IMAGE_MEDIA_TYPE},
+    Reference,
+};
+use url::Url;
+
+use super::{
+    build_fully_resolved_reference, build_immutable_ref, referrers::is_not_found,
+    referrers::referrers_tag, try_with_protocols, Registry,
+};
+use crate::{registry::errors::RegistryResult, sources::Sources};
+
+/// Media type of the layers holding the simple signing payloads of the
+/// signatures attached to an image by cosign
+pub const SIMPLE_SIGNING_MEDIA_TYPE: &str = "application/vnd.dev.cosign.simplesigning.v1+json";
+
+/// Annotation of a signature layer holding the base64 encoded signature of
+/// its payload
+pub const COSIGN_SIGNATURE_ANNOTATION: &str = "dev.cosignproject.cosign/signature";
+
+/// A layer of the cosign signature image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosignSignatureLayer {
+    /// The simple signing payload, as a JSON document
+    pub payload: Vec<u8>,
+    /// The annotations of the layer: they hold the signature of the payload
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Registry {
+    /// Attach a signature to the OCI object referenced by the given url.
+    ///
+    /// Like cosign does, the signature becomes a new layer of the image
+    /// stored under the `<alg>-<digest>.sig` tag of the same repository. The
+    /// signatures that are already attached are preserved.
+    ///
+    /// Returns the immutable reference of the signature image.
+    pub async fn push_cosign_signature(
+        &self,
+        url: &str,
+        sources: Option<&Sources>,
+        signature: &CosignSignatureLayer,
+    ) -> RegistryResult<String> {
+        let reference = build_fully_resolved_reference(url)?;
+        let url: Url = Url::parse(format!("registry://{reference}").as_str())?;
+        let registry_auth = Registry::auth(reference.registry());
+        let sources: Sources = sources.cloned().unwrap_or_default();
+
+        let (signature_reference, manifest_url) =
+            try_with_protocols(&url, &sources, |client_protocol| {
+                Box::pin({
+                    let reference = reference.clone();
+                    let registry_auth = registry_auth.clone();
+                    async move {
+                        let client = Registry::client(client_protocol);
+                        let image_digest = match reference.digest() {
+                            Some(digest) => digest.to_owned(),
+                            None => {
+                                client
+                                    .fetch_manifest_digest(&reference, &registry_auth)
+                                    .await?
+                            }
+                        };
+                        let signature_reference = Reference::with_tag(
+                            reference.registry().to_owned(),
+                            reference.repository().to_owned(),
+                            format!("{}.sig", referrers_tag(&image_digest)),
+                        );
+
+                        let mut layers = match client
+                            .pull(
+                                &signature_reference,
+                                &registry_auth,
+                                vec![SIMPLE_SIGNING_MEDIA_TYPE],
+                            )
+                            .await
+                        {
+                            Ok(image) => image.layers,
+                            Err(error) if is_not_found(&error) => Vec::new(),
+                            Err(error) => return Err(error.into()),
+                        };
+                        add_signature_layer(&mut layers, signature);
+
+                        let config = Config::oci_v1(b"{}".to_vec(), None);
+                        let mut manifest = OciImageManifest::build(&layers, &config, None);
+                        manifest.media_type = Some(OCI_IMAGE_MEDIA_TYPE.to_string());
+
+                        let manifest_url = client
+                            .push(
+                                &signature_reference,
+                                &layers,
+                                config,
+                                &registry_auth,
+                                Some(manifest),
+                            )
+                            .await?
+                            .manifest_url;
+
+                        Ok((signature_reference, manifest_url))
+                    }
+                })
+            })
+            .await?;
+
+        build_immutable_ref(&signature_reference.whole(), &manifest_url)
+    }
+}
+
+/// Append the signature to the layers of the signature image, unless the very
+/// same signature is already there
+fn add_signature_layer(layers: &mut Vec<ImageLayer>, signature: &CosignSignatureLayer) {
+    let already_signed = layers.iter().any(|layer| {
+        layer.data == signature.payload
+            && layer.annotations.as_ref() == Some(&signature.annotations)
+    });
+    if !already_signed {
+        layers.push(ImageLayer::new(
+            signature.payload.clone(),
+            SIMPLE_SIGNING_MEDIA_TYPE.to_string(),
+            Some(signature.annotations.clone()),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(payload: &str, signature: &str) -> CosignSignatureLayer {
+        CosignSignatureLayer {
+            payload: payload.as_bytes().to_vec(),
+            annotations: BTreeMap::from([(
+                COSIGN_SIGNATURE_ANNOTATION.to_owned(),
+                signature.to_owned(),
+            )]),
+        }
+    }
+
+    #[test]
+    fn add_signature_layer_preserves_existing_signatures() {
+        let mut layers = Vec::new();
+
+        add_signature_layer(&mut layers, &signature("payload", "first"));
+        add_signature_layer(&mut layers, &signature("payload", "second"));
+
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0].media_type, SIMPLE_SIGNING_MEDIA_TYPE);
+        assert_eq!(
+            layers[1]
+                .annotations
+                .as_ref()
+                .and_then(|a| a.get(COSIGN_SIGNATURE_ANNOTATION)),
+            Some(&"second".to_owned())
+        );
+    }
+
+    #[test]
+    fn add_signature_layer_skips_duplicated_signatures() {
+        let mut layers = Vec::new();
+
+        add_signature_layer(&mut layers, &signature("payload", "signature"));
+        add_signature_layer(&mut layers, &signature("payload", "signature"));
+
+        assert_eq!(layers.len(), 1);
+    }
+}
@@ -204,6 +204,41 @@ pub(crate) fn build_cli() -> Command {
             .action(ArgAction::SetTrue)
             .help("Enable pprof profiling"),
 
+        Arg::new("callback-cache-ttl")
+            .long("callback-cache-ttl")
+            .value_delimiter(',')
+            .value_name("CAPABILITY=SECONDS")
+            .env("KUBEWARDEN_CALLBACK_CACHE_TTL")
+            .help("How long the successful results of a host capability are cached. Capabilities: oci, sigstore, net, kubernetes_get_resource, kubernetes_can_i. Defaults to 60 seconds for oci, sigstore and net, 5 seconds for the kubernetes ones"),
+
+        Arg::new("callback-cache-max-entries")
+            .long("callback-cache-max-entries")
+            .value_delimiter(',')
+            .value_name("CAPABILITY=ENTRIES")
+            .env("KUBEWARDEN_CALLBACK_CACHE_MAX_ENTRIES")
+            .help("Maximum number of results of a host capability kept in cache, 0 disables the cache. Defaults to 1000"),
+
+        Arg::new("callback-cache-negative-ttl")
+            .long("callback-cache-negative-ttl")
+            .value_delimiter(',')
+            .value_name("CAPABILITY=SECONDS")
+            .env("KUBEWARDEN_CALLBACK_CACHE_NEGATIVE_TTL")
+            .help("How long the failures of a host capability are cached. Failures are not cached by default"),
+
+        Arg::new("dns-nameservers")
+            .long("dns-nameservers")
+            .value_delimiter(',')
+            .value_name("IP[:PORT]")
+            .env("KUBEWARDEN_DNS_NAMESERVERS")
+            .help("Name servers queried by the DNS host capabilities, the port defaults to 53. Defaults to the name servers configured on the system"),
+
+        Arg::new("dns-timeout")
+            .long("dns-timeout")
+            .value_name("SECONDS")
+            .env("KUBEWARDEN_DNS_TIMEOUT")
+            .default_value("5")
+            .help("Timeout of the queries done by the DNS host capabilities"),
+
         Arg::new("continue-on-errors")
             .long("continue-on-errors")
             .env("KUBEWARDEN_CONTINUE_ON_ERRORS")
@@ -3,12 +3,13 @@ use clap::ArgMatches;
 use lazy_static::lazy_static;
 use policy_evaluator::{
     admission_response_handler::policy_mode::PolicyMode,
+    callback_handler::{parse_nameserver, CacheConfig, CachedCapability},
     policy_evaluator::PolicySettings,
     policy_fetcher::{
         sources::{read_sources_file, Sources},
         verify::config::{read_verification_file, LatestVerificationConfig, VerificationConfigV1},
     },
-    policy_metadata::ContextAwareResource,
+    policy_metadata::{ContextAwareResource, HostCapabilityNamespace},
 };
 use serde::Deserialize;
 use std::{
@@ -17,6 +18,7 @@ use std::{
     fs::{self, File},
     net::SocketAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use tonic::transport::{Certificate, ClientTlsConfig, Identity};
 
@@ -51,6 +53,9 @@ pub struct Config {
     pub daemon_stdout_file: Option<String>,
     pub daemon_stderr_file: Option<String>,
     pub continue_on_errors: bool,
+    pub callback_cache_configs: HashMap<CachedCapability, CacheConfig>,
+    pub dns_nameservers: Option<Vec<SocketAddr>>,
+    pub dns_timeout: Duration,
 }
 
 pub struct TlsConfig {
@@ -144,6 +149,22 @@ impl Config {
             .expect("clap should have assigned a default value")
             .to_owned();
 
+        let callback_cache_configs = callback_cache_configs(matches)?;
+        let dns_nameservers = matches
+            .get_many::<String>("dns-nameservers")
+            .map(|nameservers| {
+                nameservers
+                    .map(|nameserver| parse_nameserver(nameserver))
+                    .collect::<Result<Vec<SocketAddr>>>()
+            })
+            .transpose()?;
+        let dns_timeout = matches
+            .get_one::<String>("dns-timeout")
+            .expect("dns-timeout should always be set")
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| anyhow!("invalid value for --dns-timeout: {e}"))?;
+
         Ok(Self {
             addr,
             readiness_probe_addr,
@@ -167,6 +188,9 @@ impl Config {
             daemon_stderr_file,
             enable_pprof,
             continue_on_errors,
+            callback_cache_configs,
+            dns_nameservers,
+            dns_timeout,
         })
     }
 }
@@ -236,12 +260,36 @@ fn policies(matches: &clap::ArgMatches) -> Result<HashMap<String, PolicyOrPolicy
 // Validate the policies and policy groups:
 //  - ensure policy names do not contain a '/' character
 //  - ensure names of policy group's policies do not contain a '/' character
+//  - ensure the refresh interval of the policy data documents is not zero
 fn validate_policies(policies: &HashMap<String, PolicyOrPolicyGroup>) -> Result<()> {
     for (name, policy) in policies.iter() {
         if name.contains('/') {
             return Err(anyhow!("policy name '{}' contains a '/' character", name));
         }
+        if let PolicyOrPolicyGroup::Policy {
+            data: Some(data), ..
+        } = policy
+        {
+            if data.refresh_interval == Some(0) {
+                return Err(anyhow!(
+                    "policy '{}' has a data refresh interval of 0 seconds",
+                    name
+                ));
+            }
+        }
         if let PolicyOrPolicyGroup::PolicyGroup { policies, .. } = policy {
+            if let Some((id, _)) = policies.iter().find(|(_, member)| {
+                member
+                    .data
+                    .as_ref()
+                    .is_some_and(|data| data.refresh_interval == Some(0))
+            }) {
+                return Err(anyhow!(
+                    "policy '{}' of policy group '{}' has a data refresh interval of 0 seconds",
+                    id,
+                    name
+                ));
+            }
             let policies_with_invalid_name: Vec<String> = policies
                 .iter()
                 .filter_map(|(id, _)| if id.contains('/') { Some(id) } else { None })
@@ -307,6 +355,16 @@ pub struct PolicyGroupMember {
     /// The list of Kubernetes resources the policy is allowed to access
     #[serde(default)]
     pub context_aware_resources: BTreeSet<ContextAwareResource>,
+    /// The list of Kubernetes resources the policy is allowed to dry-run
+    #[serde(default)]
+    pub dry_run_resources: BTreeSet<ContextAwareResource>,
+    /// The namespaces of host capabilities the policy is allowed to use.
+    /// When not set, the ones declared inside of the policy metadata are used
+    #[serde(default)]
+    pub host_capabilities: Option<BTreeSet<HostCapabilityNamespace>>,
+    /// The external data document made available to Rego policies
+    #[serde(default)]
+    pub data: Option<RegoDataConfig>,
 }
 
 impl PolicyGroupMember {
@@ -317,6 +375,46 @@ impl PolicyGroupMember {
     }
 }
 
+/// Where the external data document of a Rego policy is loaded from
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RegoDataSource {
+    /// The document is provided inline, inside of the policies file
+    Inline(serde_json::Map<String, serde_json::Value>),
+    /// The document is read from a local YAML or JSON file
+    File(PathBuf),
+    /// The document is extracted from an OPA bundle. The bundle can be
+    /// located on the local filesystem, on a HTTP(s) server or on an OCI registry
+    Bundle(String),
+}
+
+impl RegoDataSource {
+    /// Returns a unique identifier of the source. Policies referencing the
+    /// same source share the same document.
+    pub fn id(&self) -> String {
+        match self {
+            RegoDataSource::Inline(data) => {
+                format!("inline:{}", serde_json::Value::Object(data.clone()))
+            }
+            RegoDataSource::File(path) => format!("file:{}", path.display()),
+            RegoDataSource::Bundle(url) => format!("bundle:{url}"),
+        }
+    }
+}
+
+/// The external data document of a Rego policy. The document is
+/// made available to the policy under its `data` object.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RegoDataConfig {
+    /// Where the document is loaded from
+    #[serde(flatten)]
+    pub source: RegoDataSource,
+    /// How often, in seconds, the document is loaded again from its source.
+    /// When not set, the document is loaded only once at startup
+    pub refresh_interval: Option<u64>,
+}
+
 /// Describes a policy that can be either an individual policy or a group policy.
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
@@ -336,8 +434,17 @@ pub enum PolicyOrPolicyGroup {
         #[serde(default)]
         /// The list of Kubernetes resources the policy is allowed to access
         context_aware_resources: BTreeSet<ContextAwareResource>,
+        #[serde(default)]
+        /// The list of Kubernetes resources the policy is allowed to dry-run
+        dry_run_resources: BTreeSet<ContextAwareResource>,
+        #[serde(default)]
+        /// The namespaces of host capabilities the policy is allowed to use.
+        /// When not set, the ones declared inside of the policy metadata are used
+        host_capabilities: Option<BTreeSet<HostCapabilityNamespace>>,
         /// The message that is returned when the policy evaluates to false
         message: Option<String>,
+        /// The external data document made available to Rego policies
+        data: Option<RegoDataConfig>,
     },
     /// A group of policies that are evaluated together using a given expression
     #[serde(rename_all = "camelCase")]
@@ -375,6 +482,51 @@ impl PolicyOrPolicyGroup {
     }
 }
 
+/// Build the cache settings of all the cached host capabilities, starting from
+/// their defaults and applying the `CAPABILITY=VALUE` overrides given via the cli
+fn callback_cache_configs(
+    matches: &clap::ArgMatches,
+) -> Result<HashMap<CachedCapability, CacheConfig>> {
+    let mut configs: HashMap<CachedCapability, CacheConfig> = CachedCapability::ALL
+        .into_iter()
+        .map(|capability| (capability, CacheConfig::default_for(capability)))
+        .collect();
+
+    for (capability, ttl) in capability_values(matches, "callback-cache-ttl")? {
+        configs.get_mut(&capability).unwrap().ttl = Duration::from_secs(ttl);
+    }
+    for (capability, max_entries) in capability_values(matches, "callback-cache-max-entries")? {
+        configs.get_mut(&capability).unwrap().max_entries = usize::try_from(max_entries)?;
+    }
+    for (capability, ttl) in capability_values(matches, "callback-cache-negative-ttl")? {
+        configs.get_mut(&capability).unwrap().negative_ttl =
+            (ttl > 0).then(|| Duration::from_secs(ttl));
+    }
+
+    Ok(configs)
+}
+
+fn capability_values(
+    matches: &clap::ArgMatches,
+    flag: &str,
+) -> Result<Vec<(CachedCapability, u64)>> {
+    matches
+        .get_many::<String>(flag)
+        .unwrap_or_default()
+        .map(|value| {
+            let (capability, number) = value.split_once('=').ok_or_else(|| {
+                anyhow!("invalid value '{value}' for --{flag}, expected CAPABILITY=NUMBER")
+            })?;
+            let capability = capability.trim().parse::<CachedCapability>()?;
+            let number = number
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| anyhow!("invalid value '{value}' for --{flag}: {e}"))?;
+            Ok((capability, number))
+        })
+        .collect()
+}
+
 /// Reads the policies configuration file, returns a HashMap with String as value
 /// and Policy as values. The key is the name of the policy as provided by the user
 /// inside of the configuration file. This name is used to build the API path
@@ -432,6 +584,9 @@ pub fn build_client_tls_config_from_env(prefix: &str) -> Result<ClientTlsConfig>
 mod tests {
     use super::*;
     use crate::cli;
+    use policy_evaluator::{
+        callback_handler::DEFAULT_DNS_TIMEOUT, policy_metadata::ResourceProjection,
+    };
     use rstest::*;
     use serde_json::json;
     use std::io::Write;
@@ -451,6 +606,13 @@ example:
           kind: Namespace
         - apiVersion: v1
           kind: Pod
+          projection: metadataOnly
+    dryRunResources:
+        - apiVersion: apps/v1
+          kind: Deployment
+    hostCapabilities:
+        - oci
+        - kubernetes
 group_policy:
     policyMode: monitor
     expression: "true"
@@ -462,6 +624,7 @@ group_policy:
         policy2:
             module: ghcr.io/kubewarden/policies/policy2:0.1.0
             settings: {}
+            hostCapabilities: []
 "#;
 
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -482,13 +645,25 @@ group_policy:
                         ContextAwareResource {
                             api_version: "v1".to_owned(),
                             kind: "Namespace".to_owned(),
+                            projection: None,
                         },
                         ContextAwareResource {
                             api_version: "v1".to_owned(),
                             kind: "Pod".to_owned(),
+                            projection: Some(ResourceProjection::MetadataOnly),
                         },
                     ]),
+                    dry_run_resources: BTreeSet::from([ContextAwareResource {
+                        api_version: "apps/v1".to_owned(),
+                        kind: "Deployment".to_owned(),
+                        projection: None,
+                    }]),
+                    host_capabilities: Some(BTreeSet::from([
+                        HostCapabilityNamespace::Oci,
+                        HostCapabilityNamespace::Kubernetes,
+                    ])),
                     message: Some("my custom error message".to_owned()),
+                    data: None,
                 },
             ),
             (
@@ -504,6 +679,9 @@ group_policy:
                                 module: "ghcr.io/kubewarden/policies/policy1:0.1.0".to_owned(),
                                 settings: Some(PolicySettings::default()),
                                 context_aware_resources: BTreeSet::new(),
+                                dry_run_resources: BTreeSet::new(),
+                                host_capabilities: None,
+                                data: None,
                             },
                         ),
                         (
@@ -512,6 +690,9 @@ group_policy:
                                 module: "ghcr.io/kubewarden/policies/policy2:0.1.0".to_owned(),
                                 settings: Some(PolicySettings::default()),
                                 context_aware_resources: BTreeSet::new(),
+                                dry_run_resources: BTreeSet::new(),
+                                host_capabilities: Some(BTreeSet::new()),
+                                data: None,
                             },
                         ),
                     ]),
@@ -522,6 +703,71 @@ group_policy:
         assert_eq!(expected_policies, policies);
     }
 
+    #[rstest]
+    #[case::inline(
+        r#"
+---
+example:
+  module: file:///tmp/rego-policy.wasm
+  data:
+    inline:
+      allowed_registries: ["registry.example.com"]
+"#,
+        Some(RegoDataConfig {
+            source: RegoDataSource::Inline(
+                json!({"allowed_registries": ["registry.example.com"]})
+                    .as_object()
+                    .unwrap()
+                    .to_owned()
+            ),
+            refresh_interval: None,
+        })
+    )]
+    #[case::file(
+        r#"
+---
+example:
+  module: file:///tmp/rego-policy.wasm
+  data:
+    file: /etc/data.yaml
+    refreshInterval: 60
+"#,
+        Some(RegoDataConfig {
+            source: RegoDataSource::File(PathBuf::from("/etc/data.yaml")),
+            refresh_interval: Some(60),
+        })
+    )]
+    #[case::bundle(
+        r#"
+---
+example:
+  module: file:///tmp/rego-policy.wasm
+  data:
+    bundle: ghcr.io/kubewarden/tests/opa-bundle:latest
+    refreshInterval: 300
+"#,
+        Some(RegoDataConfig {
+            source: RegoDataSource::Bundle("ghcr.io/kubewarden/tests/opa-bundle:latest".to_owned()),
+            refresh_interval: Some(300),
+        })
+    )]
+    #[case::missing(
+        r#"
+---
+example:
+  module: file:///tmp/rego-policy.wasm
+"#,
+        None
+    )]
+    fn handle_rego_data(#[case] input: &str, #[case] expected: Option<RegoDataConfig>) {
+        let policies: HashMap<String, PolicyOrPolicyGroup> = serde_yaml::from_str(input).unwrap();
+
+        match policies.get("example").unwrap() {
+            PolicyOrPolicyGroup::Policy { data, .. } => assert_eq!(data, &expected),
+            _ => panic!("Expected an Individual policy"),
+        }
+    }
+
     #[rstest]
     #[case::settings_empty(
         r#"
@@ -653,6 +899,32 @@ group_policy:
     policy2:
       module: file:///tmp/namespace-validate-policy.wasm
       settings: {}
+"#,
+        false
+    )]
+    #[case::data_refresh_interval_zero(
+        r#"
+---
+example:
+  module: file:///tmp/rego-policy.wasm
+  data:
+    file: /etc/data.yaml
+    refreshInterval: 0
+"#,
+        false
+    )]
+    #[case::policy_group_member_data_refresh_interval_zero(
+        r#"
+---
+group_policy:
+  expression: "policy1()"
+  message: "group policy message"
+  policies:
+    policy1:
+      module: file:///tmp/rego-policy.wasm
+      data:
+        file: /etc/data.yaml
+        refreshInterval: 0
 "#,
         false
     )]
@@ -663,4 +935,117 @@ group_policy:
         let validation_result = validate_policies(&policies);
         assert_eq!(is_valid, validation_result.is_ok());
     }
+
+    #[test]
+    fn callback_cache_flags() {
+        let policies_yaml = r#"
+---
+example:
+  module: file:///tmp/namespace-validate-policy.wasm
+  settings: {}
+"#;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(policies_yaml.as_bytes()).unwrap();
+        let file_path = temp_file.into_temp_path();
+        let policies_flag = format!("--policies={}", file_path.to_str().unwrap());
+
+        let matches = cli::build_cli()
+            .try_get_matches_from([
+                "policy-server",
+                &policies_flag,
+                "--callback-cache-ttl=oci=120,kubernetes_can_i=1",
+                "--callback-cache-max-entries=sigstore=10",
+                "--callback-cache-negative-ttl=oci=30,sigstore=0",
+            ])
+            .unwrap();
+        let config = Config::from_args(&matches).unwrap();
+
+        assert_eq!(
+            config.callback_cache_configs[&CachedCapability::Oci],
+            CacheConfig {
+                ttl: Duration::from_secs(120),
+                max_entries: 1000,
+                negative_ttl: Some(Duration::from_secs(30)),
+            }
+        );
+        assert_eq!(
+            config.callback_cache_configs[&CachedCapability::Sigstore],
+            CacheConfig {
+                ttl: Duration::from_secs(60),
+                max_entries: 10,
+                negative_ttl: None,
+            }
+        );
+        assert_eq!(
+            config.callback_cache_configs[&CachedCapability::KubernetesCanI].ttl,
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            config.callback_cache_configs[&CachedCapability::KubernetesGetResource],
+            CacheConfig::default_for(CachedCapability::KubernetesGetResource)
+        );
+    }
+
+    /// Write a policies file with a single policy, returns its path
+    fn single_policy_file() -> tempfile::TempPath {
+        let policies_yaml = r#"
+---
+example:
+  module: file:///tmp/namespace-validate-policy.wasm
+  settings: {}
+"#;
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(policies_yaml.as_bytes()).unwrap();
+        temp_file.into_temp_path()
+    }
+
+    #[test]
+    fn dns_flags() {
+        let file_path = single_policy_file();
+        let policies_flag = format!("--policies={}", file_path.to_str().unwrap());
+
+        let matches = cli::build_cli()
+            .try_get_matches_from([
+                "policy-server",
+                &policies_flag,
+                "--dns-nameservers=10.0.0.10,[fd00::1]:5353",
+                "--dns-timeout=1",
+            ])
+            .unwrap();
+        let config = Config::from_args(&matches).unwrap();
+
+        assert_eq!(
+            config.dns_nameservers,
+            Some(vec![
+                "10.0.0.10:53".parse().unwrap(),
+                "[fd00::1]:5353".parse().unwrap(),
+            ])
+        );
+        assert_eq!(config.dns_timeout, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn dns_flags_defaults() {
+        let file_path = single_policy_file();
+        let policies_flag = format!("--policies={}", file_path.to_str().unwrap());
+
+        let matches = cli::build_cli()
+            .try_get_matches_from(["policy-server", &policies_flag])
+            .unwrap();
+        let config = Config::from_args(&matches).unwrap();
+
+        assert_eq!(config.dns_nameservers, None);
+        assert_eq!(config.dns_timeout, DEFAULT_DNS_TIMEOUT);
+    }
+
+    #[rstest]
+    #[case::unknown_capability("--callback-cache-ttl=dns=10")]
+    #[case::missing_value("--callback-cache-ttl=oci")]
+    #[case::not_a_number("--callback-cache-max-entries=oci=many")]
+    fn callback_cache_flags_invalid(#[case] flag: &str) {
+        let matches = cli::build_cli()
+            .try_get_matches_from(["policy-server", flag])
+            .unwrap();
+        assert!(callback_cache_configs(&matches).is_err());
+    }
 }
@@ -17,18 +17,19 @@ use policy_evaluator::{
     policy_evaluator::{PolicyEvaluator, PolicyEvaluatorPre, PolicyExecutionMode, ValidateRequest},
     policy_evaluator_builder::PolicyEvaluatorBuilder,
     policy_group_evaluator::{evaluator::PolicyGroupEvaluator, PolicyGroupMemberSettings},
-    policy_metadata::ContextAwareResource,
+    policy_metadata::{ContextAwareResource, HostCapabilityNamespace},
     wasmtime,
 };
 use tokio::sync::mpsc;
 use tracing::debug;
 
 use crate::{
-    config::{PolicyOrPolicyGroup, PolicyOrPolicyGroupSettings},
+    config::{PolicyOrPolicyGroup, PolicyOrPolicyGroupSettings, RegoDataConfig},
     evaluation::{
         policy_evaluation_settings::PolicyEvaluationSettings,
         precompiled_policy::{PrecompiledPolicies, PrecompiledPolicy},
     },
+    rego_data::{LoadedRegoData, RegoData},
 };
 
 #[cfg(test)]
@@ -106,6 +107,19 @@ pub(crate) struct EvaluationEnvironment {
     /// policy is allowed to access.
     policy_id_to_ctx_aware_allowed_resources: HashMap<PolicyID, BTreeSet<ContextAwareResource>>,
 
+    /// A map with the ID of the policy as key, and the list of ContextAwareResource the
+    /// policy is allowed to dry-run as value.
+    policy_id_to_dry_run_allowed_resources: HashMap<PolicyID, BTreeSet<ContextAwareResource>>,
+
+    /// A map with the ID of the policy as key, and the namespaces of host capabilities
+    /// the policy is allowed to use as value. `None` means all of them can be used.
+    policy_id_to_host_capabilities_allow_list:
+        HashMap<PolicyID, Option<BTreeSet<HostCapabilityNamespace>>>,
+
+    /// A map with the ID of the policy as key, and the external data document
+    /// made available to the Rego policy as value.
+    policy_id_to_rego_data: HashMap<PolicyID, Arc<RegoData>>,
+
     /// Map a `policy_id` to the module's digest.
     /// This allows us to deduplicate the Wasm modules defined by the user.
     policy_id_to_module_digest: HashMap<PolicyID, ModuleDigest>,
@@ -130,16 +144,19 @@ pub(crate) struct EvaluationEnvironment {
 }
 
 /// This structure is used to build the `EvaluationEnvironment` instance.
-pub(crate) struct EvaluationEnvironmentBuilder<'engine, 'precompiled_policies> {
+pub(crate) struct EvaluationEnvironmentBuilder<'engine, 'precompiled_policies, 'rego_data> {
     engine: &'engine wasmtime::Engine,
     precompiled_policies: &'precompiled_policies PrecompiledPolicies,
+    rego_data: Option<&'rego_data LoadedRegoData>,
     callback_handler_tx: mpsc::Sender<CallbackRequest>,
     continue_on_errors: bool,
     policy_evaluation_limit_seconds: Option<u64>,
     always_accept_admission_reviews_on_namespace: Option<String>,
 }
 
-impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'precompiled_policies> {
+impl<'engine, 'precompiled_policies, 'rego_data>
+    EvaluationEnvironmentBuilder<'engine, 'precompiled_policies, 'rego_data>
+{
     /// Prepare a new `EvaluationEnvironmentBuilder` instance.
     pub fn new(
         engine: &'engine wasmtime::Engine,
@@ -149,6 +166,7 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
         EvaluationEnvironmentBuilder {
             engine,
             precompiled_policies,
+            rego_data: None,
             callback_handler_tx,
             continue_on_errors: false,
             policy_evaluation_limit_seconds: None,
@@ -171,6 +189,12 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
         self
     }
 
+    /// Set the external data documents referenced by the Rego policies
+    pub fn with_rego_data(mut self, rego_data: &'rego_data LoadedRegoData) -> Self {
+        self.rego_data = Some(rego_data);
+        self
+    }
+
     /// Set the namespace where all the requests are going to be accepted
     pub fn with_always_accept_admission_reviews_on_namespace(mut self, namespace: String) -> Self {
         self.always_accept_admission_reviews_on_namespace = Some(namespace);
@@ -236,6 +260,9 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
                     message,
                     allowed_to_mutate,
                     context_aware_resources,
+                    dry_run_resources,
+                    host_capabilities,
+                    data,
                     ..
                 } => {
                     let policy_evaluation_settings = PolicyEvaluationSettings {
@@ -249,6 +276,9 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
                         policy_id: id.to_string(),
                         callback_channel: Some(self.callback_handler_tx.clone()),
                         ctx_aware_resources_allow_list: context_aware_resources.to_owned(),
+                        dry_run_resources_allow_list: dry_run_resources.to_owned(),
+                        host_capabilities_allow_list: host_capabilities.to_owned(),
+                        rego_data: None,
                     };
 
                     if let Err(e) = self.bootstrap_policy(
@@ -257,6 +287,7 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
                         url,
                         policy_evaluation_settings,
                         eval_ctx,
+                        data.as_ref(),
                     ) {
                         if !self.continue_on_errors {
                             return Err(e);
@@ -313,6 +344,9 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
                             ctx_aware_resources_allow_list: policy
                                 .context_aware_resources
                                 .to_owned(),
+                            dry_run_resources_allow_list: policy.dry_run_resources.to_owned(),
+                            host_capabilities_allow_list: policy.host_capabilities.to_owned(),
+                            rego_data: None,
                         };
 
                         if let Err(e) = self.bootstrap_policy(
@@ -321,6 +355,7 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
                             &policy.module,
                             policy_evaluation_settings,
                             eval_ctx,
+                            policy.data.as_ref(),
                         ) {
                             if !self.continue_on_errors {
                                 return Err(e);
@@ -347,7 +382,10 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
         url: &str,
         policy_evaluation_settings: PolicyEvaluationSettings,
         eval_ctx: EvaluationContext,
+        data: Option<&RegoDataConfig>,
     ) -> Result<()> {
+        let rego_data = data.map(|data| self.get_rego_data(&id, data)).transpose()?;
+
         let precompiled_policy = self
             .precompiled_policies
             .get(url)
@@ -368,8 +406,24 @@ impl<'engine, 'precompiled_policies> EvaluationEnvironmentBuilder<'engine, 'prec
             )
             .map_err(|e| EvaluationError::BootstrapFailure(e.to_string()))?;
 
+        if let Some(rego_data) = rego_data {
+            eval_env.register_rego_data(&id, &precompiled_policy.execution_mode, rego_data)?;
+        }
+
         eval_env.validate_settings(&id)
     }
+
+    /// Internal method, find the external data document loaded for the given policy
+    fn get_rego_data(&self, id: &PolicyID, data: &RegoDataConfig) -> Result<Arc<RegoData>> {
+        self.rego_data
+            .and_then(|rego_data| rego_data.get(&data.source.id()))
+            .ok_or_else(|| {
+                EvaluationError::BootstrapFailure(format!("cannot find the data document of {id}"))
+            })?
+            .as_ref()
+            .map(Arc::clone)
+            .map_err(|e| EvaluationError::BootstrapFailure(format!("{id}: {e}")))
+    }
 }
 
 #[cfg_attr(test, automock)]
@@ -384,6 +438,28 @@ impl EvaluationEnvironment {
         self.always_accept_admission_reviews_on_namespace.as_deref() == Some(namespace)
     }
 
+    /// Associate an external data document with a registered policy.
+    /// Only Rego policies can consume it, an error is returned otherwise.
+    fn register_rego_data(
+        &mut self,
+        policy_id: &PolicyID,
+        execution_mode: &PolicyExecutionMode,
+        rego_data: Arc<RegoData>,
+    ) -> Result<()> {
+        if !matches!(
+            execution_mode,
+            PolicyExecutionMode::Opa | PolicyExecutionMode::OpaGatekeeper
+        ) {
+            return Err(EvaluationError::BootstrapFailure(format!(
+                "{policy_id}: a data document can be provided only to Rego policies"
+            )));
+        }
+        self.policy_id_to_rego_data
+            .insert(policy_id.to_owned(), rego_data);
+
+        Ok(())
+    }
+
     /// Register a new policy. It takes care of creating a new `PolicyEvaluator` (when needed).
     /// This is used to register both individual policies and the ones that are part of a group
     /// policy.
@@ -434,6 +510,18 @@ impl EvaluationEnvironment {
             eval_ctx.ctx_aware_resources_allow_list,
         );
 
+        self.policy_id_to_dry_run_allowed_resources
+            .insert(policy_id.to_owned(), eval_ctx.dry_run_resources_allow_list);
+
+        // The host capabilities granted by the user take precedence over the
+        // ones declared by the policy
+        self.policy_id_to_host_capabilities_allow_list.insert(
+            policy_id.to_owned(),
+            eval_ctx
+                .host_capabilities_allow_list
+                .or_else(|| precompiled_policy.host_capabilities.clone()),
+        );
+
         Ok(())
     }
 
@@ -549,10 +637,26 @@ impl EvaluationEnvironment {
             .get(policy_id)
             .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
 
+        let dry_run_resources_allow_list = self
+            .policy_id_to_dry_run_allowed_resources
+            .get(policy_id)
+            .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
+
+        let host_capabilities_allow_list = self
+            .policy_id_to_host_capabilities_allow_list
+            .get(policy_id)
+            .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
+
         let eval_ctx = EvaluationContext {
             policy_id: policy_id.to_string(),
             callback_channel: self.callback_handler_tx.clone(),
             ctx_aware_resources_allow_list: ctx_aware_resources_allow_list.clone(),
+            dry_run_resources_allow_list: dry_run_resources_allow_list.clone(),
+            host_capabilities_allow_list: host_capabilities_allow_list.clone(),
+            rego_data: self
+                .policy_id_to_rego_data
+                .get(policy_id)
+                .map(|rego_data| rego_data.document()),
         };
 
         policy_evaluator_pre.rehydrate(&eval_ctx).map_err(|e| {
@@ -646,6 +750,16 @@ impl EvaluationEnvironment {
                 .get(&policy_id)
                 .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
 
+            let dry_run_resources_allow_list = self
+                .policy_id_to_dry_run_allowed_resources
+                .get(&policy_id)
+                .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
+
+            let host_capabilities_allow_list = self
+                .policy_id_to_host_capabilities_allow_list
+                .get(&policy_id)
+                .ok_or(EvaluationError::PolicyNotFound(policy_id.to_string()))?;
+
             let settings = match self.get_policy_settings(&policy_id)?.settings {
                 PolicyOrPolicyGroupSettings::Policy(settings) => settings,
                 _ => unreachable!(),
@@ -654,6 +768,12 @@ impl EvaluationEnvironment {
             let policy_group_member_settings = PolicyGroupMemberSettings {
                 settings,
                 ctx_aware_resources_allow_list: ctx_aware_resources_allow_list.clone(),
+                dry_run_resources_allow_list: dry_run_resources_allow_list.clone(),
+                host_capabilities_allow_list: host_capabilities_allow_list.clone(),
+                rego_data: self
+                    .policy_id_to_rego_data
+                    .get(&policy_id)
+                    .map(|rego_data| rego_data.document()),
             };
 
             evaluator.add_policy_member(
@@ -735,6 +855,7 @@ mod tests {
             precompiled_module: module.serialize().unwrap(),
             execution_mode: policy_evaluator::policy_evaluator::PolicyExecutionMode::OpaGatekeeper,
             digest: format!("{digest:x}"),
+            host_capabilities: None,
         }
     }
 
@@ -781,7 +902,10 @@ mod tests {
                     allowed_to_mutate: None,
                     settings: None,
                     context_aware_resources: BTreeSet::new(),
+                    dry_run_resources: BTreeSet::new(),
+                    host_capabilities: None,
                     message: None,
+                    data: None,
                 },
             );
             precompiled_policies.insert(policy_url, Ok(precompiled_policy.clone()));
@@ -798,6 +922,9 @@ mod tests {
                         module: "file:///tmp/happy_policy_1.wasm".to_string(),
                         settings: None,
                         context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: None,
                     },
                 )]
                 .into_iter()
@@ -825,6 +952,9 @@ mod tests {
                         module: "file:///tmp/happy_policy_1.wasm".to_string(),
                         settings: None,
                         context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: None,
                     },
                 )]
                 .into_iter()
@@ -862,6 +992,9 @@ mod tests {
                         module: "file:///tmp/happy_policy_1.wasm".to_string(),
                         settings: None,
                         context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: None,
                     },
                 )]
                 .into_iter()
@@ -881,6 +1014,9 @@ mod tests {
                             module: "file:///tmp/happy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                     (
@@ -889,6 +1025,9 @@ mod tests {
                             module: "file:///tmp/unhappy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                     (
@@ -897,6 +1036,9 @@ mod tests {
                             module: "file:///tmp/unhappy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                 ]
@@ -919,6 +1061,9 @@ mod tests {
                             module: "file:///tmp/happy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                     (
@@ -927,6 +1072,9 @@ mod tests {
                             module: "file:///tmp/unhappy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                     (
@@ -935,6 +1083,9 @@ mod tests {
                             module: "file:///tmp/unhappy_policy_1.wasm".to_string(),
                             settings: None,
                             context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: None,
                         },
                     ),
                 ]
@@ -1072,6 +1223,60 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case::granted_by_user(
+        Some(BTreeSet::from([HostCapabilityNamespace::Oci])),
+        Some(BTreeSet::from([HostCapabilityNamespace::Net])),
+        Some(BTreeSet::from([HostCapabilityNamespace::Oci]))
+    )]
+    #[case::declared_by_metadata(
+        None,
+        Some(BTreeSet::from([HostCapabilityNamespace::Net])),
+        Some(BTreeSet::from([HostCapabilityNamespace::Net]))
+    )]
+    #[case::unrestricted(None, None, None)]
+    fn host_capabilities_allow_list(
+        #[case] granted: Option<BTreeSet<HostCapabilityNamespace>>,
+        #[case] declared: Option<BTreeSet<HostCapabilityNamespace>>,
+        #[case] expected: Option<BTreeSet<HostCapabilityNamespace>>,
+    ) {
+        let engine = wasmtime::Engine::default();
+        let mut precompiled_policy = build_precompiled_policy(
+            &engine,
+            include_bytes!("../../tests/data/gatekeeper_always_happy_policy.wasm"),
+        );
+        precompiled_policy.host_capabilities = declared;
+
+        let policy_id = PolicyID::Policy("policy".to_string());
+        let mut evaluation_environment = EvaluationEnvironment::default();
+        evaluation_environment
+            .register(
+                &engine,
+                &policy_id,
+                PolicyEvaluationSettings {
+                    policy_mode: PolicyMode::Protect,
+                    allowed_to_mutate: false,
+                    settings: PolicyOrPolicyGroupSettings::Policy(Default::default()),
+                    custom_rejection_message: None,
+                },
+                EvaluationContext {
+                    policy_id: policy_id.to_string(),
+                    host_capabilities_allow_list: granted,
+                    ..Default::default()
+                },
+                &precompiled_policy,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            evaluation_environment
+                .policy_id_to_host_capabilities_allow_list
+                .get(&policy_id),
+            Some(&expected)
+        );
+    }
+
     #[test]
     fn validate_policy_with_initialization_error() {
         let mut evaluation_environment = build_evaluation_environment();
@@ -1127,4 +1332,149 @@ mod tests {
 
         assert_eq!(expression_is_valid, validation_result.is_ok());
     }
+
+    #[rstest]
+    #[case::rego_policy(PolicyExecutionMode::OpaGatekeeper, true)]
+    #[case::not_a_rego_policy(PolicyExecutionMode::KubewardenWapc, false)]
+    fn provide_data_document_to_policy(
+        #[case] execution_mode: PolicyExecutionMode,
+        #[case] accepted: bool,
+    ) {
+        let engine = wasmtime::Engine::default();
+        let (callback_handler_tx, _) = mpsc::channel(10);
+        let mut precompiled_policy = build_precompiled_policy(
+            &engine,
+            include_bytes!("../../tests/data/gatekeeper_always_happy_policy.wasm"),
+        );
+        precompiled_policy.execution_mode = execution_mode;
+
+        let policy_url = "file:///tmp/happy_policy_1.wasm".to_string();
+        let precompiled_policies =
+            PrecompiledPolicies::from([(policy_url.clone(), Ok(precompiled_policy))]);
+
+        let data = RegoDataConfig {
+            source: crate::config::RegoDataSource::Inline(
+                serde_json::json!({"allowed": true})
+                    .as_object()
+                    .unwrap()
+                    .to_owned(),
+            ),
+            refresh_interval: None,
+        };
+        let document = RegoData::new(
+            data.source.clone(),
+            None,
+            serde_json::json!({"allowed": true})
+                .as_object()
+                .unwrap()
+                .to_owned(),
+        );
+        let rego_data = LoadedRegoData::from([(data.source.id(), Ok(Arc::new(document)))]);
+
+        let policies = HashMap::from([(
+            "policy".to_string(),
+            PolicyOrPolicyGroup::Policy {
+                module: policy_url,
+                policy_mode: PolicyMode::Protect,
+                allowed_to_mutate: None,
+                settings: None,
+                context_aware_resources: BTreeSet::new(),
+                dry_run_resources: BTreeSet::new(),
+                host_capabilities: None,
+                message: None,
+                data: Some(data),
+            },
+        )]);
+
+        let evaluation_environment =
+            EvaluationEnvironmentBuilder::new(&engine, &precompiled_policies, callback_handler_tx)
+                .with_rego_data(&rego_data)
+                .build_evaluation_environment(&policies);
+
+        assert_eq!(evaluation_environment.is_ok(), accepted);
+        if let Ok(evaluation_environment) = evaluation_environment {
+            let policy_id = PolicyID::Policy("policy".to_string());
+            assert_eq!(
+                evaluation_environment
+                    .policy_id_to_rego_data
+                    .get(&policy_id)
+                    .map(|rego_data| rego_data.document().as_ref().clone()),
+                Some(
+                    serde_json::json!({"allowed": true})
+                        .as_object()
+                        .unwrap()
+                        .to_owned()
+                ),
+            );
+        }
+    }
+    #[test]
+    fn provide_data_document_to_policy_group_member() {
+        let engine = wasmtime::Engine::default();
+        let (callback_handler_tx, _) = mpsc::channel(10);
+        let mut precompiled_policy = build_precompiled_policy(
+            &engine,
+            include_bytes!("../../tests/data/gatekeeper_always_happy_policy.wasm"),
+        );
+        precompiled_policy.execution_mode = PolicyExecutionMode::OpaGatekeeper;
+
+        let policy_url = "file:///tmp/happy_policy_1.wasm".to_string();
+        let precompiled_policies =
+            PrecompiledPolicies::from([(policy_url.clone(), Ok(precompiled_policy))]);
+
+        let document = serde_json::json!({"allowed": true})
+            .as_object()
+            .unwrap()
+            .to_owned();
+        let data = RegoDataConfig {
+            source: crate::config::RegoDataSource::Inline(document.clone()),
+            refresh_interval: None,
+        };
+        let rego_data = LoadedRegoData::from([(
+            data.source.id(),
+            Ok(Arc::new(RegoData::new(
+                data.source.clone(),
+                None,
+                document.clone(),
+            ))),
+        )]);
+
+        let policies = HashMap::from([(
+            "group".to_string(),
+            PolicyOrPolicyGroup::PolicyGroup {
+                policy_mode: PolicyMode::Protect,
+                expression: "member()".to_string(),
+                message: "group policy message".to_string(),
+                policies: HashMap::from([(
+                    "member".to_string(),
+                    PolicyGroupMember {
+                        module: policy_url,
+                        settings: None,
+                        context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: Some(data),
+                    },
+                )]),
+            },
+        )]);
+
+        let evaluation_environment =
+            EvaluationEnvironmentBuilder::new(&engine, &precompiled_policies, callback_handler_tx)
+                .with_rego_data(&rego_data)
+                .build_evaluation_environment(&policies)
+                .expect("cannot build evaluation environment");
+
+        let policy_id = PolicyID::PolicyGroupPolicy {
+            group: "group".to_string(),
+            name: "member".to_string(),
+        };
+        assert_eq!(
+            evaluation_environment
+                .policy_id_to_rego_data
+                .get(&policy_id)
+                .map(|rego_data| rego_data.document().as_ref().clone()),
+            Some(document),
+        );
+    }
 }
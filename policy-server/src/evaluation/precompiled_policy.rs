@@ -1,11 +1,18 @@
 use anyhow::{anyhow, Result};
 use lazy_static::lazy_static;
 use policy_evaluator::{
-    policy_evaluator::PolicyExecutionMode, policy_metadata::Metadata, wasmtime, ProtocolVersion,
+    policy_evaluator::PolicyExecutionMode,
+    policy_metadata::{HostCapabilityNamespace, Metadata},
+    wasmtime, ProtocolVersion,
 };
 use semver::{BuildMetadata, Prerelease, Version};
 use sha2::{Digest, Sha256};
-use std::{collections::HashMap, fs, path::Path, vec::Vec};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::Path,
+    vec::Vec,
+};
 
 lazy_static! {
     static ref KUBEWARDEN_VERSION: Version = {
@@ -39,6 +46,9 @@ pub(crate) struct PrecompiledPolicy {
 
     /// sha256 digest of the precompiled module
     pub digest: String,
+
+    /// The namespaces of host capabilities declared by the policy metadata
+    pub host_capabilities: Option<BTreeSet<HostCapabilityNamespace>>,
 }
 
 impl PrecompiledPolicy {
@@ -62,6 +72,7 @@ impl PrecompiledPolicy {
             precompiled_module,
             execution_mode,
             digest: format!("{digest:x}"),
+            host_capabilities: metadata.host_capabilities,
         })
     }
 }
@@ -1,6 +1,7 @@
 mod certs;
 mod evaluation;
 mod policy_downloader;
+mod rego_data;
 
 #[cfg(test)]
 mod test_utils;
@@ -91,7 +92,16 @@ impl PolicyServer {
         let mut callback_handler_builder =
             CallbackHandlerBuilder::new(callback_handler_shutdown_channel_rx)
                 .registry_config(config.sources.clone())
-                .trust_root(sigstore_trust_root.clone());
+                .trust_root(sigstore_trust_root.clone())
+                .dns_timeout(config.dns_timeout);
+        if let Some(nameservers) = &config.dns_nameservers {
+            callback_handler_builder =
+                callback_handler_builder.dns_nameservers(nameservers.clone());
+        }
+        for (capability, cache_config) in &config.callback_cache_configs {
+            callback_handler_builder =
+                callback_handler_builder.cache_config(*capability, *cache_config);
+        }
 
         let kube_client: Option<kube::Client> = match kube::Client::try_default().await {
             Ok(client) => Some(client),
@@ -157,11 +167,14 @@ impl PolicyServer {
             }
         }
 
+        let rego_data = rego_data::load_rego_data(&config.policies, config.sources.as_ref()).await;
+
         let mut evaluation_environment_builder = EvaluationEnvironmentBuilder::new(
             &engine,
             &precompiled_policies,
             callback_sender_channel.clone(),
         )
+        .with_rego_data(&rego_data)
         .with_continue_on_errors(config.continue_on_errors);
         if let Some(namespace) = config.always_accept_admission_reviews_on_namespace {
             evaluation_environment_builder = evaluation_environment_builder
@@ -172,6 +185,7 @@ impl PolicyServer {
                 evaluation_environment_builder.with_policy_evaluation_limit_seconds(limit);
         }
         let evaluation_environment = evaluation_environment_builder.build(&config.policies)?;
+        rego_data::spawn_refresh_tasks(&rego_data, config.sources.clone());
 
         if let Some(limit) = config.policy_evaluation_limit_seconds {
             info!(
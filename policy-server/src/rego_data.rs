@@ -0,0 +1,428 @@
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
+use policy_evaluator::{policy_fetcher, policy_fetcher::sources::Sources};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::Component,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tokio::time;
+use tracing::{debug, warn};
+
+use crate::config::{PolicyOrPolicyGroup, RegoDataSource};
+
+/// The external data document of a Rego policy
+pub(crate) type RegoDocument = serde_json::Map<String, Value>;
+
+/// A Map with the id of the `RegoDataSource` as key, and the
+/// `RegoData` loaded from it as value.
+pub(crate) type LoadedRegoData = HashMap<String, Result<Arc<RegoData>>>;
+
+/// Holds the external data document loaded from a `RegoDataSource`.
+///
+/// The document is shared by all the policies referencing the same source.
+/// When a refresh interval is set, the document is periodically replaced
+/// by a background task. Policies always get the latest version of the document
+/// when they are rehydrated.
+pub(crate) struct RegoData {
+    source: RegoDataSource,
+    refresh_interval: Option<Duration>,
+    document: RwLock<Arc<RegoDocument>>,
+}
+
+impl RegoData {
+    /// Create a new instance holding the given document
+    pub fn new(
+        source: RegoDataSource,
+        refresh_interval: Option<Duration>,
+        document: RegoDocument,
+    ) -> Self {
+        RegoData {
+            source,
+            refresh_interval,
+            document: RwLock::new(Arc::new(document)),
+        }
+    }
+
+    /// Returns the latest version of the document
+    pub fn document(&self) -> Arc<RegoDocument> {
+        self.document
+            .read()
+            .expect("cannot acquire read lock on Rego data document")
+            .clone()
+    }
+
+    /// Load the document again from its source. The previous version
+    /// of the document is kept when an error occurs.
+    async fn refresh(&self, sources: Option<&Sources>) -> Result<()> {
+        let document = load_document(&self.source, sources).await?;
+        *self
+            .document
+            .write()
+            .expect("cannot acquire write lock on Rego data document") = Arc::new(document);
+        Ok(())
+    }
+}
+
+/// Load all the external data documents referenced by the policies.
+///
+/// Each source is loaded only once, even when it's referenced by multiple policies.
+/// When these policies define different refresh intervals, the shortest one is used.
+pub(crate) async fn load_rego_data(
+    policies: &HashMap<String, PolicyOrPolicyGroup>,
+    sources: Option<&Sources>,
+) -> LoadedRegoData {
+    let mut data_sources: HashMap<String, (RegoDataSource, Option<u64>)> = HashMap::new();
+    let data_configs = policies.values().flat_map(|policy| match policy {
+        PolicyOrPolicyGroup::Policy { data, .. } => data.iter().collect::<Vec<_>>(),
+        PolicyOrPolicyGroup::PolicyGroup { policies, .. } => policies
+            .values()
+            .filter_map(|member| member.data.as_ref())
+            .collect(),
+    });
+    for data in data_configs {
+        data_sources
+            .entry(data.source.id())
+            .and_modify(|(_, refresh_interval)| {
+                *refresh_interval = match (*refresh_interval, data.refresh_interval) {
+                    (Some(current), Some(wanted)) => Some(current.min(wanted)),
+                    (current, wanted) => current.or(wanted),
+                }
+            })
+            .or_insert((data.source.clone(), data.refresh_interval));
+    }
+
+    let mut loaded_rego_data = LoadedRegoData::new();
+    for (id, (source, refresh_interval)) in data_sources {
+        debug!(source = id, "loading Rego data document");
+        let rego_data = load_document(&source, sources).await.map(|document| {
+            Arc::new(RegoData::new(
+                source,
+                refresh_interval.map(Duration::from_secs),
+                document,
+            ))
+        });
+        loaded_rego_data.insert(id, rego_data);
+    }
+
+    loaded_rego_data
+}
+
+/// Start a background task for each document that has to be periodically refreshed
+pub(crate) fn spawn_refresh_tasks(loaded_rego_data: &LoadedRegoData, sources: Option<Sources>) {
+    for (id, rego_data) in loaded_rego_data {
+        let rego_data = match rego_data {
+            Ok(rego_data) => rego_data.clone(),
+            Err(_) => continue,
+        };
+        let refresh_interval = match rego_data.refresh_interval {
+            Some(refresh_interval) => refresh_interval,
+            None => continue,
+        };
+        let id = id.to_owned();
+        let sources = sources.clone();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(refresh_interval);
+            interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+            // The first tick completes immediately, the document has just been loaded
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match rego_data.refresh(sources.as_ref()).await {
+                    Ok(_) => debug!(source = id, "Rego data document refreshed"),
+                    Err(error) => warn!(
+                        source = id,
+                        %error,
+                        "cannot refresh Rego data document, the previous version is going to be used"
+                    ),
+                }
+            }
+        });
+    }
+}
+
+async fn load_document(source: &RegoDataSource, sources: Option<&Sources>) -> Result<RegoDocument> {
+    match source {
+        RegoDataSource::Inline(document) => Ok(document.to_owned()),
+        RegoDataSource::File(path) => {
+            let contents = tokio::fs::read(path)
+                .await
+                .map_err(|e| anyhow!("cannot read data file {}: {e}", path.display()))?;
+            parse_document(&contents)
+                .map_err(|e| anyhow!("cannot parse data file {}: {e}", path.display()))
+        }
+        RegoDataSource::Bundle(url) => {
+            let bundle = policy_fetcher::fetch_opa_bundle(url, sources)
+                .await
+                .map_err(|e| anyhow!("cannot fetch OPA bundle {url}: {e}"))?;
+            read_bundle(&bundle).map_err(|e| anyhow!("cannot read OPA bundle {url}: {e}"))
+        }
+    }
+}
+
+/// Parse a YAML or JSON document. The document must be an object
+fn parse_document(contents: &[u8]) -> Result<RegoDocument> {
+    match serde_yaml::from_slice::<Value>(contents)? {
+        Value::Object(document) => Ok(document),
+        _ => Err(anyhow!("the data document must be an object")),
+    }
+}
+
+/// Build the data document defined inside of an OPA bundle.
+///
+/// OPA bundles are gzipped tarballs. The data is stored inside of `data.json`
+/// and `data.yaml` files, the directory holding the file defines where the
+/// data is placed inside of the `data` document. All the other files (like
+/// the Rego sources or the manifest) are ignored.
+fn read_bundle(bundle: &[u8]) -> Result<RegoDocument> {
+    let mut archive = tar::Archive::new(GzDecoder::new(bundle));
+    let mut document = RegoDocument::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let is_data_file = path
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .is_some_and(|file_name| matches!(file_name, "data.json" | "data.yaml" | "data.yml"));
+        if !is_data_file {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        let value: Value = serde_yaml::from_slice(&contents)
+            .map_err(|e| anyhow!("cannot parse {}: {e}", path.display()))?;
+
+        let keys: Vec<String> = path
+            .parent()
+            .map(|parent| {
+                parent
+                    .components()
+                    .filter_map(|component| match component {
+                        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        insert_at(&mut document, &keys, value)
+            .map_err(|e| anyhow!("cannot load {}: {e}", path.display()))?;
+    }
+
+    Ok(document)
+}
+
+/// Merge the value inside of the document, at the location described by the keys
+fn insert_at(document: &mut RegoDocument, keys: &[String], value: Value) -> Result<()> {
+    match keys.split_first() {
+        None => match value {
+            Value::Object(object) => merge(document, object),
+            _ => Err(anyhow!("the root data document must be an object")),
+        },
+        Some((key, keys)) => {
+            let child = document
+                .entry(key.to_owned())
+                .or_insert_with(|| Value::Object(RegoDocument::new()));
+            match child {
+                Value::Object(child) => insert_at(child, keys, value),
+                _ => Err(anyhow!("conflicting data at key `{key}`")),
+            }
+        }
+    }
+}
+
+fn merge(document: &mut RegoDocument, other: RegoDocument) -> Result<()> {
+    for (key, value) in other {
+        if !document.contains_key(&key) {
+            document.insert(key, value);
+            continue;
+        }
+        match (document.get_mut(&key), value) {
+            (Some(Value::Object(existing)), Value::Object(value)) => merge(existing, value)?,
+            _ => return Err(anyhow!("conflicting data at key `{key}`")),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PolicyGroupMember, RegoDataConfig};
+    use flate2::{write::GzEncoder, Compression};
+    use policy_evaluator::admission_response_handler::policy_mode::PolicyMode;
+    use serde_json::json;
+    use std::{collections::BTreeSet, io::Write};
+    use tempfile::NamedTempFile;
+
+    fn build_bundle(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn rego_policy(data: RegoDataConfig) -> PolicyOrPolicyGroup {
+        PolicyOrPolicyGroup::Policy {
+            module: "file:///tmp/rego-policy.wasm".to_owned(),
+            policy_mode: PolicyMode::Protect,
+            allowed_to_mutate: None,
+            settings: None,
+            context_aware_resources: BTreeSet::new(),
+            dry_run_resources: BTreeSet::new(),
+            host_capabilities: None,
+            message: None,
+            data: Some(data),
+        }
+    }
+
+    #[test]
+    fn read_bundle_data_files() {
+        let bundle = build_bundle(&[
+            (".manifest", r#"{"revision": "1"}"#),
+            ("policy.rego", "package example"),
+            ("data.json", r#"{"global": true}"#),
+            (
+                "registries/data.yaml",
+                "allowed:\n  - registry.example.com\n",
+            ),
+            ("registries/internal/data.json", r#"{"insecure": false}"#),
+        ]);
+
+        let document = read_bundle(&bundle).expect("cannot read bundle");
+
+        assert_eq!(
+            Value::Object(document),
+            json!({
+                "global": true,
+                "registries": {
+                    "allowed": ["registry.example.com"],
+                    "internal": {
+                        "insecure": false
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn read_bundle_with_conflicting_data() {
+        let bundle = build_bundle(&[
+            ("data.json", r#"{"registries": "not an object"}"#),
+            ("registries/data.json", r#"{"allowed": []}"#),
+        ]);
+
+        assert!(read_bundle(&bundle).is_err());
+    }
+
+    #[tokio::test]
+    async fn load_shared_rego_data() {
+        let mut data_file = NamedTempFile::new().unwrap();
+        data_file
+            .write_all(b"allowed_registries:\n  - registry.example.com\n")
+            .unwrap();
+        let source = RegoDataSource::File(data_file.path().to_path_buf());
+
+        let policies = HashMap::from([
+            (
+                "policy1".to_owned(),
+                rego_policy(RegoDataConfig {
+                    source: source.clone(),
+                    refresh_interval: None,
+                }),
+            ),
+            (
+                "policy2".to_owned(),
+                rego_policy(RegoDataConfig {
+                    source: source.clone(),
+                    refresh_interval: Some(30),
+                }),
+            ),
+            (
+                "policy3".to_owned(),
+                rego_policy(RegoDataConfig {
+                    source: RegoDataSource::File("/does/not/exist.yaml".into()),
+                    refresh_interval: None,
+                }),
+            ),
+            (
+                "group_policy".to_owned(),
+                PolicyOrPolicyGroup::PolicyGroup {
+                    policy_mode: PolicyMode::Protect,
+                    expression: "policy4()".to_owned(),
+                    message: "group policy message".to_owned(),
+                    policies: HashMap::from([(
+                        "policy4".to_owned(),
+                        PolicyGroupMember {
+                            module: "file:///tmp/rego-policy.wasm".to_owned(),
+                            settings: None,
+                            context_aware_resources: BTreeSet::new(),
+                            dry_run_resources: BTreeSet::new(),
+                            host_capabilities: None,
+                            data: Some(RegoDataConfig {
+                                source: source.clone(),
+                                refresh_interval: Some(20),
+                            }),
+                        },
+                    )]),
+                },
+            ),
+        ]);
+
+        let loaded_rego_data = load_rego_data(&policies, None).await;
+        assert_eq!(loaded_rego_data.len(), 2);
+
+        let rego_data = loaded_rego_data
+            .get(&source.id())
+            .unwrap()
+            .as_ref()
+            .expect("data should have been loaded");
+        assert_eq!(rego_data.refresh_interval, Some(Duration::from_secs(20)));
+        assert_eq!(
+            Value::Object(rego_data.document().as_ref().clone()),
+            json!({"allowed_registries": ["registry.example.com"]})
+        );
+
+        assert!(loaded_rego_data
+            .get(&RegoDataSource::File("/does/not/exist.yaml".into()).id())
+            .unwrap()
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn refresh_keeps_previous_document_on_error() {
+        let mut data_file = NamedTempFile::new().unwrap();
+        data_file.write_all(b"{\"version\": 1}").unwrap();
+        let rego_data = RegoData::new(
+            RegoDataSource::File(data_file.path().to_path_buf()),
+            None,
+            RegoDocument::new(),
+        );
+
+        rego_data.refresh(None).await.expect("cannot refresh");
+        assert_eq!(
+            Value::Object(rego_data.document().as_ref().clone()),
+            json!({"version": 1})
+        );
+
+        std::fs::write(data_file.path(), b"[\"not an object\"]").unwrap();
+        assert!(rego_data.refresh(None).await.is_err());
+        assert_eq!(
+            Value::Object(rego_data.document().as_ref().clone()),
+            json!({"version": 1})
+        );
+    }
+}
@@ -6,6 +6,7 @@ use std::{
 
 use axum::Router;
 use policy_evaluator::admission_response_handler::policy_mode::PolicyMode;
+use policy_evaluator::callback_handler::DEFAULT_DNS_TIMEOUT;
 use policy_evaluator::policy_evaluator::PolicySettings;
 use policy_server::{
     config::{Config, PolicyGroupMember, PolicyOrPolicyGroup},
@@ -40,7 +41,10 @@ pub(crate) fn default_test_config() -> Config {
                 allowed_to_mutate: None,
                 settings: None,
                 context_aware_resources: BTreeSet::new(),
+                dry_run_resources: BTreeSet::new(),
+                host_capabilities: None,
                 message: None,
+                data: None,
             },
         ),
         (
@@ -57,7 +61,10 @@ pub(crate) fn default_test_config() -> Config {
                     .unwrap(),
                 ),
                 context_aware_resources: BTreeSet::new(),
+                dry_run_resources: BTreeSet::new(),
+                host_capabilities: None,
                 message: None,
+                data: None,
             },
         ),
         (
@@ -73,7 +80,10 @@ pub(crate) fn default_test_config() -> Config {
                     .unwrap(),
                 ),
                 context_aware_resources: BTreeSet::new(),
+                dry_run_resources: BTreeSet::new(),
+                host_capabilities: None,
                 message: None,
+                data: None,
             },
         ),
         (
@@ -88,6 +98,9 @@ pub(crate) fn default_test_config() -> Config {
                         module: "ghcr.io/kubewarden/tests/pod-privileged:v0.2.1".to_owned(),
                         settings: None,
                         context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: None,
                     },
                 )]),
             },
@@ -110,6 +123,9 @@ pub(crate) fn default_test_config() -> Config {
                             .unwrap(),
                         ),
                         context_aware_resources: BTreeSet::new(),
+                        dry_run_resources: BTreeSet::new(),
+                        host_capabilities: None,
+                        data: None,
                     },
                 )]),
             },
@@ -139,6 +155,9 @@ pub(crate) fn default_test_config() -> Config {
         daemon_stderr_file: None,
         enable_pprof: false,
         continue_on_errors: false,
+        callback_cache_configs: HashMap::new(),
+        dns_nameservers: None,
+        dns_timeout: DEFAULT_DNS_TIMEOUT,
     }
 }
 
@@ -80,7 +80,10 @@ async fn test_validate_custom_rejection_message() {
             allowed_to_mutate: None,
             settings: None,
             context_aware_resources: BTreeSet::new(),
+            dry_run_resources: BTreeSet::new(),
+            host_capabilities: None,
             message: Some("Custom error message".to_owned()),
+            data: None,
         },
     );
     let app = app(config).await;
@@ -512,7 +515,10 @@ async fn test_verified_policy() {
             allowed_to_mutate: None,
             settings: None,
             context_aware_resources: BTreeSet::new(),
+            dry_run_resources: BTreeSet::new(),
+            host_capabilities: None,
             message: None,
+            data: None,
         },
     )]);
     config.verification_config = Some(verification_config);
@@ -550,7 +556,10 @@ async fn test_policy_with_invalid_settings() {
                 .unwrap(),
             ),
             context_aware_resources: BTreeSet::new(),
+            dry_run_resources: BTreeSet::new(),
+            host_capabilities: None,
             message: None,
+            data: None,
         },
     );
     config.continue_on_errors = true;
@@ -596,7 +605,10 @@ async fn test_policy_with_wrong_url() {
             allowed_to_mutate: None,
             settings: None,
             context_aware_resources: BTreeSet::new(),
+            dry_run_resources: BTreeSet::new(),
+            host_capabilities: None,
             message: None,
+            data: None,
         },
     );
     config.continue_on_errors = true;